@@ -0,0 +1,17 @@
+# dex's messages in German. See en.ftl for how catalogs work.
+
+## Antworten auf Ja/Nein-Fragen, durch Kommas getrennt
+answer-yes = j, ja, y, yes
+
+## Vor großen Downloads (--confirm-above)
+size-prompt = { $name } ist { $size } groß — fortfahren? [j/N]
+not-downloading = { $name } wird nicht heruntergeladen
+
+## dex sync
+sync-up-to-date = { $tool }: aktuell ({ $version })
+
+## Auswahl des zu installierenden Programms aus einem Archiv
+binary-choice-header = Mehrere ausführbare Dateien kommen als Programm infrage:
+binary-choice-prompt = Welche installieren? [1-{ $count }]
+binary-choice-retry = Bitte eine Zahl zwischen 1 und { $count } eingeben
+binary-choice-none = kein Programm gewählt
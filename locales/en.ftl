@@ -0,0 +1,23 @@
+# dex's user-facing messages, in English: the catalog every translation is
+# checked against. Each line is `key = text`; `{ $name }` is replaced by
+# the value dex passes as `name`. Lines starting with `#` are comments.
+#
+# To translate dex, copy this file to `<language>.ftl` (e.g. `fr.ftl`),
+# translate the text after each `=`, and add it to `CATALOGS` in
+# `src/i18n.rs`. Keys a translation leaves out are shown in English.
+
+## Answers to yes/no questions, comma-separated
+answer-yes = y, yes
+
+## Before a big download (--confirm-above)
+size-prompt = { $name } is { $size } — continue? [y/N]
+not-downloading = Not downloading { $name }
+
+## dex sync
+sync-up-to-date = { $tool }: up to date ({ $version })
+
+## Choosing the binary to install from an archive
+binary-choice-header = Several executables could be the binary:
+binary-choice-prompt = Install which one? [1-{ $count }]
+binary-choice-retry = Please enter a number between 1 and { $count }
+binary-choice-none = no binary chosen
@@ -0,0 +1,184 @@
+use crate::http::HttpContext;
+use crate::verify::{Algorithm, Hasher};
+use reqwest::StatusCode;
+use reqwest::blocking::{RequestBuilder, Response};
+use reqwest::header::{AUTHORIZATION, ETAG, IF_NONE_MATCH};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long an answer is used without asking again. `dex list`, a
+/// --dry-run, and the download that follows them ask for the same release
+/// within a minute; anything older is checked with its ETag first.
+const MAX_AGE: Duration = Duration::from_secs(60);
+
+/// A provider API answer as kept in dex's cache directory, under
+/// `api/<SHA-256 of the URL and credentials>.json`.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    url: String,
+    etag: Option<String>,
+    /// Seconds since the Unix epoch when the server last confirmed it.
+    fetched_at: u64,
+    body: String,
+}
+
+/// What asking a provider's API came to.
+pub enum Fetched {
+    /// The answer, from the server or a recent enough copy of it.
+    Body(String),
+    /// The server said no; the response is passed back to look into.
+    Failed(Response),
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Names the answer to a request. Credentials are part of the key, so
+/// answers for one token (a private repository's releases, say) are never
+/// handed to a request made with another.
+fn key(url: &str, credentials: Option<&[u8]>) -> String {
+    let mut hasher = Hasher::new(Algorithm::Sha256);
+    hasher.update(url.as_bytes());
+    if let Some(credentials) = credentials {
+        hasher.update(b"\n");
+        hasher.update(credentials);
+    }
+    hasher.finalize().hex
+}
+
+fn path(url: &str, credentials: Option<&[u8]>) -> Option<PathBuf> {
+    Some(
+        crate::dirs::cache_dir()?
+            .join("api")
+            .join(format!("{}.json", key(url, credentials))),
+    )
+}
+
+fn load(path: &PathBuf) -> Option<Entry> {
+    serde_json::from_slice(&std::fs::read(path).ok()?).ok()
+}
+
+/// Writes the entry by way of a temporary file, so a concurrent reader
+/// never sees half of it. Failing to is harmless: the next run asks again.
+fn store(path: &PathBuf, entry: &Entry) {
+    let Some(dir) = path.parent() else {
+        return;
+    };
+    let partial = path.with_extension(format!("json.{}", std::process::id()));
+    let written = std::fs::create_dir_all(dir)
+        .and_then(|()| std::fs::write(&partial, serde_json::to_vec(entry).unwrap_or_default()))
+        .and_then(|()| std::fs::rename(&partial, path));
+    if written.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+}
+
+/// Sends an API GET, answering from the cache when the same request was
+/// answered in the last minute, and otherwise asking with the cached
+/// answer's ETag: a `304 Not Modified` reuses it, and GitHub doesn't count
+/// those against the rate limit.
+pub fn fetch(
+    http: &HttpContext,
+    request: RequestBuilder,
+) -> Result<Fetched, Box<dyn std::error::Error>> {
+    let built = request.try_clone().and_then(|r| r.build().ok());
+    let path = built.as_ref().and_then(|r| {
+        let credentials = r.headers().get(AUTHORIZATION).map(|v| v.as_bytes());
+        path(r.url().as_str(), credentials)
+    });
+    let cached = path.as_ref().and_then(load);
+    if let Some(entry) = &cached
+        && now().saturating_sub(entry.fetched_at) < MAX_AGE.as_secs()
+    {
+        return Ok(Fetched::Body(entry.body.clone()));
+    }
+
+    let request = match cached.as_ref().and_then(|e| e.etag.as_ref()) {
+        Some(etag) => request.header(IF_NONE_MATCH, etag),
+        None => request,
+    };
+    let response = http.send(request)?;
+    if response.status() == StatusCode::NOT_MODIFIED
+        && let (Some(path), Some(mut entry)) = (&path, cached)
+    {
+        entry.fetched_at = now();
+        store(path, &entry);
+        return Ok(Fetched::Body(entry.body));
+    }
+    if !response.status().is_success() {
+        return Ok(Fetched::Failed(response));
+    }
+
+    let etag = response
+        .headers()
+        .get(ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let url = response.url().to_string();
+    let body = crate::http::read_text(response)?;
+    if let Some(path) = &path {
+        let entry = Entry {
+            url,
+            etag,
+            fetched_at: now(),
+            body,
+        };
+        store(path, &entry);
+        return Ok(Fetched::Body(entry.body));
+    }
+    Ok(Fetched::Body(body))
+}
+
+/// `fetch`, with a refusal turned into its error.
+pub fn fetch_text(
+    http: &HttpContext,
+    request: RequestBuilder,
+) -> Result<String, Box<dyn std::error::Error>> {
+    match fetch(http, request)? {
+        Fetched::Body(body) => Ok(body),
+        Fetched::Failed(response) => Err(response
+            .error_for_status()
+            .err()
+            .map_or_else(|| "unexpected answer".into(), Into::into)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_includes_credentials() {
+        let url = "https://api.github.com/repos/o/r/releases/latest";
+        let anonymous = key(url, None);
+        let token = key(url, Some(b"token abc"));
+        assert_ne!(anonymous, token);
+        assert_eq!(token, key(url, Some(b"token abc")));
+        assert_ne!(token, key(url, Some(b"token xyz")));
+    }
+
+    #[test]
+    fn test_store_and_load() {
+        let dir = std::env::temp_dir().join(format!("dex-test-apicache-{}", std::process::id()));
+        let path = dir.join("api").join("entry.json");
+        let entry = Entry {
+            url: "https://api.github.com/x".into(),
+            etag: Some("W/\"abc\"".into()),
+            fetched_at: 1,
+            body: "{}".into(),
+        };
+        store(&path, &entry);
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded.etag.as_deref(), Some("W/\"abc\""));
+        assert_eq!(loaded.body, "{}");
+        assert_eq!(
+            std::fs::read_dir(path.parent().unwrap()).unwrap().count(),
+            1
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,254 @@
+use crate::github;
+use crate::http::HttpContext;
+use crate::layout;
+use crate::platform::Asset;
+use crate::provider::{Latest, Provider, Release};
+use reqwest::Url;
+use reqwest::blocking::RequestBuilder;
+use serde::Deserialize;
+
+// Only the fields we ask AQL to include.
+#[derive(Deserialize)]
+struct SearchResults {
+    results: Vec<Item>,
+}
+
+#[derive(Deserialize)]
+struct Item {
+    /// The folder holding the file, relative to the repository ("." at its top).
+    path: String,
+    name: String,
+    size: Option<u64>,
+    modified: Option<String>,
+    sha256: Option<String>,
+}
+
+/// A folder (or folder pattern) in an Artifactory repository.
+#[derive(Debug, PartialEq, Eq)]
+struct Location<'u> {
+    /// The Artifactory base URL, ending in its context path ("/artifactory").
+    base: String,
+    repo: &'u str,
+    /// Folders below the repository, `*` and `?` matching as in AQL; empty
+    /// for the whole repository.
+    path: &'u str,
+}
+
+/// Parses an Artifactory URL: the server with `artifactory://` for its scheme
+/// (`artifactory+http://` for plain HTTP), its `/artifactory` context path,
+/// then the repository key and the folders to search.
+///
+///   artifactory://acme.jfrog.io/artifactory/tools-local/mytool
+///   artifactory://repo.internal/artifactory/tools-local/mytool/2.*
+fn parse_artifactory_url(url: &str) -> Option<Location<'_>> {
+    let (scheme, rest) = match url.strip_prefix("artifactory://") {
+        Some(rest) => ("https", rest),
+        None => ("http", url.strip_prefix("artifactory+http://")?),
+    };
+    let (server, rest) = rest.split_once("/artifactory/")?;
+    let (repo, path) = rest.split_once('/').unwrap_or((rest, ""));
+    if server.is_empty() || repo.is_empty() {
+        return None;
+    }
+    Some(Location {
+        base: format!("{scheme}://{server}/artifactory"),
+        repo,
+        path: path.trim_matches('/'),
+    })
+}
+
+/// The AQL query for every file in the location's folders and the folders
+/// below them, most recently modified first.
+fn query(location: &Location) -> String {
+    let mut criteria = serde_json::json!({ "repo": location.repo, "type": "file" });
+    if !location.path.is_empty() {
+        criteria["$or"] = serde_json::json!([
+            { "path": { "$match": location.path } },
+            { "path": { "$match": format!("{}/*", location.path) } },
+        ]);
+    }
+    format!(
+        "items.find({criteria}).include(\"path\",\"name\",\"size\",\"modified\",\"sha256\")\
+         .sort({{\"$desc\":[\"modified\"]}}).limit(1000)"
+    )
+}
+
+/// Adds authentication from ARTIFACTORY_API_KEY (an API key) or, failing
+/// that, ARTIFACTORY_ACCESS_TOKEN (an access token, sent as a bearer token).
+fn authenticate(request: RequestBuilder) -> RequestBuilder {
+    if let Ok(key) = std::env::var("ARTIFACTORY_API_KEY") {
+        return request.header("X-JFrog-Art-Api", key);
+    }
+    match std::env::var("ARTIFACTORY_ACCESS_TOKEN") {
+        Ok(token) => request.bearer_auth(token),
+        Err(_) => request,
+    }
+}
+
+/// The version a file belongs to, by its folder or its name.
+fn version_of(item: &Item) -> Option<String> {
+    layout::guess_path_version(&format!("{}/{}", item.path, item.name))
+}
+
+/// Picks the latest version among the files, by `latest`: the version of the
+/// most recently modified one (they come newest first), or the highest.
+fn latest_version(items: &[Item], latest: Latest) -> Option<String> {
+    let mut versions = items.iter().filter_map(version_of);
+    match latest {
+        Latest::Date => versions.next(),
+        Latest::Semver => versions
+            .filter_map(|v| Some((github::tag_version(&v)?, v)))
+            .max_by(|a, b| a.0.cmp(&b.0))
+            .map(|(_, v)| v),
+    }
+}
+
+/// Artifacts in a JFrog Artifactory repository. Files aren't grouped into
+/// releases there, so the latest version is found with an AQL search over
+/// the folders the URL names, and that version's files are the assets.
+pub struct Artifactory;
+
+impl Provider for Artifactory {
+    fn name(&self) -> &'static str {
+        "Artifactory repository"
+    }
+
+    fn matches(&self, url: &str) -> bool {
+        parse_artifactory_url(url).is_some()
+    }
+
+    fn repo_name<'u>(&self, url: &'u str) -> Option<&'u str> {
+        let location = parse_artifactory_url(url)?;
+        // The last folder that isn't a pattern names the tool best.
+        location
+            .path
+            .split('/')
+            .rfind(|s| !s.is_empty() && !s.contains(['*', '?']))
+            .or(Some(location.repo))
+    }
+
+    fn fetch_release(
+        &self,
+        http: &HttpContext,
+        url: &str,
+        latest: Latest,
+    ) -> Result<Release, Box<dyn std::error::Error>> {
+        let location = parse_artifactory_url(url).ok_or("Not a valid Artifactory URL")?;
+        let request = http
+            .post(&format!("{}/api/search/aql", location.base))
+            .header(reqwest::header::CONTENT_TYPE, "text/plain")
+            .body(query(&location));
+        let response = http.send(authenticate(request))?.error_for_status()?;
+        let results: SearchResults = serde_json::from_reader(response)?;
+        if results.results.is_empty() {
+            return Err(format!(
+                "no files in {}/{}/{}",
+                location.base, location.repo, location.path
+            )
+            .into());
+        }
+
+        // Without versions to tell them apart, every file is one release.
+        let tag = latest_version(&results.results, latest);
+        let mut assets = Vec::new();
+        for item in &results.results {
+            if tag.is_some() && version_of(item) != tag {
+                continue;
+            }
+            let mut url = Url::parse(&location.base)?;
+            url.path_segments_mut()
+                .map_err(|()| "not a base URL")?
+                .push(location.repo)
+                .extend(item.path.split('/').filter(|s| *s != "."))
+                .push(&item.name);
+            assets.push(Asset {
+                name: item.name.clone(),
+                url: url.to_string(),
+                digest: item.sha256.as_ref().map(|hex| format!("sha256:{hex}")),
+                size: item.size,
+                content_type: None,
+                updated_at: item.modified.clone(),
+            });
+        }
+        Ok(Release {
+            owner: location.repo.to_string(),
+            repo: self.repo_name(url).unwrap_or(location.repo).to_string(),
+            tag: tag.unwrap_or_else(|| "latest".to_string()),
+            assets,
+            notes: None,
+            source_tarball: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_artifactory_url() {
+        assert_eq!(
+            parse_artifactory_url("artifactory://acme.jfrog.io/artifactory/tools-local/mytool/"),
+            Some(Location {
+                base: "https://acme.jfrog.io/artifactory".into(),
+                repo: "tools-local",
+                path: "mytool",
+            })
+        );
+        let http =
+            parse_artifactory_url("artifactory+http://repo:8081/artifactory/generic").unwrap();
+        assert_eq!(http.base, "http://repo:8081/artifactory");
+        assert_eq!((http.repo, http.path), ("generic", ""));
+        assert!(parse_artifactory_url("artifactory://acme.jfrog.io/tools-local/mytool").is_none());
+        assert!(parse_artifactory_url("https://acme.jfrog.io/artifactory/tools-local").is_none());
+
+        let name = |url| Artifactory.repo_name(url);
+        assert_eq!(
+            name("artifactory://a.io/artifactory/tools-local/team/mytool/2.*"),
+            Some("mytool")
+        );
+        assert_eq!(
+            name("artifactory://a.io/artifactory/tools-local"),
+            Some("tools-local")
+        );
+    }
+
+    #[test]
+    fn test_query() {
+        let location =
+            parse_artifactory_url("artifactory://a.io/artifactory/tools/my\"tool").unwrap();
+        assert_eq!(
+            query(&location),
+            r#"items.find({"$or":[{"path":{"$match":"my\"tool"}},{"path":{"$match":"my\"tool/*"}}],"repo":"tools","type":"file"}).include("path","name","size","modified","sha256").sort({"$desc":["modified"]}).limit(1000)"#
+        );
+    }
+
+    #[test]
+    fn test_latest_version() {
+        let json = r#"{
+            "results": [
+                {"repo": "tools", "path": "mytool/1.9.3", "name": "mytool-linux-amd64.tar.gz",
+                 "size": 1024, "modified": "2024-05-02T10:00:00.000Z", "sha256": "ab12"},
+                {"repo": "tools", "path": "mytool/1.9.3", "name": "SHA256SUMS",
+                 "modified": "2024-05-02T10:00:00.000Z"},
+                {"repo": "tools", "path": "mytool", "name": "mytool-1.10.0-linux-amd64.tar.gz",
+                 "modified": "2024-04-01T10:00:00.000Z"},
+                {"repo": "tools", "path": "mytool", "name": "README.md"}
+            ],
+            "range": {"start_pos": 0, "end_pos": 4, "total": 4}
+        }"#;
+        let results: SearchResults = serde_json::from_str(json).unwrap();
+        let items = &results.results;
+        assert_eq!(version_of(&items[1]).as_deref(), Some("1.9.3"));
+        assert_eq!(version_of(&items[3]), None);
+        assert_eq!(
+            latest_version(items, Latest::Date).as_deref(),
+            Some("1.9.3")
+        );
+        assert_eq!(
+            latest_version(items, Latest::Semver).as_deref(),
+            Some("1.10.0")
+        );
+        assert_eq!(latest_version(&items[3..], Latest::Date), None);
+    }
+}
@@ -0,0 +1,327 @@
+use crate::http::HttpContext;
+use crate::platform::Asset;
+use crate::verify::{from_hex, to_hex};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use p256::ecdsa::signature::Verifier;
+use p256::ecdsa::signature::hazmat::PrehashVerifier;
+use p256::ecdsa::{Signature, VerifyingKey};
+use p256::pkcs8::DecodePublicKey;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+// Only the fields we need from a Sigstore bundle (the format used by both
+// GitHub artifact attestations and `cosign sign-blob --new-bundle-format`).
+// A bundle carries either a DSSE envelope (an in-toto attestation) or a plain
+// signature over the artifact bytes.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Bundle {
+    dsse_envelope: Option<DsseEnvelope>,
+    message_signature: Option<MessageSignature>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DsseEnvelope {
+    payload: String,
+    payload_type: String,
+    signatures: Vec<DsseSignature>,
+}
+
+#[derive(Deserialize)]
+struct DsseSignature {
+    sig: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MessageSignature {
+    message_digest: Option<MessageDigest>,
+    signature: String,
+}
+
+#[derive(Deserialize)]
+struct MessageDigest {
+    algorithm: String,
+    digest: String,
+}
+
+// The in-toto statement inside a DSSE payload. The subjects name the
+// artifacts (by digest) that the attestation is about.
+#[derive(Deserialize)]
+struct Statement {
+    subject: Vec<Subject>,
+}
+
+#[derive(Deserialize)]
+struct Subject {
+    #[serde(default)]
+    digest: HashMap<String, String>,
+}
+
+/// Outcome of checking attestations for a downloaded file.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// A bundle covers the file's digest and its signature verified against the key.
+    Signed,
+    /// A bundle covers the file's digest, but no key was given to check the signature.
+    DigestOnly,
+}
+
+/// Loads a PEM-encoded ECDSA P-256 public key, as produced by `cosign generate-key-pair`.
+pub fn load_key(path: &Path) -> Result<VerifyingKey, Box<dyn std::error::Error>> {
+    let pem = std::fs::read_to_string(path)?;
+    VerifyingKey::from_public_key_pem(&pem)
+        .map_err(|e| format!("Invalid public key {}: {e}", path.display()).into())
+}
+
+/// Parses a Sigstore bundle from JSON.
+pub fn parse_bundle(json: &str) -> Result<Bundle, serde_json::Error> {
+    serde_json::from_str(json)
+}
+
+// Suffixes of bundle files published next to release assets.
+const BUNDLE_SUFFIXES: &[&str] = &[".sigstore.json", ".sigstore", ".bundle"];
+
+/// Finds a Sigstore bundle published alongside `asset_name`, e.g. "tool.tar.gz.sigstore.json".
+pub fn find_bundle_asset<'a>(assets: &'a [Asset], asset_name: &str) -> Option<&'a Asset> {
+    BUNDLE_SUFFIXES.iter().find_map(|suffix| {
+        let wanted = format!("{asset_name}{suffix}");
+        assets.iter().find(|a| a.name == wanted)
+    })
+}
+
+/// Downloads and parses a bundle published as a release asset.
+pub fn fetch_bundle(
+    http: &HttpContext,
+    asset: &Asset,
+) -> Result<Bundle, Box<dyn std::error::Error>> {
+    let json = http.fetch_text(&asset.url)?;
+    parse_bundle(&json).map_err(|e| format!("Invalid bundle {}: {e}", asset.name).into())
+}
+
+/// Checks bundles against a file's SHA-256 digest.
+///
+/// A bundle passes if its subject digest matches `sha256_hex` and, when `key`
+/// is given, its signature verifies against that key. Keyless (Fulcio
+/// certificate) signatures are not chain-validated, so without a key only the
+/// subject digest is checked.
+pub fn evaluate(
+    bundles: &[Bundle],
+    sha256_hex: &str,
+    key: Option<&VerifyingKey>,
+) -> Result<Verdict, String> {
+    if bundles.is_empty() {
+        return Err("no attestations found".into());
+    }
+
+    let mut digest_matched = false;
+    for bundle in bundles {
+        if !subject_matches(bundle, sha256_hex) {
+            continue;
+        }
+        digest_matched = true;
+
+        match key {
+            None => return Ok(Verdict::DigestOnly),
+            Some(key) if signature_verifies(bundle, sha256_hex, key) => return Ok(Verdict::Signed),
+            Some(_) => {}
+        }
+    }
+
+    if digest_matched {
+        Err("no attestation signature verified with the provided key".into())
+    } else {
+        Err(format!(
+            "no attestation covers the downloaded file (sha256:{sha256_hex})"
+        ))
+    }
+}
+
+/// Checks whether a bundle is about the artifact with the given SHA-256.
+fn subject_matches(bundle: &Bundle, sha256_hex: &str) -> bool {
+    if let Some(envelope) = &bundle.dsse_envelope {
+        let Some(statement) = BASE64
+            .decode(&envelope.payload)
+            .ok()
+            .and_then(|p| serde_json::from_slice::<Statement>(&p).ok())
+        else {
+            return false;
+        };
+        return statement.subject.iter().any(|s| {
+            s.digest
+                .get("sha256")
+                .is_some_and(|d| d.eq_ignore_ascii_case(sha256_hex))
+        });
+    }
+
+    if let Some(message) = &bundle.message_signature
+        && let Some(digest) = &message.message_digest
+    {
+        return digest.algorithm == "SHA2_256"
+            && BASE64
+                .decode(&digest.digest)
+                .is_ok_and(|d| to_hex(&d) == sha256_hex.to_lowercase());
+    }
+
+    false
+}
+
+/// Verifies a bundle's ECDSA signature with `key`.
+///
+/// DSSE signatures cover the envelope's pre-authentication encoding; message
+/// signatures cover the artifact itself, so they're checked against its digest.
+fn signature_verifies(bundle: &Bundle, sha256_hex: &str, key: &VerifyingKey) -> bool {
+    if let Some(envelope) = &bundle.dsse_envelope {
+        let Ok(payload) = BASE64.decode(&envelope.payload) else {
+            return false;
+        };
+        let message = pae(&envelope.payload_type, &payload);
+        return envelope.signatures.iter().any(|s| {
+            decode_signature(&s.sig).is_some_and(|sig| key.verify(&message, &sig).is_ok())
+        });
+    }
+
+    if let Some(message) = &bundle.message_signature {
+        let Some(digest) = from_hex(sha256_hex) else {
+            return false;
+        };
+        return decode_signature(&message.signature)
+            .is_some_and(|sig| key.verify_prehash(&digest, &sig).is_ok());
+    }
+
+    false
+}
+
+/// DSSE pre-authentication encoding: the exact bytes a DSSE signature covers.
+/// See https://github.com/secure-systems-lab/dsse/blob/master/protocol.md
+fn pae(payload_type: &str, payload: &[u8]) -> Vec<u8> {
+    let mut out = format!(
+        "DSSEv1 {} {} {} ",
+        payload_type.len(),
+        payload_type,
+        payload.len()
+    )
+    .into_bytes();
+    out.extend_from_slice(payload);
+    out
+}
+
+/// Decodes a base64 DER-encoded ECDSA signature (cosign's encoding).
+fn decode_signature(b64: &str) -> Option<Signature> {
+    let der = BASE64.decode(b64).ok()?;
+    Signature::from_der(&der).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::SigningKey;
+    use p256::ecdsa::signature::Signer;
+    use p256::ecdsa::signature::hazmat::PrehashSigner;
+
+    // sha256("hello")
+    const SHA256_HELLO: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+    const PAYLOAD_TYPE: &str = "application/vnd.in-toto+json";
+
+    fn signing_key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32].into()).unwrap()
+    }
+
+    fn dsse_bundle(subject_sha256: &str, key: &SigningKey) -> Bundle {
+        let statement = format!(
+            r#"{{"_type":"https://in-toto.io/Statement/v1","subject":[{{"name":"tool.tar.gz","digest":{{"sha256":"{subject_sha256}"}}}}],"predicateType":"https://slsa.dev/provenance/v1"}}"#
+        );
+        let sig: Signature = key.sign(&pae(PAYLOAD_TYPE, statement.as_bytes()));
+        let json = format!(
+            r#"{{"mediaType":"application/vnd.dev.sigstore.bundle.v0.3+json","dsseEnvelope":{{"payload":"{}","payloadType":"{PAYLOAD_TYPE}","signatures":[{{"sig":"{}"}}]}}}}"#,
+            BASE64.encode(statement),
+            BASE64.encode(sig.to_der().as_bytes())
+        );
+        parse_bundle(&json).unwrap()
+    }
+
+    fn message_bundle(sha256_hex: &str, key: &SigningKey) -> Bundle {
+        let digest = from_hex(sha256_hex).unwrap();
+        let sig: Signature = key.sign_prehash(&digest).unwrap();
+        let json = format!(
+            r#"{{"messageSignature":{{"messageDigest":{{"algorithm":"SHA2_256","digest":"{}"}},"signature":"{}"}}}}"#,
+            BASE64.encode(&digest),
+            BASE64.encode(sig.to_der().as_bytes())
+        );
+        parse_bundle(&json).unwrap()
+    }
+
+    // ── DSSE encoding ───────────────────────────────────────────────
+
+    #[test]
+    fn test_pae() {
+        assert_eq!(
+            pae("text/plain", b"hi"),
+            b"DSSEv1 10 text/plain 2 hi".to_vec()
+        );
+    }
+
+    // ── Evaluation ──────────────────────────────────────────────────
+
+    #[test]
+    fn test_dsse_signed_with_key() {
+        let key = signing_key(1);
+        let bundles = [dsse_bundle(SHA256_HELLO, &key)];
+        let verdict = evaluate(&bundles, SHA256_HELLO, Some(key.verifying_key()));
+        assert_eq!(verdict, Ok(Verdict::Signed));
+    }
+
+    #[test]
+    fn test_dsse_without_key_checks_digest_only() {
+        let bundles = [dsse_bundle(SHA256_HELLO, &signing_key(1))];
+        assert_eq!(
+            evaluate(&bundles, SHA256_HELLO, None),
+            Ok(Verdict::DigestOnly)
+        );
+    }
+
+    #[test]
+    fn test_dsse_wrong_key_fails() {
+        let bundles = [dsse_bundle(SHA256_HELLO, &signing_key(1))];
+        let other = signing_key(2);
+        assert!(evaluate(&bundles, SHA256_HELLO, Some(other.verifying_key())).is_err());
+    }
+
+    #[test]
+    fn test_subject_mismatch_fails() {
+        let bundles = [dsse_bundle(&"0".repeat(64), &signing_key(1))];
+        assert!(evaluate(&bundles, SHA256_HELLO, None).is_err());
+    }
+
+    #[test]
+    fn test_message_signature_signed_with_key() {
+        let key = signing_key(3);
+        let bundles = [message_bundle(SHA256_HELLO, &key)];
+        let verdict = evaluate(&bundles, SHA256_HELLO, Some(key.verifying_key()));
+        assert_eq!(verdict, Ok(Verdict::Signed));
+    }
+
+    #[test]
+    fn test_no_bundles() {
+        assert!(evaluate(&[], SHA256_HELLO, None).is_err());
+    }
+
+    // ── Bundle discovery ────────────────────────────────────────────
+
+    #[test]
+    fn test_find_bundle_asset() {
+        let assets = crate::platform::make_assets(&[
+            "tool.tar.gz",
+            "tool.tar.gz.sigstore.json",
+            "other.zip.bundle",
+        ]);
+        assert_eq!(
+            find_bundle_asset(&assets, "tool.tar.gz").unwrap().name,
+            "tool.tar.gz.sigstore.json"
+        );
+        assert!(find_bundle_asset(&assets, "other.zip.sha256").is_none());
+    }
+}
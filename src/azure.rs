@@ -0,0 +1,337 @@
+use crate::apicache;
+use crate::github;
+use crate::http::HttpContext;
+use crate::platform::Asset;
+use crate::provider::{Latest, Provider, Release};
+use reqwest::Url;
+use reqwest::blocking::RequestBuilder;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Only the fields we need from the feed's package listing.
+#[derive(Deserialize)]
+struct PackageList {
+    value: Vec<Package>,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    name: String,
+    versions: Vec<PackageVersion>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PackageVersion {
+    version: String,
+    #[serde(default)]
+    is_latest: bool,
+    publish_date: Option<String>,
+}
+
+/// A Universal Package in an Azure Artifacts feed.
+#[derive(Debug, PartialEq, Eq)]
+struct PackageRef<'u> {
+    organization: &'u str,
+    /// None for a feed scoped to the whole organization.
+    project: Option<&'u str>,
+    feed: &'u str,
+    name: &'u str,
+    version: Option<&'u str>,
+}
+
+/// Parses an Azure Artifacts URL into the package it names.
+///
+/// Supported patterns:
+///   azdo://organization/project/feed/package
+///   azdo://organization/feed/package@1.2.0   (an organization-scoped feed)
+fn parse_azdo_url(url: &str) -> Option<PackageRef<'_>> {
+    let path = url.strip_prefix("azdo://")?.trim_end_matches('/');
+    let (path, version) = match path.rsplit_once('@') {
+        Some((path, version)) => (path, Some(version).filter(|v| !v.is_empty())),
+        None => (path, None),
+    };
+    let segments: Vec<&str> = path.split('/').collect();
+    if segments.iter().any(|s| s.is_empty()) {
+        return None;
+    }
+    let (organization, project, feed, name) = match segments.as_slice() {
+        [organization, feed, name] => (*organization, None, *feed, *name),
+        [organization, project, feed, name] => (*organization, Some(*project), *feed, *name),
+        _ => return None,
+    };
+    Some(PackageRef {
+        organization,
+        project,
+        feed,
+        name,
+        version,
+    })
+}
+
+/// Adds authentication from AZURE_DEVOPS_EXT_PAT, if set: a personal access
+/// token, sent as basic auth as Azure DevOps expects. The az CLI reads the
+/// same variable.
+fn authenticate(request: RequestBuilder) -> RequestBuilder {
+    match std::env::var("AZURE_DEVOPS_EXT_PAT") {
+        Ok(token) => request.basic_auth("", Some(token)),
+        Err(_) => request,
+    }
+}
+
+/// The version `latest` picks: the one the feed marks latest (or published
+/// last), or the highest.
+fn latest_version(versions: &[PackageVersion], latest: Latest) -> Option<&str> {
+    let best = match latest {
+        Latest::Semver => versions
+            .iter()
+            .filter_map(|v| Some((github::tag_version(&v.version)?, v)))
+            .max_by(|a, b| a.0.cmp(&b.0))
+            .map(|(_, v)| v),
+        Latest::Date => None,
+    };
+    best.or_else(|| versions.iter().find(|v| v.is_latest))
+        .or_else(|| versions.iter().max_by_key(|v| &v.publish_date))
+        .map(|v| v.version.as_str())
+}
+
+/// A Universal Package in an Azure Artifacts feed. Its files can only be
+/// fetched with the az CLI, so the package is downloaded whole into dex's
+/// cache and its files offered from there.
+pub struct AzureArtifacts;
+
+impl Provider for AzureArtifacts {
+    fn name(&self) -> &'static str {
+        "Azure Artifacts package"
+    }
+
+    fn matches(&self, url: &str) -> bool {
+        parse_azdo_url(url).is_some()
+    }
+
+    fn local_assets(&self) -> bool {
+        true
+    }
+
+    fn repo_name<'u>(&self, url: &'u str) -> Option<&'u str> {
+        parse_azdo_url(url).map(|p| p.name)
+    }
+
+    fn fetch_release(
+        &self,
+        http: &HttpContext,
+        url: &str,
+        latest: Latest,
+    ) -> Result<Release, Box<dyn std::error::Error>> {
+        let package = parse_azdo_url(url).ok_or("Not a valid Azure Artifacts URL")?;
+        let version = match package.version {
+            Some(version) => version.to_string(),
+            None => fetch_latest_version(http, &package, latest)?,
+        };
+        // Each of these names a directory in the cache.
+        for segment in [package.organization, package.feed, package.name, &version] {
+            if !is_path_segment(segment) {
+                return Err(
+                    format!("'{segment}' isn't a valid name in an Azure Artifacts URL").into(),
+                );
+            }
+        }
+        let dir = crate::dirs::cache_dir()
+            .ok_or("no cache directory to download the package into")?
+            .join("azure-artifacts")
+            .join(package.organization)
+            .join(package.feed)
+            .join(package.name)
+            .join(&version);
+        if !dir.is_dir() {
+            download_package(&package, &version, &dir)?;
+        }
+        let mut assets = Vec::new();
+        list_files(&dir, &dir, &mut assets)?;
+        assets.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(Release {
+            owner: package.feed.to_string(),
+            repo: package.name.to_string(),
+            tag: version,
+            assets,
+            notes: None,
+            source_tarball: None,
+        })
+    }
+}
+
+/// Whether `segment` is a single plain path component, so joining it onto a
+/// directory can't climb out of it.
+fn is_path_segment(segment: &str) -> bool {
+    !matches!(segment, "" | "." | "..") && !segment.contains(['/', '\\', ':'])
+}
+
+/// Asks the feed for the package's versions and picks the latest.
+fn fetch_latest_version(
+    http: &HttpContext,
+    package: &PackageRef,
+    latest: Latest,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let project = package.project.map(|p| format!("{p}/")).unwrap_or_default();
+    let mut url = Url::parse(&format!(
+        "https://feeds.dev.azure.com/{}/{project}_apis/packaging/feeds/{}/packages",
+        package.organization, package.feed
+    ))?;
+    url.query_pairs_mut()
+        .append_pair("protocolType", "UPack")
+        .append_pair("packageNameQuery", package.name)
+        .append_pair("includeAllVersions", "true")
+        .append_pair("api-version", "7.1");
+    let body = apicache::fetch_text(http, authenticate(http.get(url.as_str())))?;
+    let list: PackageList = serde_json::from_str(&body)?;
+    // The query matches names containing it; package names ignore case.
+    let found = list
+        .value
+        .iter()
+        .find(|p| p.name.eq_ignore_ascii_case(package.name))
+        .ok_or_else(|| format!("feed {} has no package {}", package.feed, package.name))?;
+    latest_version(&found.versions, latest)
+        .map(str::to_string)
+        .ok_or_else(|| format!("package {} has no versions", package.name).into())
+}
+
+/// Downloads the package with `az artifacts universal download` into `dir`,
+/// by way of a scratch directory so a failed download leaves nothing behind.
+fn download_package(
+    package: &PackageRef,
+    version: &str,
+    dir: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let scratch = dir.with_file_name(format!("{version}.part-{}", std::process::id()));
+    std::fs::create_dir_all(&scratch)?;
+    let mut command = Command::new(if cfg!(windows) { "az.cmd" } else { "az" });
+    command
+        .args(["artifacts", "universal", "download"])
+        .arg("--organization")
+        .arg(format!("https://dev.azure.com/{}", package.organization))
+        .args(["--feed", package.feed, "--name", package.name])
+        .args(["--version", version])
+        .arg("--path")
+        .arg(&scratch);
+    match package.project {
+        Some(project) => command.args(["--project", project, "--scope", "project"]),
+        None => command.args(["--scope", "organization"]),
+    };
+    // Results go to stdout; az's chatter doesn't.
+    command.stdout(std::io::stderr());
+    eprintln!("Downloading {} {version} with az…", package.name);
+    let status = command.status().map_err(|e| {
+        let _ = std::fs::remove_dir_all(&scratch);
+        if e.kind() == std::io::ErrorKind::NotFound {
+            "the az CLI is needed for Azure Artifacts packages (with its azure-devops extension)"
+                .to_string()
+        } else {
+            format!("running az: {e}")
+        }
+    })?;
+    if !status.success() {
+        let _ = std::fs::remove_dir_all(&scratch);
+        return Err(format!("az artifacts universal download failed ({status})").into());
+    }
+    std::fs::rename(&scratch, dir)?;
+    Ok(())
+}
+
+/// Every file under `dir`, as assets named by their path below `root`.
+fn list_files(
+    root: &Path,
+    dir: &Path,
+    assets: &mut Vec<Asset>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for entry in std::fs::read_dir(dir)? {
+        let path: PathBuf = entry?.path();
+        if path.is_dir() {
+            list_files(root, &path, assets)?;
+            continue;
+        }
+        let name = path
+            .strip_prefix(root)?
+            .to_string_lossy()
+            .replace('\\', "/");
+        let url = Url::from_file_path(&path)
+            .map_err(|()| format!("can't make a URL of {}", path.display()))?;
+        assets.push(Asset {
+            name,
+            url: url.to_string(),
+            digest: None,
+            size: Some(path.metadata()?.len()),
+            content_type: None,
+            updated_at: None,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_azdo_url() {
+        assert_eq!(
+            parse_azdo_url("azdo://contoso/tools/internal/cli@1.4.0"),
+            Some(PackageRef {
+                organization: "contoso",
+                project: Some("tools"),
+                feed: "internal",
+                name: "cli",
+                version: Some("1.4.0"),
+            })
+        );
+        let org_scoped = parse_azdo_url("azdo://contoso/shared/cli/").unwrap();
+        assert_eq!(org_scoped.project, None);
+        assert_eq!(org_scoped.feed, "shared");
+        assert_eq!(org_scoped.version, None);
+        assert!(parse_azdo_url("azdo://contoso/cli").is_none());
+        assert!(parse_azdo_url("azdo://contoso//feed/cli").is_none());
+        assert!(parse_azdo_url("https://dev.azure.com/contoso").is_none());
+    }
+
+    #[test]
+    fn test_rejects_paths_out_of_the_cache() {
+        let http = HttpContext::new(&Default::default()).unwrap();
+        for url in [
+            "azdo://contoso/feed/cli@../../x",
+            "azdo://contoso/feed/cli@..",
+            "azdo://contoso/feed/..@1.0",
+            "azdo://contoso/feed/cli@..\\..\\x",
+        ] {
+            let error = AzureArtifacts
+                .fetch_release(&http, url, Latest::default())
+                .err()
+                .unwrap();
+            assert!(
+                error.to_string().contains("isn't a valid name"),
+                "{url}: {error}"
+            );
+        }
+        assert!(is_path_segment("1.4.0-beta"));
+    }
+
+    #[test]
+    fn test_parse_package_list() {
+        let json = r#"{
+            "count": 1,
+            "value": [{
+                "id": "3f2a",
+                "name": "cli",
+                "protocolType": "UPack",
+                "versions": [
+                    {"version": "1.10.0", "isLatest": false, "publishDate": "2024-03-01T00:00:00Z"},
+                    {"version": "1.9.3", "isLatest": true, "publishDate": "2024-04-01T00:00:00Z"}
+                ]
+            }]
+        }"#;
+        let list: PackageList = serde_json::from_str(json).unwrap();
+        let versions = &list.value[0].versions;
+        assert_eq!(latest_version(versions, Latest::Date), Some("1.9.3"));
+        assert_eq!(latest_version(versions, Latest::Semver), Some("1.10.0"));
+        assert_eq!(latest_version(&[], Latest::Date), None);
+    }
+}
@@ -0,0 +1,264 @@
+use crate::stats::Stats;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Why a download failed. Each kind has its own exit code so scripts can
+/// tell a flaky network from a tampered file:
+///
+///   0  everything succeeded
+///   1  any other failure (bad arguments, unknown format, install errors)
+///   2  partial failure: some downloads of a batch succeeded, some didn't
+///   3  network: the server couldn't be reached or refused the request
+///   4  verification: a checksum or attestation didn't match
+///   5  extraction: the archive couldn't be unpacked
+///   6  unchanged: --if-newer found the local copy current (not really a
+///      failure, but scripts need to tell it apart from a fresh download)
+///
+/// The reason itself is printed where the failure happens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failed {
+    Other,
+    Partial,
+    Network,
+    Verification,
+    Extraction,
+    Unchanged,
+}
+
+impl Failed {
+    /// Sorts an error from resolving or fetching into network or other.
+    pub fn classify(error: &(dyn std::error::Error + 'static)) -> Failed {
+        if error.is::<reqwest::Error>()
+            || error.is::<crate::github::RateLimited>()
+            || error.is::<crate::download::Stalled>()
+            || error.is::<crate::http::Offline>()
+        {
+            Failed::Network
+        } else {
+            Failed::Other
+        }
+    }
+
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Failed::Other => 1,
+            Failed::Partial => 2,
+            Failed::Network => 3,
+            Failed::Verification => 4,
+            Failed::Extraction => 5,
+            Failed::Unchanged => 6,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Failed::Other => "failed",
+            Failed::Partial => "partly failed",
+            Failed::Network => "network error",
+            Failed::Verification => "verification failed",
+            Failed::Extraction => "extraction failed",
+            Failed::Unchanged => "unchanged",
+        }
+    }
+}
+
+/// The result for one item of a batch; `None` if --fail-fast stopped the
+/// batch before it started.
+pub type Outcome = Option<Result<(), Failed>>;
+
+/// Reads a URL list from a file, or from standard input when `path` is "-".
+pub fn read_url_list(path: &Path) -> io::Result<Vec<String>> {
+    let text = if path == Path::new("-") {
+        let mut text = String::new();
+        io::stdin().read_to_string(&mut text)?;
+        text
+    } else {
+        std::fs::read_to_string(path)?
+    };
+    Ok(parse_url_list(&text))
+}
+
+/// Splits a URL list on whitespace. A "#" at the start of a word comments out
+/// the rest of its line; a "#" inside a URL is a fragment and is kept.
+pub fn parse_url_list(text: &str) -> Vec<String> {
+    text.lines()
+        .flat_map(|line| {
+            line.split_whitespace()
+                .take_while(|word| !word.starts_with('#'))
+        })
+        .map(String::from)
+        .collect()
+}
+
+/// Runs `work` on every item, `jobs` at a time, returning each item's
+/// outcome in order. With `fail_fast`, nothing new starts once an item has
+/// failed (items already running finish).
+pub fn run<T: Sync>(
+    items: &[T],
+    jobs: usize,
+    fail_fast: bool,
+    work: impl Fn(&T) -> Result<(), Failed> + Sync,
+) -> Vec<Outcome> {
+    let queue = Mutex::new(items.iter().enumerate());
+    let outcomes = Mutex::new(vec![None; items.len()]);
+    let stop = std::sync::atomic::AtomicBool::new(false);
+
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.max(1) {
+            scope.spawn(|| {
+                loop {
+                    if stop.load(std::sync::atomic::Ordering::Relaxed) {
+                        break;
+                    }
+                    let Some((i, item)) = queue.lock().unwrap().next() else {
+                        break;
+                    };
+                    let result = work(item);
+                    if result.is_err() && fail_fast {
+                        stop.store(true, std::sync::atomic::Ordering::Relaxed);
+                    }
+                    outcomes.lock().unwrap()[i] = Some(result);
+                }
+            });
+        }
+    });
+
+    outcomes.into_inner().unwrap()
+}
+
+/// Sums up a batch: fine if nothing failed, `Partial` if some items
+/// succeeded, otherwise the failures' shared kind (`Other` if they differ).
+pub fn overall(outcomes: &[Outcome]) -> Result<(), Failed> {
+    let failures: Vec<Failed> = outcomes.iter().flatten().filter_map(|r| r.err()).collect();
+    let Some(&first) = failures.first() else {
+        return Ok(());
+    };
+    if outcomes.iter().flatten().any(|r| r.is_ok()) {
+        Err(Failed::Partial)
+    } else if failures.iter().all(|&f| f == first) {
+        Err(first)
+    } else {
+        Err(Failed::Other)
+    }
+}
+
+/// Prints what happened to each URL of a batch, in list order.
+pub fn print_summary(urls: &[String], outcomes: &[Outcome]) {
+    let count = |wanted: fn(&Outcome) -> bool| outcomes.iter().filter(|o| wanted(o)).count();
+    let succeeded = count(|o| matches!(o, Some(Ok(()))));
+    let failed = count(|o| matches!(o, Some(Err(_))));
+    let skipped = count(|o| o.is_none());
+
+    eprintln!();
+    if skipped > 0 {
+        eprintln!("{succeeded} succeeded, {failed} failed, {skipped} skipped");
+    } else {
+        eprintln!("{succeeded} succeeded, {failed} failed");
+    }
+    for (url, outcome) in urls.iter().zip(outcomes) {
+        eprintln!("  {:<19}  {url}", status(outcome));
+    }
+}
+
+/// The --json report: each URL's outcome, the exit code, and `stats`.
+pub fn json_report(urls: &[String], outcomes: &[Outcome], stats: &Stats) -> serde_json::Value {
+    let results: Vec<serde_json::Value> = urls
+        .iter()
+        .zip(outcomes)
+        .map(|(url, outcome)| serde_json::json!({ "url": url, "status": status(outcome) }))
+        .collect();
+    let exit_code = overall(outcomes).err().map_or(0, Failed::exit_code);
+    serde_json::json!({ "exit_code": exit_code, "results": results, "stats": stats })
+}
+
+/// One word or phrase for an outcome: "ok", "skipped", or the failure.
+pub fn status(outcome: &Outcome) -> &'static str {
+    match outcome {
+        Some(Ok(())) => "ok",
+        Some(Err(failed)) => failed.describe(),
+        None => "skipped",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url_list() {
+        let text = "\
+# release mirrors
+https://example.com/a.tar.gz https://example.com/b.zip
+
+  https://example.com/c.tar.gz   # nightly
+https://example.com/d.tar.gz#sha256=abc
+";
+        assert_eq!(
+            parse_url_list(text),
+            [
+                "https://example.com/a.tar.gz",
+                "https://example.com/b.zip",
+                "https://example.com/c.tar.gz",
+                "https://example.com/d.tar.gz#sha256=abc",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_empty_list() {
+        assert!(parse_url_list("\n# nothing here\n\n").is_empty());
+    }
+
+    // ── Running batches ─────────────────────────────────────────────
+
+    #[test]
+    fn test_run_keep_going() {
+        let outcomes = run(&[1, 2, 3], 1, false, |&n| {
+            if n == 2 { Err(Failed::Network) } else { Ok(()) }
+        });
+        assert_eq!(
+            outcomes,
+            [Some(Ok(())), Some(Err(Failed::Network)), Some(Ok(()))]
+        );
+        assert_eq!(overall(&outcomes), Err(Failed::Partial));
+    }
+
+    #[test]
+    fn test_run_fail_fast() {
+        let outcomes = run(&[1, 2, 3], 1, true, |&n| {
+            if n == 1 {
+                Err(Failed::Verification)
+            } else {
+                Ok(())
+            }
+        });
+        assert_eq!(outcomes, [Some(Err(Failed::Verification)), None, None]);
+        assert_eq!(overall(&outcomes), Err(Failed::Verification));
+    }
+
+    #[test]
+    fn test_overall() {
+        assert_eq!(overall(&[Some(Ok(())), Some(Ok(()))]), Ok(()));
+        assert_eq!(
+            overall(&[Some(Err(Failed::Network)), Some(Err(Failed::Extraction))]),
+            Err(Failed::Other)
+        );
+        assert_eq!(overall(&[]), Ok(()));
+    }
+
+    #[test]
+    fn test_json_report() {
+        let urls = [
+            "https://example.com/a.zip".to_string(),
+            "https://example.com/b.zip".to_string(),
+        ];
+        let outcomes = [Some(Ok(())), Some(Err(Failed::Extraction))];
+        let report = json_report(&urls, &outcomes, &Stats::default());
+        assert_eq!(report["exit_code"], 2);
+        assert_eq!(report["results"][0]["status"], "ok");
+        assert_eq!(report["results"][1]["status"], "extraction failed");
+        assert_eq!(report["results"][1]["url"], "https://example.com/b.zip");
+        assert_eq!(report["stats"]["downloads"], 0);
+    }
+}
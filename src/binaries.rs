@@ -0,0 +1,598 @@
+use crate::i18n::tr;
+use std::io::{BufRead, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// How a file was recognized as executable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Elf,
+    MachO,
+    Pe,
+    /// A `#!` script with an execute bit.
+    Script,
+    /// Anything else with an execute bit.
+    Executable,
+}
+
+/// A file in an extracted tree that might be the binary to install.
+#[derive(Debug)]
+pub struct Candidate {
+    pub path: PathBuf,
+    pub format: Format,
+    pub score: i32,
+}
+
+/// What a native executable was built for, going by its header.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Machine {
+    pub format: Format,
+    /// One arch, or several for a universal Mach-O binary.
+    pub arches: Vec<&'static str>,
+}
+
+impl Machine {
+    /// Whether the binary runs on `platform` and one of `arches` (canonical
+    /// names, as `platform::canonical_arch` gives). ELF binaries are taken
+    /// to be for any platform but macOS and Windows, since the BSDs share
+    /// the format and few binaries record which one they're for.
+    pub fn fits(&self, platform: &str, arches: &[&str]) -> bool {
+        let platform_fits = match self.format {
+            Format::Elf => platform != "macos" && platform != "windows",
+            Format::MachO => platform == "macos",
+            Format::Pe => platform == "windows",
+            Format::Script | Format::Executable => true,
+        };
+        let arch_fits = arches.iter().any(|arch| {
+            self.arches.contains(arch) || (*arch == "universal" && self.arches.len() > 1)
+        });
+        platform_fits && arch_fits
+    }
+
+    /// "ELF x86_64", say, or "Mach-O x86_64 + arm64".
+    pub fn describe(&self) -> String {
+        let format = match self.format {
+            Format::Elf => "ELF",
+            Format::MachO => "Mach-O",
+            Format::Pe => "PE",
+            Format::Script | Format::Executable => "executable",
+        };
+        format!("{format} {}", self.arches.join(" + "))
+    }
+}
+
+/// A binary built for another platform or arch than the one asked for
+/// (--strict-arch).
+#[derive(Debug)]
+pub struct ArchMismatch(pub String);
+
+impl std::fmt::Display for ArchMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ArchMismatch {}
+
+/// Reads what the native executable at `path` was built for. None when it
+/// isn't one, or its machine type isn't one dex knows.
+pub fn machine(path: &Path) -> Option<Machine> {
+    let mut header = Vec::with_capacity(4096);
+    std::fs::File::open(path)
+        .ok()?
+        .take(4096)
+        .read_to_end(&mut header)
+        .ok()?;
+    parse_machine(&header)
+}
+
+fn parse_machine(header: &[u8]) -> Option<Machine> {
+    let u16_at = |at: usize, big: bool| {
+        let bytes: [u8; 2] = header.get(at..at + 2)?.try_into().ok()?;
+        Some(match big {
+            true => u16::from_be_bytes(bytes),
+            false => u16::from_le_bytes(bytes),
+        })
+    };
+    let u32_at = |at: usize, big: bool| {
+        let bytes: [u8; 4] = header.get(at..at + 4)?.try_into().ok()?;
+        Some(match big {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes),
+        })
+    };
+    let (format, arches) = match magic_format(header)? {
+        Format::Elf => {
+            // e_ident: class (1 = 32-bit, 2 = 64-bit), then byte order.
+            let wide = *header.get(4)? == 2;
+            let big = *header.get(5)? == 2;
+            let arch = match u16_at(18, big)? {
+                0x03 => "x86",
+                0x3e => "x86_64",
+                0x28 => "arm",
+                0xb7 => "arm64",
+                0x15 if !big => "ppc64le",
+                0x16 if wide => "s390x",
+                0xf3 if wide => "riscv64",
+                _ => return None,
+            };
+            (Format::Elf, vec![arch])
+        }
+        Format::MachO => {
+            let macho_arch = |cputype: u32| match cputype {
+                0x0000_0007 => Some("x86"),
+                0x0100_0007 => Some("x86_64"),
+                0x0000_000c => Some("arm"),
+                0x0100_000c => Some("arm64"),
+                _ => None,
+            };
+            match header[..4] {
+                // Universal: a big-endian count of slices, 20 bytes each.
+                // Java class files share the magic, but put a version of
+                // 45 or more where the count goes.
+                [0xca, 0xfe, 0xba, 0xbe] => {
+                    let count = u32_at(4, true)?;
+                    if count == 0 || count > 16 {
+                        return None;
+                    }
+                    let arches = (0..count as usize)
+                        .map(|i| macho_arch(u32_at(8 + i * 20, true)?))
+                        .collect::<Option<Vec<_>>>()?;
+                    (Format::MachO, arches)
+                }
+                [0xfe, ..] => (Format::MachO, vec![macho_arch(u32_at(4, true)?)?]),
+                _ => (Format::MachO, vec![macho_arch(u32_at(4, false)?)?]),
+            }
+        }
+        Format::Pe => {
+            // The MZ stub gives the offset of the "PE\0\0" header.
+            let pe = u32_at(0x3c, false)? as usize;
+            if header.get(pe..pe + 4)? != b"PE\0\0" {
+                return None;
+            }
+            let arch = match u16_at(pe + 4, false)? {
+                0x014c => "x86",
+                0x8664 => "x86_64",
+                0x01c4 => "arm",
+                0xaa64 => "arm64",
+                _ => return None,
+            };
+            (Format::Pe, vec![arch])
+        }
+        Format::Script | Format::Executable => return None,
+    };
+    Some(Machine { format, arches })
+}
+
+/// Whether the file at `path` looks executable: native executable magic,
+/// or an execute bit (which a script also needs).
+pub fn is_executable(path: &Path) -> bool {
+    classify(path).is_some()
+}
+
+/// Reads the first bytes of a file and identifies native executable formats.
+fn detect_magic(path: &Path) -> Option<Format> {
+    let mut magic = [0u8; 4];
+    std::fs::File::open(path)
+        .ok()?
+        .read_exact(&mut magic)
+        .ok()?;
+    magic_format(&magic)
+}
+
+fn magic_format(header: &[u8]) -> Option<Format> {
+    match header {
+        [0x7f, b'E', b'L', b'F', ..] => Some(Format::Elf),
+        // 32/64-bit, both byte orders, plus universal ("fat") binaries.
+        // 0xCAFEBABE is shared with Java class files, which the .class
+        // filter in `find_candidates` keeps out.
+        [0xfe, 0xed, 0xfa, 0xce | 0xcf, ..]
+        | [0xce | 0xcf, 0xfa, 0xed, 0xfe, ..]
+        | [0xca, 0xfe, 0xba, 0xbe, ..] => Some(Format::MachO),
+        [b'M', b'Z', ..] => Some(Format::Pe),
+        [b'#', b'!', ..] => Some(Format::Script),
+        _ => None,
+    }
+}
+
+/// Whether a file starting with `header` is a native executable (ELF,
+/// Mach-O, or PE), going by its first four bytes.
+pub fn is_native(header: &[u8]) -> bool {
+    matches!(
+        magic_format(header),
+        Some(Format::Elf | Format::MachO | Format::Pe)
+    )
+}
+
+#[cfg(unix)]
+fn has_exec_bit(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).is_ok_and(|m| m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn has_exec_bit(_path: &Path) -> bool {
+    false
+}
+
+/// Classifies a file, or returns None if it doesn't look executable.
+/// Native binaries are recognized by magic alone (zip archives don't carry
+/// mode bits); scripts and unknown formats need an execute bit.
+fn classify(path: &Path) -> Option<Format> {
+    let exec = has_exec_bit(path);
+    match detect_magic(path) {
+        Some(Format::Script) if !exec => None,
+        Some(format) => Some(format),
+        None if exec => Some(Format::Executable),
+        None => None,
+    }
+}
+
+// Files that can carry executable magic or mode bits but are never the tool itself.
+const IGNORED_EXTENSIONS: &[&str] = &[
+    ".so", ".dylib", ".dll", ".a", ".o", ".class", ".jar", ".txt", ".md", ".1",
+];
+
+/// Scores a candidate: higher means more likely to be "the" binary.
+///
+///   +10  file stem equals the tool name
+///    +5  file stem contains the tool name (or vice versa)
+///    +3  lives in a `bin/` directory
+///    +2  native binary (ELF, Mach-O, PE) rather than a script
+///    -1  per directory level below the extraction root
+fn score(path: &Path, root: &Path, format: Format, tool: &str) -> i32 {
+    let mut score = 0;
+
+    let stem = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let stem = stem.strip_suffix(".exe").unwrap_or(&stem);
+    let tool = tool.to_lowercase();
+
+    if stem == tool {
+        score += 10;
+    } else if !tool.is_empty() && (stem.contains(&tool) || tool.contains(stem)) {
+        score += 5;
+    }
+
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    if relative
+        .parent()
+        .and_then(|p| p.file_name())
+        .is_some_and(|d| d == "bin")
+    {
+        score += 3;
+    }
+
+    if matches!(format, Format::Elf | Format::MachO | Format::Pe) {
+        score += 2;
+    }
+
+    let depth = relative.components().count().saturating_sub(1);
+    score - depth as i32
+}
+
+/// Recursively collects files under `dir`. Symlinks are not followed.
+pub fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            walk(&entry.path(), files)?;
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// Finds executables under `root`, best candidate first.
+pub fn find_candidates(root: &Path, tool: &str) -> std::io::Result<Vec<Candidate>> {
+    let mut files = Vec::new();
+    walk(root, &mut files)?;
+    Ok(rank(root, files, tool))
+}
+
+/// The executables among `files` (extracted under `root`), best candidate first.
+pub fn rank(root: &Path, files: Vec<PathBuf>, tool: &str) -> Vec<Candidate> {
+    let mut candidates: Vec<Candidate> = files
+        .into_iter()
+        .filter(|path| {
+            let name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_lowercase())
+                .unwrap_or_default();
+            !IGNORED_EXTENSIONS.iter().any(|ext| name.ends_with(ext)) && !name.contains(".so.")
+        })
+        .filter_map(|path| {
+            let format = classify(&path)?;
+            let score = score(&path, root, format, tool);
+            Some(Candidate {
+                path,
+                format,
+                score,
+            })
+        })
+        .collect();
+
+    candidates.sort_by(|a, b| b.score.cmp(&a.score).then(a.path.cmp(&b.path)));
+    candidates
+}
+
+/// Deletes the files among `files` that don't look executable (see
+/// `classify`) for --binaries-only, then any directories under `root` that
+/// leaves empty. Docs, licenses, and completion scripts carry neither
+/// executable magic nor an execute bit, so they go. Returns the files kept
+/// and how many were removed.
+pub fn keep_executables(
+    root: &Path,
+    files: Vec<PathBuf>,
+) -> std::io::Result<(Vec<PathBuf>, usize)> {
+    let (kept, removed): (Vec<PathBuf>, Vec<PathBuf>) = files
+        .into_iter()
+        .partition(|path| path.is_dir() || is_executable(path));
+    for path in &removed {
+        std::fs::remove_file(path)?;
+    }
+    for path in &removed {
+        // Emptied directories go too, stopping at the first one that isn't.
+        for dir in path.ancestors().skip(1) {
+            if dir == root || !dir.starts_with(root) || std::fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+    }
+    Ok((kept, removed.len()))
+}
+
+/// Picks the binary to install from `candidates` (sorted best first).
+///
+/// A single best-scoring candidate wins outright. On a tie, the user is asked
+/// to choose, answering on `input`, when `interactive` (stdin is a terminal);
+/// otherwise it's an error listing the options.
+pub fn choose(
+    candidates: &[Candidate],
+    root: &Path,
+    interactive: bool,
+    mut input: impl BufRead,
+) -> Result<usize, String> {
+    let Some(best) = candidates.first() else {
+        return Err("no executables found in the archive".into());
+    };
+
+    let tied = candidates
+        .iter()
+        .take_while(|c| c.score == best.score)
+        .count();
+    if tied == 1 {
+        return Ok(0);
+    }
+
+    let display = |c: &Candidate| {
+        format!(
+            "{} ({:?})",
+            c.path.strip_prefix(root).unwrap_or(&c.path).display(),
+            c.format
+        )
+    };
+
+    if !interactive {
+        let names: Vec<String> = candidates[..tied].iter().map(display).collect();
+        return Err(format!(
+            "several executables could be the binary: {}",
+            names.join(", ")
+        ));
+    }
+
+    eprintln!("{}", tr!("binary-choice-header"));
+    for (i, candidate) in candidates[..tied].iter().enumerate() {
+        eprintln!("  {}) {}", i + 1, display(candidate));
+    }
+    loop {
+        eprint!("{} ", tr!("binary-choice-prompt", count = tied));
+        let _ = std::io::stderr().flush();
+
+        let mut line = String::new();
+        if input.read_line(&mut line).unwrap_or(0) == 0 {
+            return Err(tr!("binary-choice-none"));
+        }
+        match line.trim().parse::<usize>() {
+            Ok(n) if (1..=tied).contains(&n) => return Ok(n - 1),
+            _ => eprintln!("{}", tr!("binary-choice-retry", count = tied)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dex-binaries-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(root: &Path, relative: &str, contents: &[u8]) -> PathBuf {
+        let path = root.join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    const ELF: &[u8] = b"\x7fELF\x02\x01\x01\0";
+    const MACHO: &[u8] = b"\xcf\xfa\xed\xfe\x07\0\0\x01";
+
+    // ── Magic detection ─────────────────────────────────────────────
+
+    #[test]
+    fn test_detect_magic() {
+        let root = temp_dir("magic");
+        assert_eq!(detect_magic(&write(&root, "a", ELF)), Some(Format::Elf));
+        assert_eq!(detect_magic(&write(&root, "b", MACHO)), Some(Format::MachO));
+        assert_eq!(
+            detect_magic(&write(&root, "c.exe", b"MZ\x90\0")),
+            Some(Format::Pe)
+        );
+        assert_eq!(detect_magic(&write(&root, "d", b"hello")), None);
+        assert_eq!(detect_magic(&write(&root, "e", b"x")), None);
+        assert!(is_native(ELF) && is_native(b"MZ"));
+        assert!(!is_native(b"#!/bin/sh") && !is_native(b""));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_keep_executables() {
+        let root = temp_dir("keep");
+        let files = vec![
+            write(&root, "tool-1.0/bin/tool", ELF),
+            write(&root, "tool-1.0/README.md", b"# tool"),
+            write(&root, "tool-1.0/LICENSE", b"MIT"),
+            write(
+                &root,
+                "tool-1.0/completions/tool.bash",
+                b"#!/bin/bash\ncomplete",
+            ),
+            write(&root, "tool-1.0/docs/tool.1", b".TH TOOL 1"),
+        ];
+        let (kept, removed) = keep_executables(&root, files).unwrap();
+        assert_eq!(kept, vec![root.join("tool-1.0/bin/tool")]);
+        assert_eq!(removed, 4);
+        assert!(!root.join("tool-1.0/completions").exists());
+        assert!(!root.join("tool-1.0/docs").exists());
+        assert!(root.join("tool-1.0/bin/tool").exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    // ── Machine types ───────────────────────────────────────────────
+
+    #[test]
+    fn test_parse_machine() {
+        // ELF64, little-endian, e_machine at 18.
+        let mut elf = vec![0x7f, b'E', b'L', b'F', 2, 1, 1, 0];
+        elf.resize(20, 0);
+        elf[18..20].copy_from_slice(&0xb7u16.to_le_bytes());
+        let machine = parse_machine(&elf).unwrap();
+        assert_eq!(machine.arches, ["arm64"]);
+        assert!(machine.fits("linux", &["arm64"]));
+        assert!(machine.fits("freebsd", &["arm64"]));
+        assert!(!machine.fits("linux", &["x86_64"]));
+        assert!(!machine.fits("macos", &["arm64"]));
+        assert_eq!(machine.describe(), "ELF arm64");
+
+        // A thin 64-bit Mach-O: cputype after the magic.
+        let mut macho = MACHO[..4].to_vec();
+        macho.extend(0x0100_0007u32.to_le_bytes());
+        let machine = parse_machine(&macho).unwrap();
+        assert_eq!(machine.arches, ["x86_64"]);
+        assert!(machine.fits("macos", &["arm64", "x86_64"]));
+
+        // A universal Mach-O with x86_64 and arm64 slices.
+        let mut fat = vec![0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 2];
+        for cputype in [0x0100_0007u32, 0x0100_000c] {
+            fat.extend(cputype.to_be_bytes());
+            fat.extend([0; 16]);
+        }
+        let machine = parse_machine(&fat).unwrap();
+        assert_eq!(machine.describe(), "Mach-O x86_64 + arm64");
+        assert!(machine.fits("macos", &["arm64"]) && machine.fits("macos", &["universal"]));
+
+        // A Java class file (version 52 where the slice count would be).
+        assert_eq!(parse_machine(&[0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 52]), None);
+
+        // PE: the MZ stub points at the "PE\0\0" header at 0x80.
+        let mut pe = vec![0; 0x86];
+        pe[..2].copy_from_slice(b"MZ");
+        pe[0x3c..0x40].copy_from_slice(&0x80u32.to_le_bytes());
+        pe[0x80..0x84].copy_from_slice(b"PE\0\0");
+        pe[0x84..0x86].copy_from_slice(&0x8664u16.to_le_bytes());
+        let machine = parse_machine(&pe).unwrap();
+        assert!(machine.fits("windows", &["x86_64"]));
+        assert!(!machine.fits("linux", &["x86_64"]));
+
+        // 32-bit ELF for what --target i686-… and armv7-… name otherwise.
+        for (e_machine, target) in [
+            (0x03u16, "i686-unknown-linux-gnu"),
+            (0x28, "armv7-unknown-linux-gnueabihf"),
+        ] {
+            let mut elf = vec![0x7f, b'E', b'L', b'F', 1, 1, 1, 0];
+            elf.resize(20, 0);
+            elf[18..20].copy_from_slice(&e_machine.to_le_bytes());
+            let target = crate::platform::parse_target(target).unwrap();
+            let arch = crate::platform::canonical_arch(&target.arch);
+            assert!(parse_machine(&elf).unwrap().fits(&target.platform, &[arch]));
+            assert!(!parse_machine(&elf).unwrap().fits("linux", &["x86_64"]));
+        }
+
+        assert_eq!(parse_machine(b"#!/bin/sh\n"), None);
+        assert_eq!(parse_machine(b"\x7fELF"), None);
+    }
+
+    // ── Candidate ranking ───────────────────────────────────────────
+
+    #[test]
+    fn test_prefers_name_match() {
+        let root = temp_dir("name");
+        write(&root, "tool-1.0/tool", ELF);
+        write(&root, "tool-1.0/helper", ELF);
+        write(&root, "tool-1.0/README.md", b"# tool");
+
+        let candidates = find_candidates(&root, "tool").unwrap();
+        assert_eq!(candidates.len(), 2);
+        assert!(candidates[0].path.ends_with("tool-1.0/tool"));
+        assert_eq!(choose(&candidates, &root, false, std::io::empty()), Ok(0));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_prefers_bin_dir() {
+        let root = temp_dir("bindir");
+        write(&root, "pkg/bin/rg", ELF);
+        write(&root, "pkg/lib/rg", ELF);
+
+        let candidates = find_candidates(&root, "ripgrep").unwrap();
+        assert!(candidates[0].path.ends_with("pkg/bin/rg"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_ignores_libraries_and_plain_files() {
+        let root = temp_dir("ignore");
+        write(&root, "libtool.so", ELF);
+        write(&root, "libtool.so.1", ELF);
+        write(&root, "notes.txt", b"not a binary");
+        write(&root, "script.sh", b"#!/bin/sh\n");
+
+        let candidates = find_candidates(&root, "tool").unwrap();
+        assert!(candidates.is_empty());
+        assert!(choose(&candidates, &root, false, std::io::empty()).is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_tie_without_terminal_is_error() {
+        let root = temp_dir("tie");
+        write(&root, "alpha", ELF);
+        write(&root, "beta", ELF);
+
+        let candidates = find_candidates(&root, "tool").unwrap();
+        let err = choose(&candidates, &root, false, std::io::empty()).unwrap_err();
+        assert!(err.contains("alpha") && err.contains("beta"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_tie_at_terminal_asks() {
+        let root = temp_dir("tie-ask");
+        write(&root, "alpha", ELF);
+        write(&root, "beta", ELF);
+
+        let candidates = find_candidates(&root, "tool").unwrap();
+        // An answer out of range is asked again.
+        assert_eq!(choose(&candidates, &root, true, &b"3\n2\n"[..]), Ok(1));
+        // No answer at all is an error.
+        assert!(choose(&candidates, &root, true, std::io::empty()).is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
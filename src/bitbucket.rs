@@ -0,0 +1,233 @@
+use crate::apicache;
+use crate::http::HttpContext;
+use crate::layout;
+use crate::platform::Asset;
+use crate::provider::{Latest, Provider, Release};
+use reqwest::blocking::RequestBuilder;
+use serde::Deserialize;
+
+// Only the fields we need from the Bitbucket downloads API response.
+#[derive(Deserialize)]
+struct DownloadsPage {
+    values: Vec<BitbucketDownload>,
+    /// URL of the next page, absent on the last one.
+    next: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BitbucketDownload {
+    name: String,
+    size: Option<u64>,
+    /// Bitbucket only keeps when a file was uploaded.
+    created_on: Option<String>,
+    links: DownloadLinks,
+}
+
+#[derive(Deserialize)]
+struct DownloadLinks {
+    #[serde(rename = "self")]
+    self_link: Link,
+}
+
+#[derive(Deserialize)]
+struct Link {
+    href: String,
+}
+
+/// Returns true if the URL looks like a Bitbucket repository's downloads page.
+pub fn is_bitbucket_downloads_url(url: &str) -> bool {
+    parse_bitbucket_url(url).is_some()
+}
+
+/// Parses a Bitbucket downloads URL into (workspace, repo).
+///
+/// Supported patterns:
+///   https://bitbucket.org/workspace/repo/downloads
+///   https://bitbucket.org/workspace/repo/downloads/
+///
+/// Links to a single file (".../downloads/tool.tar.gz") are plain downloads
+/// and don't need the API.
+fn parse_bitbucket_url(url: &str) -> Option<(&str, &str)> {
+    let path = url
+        .strip_prefix("https://bitbucket.org/")
+        .or_else(|| url.strip_prefix("http://bitbucket.org/"))?;
+
+    let segments: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+    match segments.as_slice() {
+        [workspace, repo, "downloads"] => Some((workspace, repo)),
+        _ => None,
+    }
+}
+
+/// Adds authentication from BITBUCKET_TOKEN, if set. A "username:app-password"
+/// pair is sent as basic auth; anything else as a bearer (access) token.
+fn authenticate(request: RequestBuilder) -> RequestBuilder {
+    match std::env::var("BITBUCKET_TOKEN") {
+        Ok(token) => match token.split_once(':') {
+            Some((user, password)) => request.basic_auth(user, Some(password)),
+            None => request.bearer_auth(token),
+        },
+        Err(_) => request,
+    }
+}
+
+/// A Bitbucket repository's downloads section, listed through the API.
+pub struct Bitbucket;
+
+impl Provider for Bitbucket {
+    fn name(&self) -> &'static str {
+        "Bitbucket downloads"
+    }
+
+    fn matches(&self, url: &str) -> bool {
+        is_bitbucket_downloads_url(url)
+    }
+
+    fn repo_name<'u>(&self, url: &'u str) -> Option<&'u str> {
+        parse_bitbucket_url(url).map(|(_, repo)| repo)
+    }
+
+    fn fetch_release(
+        &self,
+        http: &HttpContext,
+        url: &str,
+        _: Latest,
+    ) -> Result<Release, Box<dyn std::error::Error>> {
+        // The newest upload's version is the only "latest" the downloads have.
+        fetch_release(http, url)
+    }
+}
+
+/// Fetches the newest version's files in a repository's downloads section
+/// as a release.
+///
+/// Bitbucket downloads aren't grouped into tagged releases, so the "tag" is
+/// whatever version the newest file's name carries.
+fn fetch_release(http: &HttpContext, url: &str) -> Result<Release, Box<dyn std::error::Error>> {
+    let (workspace, repo) =
+        parse_bitbucket_url(url).ok_or("Not a valid Bitbucket downloads URL")?;
+
+    let mut assets = Vec::new();
+    let mut next = Some(format!(
+        "https://api.bitbucket.org/2.0/repositories/{workspace}/{repo}/downloads"
+    ));
+    while let Some(page_url) = next {
+        let body = apicache::fetch_text(http, authenticate(http.get(&page_url)))?;
+        let page: DownloadsPage = serde_json::from_str(&body)?;
+        assets.extend(page.values.into_iter().map(|d| Asset {
+            name: d.name,
+            url: d.links.self_link.href,
+            digest: None,
+            size: d.size,
+            content_type: None,
+            updated_at: d.created_on,
+        }));
+        next = page.next;
+    }
+
+    let (tag, assets) = newest_version(assets);
+    Ok(Release {
+        owner: workspace.to_string(),
+        repo: repo.to_string(),
+        tag,
+        assets,
+        notes: None,
+        source_tarball: None,
+    })
+}
+
+/// The version of the newest upload in `assets` (listed newest first, as
+/// the API does) and the files named with that version. Without versions to
+/// tell them apart, every file is one release, tagged "downloads".
+fn newest_version(assets: Vec<Asset>) -> (String, Vec<Asset>) {
+    let Some(tag) = assets.iter().find_map(|a| layout::guess_version(&a.name)) else {
+        return ("downloads".to_string(), assets);
+    };
+    let assets = assets
+        .into_iter()
+        .filter(|a| layout::guess_version(&a.name).as_ref() == Some(&tag))
+        .collect();
+    (tag, assets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ── URL parsing ─────────────────────────────────────────────────
+
+    #[test]
+    fn test_parse_downloads_url() {
+        let expected = Some(("atlassian", "tool"));
+        assert_eq!(
+            parse_bitbucket_url("https://bitbucket.org/atlassian/tool/downloads"),
+            expected
+        );
+        assert_eq!(
+            parse_bitbucket_url("https://bitbucket.org/atlassian/tool/downloads/"),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_parse_non_downloads_url() {
+        assert!(parse_bitbucket_url("https://bitbucket.org/atlassian/tool").is_none());
+        assert!(
+            parse_bitbucket_url("https://bitbucket.org/atlassian/tool/downloads/tool.tar.gz")
+                .is_none()
+        );
+        assert!(parse_bitbucket_url("https://github.com/owner/repo/downloads").is_none());
+    }
+
+    #[test]
+    fn test_parse_downloads_page() {
+        let json = r#"{
+            "pagelen": 10,
+            "values": [
+                {
+                    "name": "tool-1.2.0-linux-amd64.tar.gz",
+                    "size": 1024,
+                    "created_on": "2024-05-01T12:00:00.000000+00:00",
+                    "links": {"self": {"href": "https://api.bitbucket.org/2.0/repositories/ws/tool/downloads/tool-1.2.0-linux-amd64.tar.gz"}}
+                }
+            ],
+            "next": "https://api.bitbucket.org/2.0/repositories/ws/tool/downloads?page=2"
+        }"#;
+        let page: DownloadsPage = serde_json::from_str(json).unwrap();
+        assert_eq!(page.values[0].name, "tool-1.2.0-linux-amd64.tar.gz");
+        assert_eq!(page.values[0].size, Some(1024));
+        assert!(page.values[0].created_on.is_some());
+        assert!(page.values[0].links.self_link.href.ends_with(".tar.gz"));
+        assert!(page.next.is_some());
+    }
+
+    #[test]
+    fn test_newest_version_only() {
+        let asset = |name: &str, size| Asset {
+            name: name.to_string(),
+            url: format!("https://bitbucket.org/ws/tool/downloads/{name}"),
+            digest: None,
+            size: Some(size),
+            content_type: None,
+            updated_at: None,
+        };
+        // The older build is smaller, so it would win a tie on size.
+        let (tag, assets) = newest_version(vec![
+            asset("tool-1.1-linux-amd64.tar.gz", 2048),
+            asset("tool-1.1-darwin-arm64.tar.gz", 2048),
+            asset("tool-1.0-linux-amd64.tar.gz", 1024),
+        ]);
+        assert_eq!(tag, "1.1");
+        let names: Vec<&str> = assets.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "tool-1.1-linux-amd64.tar.gz",
+                "tool-1.1-darwin-arm64.tar.gz"
+            ]
+        );
+
+        let (tag, assets) = newest_version(vec![asset("tool-linux.tar.gz", 1)]);
+        assert_eq!((tag.as_str(), assets.len()), ("downloads", 1));
+    }
+}
@@ -0,0 +1,141 @@
+use crate::github;
+use crate::http::HttpContext;
+use crate::provider::Release;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// What a project can be built and installed with, straight from its repository.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Toolchain {
+    /// `cargo install --git`, for a Rust project.
+    Cargo,
+    /// `go install`, for a Go module, by its module path.
+    Go(String),
+}
+
+impl std::fmt::Display for Toolchain {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Toolchain::Cargo => write!(f, "cargo"),
+            Toolchain::Go(_) => write!(f, "go"),
+        }
+    }
+}
+
+/// How to build the release's repository at its tag: with cargo if it has a
+/// Cargo.toml at the top, with go if it has a go.mod. Only GitHub
+/// repositories can be looked into.
+pub fn detect(http: &HttpContext, release: &Release) -> Option<Toolchain> {
+    let file = |path| {
+        http.fetch_text(&github::raw_file_url(
+            &release.owner,
+            &release.repo,
+            &release.tag,
+            path,
+        ))
+    };
+    if file("Cargo.toml").is_ok() {
+        return Some(Toolchain::Cargo);
+    }
+    module_path(&file("go.mod").ok()?).map(Toolchain::Go)
+}
+
+/// The module path a go.mod declares.
+fn module_path(go_mod: &str) -> Option<String> {
+    go_mod.lines().find_map(|line| {
+        let path = line.trim().strip_prefix("module")?;
+        // "module" must be a word of its own, not the start of "modules".
+        path.starts_with(char::is_whitespace)
+            .then(|| path.trim().trim_matches('"').to_string())
+    })
+}
+
+/// Builds the release's tag with `toolchain` and moves the binaries it makes
+/// into `dest`, returning where they went. The toolchain's output is shown
+/// as it goes, on stderr.
+pub fn build(
+    toolchain: &Toolchain,
+    release: &Release,
+    dest: &Path,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(dest)?;
+    let scratch = dest.join(format!(".dex-build-{}", std::process::id()));
+    let built = scratch.join("bin");
+    let mut command = match toolchain {
+        Toolchain::Cargo => {
+            let mut command = Command::new("cargo");
+            command
+                .arg("install")
+                .arg("--git")
+                .arg(format!(
+                    "https://github.com/{}/{}",
+                    release.owner, release.repo
+                ))
+                .arg("--tag")
+                .arg(&release.tag)
+                .arg("--root")
+                .arg(&scratch);
+            command
+        }
+        Toolchain::Go(module) => {
+            let mut command = Command::new("go");
+            command
+                .arg("install")
+                .arg(format!("{module}/...@{}", release.tag))
+                .env("GOBIN", &built);
+            command
+        }
+    };
+    // Results go to stdout; the build's chatter doesn't.
+    command.stdout(std::io::stderr());
+
+    let result = run(&mut command, toolchain).and_then(|()| {
+        let mut binaries = Vec::new();
+        for entry in std::fs::read_dir(&built)? {
+            let entry = entry?;
+            let path = dest.join(entry.file_name());
+            std::fs::rename(entry.path(), &path)?;
+            binaries.push(path);
+        }
+        binaries.sort();
+        Ok(binaries)
+    });
+    let _ = std::fs::remove_dir_all(&scratch);
+    match result {
+        Ok(binaries) if binaries.is_empty() => Err("the build made no binaries".into()),
+        result => result,
+    }
+}
+
+fn run(command: &mut Command, toolchain: &Toolchain) -> Result<(), Box<dyn std::error::Error>> {
+    let status = command.status().map_err(|e| {
+        if e.kind() == std::io::ErrorKind::NotFound {
+            format!("{toolchain} is not installed")
+        } else {
+            format!("running {toolchain}: {e}")
+        }
+    })?;
+    if !status.success() {
+        return Err(format!("{toolchain} install failed ({status})").into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module_path() {
+        let go_mod = "// A tool\nmodule \"github.com/owner/tool/v2\"\n\ngo 1.22\n";
+        assert_eq!(
+            module_path(go_mod).as_deref(),
+            Some("github.com/owner/tool/v2")
+        );
+        assert_eq!(
+            module_path("module github.com/owner/tool\n").as_deref(),
+            Some("github.com/owner/tool")
+        );
+        assert_eq!(module_path("modules are elsewhere\n"), None);
+    }
+}
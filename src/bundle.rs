@@ -0,0 +1,132 @@
+use crate::download::{self, DownloadOptions};
+use crate::http::HttpContext;
+use crate::project::{LOCKFILE, Locked, Lockfile};
+use crate::verify::{self, Algorithm, Digest};
+use std::path::{Path, PathBuf};
+
+/// Where a locked file is kept inside a bundle: `assets/<tool>/<build>/<asset>`.
+pub fn asset_path(tool: &str, build: &str, asset: &str) -> PathBuf {
+    Path::new("assets").join(tool).join(build).join(asset)
+}
+
+/// The digest a locked file must have.
+pub fn expected(locked: &Locked) -> Result<Digest, String> {
+    verify::parse_pinned(Algorithm::Sha256, &locked.sha256)
+        .map_err(|e| format!("{} in {LOCKFILE}: {e}", locked.asset))
+}
+
+/// Downloads every file the lockfile at `lock_path` pins, for every build,
+/// and packs them with the lockfile into the tar `bundle`. Each file is
+/// checked against its digest first. Returns how many files were packed.
+pub fn create(
+    http: &HttpContext,
+    lock_path: &Path,
+    bundle: &Path,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    if !lock_path.is_file() {
+        return Err(format!("{}: no such lockfile", lock_path.display()).into());
+    }
+    let lock = Lockfile::load(lock_path)?;
+    let scratch = std::env::temp_dir().join(format!("dex-bundle-{}", std::process::id()));
+
+    let result = (|| {
+        let mut builder = tar::Builder::new(std::fs::File::create(bundle)?);
+        builder.append_path_with_name(lock_path, LOCKFILE)?;
+        let mut count = 0;
+        for (tool, locked_tool) in &lock.tools {
+            for (build, locked) in &locked_tool.builds {
+                eprintln!("{tool} ({build}): {}", locked.asset);
+                let expected = expected(locked)?;
+                let options = DownloadOptions {
+                    algorithms: &[Algorithm::Sha256],
+                    ..Default::default()
+                };
+                let dir = scratch.join(tool).join(build);
+                let download = download::download_file(http, &locked.url, &dir, &options)?;
+                let actual = download
+                    .digest(Algorithm::Sha256)
+                    .expect("sha256 was asked for");
+                verify::check(&expected, actual).map_err(|e| format!("{}: {e}", locked.asset))?;
+                builder.append_path_with_name(
+                    &download.path,
+                    asset_path(tool, build, &locked.asset),
+                )?;
+                count += 1;
+            }
+        }
+        builder.finish()?;
+        Ok(count)
+    })();
+
+    let _ = std::fs::remove_dir_all(&scratch);
+    if result.is_err() {
+        let _ = std::fs::remove_file(bundle);
+    }
+    result
+}
+
+/// Unpacks a bundle into `dir` and returns the lockfile it carries; the
+/// files it pins are at `dir/`[`asset_path`].
+pub fn unpack(bundle: &Path, dir: &Path) -> Result<Lockfile, Box<dyn std::error::Error>> {
+    crate::extract::extract_file(bundle, dir, &Default::default())?;
+    let lock_path = dir.join(LOCKFILE);
+    if !lock_path.is_file() {
+        return Err(format!(
+            "{} is not a dex bundle (no {LOCKFILE} inside)",
+            bundle.display()
+        )
+        .into());
+    }
+    Ok(Lockfile::load(&lock_path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::LockedTool;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_unpack_reads_lockfile() {
+        let root = std::env::temp_dir().join(format!("dex-test-bundle-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let asset = root.join("tool.tar.gz");
+        std::fs::write(&asset, b"not really a tarball").unwrap();
+
+        let mut lock = Lockfile::default();
+        let locked = Locked {
+            tag: Some("v1.0".into()),
+            asset: "tool.tar.gz".into(),
+            url: "https://example.com/tool.tar.gz".into(),
+            sha256: verify::hash_file(&asset, Algorithm::Sha256).unwrap().hex,
+        };
+        let builds = BTreeMap::from([("linux-x86_64".to_string(), locked.clone())]);
+        lock.tools.insert(
+            "tool".into(),
+            LockedTool {
+                source: "owner/tool".into(),
+                builds,
+            },
+        );
+        let lock_path = root.join(LOCKFILE);
+        lock.save(&lock_path).unwrap();
+
+        // What create() writes, minus the downloading.
+        let bundle = root.join("bundle.tar");
+        let mut builder = tar::Builder::new(std::fs::File::create(&bundle).unwrap());
+        builder.append_path_with_name(&lock_path, LOCKFILE).unwrap();
+        let inside = asset_path("tool", "linux-x86_64", "tool.tar.gz");
+        builder.append_path_with_name(&asset, &inside).unwrap();
+        builder.finish().unwrap();
+        drop(builder);
+
+        let out = root.join("out");
+        let unpacked = unpack(&bundle, &out).unwrap();
+        assert_eq!(unpacked.tools, lock.tools);
+        let actual = verify::hash_file(&out.join(inside), Algorithm::Sha256).unwrap();
+        assert!(verify::check(&expected(&locked).unwrap(), &actual).is_ok());
+
+        assert!(unpack(&asset, &root.join("bad")).is_err());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,380 @@
+use crate::download::{Download, DownloadOptions};
+use crate::provider::Release;
+use crate::verify::{Algorithm, Hasher};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// What the cache knows about a stored download, kept next to it as JSON.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    url: String,
+    /// The name the server gave the file.
+    name: String,
+    /// SHA-256 of the file when it went in, checked before every reuse.
+    sha256: String,
+    /// The server's Content-Type (entries from older versions lack it).
+    #[serde(default)]
+    content_type: Option<String>,
+}
+
+/// A cached download, as `dex cache list` shows it.
+pub struct Cached {
+    pub name: String,
+    pub url: String,
+    pub size: u64,
+}
+
+/// Downloads and release listings kept for reuse (--cache, --offline), in
+/// dex's cache directory. Everything is stored under the SHA-256 of its URL:
+/// a download's file in `downloads/` with an `.json` entry beside it, a
+/// release listing in `releases/`.
+pub struct Cache {
+    root: PathBuf,
+}
+
+/// Names a URL's files in the cache.
+fn key(url: &str) -> String {
+    let mut hasher = Hasher::new(Algorithm::Sha256);
+    hasher.update(url.as_bytes());
+    hasher.finalize().hex
+}
+
+impl Cache {
+    /// The cache in dex's cache directory, or None if there's no home
+    /// directory to put it in.
+    pub fn open() -> Option<Cache> {
+        Some(Cache::at(crate::dirs::cache_dir()?))
+    }
+
+    fn at(root: PathBuf) -> Cache {
+        Cache { root }
+    }
+
+    /// The directory everything is kept in.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Where the file and entry for a download of `url` are stored.
+    fn paths(&self, url: &str) -> (PathBuf, PathBuf) {
+        let key = key(url);
+        let dir = self.root.join("downloads");
+        (dir.join(&key), dir.join(format!("{key}.json")))
+    }
+
+    fn release_path(&self, url: &str) -> PathBuf {
+        self.root
+            .join("releases")
+            .join(format!("{}.json", key(url)))
+    }
+
+    /// The release a provider last listed for `url`, if it was cached.
+    pub fn release(&self, url: &str) -> Option<Release> {
+        let json = fs::read(self.release_path(url)).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+
+    /// Remembers the release listed for `url`, so it can be resolved offline.
+    pub fn insert_release(
+        &self,
+        url: &str,
+        release: &Release,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let path = self.release_path(url);
+        let _guard = crate::filelock::lock(&path)?;
+        fs::write(path, serde_json::to_vec_pretty(release)?)?;
+        Ok(())
+    }
+
+    /// Copies the cached file for `url` into `output_dir`, as `download_file`
+    /// would have saved it, hashing it on the way.
+    ///
+    /// Returns None if `url` isn't cached. A cached file whose SHA-256 no
+    /// longer matches the one recorded when it was stored is corrupt: it's
+    /// removed, and None is returned so the caller downloads it again.
+    pub fn restore(
+        &self,
+        url: &str,
+        output_dir: &Path,
+        options: &DownloadOptions,
+    ) -> Result<Option<Download>, Box<dyn std::error::Error>> {
+        let started = std::time::Instant::now();
+        let (blob, entry_path) = self.paths(url);
+        let _guard = crate::filelock::lock(&blob)?;
+        // No entry (or an unreadable one) is simply a miss.
+        let Some(entry) = fs::read(&entry_path)
+            .ok()
+            .and_then(|json| serde_json::from_slice::<Entry>(&json).ok())
+        else {
+            return Ok(None);
+        };
+        let Ok(mut source) = fs::File::open(&blob) else {
+            return Ok(None);
+        };
+
+        let name = match options.rename {
+            Some(rename) => {
+                rename(&entry.name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?
+            }
+            None => entry.name.clone(),
+        };
+        fs::create_dir_all(output_dir)?;
+        let path = output_dir.join(&name);
+
+        // Copy and hash in one pass: SHA-256 to check the cached copy, plus
+        // whatever the caller wants to verify against.
+        let mut integrity = Hasher::new(Algorithm::Sha256);
+        let mut hashers: Vec<Hasher> = options.algorithms.iter().map(|&a| Hasher::new(a)).collect();
+        let mut output = fs::File::create(&path)?;
+        let mut buffer = [0u8; 8192];
+        let mut bytes = 0u64;
+        loop {
+            let n = source.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            output.write_all(&buffer[..n])?;
+            integrity.update(&buffer[..n]);
+            for hasher in &mut hashers {
+                hasher.update(&buffer[..n]);
+            }
+            bytes += n as u64;
+        }
+        drop(output);
+
+        let actual = integrity.finalize();
+        if actual.hex != entry.sha256 {
+            eprintln!(
+                "Warning: cached copy of {} is corrupt (sha256 {}, expected {}); downloading it again",
+                entry.name, actual.hex, entry.sha256
+            );
+            let _ = fs::remove_file(&path);
+            self.remove(url);
+            return Ok(None);
+        }
+
+        Ok(Some(Download {
+            path,
+            name: entry.name,
+            digests: hashers.into_iter().map(Hasher::finalize).collect(),
+            content_type: entry.content_type,
+            content_encoding: None,
+            etag: None,
+            bytes,
+            elapsed: started.elapsed(),
+        }))
+    }
+
+    /// Stores a copy of a finished download of `url`. Its SHA-256 must be
+    /// among the download's digests.
+    pub fn insert(&self, url: &str, download: &Download) -> Result<(), Box<dyn std::error::Error>> {
+        let sha256 = download
+            .digest(Algorithm::Sha256)
+            .ok_or("the download's SHA-256 wasn't computed")?;
+        let (blob, entry_path) = self.paths(url);
+        let _guard = crate::filelock::lock(&blob)?;
+
+        // Copy under a temporary name first, so a reader never sees half a file.
+        let mut partial = blob.clone().into_os_string();
+        partial.push(".part");
+        fs::copy(&download.path, &partial)?;
+        fs::rename(&partial, &blob)?;
+
+        let entry = Entry {
+            url: url.to_string(),
+            name: download.name.clone(),
+            sha256: sha256.hex.clone(),
+            content_type: download.content_type.clone(),
+        };
+        fs::write(entry_path, serde_json::to_vec_pretty(&entry)?)?;
+        Ok(())
+    }
+
+    /// The cached file most likely to be an earlier version of `url`'s, to
+    /// update from with --delta: the newest one whose name is the same once
+    /// the version is taken out ("tool-1.4.2-linux.tar.gz" for
+    /// "tool-1.5.0-linux.tar.gz").
+    pub fn seed_for(&self, url: &str) -> Option<PathBuf> {
+        let unversioned = |name: &str| match crate::layout::guess_version(name) {
+            Some(version) => name.replacen(&version, "", 1),
+            None => name.to_string(),
+        };
+        let wanted = unversioned(&crate::download::filename_from_url(url));
+
+        self.entries()
+            .filter(|(entry, _, _)| entry.url != url && unversioned(&entry.name) == wanted)
+            .filter_map(|(_, blob, metadata)| Some((metadata.modified().ok()?, blob)))
+            .max_by_key(|(modified, _)| *modified)
+            .map(|(_, blob)| blob)
+    }
+
+    /// Every cached download, by name.
+    pub fn list(&self) -> Vec<Cached> {
+        let mut files: Vec<Cached> = self
+            .entries()
+            .map(|(entry, _, metadata)| Cached {
+                name: entry.name,
+                url: entry.url,
+                size: metadata.len(),
+            })
+            .collect();
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        files
+    }
+
+    /// Deletes every cached download and release listing.
+    pub fn clear(&self) -> io::Result<()> {
+        match fs::remove_dir_all(&self.root) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// The downloads that have both an entry and a file, with the file's path
+    /// and metadata.
+    fn entries(&self) -> impl Iterator<Item = (Entry, PathBuf, fs::Metadata)> {
+        let dir = self.root.join("downloads");
+        fs::read_dir(dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|e| e.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| {
+                let entry: Entry = serde_json::from_slice(&fs::read(&path).ok()?).ok()?;
+                let blob = path.with_extension("");
+                let metadata = fs::metadata(&blob).ok()?;
+                Some((entry, blob, metadata))
+            })
+    }
+
+    /// Forgets `url`, deleting its cached file.
+    fn remove(&self, url: &str) {
+        let (blob, entry_path) = self.paths(url);
+        let _ = fs::remove_file(entry_path);
+        let _ = fs::remove_file(blob);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "https://example.com/tool-1.0.tar.gz";
+
+    /// A file as `download_file` would leave it, with its SHA-256.
+    fn downloaded(dir: &Path, contents: &[u8]) -> Download {
+        let path = dir.join("tool-1.0.tar.gz");
+        fs::write(&path, contents).unwrap();
+        let mut hasher = Hasher::new(Algorithm::Sha256);
+        hasher.update(contents);
+        Download {
+            path,
+            name: "tool-1.0.tar.gz".to_string(),
+            digests: vec![hasher.finalize()],
+            content_type: None,
+            content_encoding: None,
+            etag: None,
+            bytes: contents.len() as u64,
+            elapsed: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_restore_verifies_cached_copy() {
+        let dir = std::env::temp_dir().join(format!("dex-test-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cache = Cache::at(dir.join("cache"));
+        let options = DownloadOptions {
+            algorithms: &[Algorithm::Sha512],
+            ..Default::default()
+        };
+        assert!(
+            cache
+                .restore(URL, &dir.join("out"), &options)
+                .unwrap()
+                .is_none()
+        );
+
+        let download = downloaded(&dir, b"archive bytes");
+        cache.insert(URL, &download).unwrap();
+        let restored = cache
+            .restore(URL, &dir.join("out"), &options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(restored.path, dir.join("out/tool-1.0.tar.gz"));
+        assert_eq!(fs::read(&restored.path).unwrap(), b"archive bytes");
+        assert_eq!(restored.digests[0].algorithm, Algorithm::Sha512);
+
+        // Damage the cached copy: it's dropped rather than handed out.
+        let (blob, entry_path) = cache.paths(URL);
+        fs::write(&blob, b"archive bytez").unwrap();
+        assert!(
+            cache
+                .restore(URL, &dir.join("again"), &options)
+                .unwrap()
+                .is_none()
+        );
+        assert!(!entry_path.exists() && !blob.exists());
+        assert!(!dir.join("again/tool-1.0.tar.gz").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_seed_for_earlier_version() {
+        let dir = std::env::temp_dir().join(format!("dex-test-seed-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cache = Cache::at(dir.join("cache"));
+        let next = "https://example.com/tool-1.1.tar.gz";
+        assert!(cache.seed_for(next).is_none());
+
+        cache
+            .insert(URL, &downloaded(&dir, b"version one"))
+            .unwrap();
+        assert_eq!(cache.seed_for(next), Some(cache.paths(URL).0));
+        // Neither a file of another name nor the URL's own copy qualifies.
+        assert!(
+            cache
+                .seed_for("https://example.com/other-1.1.tar.gz")
+                .is_none()
+        );
+        assert!(cache.seed_for(URL).is_none());
+
+        let files = cache.list();
+        assert_eq!(files.len(), 1);
+        assert_eq!(
+            (files[0].name.as_str(), files[0].size),
+            ("tool-1.0.tar.gz", 11)
+        );
+        cache.clear().unwrap();
+        assert!(cache.list().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_release_round_trip() {
+        let dir = std::env::temp_dir().join(format!("dex-test-releases-{}", std::process::id()));
+        let cache = Cache::at(dir.clone());
+        let url = "https://github.com/owner/tool/releases";
+        assert!(cache.release(url).is_none());
+
+        let release = Release {
+            owner: "owner".into(),
+            repo: "tool".into(),
+            tag: "v1.0".into(),
+            assets: crate::platform::make_assets(&["tool-linux-amd64.tar.gz"]),
+            notes: None,
+            source_tarball: None,
+        };
+        cache.insert_release(url, &release).unwrap();
+        let cached = cache.release(url).unwrap();
+        assert_eq!(cached.tag, "v1.0");
+        assert_eq!(cached.assets[0].name, "tool-linux-amd64.tar.gz");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,92 @@
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// When to color and style output (`--color`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Choice {
+    /// At a terminal, unless `NO_COLOR` says otherwise (or `CLICOLOR_FORCE`
+    /// asks for color anyway).
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl std::str::FromStr for Choice {
+    type Err = String;
+
+    /// Parses "auto", "always", or "never".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Choice::Auto),
+            "always" => Ok(Choice::Always),
+            "never" => Ok(Choice::Never),
+            _ => Err(format!(
+                "unknown color choice {s} (expected auto, always, or never)"
+            )),
+        }
+    }
+}
+
+// Whether stdout gets color, decided once at startup. Stderr's is kept by
+// `console`, which draws the progress bars.
+static STDOUT: AtomicBool = AtomicBool::new(false);
+
+/// Settles whether stdout and stderr get color, for dex's own styling and
+/// the progress bars.
+pub fn init(choice: Choice) {
+    let env = |name: &str| std::env::var(name).ok();
+    let stdout = decide(choice, env, std::io::stdout().is_terminal());
+    let stderr = decide(choice, env, std::io::stderr().is_terminal());
+    STDOUT.store(stdout, Ordering::Relaxed);
+    // indicatif styles through `console`, which checks its stdout setting
+    // even for bars drawn on stderr, where all of dex's are.
+    console::set_colors_enabled(stderr);
+    console::set_colors_enabled_stderr(stderr);
+}
+
+/// Whether to style what goes to stdout.
+pub fn stdout() -> bool {
+    STDOUT.load(Ordering::Relaxed)
+}
+
+/// Whether a stream gets color, given the choice, the environment, and
+/// whether it's a terminal. `NO_COLOR` (https://no-color.org) turns color
+/// off, `CLICOLOR_FORCE` turns it on even when piped, and `CLICOLOR=0`
+/// turns it off; an explicit --color wins over all of them.
+fn decide(choice: Choice, env: impl Fn(&str) -> Option<String>, terminal: bool) -> bool {
+    let set = |name: &str| env(name).is_some_and(|value| !value.is_empty());
+    match choice {
+        Choice::Always => true,
+        Choice::Never => false,
+        Choice::Auto if set("NO_COLOR") => false,
+        Choice::Auto if set("CLICOLOR_FORCE") && env("CLICOLOR_FORCE").as_deref() != Some("0") => {
+            true
+        }
+        Choice::Auto => terminal && env("CLICOLOR").as_deref() != Some("0"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decide() {
+        let none = |_: &str| None;
+        assert!(decide(Choice::Auto, none, true));
+        assert!(!decide(Choice::Auto, none, false));
+        assert!(decide(Choice::Always, none, false));
+
+        let no_color = |name: &str| (name == "NO_COLOR").then(|| "1".to_string());
+        assert!(!decide(Choice::Auto, no_color, true));
+        assert!(decide(Choice::Always, no_color, true));
+
+        let forced = |name: &str| (name == "CLICOLOR_FORCE").then(|| "1".to_string());
+        assert!(decide(Choice::Auto, forced, false));
+        assert!(!decide(Choice::Never, forced, true));
+
+        let empty = |_: &str| Some(String::new());
+        assert!(decide(Choice::Auto, empty, true));
+    }
+}
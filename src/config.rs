@@ -0,0 +1,199 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// User configuration, read from `config.toml`. Every section is optional.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    pub selection: SelectionConfig,
+    /// Host pattern → settings for requests to matching hosts.
+    pub hosts: BTreeMap<String, HostConfig>,
+    /// URL prefix → prefixes of mirrors serving the same files; see `mirror.rs`.
+    pub mirrors: BTreeMap<String, Vec<String>>,
+    /// What to do where symlinks can't be made; see `links.rs`.
+    pub symlinks: crate::links::SymlinkConfig,
+    /// The User-Agent to send instead of "dex/<version>", for servers that
+    /// only answer clients they know. Must come before any `[section]`.
+    pub user_agent: Option<String>,
+    /// Where to record and check the digest of every download, shared across
+    /// an organization; see `digestlog.rs`. Must come before any `[section]`.
+    pub digest_log: Option<String>,
+}
+
+/// Additions to the built-in asset selection tables in `platform.rs`.
+///
+/// ```toml
+/// [selection]
+/// skip_keywords = ["debug", "dbgsym"]
+/// preferred_formats = [".tar.zst", ".tar.gz"]
+///
+/// [selection.libc_aliases]
+/// gnu = ["manylinux"]
+///
+/// [selection.platform_aliases]
+/// linux = ["portable"]
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct SelectionConfig {
+    /// Group name → extra filename substrings that identify that platform.
+    pub platform_aliases: BTreeMap<String, Vec<String>>,
+    /// Group name → extra filename substrings that identify that architecture.
+    pub arch_aliases: BTreeMap<String, Vec<String>>,
+    /// Group name → extra filename substrings that identify that C library.
+    pub libc_aliases: BTreeMap<String, Vec<String>>,
+    /// Extra extensions of files that are never selected.
+    pub skip_extensions: Vec<String>,
+    /// Extra filename keywords of files that are never selected.
+    pub skip_keywords: Vec<String>,
+    /// Extensions to prefer over others, replacing the built-in preference.
+    pub preferred_formats: Option<Vec<String>>,
+}
+
+/// Settings for requests to one host (or, as "*.example.com", its
+/// subdomains), picked by each request's URL — API calls, downloads, and
+/// checksum files alike.
+///
+/// ```toml
+/// [hosts."git.internal"]
+/// token_env = "GITEA_TOKEN"
+/// proxy = "http://proxy.internal:3128"
+/// ca_cert = "/etc/ssl/internal-ca.pem"
+/// headers = { "X-Team" = "infra" }
+/// ```
+#[derive(Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct HostConfig {
+    /// Sent as `Authorization: Bearer <token>`, or as the password with `username`.
+    pub token: Option<String>,
+    /// Environment variable to read the token from, keeping it out of the file.
+    pub token_env: Option<String>,
+    /// Authenticate with HTTP basic auth as this user instead of a bearer token.
+    pub username: Option<String>,
+    /// Proxy to send the host's requests through.
+    pub proxy: Option<String>,
+    /// PEM file of extra CA certificates to trust for the host.
+    pub ca_cert: Option<PathBuf>,
+    /// Headers added to every request.
+    pub headers: BTreeMap<String, String>,
+}
+
+impl HostConfig {
+    /// The token from `token_env` if that variable is set, else `token`.
+    pub fn token(&self) -> Option<String> {
+        self.token_env
+            .as_ref()
+            .and_then(|name| std::env::var(name).ok())
+            .or_else(|| self.token.clone())
+    }
+}
+
+/// Default config location: `config.toml` in dex's config directory
+/// (`~/.config/dex` on Linux; see `dirs.rs` for the rest).
+pub fn default_path() -> Option<PathBuf> {
+    Some(crate::dirs::config_dir()?.join("config.toml"))
+}
+
+/// Loads the config from `path`, or from the default location if `None`.
+///
+/// An explicitly given file must exist; a missing default file just means
+/// no configuration.
+pub fn load(path: Option<&Path>) -> Result<Config, String> {
+    let (path, required) = match path {
+        Some(p) => (p.to_path_buf(), true),
+        None => match default_path() {
+            Some(p) => (p, false),
+            None => return Ok(Config::default()),
+        },
+    };
+
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => parse(&contents).map_err(|e| format!("{}: {e}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && !required => Ok(Config::default()),
+        Err(e) => Err(format!("{}: {e}", path.display())),
+    }
+}
+
+fn parse(contents: &str) -> Result<Config, toml::de::Error> {
+    toml::from_str(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_empty() {
+        let config = parse("").unwrap();
+        assert!(config.selection.platform_aliases.is_empty());
+        assert!(config.selection.preferred_formats.is_none());
+    }
+
+    #[test]
+    fn test_parse_selection() {
+        let config = parse(
+            r#"
+            [selection]
+            skip_keywords = ["debug"]
+            preferred_formats = [".tar.zst"]
+
+            [selection.libc_aliases]
+            musl = ["alpine", "static"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.selection.skip_keywords, ["debug"]);
+        assert_eq!(config.selection.libc_aliases["musl"], ["alpine", "static"]);
+        assert_eq!(
+            config.selection.preferred_formats.as_deref(),
+            Some(&[".tar.zst".to_string()][..])
+        );
+    }
+
+    #[test]
+    fn test_parse_hosts() {
+        let config = parse(
+            r#"
+            [hosts."git.internal"]
+            token = "secret"
+            proxy = "http://proxy:3128"
+            headers = { "X-Team" = "infra" }
+
+            [hosts."*.s3.amazonaws.com"]
+            ca_cert = "/etc/ssl/ca.pem"
+            "#,
+        )
+        .unwrap();
+        let gitea = &config.hosts["git.internal"];
+        assert_eq!(gitea.token().as_deref(), Some("secret"));
+        assert_eq!(gitea.proxy.as_deref(), Some("http://proxy:3128"));
+        assert_eq!(gitea.headers["X-Team"], "infra");
+        let s3 = &config.hosts["*.s3.amazonaws.com"];
+        assert_eq!(s3.ca_cert.as_deref(), Some(Path::new("/etc/ssl/ca.pem")));
+        assert!(s3.token().is_none());
+    }
+
+    #[test]
+    fn test_parse_user_agent() {
+        let config = parse("user_agent = \"acme-fetch/2.0\"\n\n[selection]\n").unwrap();
+        assert_eq!(config.user_agent.as_deref(), Some("acme-fetch/2.0"));
+        assert!(parse("").unwrap().user_agent.is_none());
+
+        let config = parse("digest_log = \"https://digests.internal/dex\"\n").unwrap();
+        assert_eq!(
+            config.digest_log.as_deref(),
+            Some("https://digests.internal/dex")
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse("[selection]\nskip_keywords = 3").is_err());
+    }
+
+    #[test]
+    fn test_load_missing_explicit_file_fails() {
+        assert!(load(Some(Path::new("/nonexistent/dex/config.toml"))).is_err());
+    }
+}
@@ -0,0 +1,465 @@
+use crate::download::{Download, DownloadOptions, TooLarge};
+use crate::http::HttpContext;
+use crate::verify::{Hasher, to_hex};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// The largest block size accepted; zsyncmake never uses more than 64 KiB,
+/// and each block is read into memory whole.
+const MAX_BLOCK_SIZE: usize = 64 * 1024;
+
+/// The largest control file read. It holds a few bytes of checksums per
+/// block, so this covers files of several gigabytes at the usual 2K blocks.
+const MAX_CONTROL_SIZE: u64 = 64 * 1024 * 1024;
+
+/// A parsed `.zsync` control file: the target's size and a weak (rolling)
+/// and strong (MD4) checksum for each of its fixed-size blocks.
+///
+/// The format, as written by `zsyncmake`, is a few "Key: value" header lines,
+/// a blank line, then the block checksums in binary.
+struct Control {
+    block_size: usize,
+    length: u64,
+    /// Where the target lives, relative to the control file.
+    url: Option<String>,
+    /// SHA-1 of the whole target, checked after reassembly.
+    sha1: Option<String>,
+    /// How many bytes of each checksum are stored (zsyncmake truncates them
+    /// to keep the control file small).
+    rsum_bytes: usize,
+    checksum_bytes: usize,
+    blocks: Vec<BlockSum>,
+}
+
+struct BlockSum {
+    /// The weak checksum, masked to `rsum_bytes`.
+    rsum: u32,
+    /// The first `checksum_bytes` of the block's MD4.
+    checksum: Vec<u8>,
+}
+
+impl Control {
+    fn parse(data: &[u8]) -> Result<Control, String> {
+        let header_end = data
+            .windows(2)
+            .position(|w| w == b"\n\n")
+            .ok_or("no end of header")?;
+        let header = std::str::from_utf8(&data[..header_end]).map_err(|e| e.to_string())?;
+        let mut fields = HashMap::new();
+        for line in header.lines() {
+            if let Some((key, value)) = line.split_once(':') {
+                fields.insert(key.trim(), value.trim());
+            }
+        }
+        let field = |key| fields.get(key).copied().ok_or(format!("no {key} header"));
+        let number = |key| -> Result<u64, String> {
+            field(key)?.parse().map_err(|_| format!("bad {key} header"))
+        };
+
+        let block_size = number("Blocksize")? as usize;
+        let length = number("Length")?;
+        // "Hash-Lengths: <consecutive matches>,<rsum bytes>,<checksum bytes>"
+        let lengths: Vec<usize> = field("Hash-Lengths")?
+            .split(',')
+            .map(|n| n.parse().map_err(|_| "bad Hash-Lengths header"))
+            .collect::<Result<_, _>>()?;
+        let [_, rsum_bytes, checksum_bytes] = lengths[..] else {
+            return Err("bad Hash-Lengths header".into());
+        };
+        if !block_size.is_power_of_two()
+            || block_size > MAX_BLOCK_SIZE
+            || !(1..=4).contains(&rsum_bytes)
+            || checksum_bytes > 16
+        {
+            return Err("unsupported block or hash sizes".into());
+        }
+
+        let count = length.div_ceil(block_size as u64) as usize;
+        let sums = &data[header_end + 2..];
+        let record = rsum_bytes + checksum_bytes;
+        if sums.len() < count * record {
+            return Err("truncated block checksums".into());
+        }
+        let blocks = sums
+            .chunks(record)
+            .take(count)
+            .map(|chunk| BlockSum {
+                // Stored big-endian: the last `rsum_bytes` of a, b (16 bits each).
+                rsum: chunk[..rsum_bytes]
+                    .iter()
+                    .fold(0, |n, &byte| (n << 8) | u32::from(byte)),
+                checksum: chunk[rsum_bytes..].to_vec(),
+            })
+            .collect();
+
+        Ok(Control {
+            block_size,
+            length,
+            url: fields.get("URL").map(|s| s.to_string()),
+            sha1: fields.get("SHA-1").map(|s| s.to_lowercase()),
+            rsum_bytes,
+            checksum_bytes,
+            blocks,
+        })
+    }
+}
+
+/// zsync's rolling checksum of a block: `a` is the sum of its bytes, `b` the
+/// sum weighted by distance from the end, both modulo 2^16. Sliding the
+/// window one byte along updates both without rereading the block.
+#[derive(Clone, Copy)]
+struct Rsum {
+    a: u16,
+    b: u16,
+}
+
+impl Rsum {
+    fn of(block: &[u8]) -> Rsum {
+        let len = block.len();
+        let (mut a, mut b) = (0u16, 0u16);
+        for (i, &byte) in block.iter().enumerate() {
+            a = a.wrapping_add(u16::from(byte));
+            b = b.wrapping_add(((len - i) as u16).wrapping_mul(u16::from(byte)));
+        }
+        Rsum { a, b }
+    }
+
+    /// Slides the window: `old` leaves at the front, `new` joins at the back.
+    fn roll(self, old: u8, new: u8, block_size: usize) -> Rsum {
+        let a = self
+            .a
+            .wrapping_sub(u16::from(old))
+            .wrapping_add(u16::from(new));
+        let b = self
+            .b
+            .wrapping_add(a)
+            .wrapping_sub((block_size as u16).wrapping_mul(u16::from(old)));
+        Rsum { a, b }
+    }
+
+    /// The low `bytes` bytes of a:b, as stored in the control file.
+    fn masked(self, bytes: usize) -> u32 {
+        let full = (u32::from(self.a) << 16) | u32::from(self.b);
+        match bytes {
+            4 => full,
+            n => full & ((1 << (8 * n)) - 1),
+        }
+    }
+}
+
+fn md4(block: &[u8]) -> [u8; 16] {
+    use md4::Digest as _;
+    md4::Md4::digest(block).into()
+}
+
+/// Scans `seed` for blocks of the target and writes each one found into
+/// `target` at its place. Returns which blocks were found.
+fn copy_matching_blocks(
+    seed: &mut impl Read,
+    control: &Control,
+    target: &mut (impl Write + Seek),
+) -> io::Result<Vec<bool>> {
+    let block_size = control.block_size;
+    let mut index: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (i, block) in control.blocks.iter().enumerate() {
+        index.entry(block.rsum).or_default().push(i);
+    }
+    let mut found = vec![false; control.blocks.len()];
+
+    // `buffer[pos..pos + block_size]` is the window being checked.
+    let mut buffer = Vec::new();
+    let mut pos = 0;
+    let mut eof = false;
+    let mut sum: Option<Rsum> = None;
+    let mut chunk = vec![0u8; 64 * 1024];
+    loop {
+        // Keep a whole window, plus the byte after it for rolling, buffered.
+        while !eof && buffer.len() < pos + block_size + 1 {
+            if pos >= 1 << 20 {
+                buffer.drain(..pos);
+                pos = 0;
+            }
+            let n = seed.read(&mut chunk)?;
+            if n == 0 {
+                eof = true;
+            } else {
+                buffer.extend_from_slice(&chunk[..n]);
+            }
+        }
+        if buffer.len() < pos + block_size {
+            // The target's last block is padded with zeros; pad the seed's
+            // tail the same way so it can match.
+            if buffer.len() == pos {
+                break;
+            }
+            buffer.resize(pos + block_size, 0);
+            sum = None;
+        }
+
+        let window = &buffer[pos..pos + block_size];
+        let rsum = *sum.get_or_insert_with(|| Rsum::of(window));
+        let mut matched = false;
+        if let Some(candidates) = index.get(&rsum.masked(control.rsum_bytes)) {
+            // The weak sum only narrows it down; the MD4 decides.
+            let strong = md4(window);
+            for &i in candidates {
+                if !found[i] && control.blocks[i].checksum == strong[..control.checksum_bytes] {
+                    let start = i as u64 * block_size as u64;
+                    let len = (control.length - start).min(block_size as u64) as usize;
+                    target.seek(SeekFrom::Start(start))?;
+                    target.write_all(&window[..len])?;
+                    found[i] = true;
+                    matched = true;
+                }
+            }
+        }
+
+        if matched {
+            // Blocks in a changed file mostly stay in order: jump to the next one.
+            pos += block_size;
+            sum = None;
+        } else if let Some(&new) = buffer.get(pos + block_size) {
+            sum = Some(rsum.roll(buffer[pos], new, block_size));
+            pos += 1;
+        } else {
+            break;
+        }
+    }
+    Ok(found)
+}
+
+/// Byte ranges (inclusive, as HTTP wants them) covering the blocks not found,
+/// with neighbouring blocks merged into one request.
+fn missing_ranges(found: &[bool], block_size: usize, length: u64) -> Vec<(u64, u64)> {
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+    for (i, _) in found.iter().enumerate().filter(|(_, found)| !**found) {
+        let start = i as u64 * block_size as u64;
+        let end = (start + block_size as u64).min(length) - 1;
+        match ranges.last_mut() {
+            Some(last) if last.1 + 1 == start => last.1 = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+    ranges
+}
+
+/// Downloads `url` by updating `seed`, an earlier version of the same file,
+/// when the server publishes a zsync control file (`<url>.zsync`): only the
+/// blocks that changed are fetched, with range requests.
+///
+/// Returns None if there's no control file. Any other failure is an error,
+/// and the caller should download the whole file instead.
+pub fn download(
+    http: &HttpContext,
+    url: &str,
+    seed: &Path,
+    output_dir: &Path,
+    options: &DownloadOptions,
+) -> Result<Option<Download>, Box<dyn std::error::Error>> {
+    let started = std::time::Instant::now();
+    let control_url = format!("{url}.zsync");
+    let response = http.send(http.get(&control_url))?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let body =
+        crate::http::read_limited(response.error_for_status()?, &control_url, MAX_CONTROL_SIZE)?;
+    let control = Control::parse(&body).map_err(|e| format!("{control_url}: {e}"))?;
+    // The control file names the target relative to itself.
+    let target_url = match &control.url {
+        Some(relative) => reqwest::Url::parse(&control_url)?
+            .join(relative)?
+            .to_string(),
+        None => url.to_string(),
+    };
+
+    let name = crate::download::filename_from_url(url);
+    let file_name = match options.rename {
+        Some(rename) => {
+            rename(&name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?
+        }
+        None => name.clone(),
+    };
+    // The control file's length is all there is to go by before writing.
+    if let Some(limit) = options.max_size
+        && control.length > limit
+    {
+        return Err(TooLarge {
+            size: Some(control.length),
+            limit,
+        }
+        .into());
+    }
+    fs::create_dir_all(output_dir)?;
+    let path = output_dir.join(&file_name);
+    let part_path = output_dir.join(format!("{file_name}.part"));
+
+    let result = (|| {
+        let mut target = fs::File::create(&part_path)?;
+        target.set_len(control.length)?;
+        let found = copy_matching_blocks(&mut fs::File::open(seed)?, &control, &mut target)?;
+
+        let ranges = missing_ranges(&found, control.block_size, control.length);
+        let fetch: u64 = ranges.iter().map(|(start, end)| end - start + 1).sum();
+        eprintln!(
+            "Delta: reusing {} of {} blocks from the cached earlier version, fetching {}",
+            found.iter().filter(|f| **f).count(),
+            found.len(),
+            indicatif::HumanBytes(fetch)
+        );
+        for (start, end) in ranges {
+            let request = http
+                .get(&target_url)
+                .header(reqwest::header::RANGE, format!("bytes={start}-{end}"));
+            let mut response = http.send(request)?.error_for_status()?;
+            // A 200 would be the whole file, not the range.
+            if response.status() != reqwest::StatusCode::PARTIAL_CONTENT {
+                return Err("the server doesn't support range requests".into());
+            }
+            target.seek(SeekFrom::Start(start))?;
+            let copied = io::copy(&mut (&mut response).take(end - start + 1), &mut target)?;
+            if copied != end - start + 1 {
+                return Err("range response ended early".into());
+            }
+        }
+        drop(target);
+
+        // Hash the reassembled file: SHA-1 to check it against the control
+        // file, plus whatever the caller wants to verify.
+        let mut sha1 = <sha1::Sha1 as sha1::Digest>::new();
+        let mut hashers: Vec<Hasher> = options.algorithms.iter().map(|&a| Hasher::new(a)).collect();
+        let mut file = fs::File::open(&part_path)?;
+        let mut buffer = [0u8; 8192];
+        loop {
+            let n = file.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            sha1::Digest::update(&mut sha1, &buffer[..n]);
+            for hasher in &mut hashers {
+                hasher.update(&buffer[..n]);
+            }
+        }
+        let actual = to_hex(&sha1::Digest::finalize(sha1));
+        if let Some(expected) = &control.sha1
+            && actual != *expected
+        {
+            return Err(format!("reassembled file has SHA-1 {actual}, expected {expected}").into());
+        }
+
+        fs::rename(&part_path, &path)?;
+        Ok(Download {
+            path: path.clone(),
+            name,
+            digests: hashers.into_iter().map(Hasher::finalize).collect(),
+            // The .zsync file doesn't say; the name has to.
+            content_type: None,
+            content_encoding: None,
+            etag: None,
+            bytes: fetch,
+            elapsed: started.elapsed(),
+        })
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&part_path);
+    }
+    result.map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a control file the way zsyncmake does.
+    fn control_file(target: &[u8], block_size: usize, rsum_bytes: usize) -> Vec<u8> {
+        let mut data = format!(
+            "zsync: 0.6.2\nFilename: target\nBlocksize: {block_size}\nLength: {}\n\
+             Hash-Lengths: 2,{rsum_bytes},5\nURL: target\n\n",
+            target.len()
+        )
+        .into_bytes();
+        for block in target.chunks(block_size) {
+            let mut padded = block.to_vec();
+            padded.resize(block_size, 0);
+            let rsum = Rsum::of(&padded);
+            let bytes = [
+                (rsum.a >> 8) as u8,
+                rsum.a as u8,
+                (rsum.b >> 8) as u8,
+                rsum.b as u8,
+            ];
+            data.extend_from_slice(&bytes[4 - rsum_bytes..]);
+            data.extend_from_slice(&md4(&padded)[..5]);
+        }
+        data
+    }
+
+    #[test]
+    fn test_rolling_checksum() {
+        let data: Vec<u8> = (0..200u32).map(|i| (i * 7 % 251) as u8).collect();
+        let mut sum = Rsum::of(&data[..64]);
+        for start in 1..=100 {
+            sum = sum.roll(data[start - 1], data[start + 63], 64);
+            let fresh = Rsum::of(&data[start..start + 64]);
+            assert_eq!((sum.a, sum.b), (fresh.a, fresh.b));
+        }
+    }
+
+    #[test]
+    fn test_block_size_limit() {
+        let huge = control_file(&[1u8; 10], MAX_BLOCK_SIZE * 2, 4);
+        assert!(Control::parse(&huge).is_err());
+        let largest = control_file(&[1u8; 10], MAX_BLOCK_SIZE, 4);
+        assert!(Control::parse(&largest).is_ok());
+    }
+
+    #[test]
+    fn test_parse_control() {
+        let control = Control::parse(&control_file(&[1u8; 5000], 2048, 3)).unwrap();
+        assert_eq!(control.block_size, 2048);
+        assert_eq!(control.length, 5000);
+        assert_eq!(control.blocks.len(), 3);
+        assert_eq!(control.url.as_deref(), Some("target"));
+        assert!(control.blocks.iter().all(|b| b.rsum < 1 << 24));
+
+        assert!(Control::parse(b"zsync: 0.6.2\nBlocksize: 2048\n\n").is_err());
+    }
+
+    #[test]
+    fn test_reassemble_from_seed() {
+        // The new version: the old one with a changed middle and a byte
+        // inserted near the start, which shifts every later block.
+        // Pseudo-random, so no block repeats elsewhere in the file.
+        let mut state = 1u32;
+        let old: Vec<u8> = (0..10_000)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8
+            })
+            .collect();
+        let mut new = old.clone();
+        new.insert(100, 42);
+        new[5000..5100].fill(0xff);
+
+        let control = Control::parse(&control_file(&new, 512, 4)).unwrap();
+        let mut target = io::Cursor::new(vec![0u8; new.len()]);
+        let found = copy_matching_blocks(&mut &old[..], &control, &mut target).unwrap();
+
+        // Only the blocks holding the insertion and the change are missing.
+        let missing: Vec<usize> = (0..found.len()).filter(|&i| !found[i]).collect();
+        assert_eq!(missing, vec![0, 9]);
+        let ranges = missing_ranges(&found, 512, new.len() as u64);
+        assert_eq!(ranges, vec![(0, 511), (4608, 5119)]);
+
+        // Filling those in from the new version gives back the whole file.
+        let mut rebuilt = target.into_inner();
+        for (start, end) in ranges {
+            let (start, end) = (start as usize, end as usize);
+            rebuilt[start..=end].copy_from_slice(&new[start..=end]);
+        }
+        assert_eq!(rebuilt, new);
+    }
+}
@@ -0,0 +1,586 @@
+use crate::verify::{self, Algorithm};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// A tree of files --install-to copied into a directory (a theme, a set of
+/// completions), as `dex uninstall` needs it. Every copy of a bundle is kept
+/// in dex's state directory as `copies/<name>.json`, one per target.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Deployment {
+    pub target: PathBuf,
+    /// URL the files were downloaded from.
+    pub source: String,
+    /// Seconds since the Unix epoch.
+    pub installed_at: u64,
+    pub files: Vec<Deployed>,
+    /// Directories the copy created, relative to the target, parents first.
+    #[serde(default)]
+    pub dirs: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Deployed {
+    /// Path relative to the target, with `/` separators.
+    pub path: String,
+    /// Of the copy, to tell whether it's been edited since; absent for a symlink.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<PathBuf>,
+    /// Where the file this one replaced was moved to, relative to the target.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup: Option<String>,
+}
+
+/// What installing or uninstalling a copy did.
+#[derive(Debug, Default)]
+pub struct Outcome {
+    /// Files copied in, or removed.
+    pub files: usize,
+    /// Files moved aside for the copy, or the ones put back in their place.
+    pub backups: Vec<String>,
+    /// Files left in place because they were edited after being installed.
+    pub kept: Vec<String>,
+}
+
+/// Where a bundle's copies are recorded.
+fn path(name: &str) -> Option<PathBuf> {
+    Some(
+        crate::dirs::state_dir()?
+            .join("copies")
+            .join(format!("{name}.json")),
+    )
+}
+
+/// Every recorded copy of `name`, one per directory it was installed into.
+pub fn load(name: &str) -> Result<Vec<Deployment>, Box<dyn std::error::Error>> {
+    let Some(path) = path(name) else {
+        return Ok(Vec::new());
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => {
+            serde_json::from_str(&contents).map_err(|e| format!("{}: {e}", path.display()).into())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("{}: {e}", path.display()).into()),
+    }
+}
+
+fn save(path: &Path, deployments: &[Deployment]) -> Result<(), Box<dyn std::error::Error>> {
+    if deployments.is_empty() {
+        return match std::fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        };
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(deployments)?)?;
+    Ok(())
+}
+
+/// The extracted `files` under `root`, each with the path it's copied to
+/// below the target. A tree with one top-level folder (`theme-1.2/…`) is
+/// unwrapped, so its contents land in the target itself.
+pub fn relative_files(root: &Path, files: &[PathBuf]) -> Vec<(PathBuf, PathBuf)> {
+    let relative: Vec<PathBuf> = files
+        .iter()
+        .map(|f| f.strip_prefix(root).unwrap_or(f).to_path_buf())
+        .collect();
+    let first = |p: &PathBuf| p.components().next().map(|c| c.as_os_str().to_owned());
+    let wrapped = relative.iter().all(|p| p.components().count() > 1)
+        && relative.windows(2).all(|w| first(&w[0]) == first(&w[1]));
+    files
+        .iter()
+        .cloned()
+        .zip(relative.into_iter().map(|p| match wrapped {
+            true => p.components().skip(1).collect(),
+            false => p,
+        }))
+        .collect()
+}
+
+fn slash_path(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Copies `files` (from `relative_files`) into `target` as bundle `name`,
+/// keeping each file's permissions and modification time, and records what
+/// was installed. A file already there is moved aside to `<file>.dex-bak`,
+/// unless it's an unedited copy from an earlier install of the bundle; files
+/// that install had and this one doesn't are removed. If a file can't be
+/// copied, what was installed up to then is still recorded.
+pub fn install(
+    name: &str,
+    source: &str,
+    files: &[(PathBuf, PathBuf)],
+    target: &Path,
+) -> Result<Outcome, Box<dyn std::error::Error>> {
+    let record = path(name).ok_or("could not determine dex's state directory")?;
+    let _guard = crate::filelock::lock(&record)?;
+    let target = std::path::absolute(target)?;
+    let mut deployments = load(name)?;
+    let previous = deployments
+        .iter()
+        .position(|d| d.target == target)
+        .map(|i| deployments.remove(i));
+
+    std::fs::create_dir_all(&target)?;
+    let mut outcome = Outcome::default();
+    let mut deployment = Deployment {
+        target: target.clone(),
+        source: source.to_string(),
+        installed_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        files: Vec::new(),
+        dirs: previous
+            .as_ref()
+            .map(|p| p.dirs.clone())
+            .unwrap_or_default(),
+    };
+    let copied = copy_files(
+        files,
+        &target,
+        previous.as_ref(),
+        &mut deployment,
+        &mut outcome,
+    );
+    if let Err(e) = copied {
+        // Record what made it in, so `dex uninstall` can still take it out
+        // and put back what it replaced.
+        keep_unfinished(&mut deployment, previous.as_ref());
+        deployments.push(deployment);
+        save(&record, &deployments)?;
+        return Err(e);
+    }
+    if let Some(previous) = &previous {
+        let stale = previous
+            .files
+            .iter()
+            .filter(|f| !deployment.files.iter().any(|d| d.path == f.path));
+        for file in stale {
+            if !remove(&target, file)? {
+                outcome.kept.push(file.path.clone());
+            }
+        }
+    }
+
+    deployments.push(deployment);
+    save(&record, &deployments)?;
+    Ok(outcome)
+}
+
+/// The copying half of `install`. Stops at the first file that can't be
+/// copied, having put back the one it moved aside for it; `deployment` then
+/// lists the files copied before it.
+fn copy_files(
+    files: &[(PathBuf, PathBuf)],
+    target: &Path,
+    previous: Option<&Deployment>,
+    deployment: &mut Deployment,
+    outcome: &mut Outcome,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for (file, relative) in files {
+        let path = slash_path(relative);
+        let dest = target.join(relative);
+        create_parents(target, relative, &mut deployment.dirs)?;
+        let earlier = previous.and_then(|p| p.files.iter().find(|f| f.path == path));
+        let (backup, moved) = match earlier {
+            Some(earlier) if unchanged(&dest, earlier) => (earlier.backup.clone(), false),
+            _ if dest.symlink_metadata().is_ok() => {
+                let backup = backup_name(target, &path);
+                std::fs::rename(&dest, target.join(&backup))
+                    .map_err(|e| format!("backing up {}: {e}", dest.display()))?;
+                (Some(backup), true)
+            }
+            _ => (None, false),
+        };
+        let (sha256, link) = match copy(file, &dest) {
+            Ok(copied) => copied,
+            Err(e) => {
+                if let Some(backup) = backup.as_ref().filter(|_| moved) {
+                    let _ = std::fs::rename(target.join(backup), &dest);
+                }
+                let message = format!("copying {} to {}: {e}", file.display(), dest.display());
+                return Err(message.into());
+            }
+        };
+        if moved && let Some(backup) = &backup {
+            outcome.backups.push(backup.clone());
+        }
+        deployment.files.push(Deployed {
+            path,
+            sha256,
+            link,
+            backup,
+        });
+        outcome.files += 1;
+    }
+    Ok(())
+}
+
+/// After `copy_files` failed: the earlier install's files it didn't get to
+/// are still in place, so they stay in the record with the new ones.
+fn keep_unfinished(deployment: &mut Deployment, previous: Option<&Deployment>) {
+    let Some(previous) = previous else {
+        return;
+    };
+    for file in &previous.files {
+        if !deployment.files.iter().any(|d| d.path == file.path) {
+            deployment.files.push(file.clone());
+        }
+    }
+}
+
+/// Removes bundle `name`'s files from every directory it was copied into,
+/// or only `from`, and puts back the files they replaced.
+pub fn uninstall(
+    name: &str,
+    from: Option<&Path>,
+) -> Result<Vec<(PathBuf, Outcome)>, Box<dyn std::error::Error>> {
+    let record = path(name).ok_or("could not determine dex's state directory")?;
+    let _guard = crate::filelock::lock(&record)?;
+    let from = from.map(std::path::absolute).transpose()?;
+    let mut deployments = load(name)?;
+    let (chosen, rest): (Vec<_>, Vec<_>) = deployments
+        .drain(..)
+        .partition(|d| from.as_ref().is_none_or(|from| d.target == *from));
+    if chosen.is_empty() {
+        return Err(match from {
+            Some(from) => format!("{name} wasn't installed into {}", from.display()),
+            None => format!("{name} wasn't installed with --install-to"),
+        }
+        .into());
+    }
+
+    let mut removed = Vec::new();
+    for deployment in chosen {
+        let mut outcome = Outcome::default();
+        for file in &deployment.files {
+            if !remove(&deployment.target, file)? {
+                outcome.kept.push(file.path.clone());
+                continue;
+            }
+            outcome.files += 1;
+            if file.backup.is_some() {
+                outcome.backups.push(file.path.clone());
+            }
+        }
+        // Only the directories nothing else has been put in since.
+        for dir in deployment.dirs.iter().rev() {
+            let _ = std::fs::remove_dir(deployment.target.join(dir));
+        }
+        removed.push((deployment.target, outcome));
+    }
+    save(&record, &rest)?;
+    Ok(removed)
+}
+
+/// Whether the file at `dest` is still the one `deployed` recorded.
+fn unchanged(dest: &Path, deployed: &Deployed) -> bool {
+    match (&deployed.link, &deployed.sha256) {
+        (Some(link), _) => std::fs::read_link(dest).is_ok_and(|l| &l == link),
+        (None, Some(sha256)) => {
+            dest.symlink_metadata().is_ok_and(|m| m.is_file())
+                && verify::hash_file(dest, Algorithm::Sha256).is_ok_and(|d| d.hex == *sha256)
+        }
+        (None, None) => false,
+    }
+}
+
+/// Removes one installed file and puts back the one it replaced, unless
+/// it's been edited since; returns whether it was removed.
+fn remove(target: &Path, deployed: &Deployed) -> std::io::Result<bool> {
+    let dest = target.join(&deployed.path);
+    if dest.symlink_metadata().is_err() {
+        return Ok(true);
+    }
+    if !unchanged(&dest, deployed) {
+        return Ok(false);
+    }
+    std::fs::remove_file(&dest)?;
+    if let Some(backup) = &deployed.backup {
+        std::fs::rename(target.join(backup), &dest)?;
+    }
+    Ok(true)
+}
+
+/// A name for moving `path` aside that nothing is using yet.
+fn backup_name(target: &Path, path: &str) -> String {
+    let mut backup = format!("{path}.dex-bak");
+    let mut n = 1;
+    while target.join(&backup).symlink_metadata().is_ok() {
+        n += 1;
+        backup = format!("{path}.dex-bak.{n}");
+    }
+    backup
+}
+
+/// Creates the folders `relative` needs below `target`, noting each one
+/// that's new in `dirs`.
+fn create_parents(target: &Path, relative: &Path, dirs: &mut Vec<String>) -> std::io::Result<()> {
+    let Some(parent) = relative.parent() else {
+        return Ok(());
+    };
+    let mut dir = PathBuf::new();
+    for component in parent.components() {
+        dir.push(component);
+        let full = target.join(&dir);
+        if !full.is_dir() {
+            std::fs::create_dir(&full)?;
+            let dir = slash_path(&dir);
+            if !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Copies one file (or symlink) into place by way of a temporary file, so
+/// nothing ever sees it half-written. Returns its SHA-256, or its link.
+fn copy(file: &Path, dest: &Path) -> std::io::Result<(Option<String>, Option<PathBuf>)> {
+    let metadata = file.symlink_metadata()?;
+    let name = dest.file_name().unwrap_or_default().to_string_lossy();
+    let partial = dest.with_file_name(format!(".{name}.dex-{}", std::process::id()));
+    #[cfg(unix)]
+    if metadata.is_symlink() {
+        // Renamed over whatever is there, like a file: an unchanged link
+        // from an earlier install is still in place.
+        let link = std::fs::read_link(file)?;
+        let linked = std::os::unix::fs::symlink(&link, &partial)
+            .and_then(|()| std::fs::rename(&partial, dest));
+        if let Err(e) = linked {
+            let _ = std::fs::remove_file(&partial);
+            return Err(e);
+        }
+        return Ok((None, Some(link)));
+    }
+    // fs::copy carries the permission bits over.
+    let copied = std::fs::copy(file, &partial).and_then(|_| {
+        if let Ok(modified) = metadata.modified() {
+            std::fs::File::options()
+                .write(true)
+                .open(&partial)?
+                .set_modified(modified)?;
+        }
+        std::fs::rename(&partial, dest)
+    });
+    if let Err(e) = copied {
+        let _ = std::fs::remove_file(&partial);
+        return Err(e);
+    }
+    Ok((Some(verify::hash_file(dest, Algorithm::Sha256)?.hex), None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_files() {
+        let root = Path::new("/tmp/x");
+        let names = |files: &[&str]| -> Vec<PathBuf> {
+            let files: Vec<PathBuf> = files.iter().map(|f| root.join(f)).collect();
+            relative_files(root, &files)
+                .into_iter()
+                .map(|(_, relative)| relative)
+                .collect()
+        };
+        assert_eq!(
+            names(&["theme-1.2/colors.toml", "theme-1.2/fonts/a.ttf"]),
+            [Path::new("colors.toml"), Path::new("fonts/a.ttf")]
+        );
+        assert_eq!(
+            names(&["colors.toml", "fonts/a.ttf"]),
+            [Path::new("colors.toml"), Path::new("fonts/a.ttf")]
+        );
+        assert_eq!(names(&["_dex"]), [Path::new("_dex")]);
+    }
+
+    #[test]
+    fn test_copy_backs_up_and_restores() {
+        let dir = std::env::temp_dir().join(format!("dex-test-deploy-{}", std::process::id()));
+        let (source, target) = (dir.join("source"), dir.join("target"));
+        std::fs::create_dir_all(source.join("completions")).unwrap();
+        std::fs::create_dir_all(&target).unwrap();
+        std::fs::write(source.join("config.toml"), "new").unwrap();
+        std::fs::write(source.join("completions/_tool"), "#compdef tool").unwrap();
+        std::fs::write(target.join("config.toml"), "mine").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::Permissions::from_mode(0o600);
+            std::fs::set_permissions(source.join("config.toml"), mode).unwrap();
+        }
+
+        let files = [
+            (source.join("config.toml"), PathBuf::from("config.toml")),
+            (
+                source.join("completions/_tool"),
+                PathBuf::from("completions/_tool"),
+            ),
+        ];
+        let mut dirs = Vec::new();
+        let mut deployed = Vec::new();
+        for (file, relative) in &files {
+            create_parents(&target, relative, &mut dirs).unwrap();
+            let dest = target.join(relative);
+            let backup = dest.exists().then(|| {
+                let backup = backup_name(&target, &slash_path(relative));
+                std::fs::rename(&dest, target.join(&backup)).unwrap();
+                backup
+            });
+            let (sha256, link) = copy(file, &dest).unwrap();
+            deployed.push(Deployed {
+                path: slash_path(relative),
+                sha256,
+                link,
+                backup,
+            });
+        }
+        assert_eq!(dirs, ["completions"]);
+        assert_eq!(deployed[0].backup.as_deref(), Some("config.toml.dex-bak"));
+        assert_eq!(
+            std::fs::read_to_string(target.join("config.toml")).unwrap(),
+            "new"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(target.join("config.toml"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // An edited file stays; the rest go, and the backup comes back.
+        std::fs::write(target.join("completions/_tool"), "edited").unwrap();
+        assert!(remove(&target, &deployed[0]).unwrap());
+        assert!(!remove(&target, &deployed[1]).unwrap());
+        assert_eq!(
+            std::fs::read_to_string(target.join("config.toml")).unwrap(),
+            "mine"
+        );
+        assert!(target.join("completions/_tool").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_failed_install_keeps_record() {
+        let dir = std::env::temp_dir().join(format!("dex-test-deploy-fail-{}", std::process::id()));
+        let (source, target) = (dir.join("source"), dir.join("target"));
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::create_dir_all(&target).unwrap();
+        std::fs::write(source.join("a.toml"), "new a").unwrap();
+        std::fs::write(target.join("a.toml"), "my a").unwrap();
+        std::fs::write(target.join("b.toml"), "my b").unwrap();
+        std::fs::write(target.join("old.toml"), "old").unwrap();
+        let previous = Deployment {
+            target: target.clone(),
+            source: "https://example.com/theme-1.0.tar.gz".into(),
+            installed_at: 0,
+            files: vec![Deployed {
+                path: "old.toml".into(),
+                sha256: Some(
+                    verify::hash_file(&target.join("old.toml"), Algorithm::Sha256)
+                        .unwrap()
+                        .hex,
+                ),
+                link: None,
+                backup: None,
+            }],
+            dirs: Vec::new(),
+        };
+
+        // b.toml's source is missing, so copying it fails halfway through.
+        let files = [
+            (source.join("a.toml"), PathBuf::from("a.toml")),
+            (source.join("b.toml"), PathBuf::from("b.toml")),
+        ];
+        let mut deployment = Deployment {
+            target: target.clone(),
+            source: "https://example.com/theme-1.1.tar.gz".into(),
+            installed_at: 1,
+            files: Vec::new(),
+            dirs: Vec::new(),
+        };
+        let mut outcome = Outcome::default();
+        let copied = copy_files(
+            &files,
+            &target,
+            Some(&previous),
+            &mut deployment,
+            &mut outcome,
+        );
+        assert!(copied.is_err());
+        assert_eq!(outcome.backups, ["a.toml.dex-bak"]);
+        // The file moved aside for b.toml is back.
+        assert_eq!(
+            std::fs::read_to_string(target.join("b.toml")).unwrap(),
+            "my b"
+        );
+        assert!(!target.join("b.toml.dex-bak").exists());
+
+        keep_unfinished(&mut deployment, Some(&previous));
+        let paths: Vec<&str> = deployment.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, ["a.toml", "old.toml"]);
+
+        // So uninstalling it still undoes all of it.
+        for file in &deployment.files {
+            assert!(remove(&target, file).unwrap());
+        }
+        assert_eq!(
+            std::fs::read_to_string(target.join("a.toml")).unwrap(),
+            "my a"
+        );
+        assert!(!target.join("old.toml").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_reinstall_with_symlink() {
+        let dir = std::env::temp_dir().join(format!("dex-test-deploy-link-{}", std::process::id()));
+        let (source, target) = (dir.join("source"), dir.join("target"));
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::create_dir_all(&target).unwrap();
+        std::fs::write(source.join("theme.toml"), "theme").unwrap();
+        std::os::unix::fs::symlink("theme.toml", source.join("default.toml")).unwrap();
+        let files = [
+            (source.join("theme.toml"), PathBuf::from("theme.toml")),
+            (source.join("default.toml"), PathBuf::from("default.toml")),
+        ];
+
+        let install = |previous: Option<&Deployment>| {
+            let mut deployment = Deployment {
+                target: target.clone(),
+                source: "https://example.com/theme.tar.gz".into(),
+                installed_at: 0,
+                files: Vec::new(),
+                dirs: Vec::new(),
+            };
+            let mut outcome = Outcome::default();
+            copy_files(&files, &target, previous, &mut deployment, &mut outcome).unwrap();
+            (deployment, outcome)
+        };
+        let (first, _) = install(None);
+        // The unchanged link is replaced in place, not backed up.
+        let (second, outcome) = install(Some(&first));
+        assert!(outcome.backups.is_empty());
+        assert_eq!(second.files, first.files);
+        assert_eq!(
+            std::fs::read_link(target.join("default.toml")).unwrap(),
+            Path::new("theme.toml")
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+use crate::http::HttpContext;
+use crate::provider::ResolvedAsset;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+/// A shared, append-only record of the SHA-256 each asset had when it was
+/// first downloaded anywhere in an organization (--digest-log, or the
+/// config's `digest_log`), so a file that changes after the fact is noticed
+/// on every machine rather than trusted anew on each.
+///
+/// The log is any HTTP endpoint that answers two requests:
+///
+///   GET  <log>?key=<key>               → {"sha256": "<hex>"}, or 404 if unseen
+///   POST <log>  {"key": …, "sha256": …} → records the digest if the key is new
+pub struct DigestLog<'a> {
+    pub url: &'a str,
+}
+
+/// What the log said about a download's digest.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Observation {
+    /// Nobody had seen the asset, so this digest is now the one on record.
+    Recorded,
+    /// The digest is the one on record.
+    Matches,
+    /// Someone saw different bytes under the same name: the digest on record.
+    Differs(String),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry<'a> {
+    #[serde(default, skip_serializing_if = "str::is_empty")]
+    key: &'a str,
+    sha256: String,
+}
+
+/// What the log files a download under: `owner/repo@tag/asset` for a release
+/// asset, or the URL itself for anything else.
+pub fn key(resolved: Option<&ResolvedAsset>, url: &str) -> String {
+    match resolved {
+        Some(r) => format!(
+            "{}/{}@{}/{}",
+            r.release.owner, r.release.repo, r.release.tag, r.asset.name
+        ),
+        None => url.split('#').next().unwrap_or(url).to_string(),
+    }
+}
+
+impl DigestLog<'_> {
+    /// Checks `sha256` against the digest on record for `key`, recording it
+    /// if there's none yet (trust on first use).
+    pub fn observe(
+        &self,
+        http: &HttpContext,
+        key: &str,
+        sha256: &str,
+    ) -> Result<Observation, Box<dyn std::error::Error>> {
+        let response = http.send(http.get(self.url).query(&[("key", key)]))?;
+        if response.status() != StatusCode::NOT_FOUND {
+            let body = crate::http::read_text(response.error_for_status()?)?;
+            let recorded: Entry = serde_json::from_str(&body)
+                .map_err(|e| format!("unexpected answer from the digest log: {e}"))?;
+            return Ok(compare(recorded.sha256, sha256));
+        }
+        let entry = Entry {
+            key,
+            sha256: sha256.to_string(),
+        };
+        let request = http
+            .post(self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_string(&entry)?);
+        http.send(request)?.error_for_status()?;
+        Ok(Observation::Recorded)
+    }
+}
+
+fn compare(recorded: String, sha256: &str) -> Observation {
+    if recorded.eq_ignore_ascii_case(sha256) {
+        Observation::Matches
+    } else {
+        Observation::Differs(recorded.to_ascii_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::make_assets;
+    use crate::provider::Release;
+
+    #[test]
+    fn test_key() {
+        let resolved = ResolvedAsset {
+            release: Release {
+                owner: "BurntSushi".into(),
+                repo: "ripgrep".into(),
+                tag: "14.1.1".into(),
+                assets: Vec::new(),
+                notes: None,
+                source_tarball: None,
+            },
+            asset: make_assets(&["ripgrep-14.1.1-x86_64-unknown-linux-musl.tar.gz"]).remove(0),
+            checksum: None,
+        };
+        assert_eq!(
+            key(Some(&resolved), "https://github.com/BurntSushi/ripgrep"),
+            "BurntSushi/ripgrep@14.1.1/ripgrep-14.1.1-x86_64-unknown-linux-musl.tar.gz"
+        );
+        assert_eq!(
+            key(None, "https://example.com/tool.tar.gz#sha256=ab12"),
+            "https://example.com/tool.tar.gz"
+        );
+    }
+
+    #[test]
+    fn test_compare() {
+        let entry: Entry = serde_json::from_str(r#"{"sha256": "AB12"}"#).unwrap();
+        assert_eq!(compare(entry.sha256, "ab12"), Observation::Matches);
+        assert_eq!(
+            compare("ab12".into(), "cd34"),
+            Observation::Differs("ab12".into())
+        );
+        let posted = serde_json::to_string(&Entry {
+            key: "o/r@v1/a.zip",
+            sha256: "cd34".into(),
+        })
+        .unwrap();
+        assert_eq!(posted, r#"{"key":"o/r@v1/a.zip","sha256":"cd34"}"#);
+    }
+}
@@ -0,0 +1,209 @@
+use std::path::PathBuf;
+
+// Where dex keeps its own files, per platform:
+//
+//            Linux (XDG)          macOS                              Windows
+//   config   ~/.config/dex        ~/Library/Application Support/dex  %APPDATA%\dex
+//   cache    ~/.cache/dex         ~/Library/Caches/dex               %LOCALAPPDATA%\dex\cache
+//   data     ~/.local/share/dex   ~/Library/Application Support/dex  %LOCALAPPDATA%\dex\data
+//   state    ~/.local/state/dex   ~/Library/Application Support/dex/state  %LOCALAPPDATA%\dex\state
+//
+// The XDG_*_HOME variables are honored on every platform, and DEX_HOME puts
+// everything under one directory (<DEX_HOME>/config, <DEX_HOME>/cache, ...).
+
+/// Kinds of files dex keeps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    /// Settings the user edits (config.toml).
+    Config,
+    /// Anything that can be re-downloaded.
+    Cache,
+    /// Files dex manages that can't be recreated (the store).
+    Data,
+    /// Records of past runs (history, locks).
+    State,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Config => "config",
+            Kind::Cache => "cache",
+            Kind::Data => "data",
+            Kind::State => "state",
+        }
+    }
+
+    fn xdg_var(self) -> &'static str {
+        match self {
+            Kind::Config => "XDG_CONFIG_HOME",
+            Kind::Cache => "XDG_CACHE_HOME",
+            Kind::Data => "XDG_DATA_HOME",
+            Kind::State => "XDG_STATE_HOME",
+        }
+    }
+}
+
+/// dex's config directory, holding config.toml.
+pub fn config_dir() -> Option<PathBuf> {
+    // Before Library was used on macOS, config lived in ~/.config/dex.
+    if cfg!(target_os = "macos")
+        && env("DEX_HOME").is_none()
+        && env(Kind::Config.xdg_var()).is_none()
+        && let Some(legacy) = env("HOME").map(|home| home.join(".config").join("dex"))
+        && legacy.is_dir()
+    {
+        return Some(legacy);
+    }
+    dex_dir(Kind::Config, std::env::consts::OS, &env)
+}
+
+/// dex's cache directory, for files that can be fetched again.
+pub fn cache_dir() -> Option<PathBuf> {
+    dex_dir(Kind::Cache, std::env::consts::OS, &env)
+}
+
+/// dex's data directory, for files that can't be recreated.
+pub fn data_dir() -> Option<PathBuf> {
+    dex_dir(Kind::Data, std::env::consts::OS, &env)
+}
+
+/// dex's state directory, for records of earlier runs.
+pub fn state_dir() -> Option<PathBuf> {
+    dex_dir(Kind::State, std::env::consts::OS, &env)
+}
+
+/// The directory binaries are installed into when --bin-dir isn't given:
+/// ~/.local/bin on Unix, %LOCALAPPDATA%\Programs\dex\bin on Windows.
+pub fn bin_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        return Some(
+            env("LOCALAPPDATA")?
+                .join("Programs")
+                .join("dex")
+                .join("bin"),
+        );
+    }
+    Some(env("HOME")?.join(".local").join("bin"))
+}
+
+/// The user's shared data directory other programs read from (man pages,
+/// completions): `$XDG_DATA_HOME`, falling back to ~/.local/share.
+pub fn user_data_dir() -> Option<PathBuf> {
+    env("XDG_DATA_HOME").or_else(|| Some(env("HOME")?.join(".local").join("share")))
+}
+
+/// The user's shared config directory: `$XDG_CONFIG_HOME`, falling back to ~/.config.
+pub fn user_config_dir() -> Option<PathBuf> {
+    env("XDG_CONFIG_HOME").or_else(|| Some(env("HOME")?.join(".config")))
+}
+
+/// Reads a directory from the environment. Empty values count as unset,
+/// as the XDG spec asks.
+fn env(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Works out where files of `kind` go on `os`, reading variables through
+/// `env` so tests can supply their own.
+fn dex_dir(kind: Kind, os: &str, env: &dyn Fn(&str) -> Option<PathBuf>) -> Option<PathBuf> {
+    if let Some(home) = env("DEX_HOME") {
+        return Some(home.join(kind.name()));
+    }
+    if let Some(base) = env(kind.xdg_var()) {
+        return Some(base.join("dex"));
+    }
+
+    match os {
+        "windows" => match kind {
+            // Roaming, so settings follow the user between machines.
+            Kind::Config => Some(env("APPDATA")?.join("dex")),
+            _ => Some(env("LOCALAPPDATA")?.join("dex").join(kind.name())),
+        },
+        "macos" => {
+            let library = env("HOME")?.join("Library");
+            let support = library.join("Application Support").join("dex");
+            Some(match kind {
+                Kind::Cache => library.join("Caches").join("dex"),
+                Kind::Config | Kind::Data => support,
+                Kind::State => support.join("state"),
+            })
+        }
+        _ => {
+            let home = env("HOME")?;
+            let base = match kind {
+                Kind::Config => home.join(".config"),
+                Kind::Cache => home.join(".cache"),
+                Kind::Data => home.join(".local").join("share"),
+                Kind::State => home.join(".local").join("state"),
+            };
+            Some(base.join("dex"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake_env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<PathBuf> {
+        let vars: Vec<(String, PathBuf)> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), PathBuf::from(v)))
+            .collect();
+        move |name| vars.iter().find(|(k, _)| k == name).map(|(_, v)| v.clone())
+    }
+
+    #[test]
+    fn test_linux_dirs() {
+        let env = fake_env(&[("HOME", "/home/me"), ("XDG_CACHE_HOME", "/tmp/cache")]);
+        let dir = |kind| dex_dir(kind, "linux", &env).unwrap();
+        assert_eq!(dir(Kind::Config), PathBuf::from("/home/me/.config/dex"));
+        assert_eq!(dir(Kind::Cache), PathBuf::from("/tmp/cache/dex"));
+        assert_eq!(dir(Kind::Data), PathBuf::from("/home/me/.local/share/dex"));
+        assert_eq!(dir(Kind::State), PathBuf::from("/home/me/.local/state/dex"));
+    }
+
+    #[test]
+    fn test_macos_dirs() {
+        let env = fake_env(&[("HOME", "/Users/me")]);
+        let dir = |kind| dex_dir(kind, "macos", &env).unwrap();
+        assert_eq!(
+            dir(Kind::Config),
+            PathBuf::from("/Users/me/Library/Application Support/dex")
+        );
+        assert_eq!(
+            dir(Kind::Cache),
+            PathBuf::from("/Users/me/Library/Caches/dex")
+        );
+        assert_eq!(
+            dir(Kind::State),
+            PathBuf::from("/Users/me/Library/Application Support/dex/state")
+        );
+    }
+
+    #[test]
+    fn test_windows_dirs() {
+        let env = fake_env(&[("APPDATA", "C:/Roaming"), ("LOCALAPPDATA", "C:/Local")]);
+        let dir = |kind| dex_dir(kind, "windows", &env).unwrap();
+        assert_eq!(dir(Kind::Config), PathBuf::from("C:/Roaming/dex"));
+        assert_eq!(dir(Kind::Cache), PathBuf::from("C:/Local/dex/cache"));
+        assert_eq!(dir(Kind::Data), PathBuf::from("C:/Local/dex/data"));
+    }
+
+    #[test]
+    fn test_dex_home_overrides() {
+        let env = fake_env(&[("DEX_HOME", "/opt/dex"), ("XDG_CONFIG_HOME", "/etc/xdg")]);
+        assert_eq!(
+            dex_dir(Kind::Config, "linux", &env),
+            Some(PathBuf::from("/opt/dex/config"))
+        );
+        assert_eq!(
+            dex_dir(Kind::Cache, "windows", &env),
+            Some(PathBuf::from("/opt/dex/cache"))
+        );
+        assert_eq!(dex_dir(Kind::Cache, "linux", &fake_env(&[])), None);
+    }
+}
@@ -1,11 +1,145 @@
-use indicatif::{ProgressBar, ProgressStyle};
-use reqwest::blocking::Client;
+use crate::http::HttpContext;
+use crate::progress::TransferMonitor;
+use crate::verify::{Algorithm, Digest, Hasher};
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressStyle};
+use serde::Serialize;
 use std::fs;
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 
+/// The result of a completed download.
+pub struct Download {
+    /// Full path to the downloaded file.
+    pub path: PathBuf,
+    /// The name the server gave the file, before any --filename-template.
+    pub name: String,
+    /// Digests of the downloaded bytes, one per requested algorithm.
+    pub digests: Vec<Digest>,
+    /// The server's Content-Type for the file, for telling archive formats
+    /// apart when the name doesn't.
+    pub content_type: Option<String>,
+    /// The server's Content-Encoding, if it sent one.
+    pub content_encoding: Option<String>,
+    /// The server's ETag, for `remember_etag` once the file has been checked.
+    pub etag: Option<String>,
+    /// Number of bytes received.
+    pub bytes: u64,
+    /// Time from sending the request to receiving the last byte.
+    pub elapsed: std::time::Duration,
+}
+
+impl Download {
+    /// Returns the digest computed with `algorithm`, if it was requested.
+    pub fn digest(&self, algorithm: Algorithm) -> Option<&Digest> {
+        self.digests.iter().find(|d| d.algorithm == algorithm)
+    }
+}
+
+/// Options controlling a single download.
+#[derive(Default)]
+pub struct DownloadOptions<'a> {
+    /// Hash algorithms to compute while the file streams to disk, so
+    /// verification doesn't need a second read pass.
+    pub algorithms: &'a [Algorithm],
+    /// Progress bar group to draw into when several downloads run at once.
+    pub progress: Option<&'a MultiProgress>,
+    /// Print the final URL after redirects.
+    pub verbose: bool,
+    /// Only download if the remote file changed since this local copy.
+    pub if_newer: Option<&'a Path>,
+    /// Maps the name taken from the URL to the name to save under
+    /// (--filename-template).
+    pub rename: Option<&'a Rename<'a>>,
+    /// Refuse files larger than this many bytes (--max-size).
+    pub max_size: Option<u64>,
+    /// Bytes to read from the network and write to disk at a time
+    /// (--chunk-size); `CHUNK_SIZE` if not set.
+    pub chunk_size: Option<usize>,
+    /// Copy file:// URLs, which only a provider that fetched the files
+    /// itself (`Provider::local_assets`) hands out.
+    pub local_files: bool,
+}
+
+impl DownloadOptions<'_> {
+    /// The chunk size to use, kept within `MIN_CHUNK_SIZE..=MAX_CHUNK_SIZE`.
+    fn chunk_size(&self) -> usize {
+        self.chunk_size
+            .unwrap_or(CHUNK_SIZE)
+            .clamp(MIN_CHUNK_SIZE, MAX_CHUNK_SIZE)
+    }
+}
+
+/// How much of the body is read at once, and buffered before a write. Big
+/// enough that a fast link isn't held up by system calls.
+pub const CHUNK_SIZE: usize = 128 * 1024;
+
+/// The smallest and largest --chunk-size allowed: smaller makes a system
+/// call for every few packets, larger is memory held for no gain.
+pub const MIN_CHUNK_SIZE: usize = 4 * 1024;
+pub const MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+
+/// Turns the file name from a URL into the one to save under, or explains
+/// why it can't.
+pub type Rename<'a> = dyn Fn(&str) -> Result<String, String> + 'a;
+
+/// The server reported that the file hasn't changed since the local copy
+/// named by `--if-newer` (HTTP 304 Not Modified).
+#[derive(Debug)]
+pub struct NotModified;
+
+impl std::fmt::Display for NotModified {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "not modified since the local copy")
+    }
+}
+
+impl std::error::Error for NotModified {}
+
+/// Nothing arrived for longer than the stall timeout (--stall-timeout).
+#[derive(Debug)]
+pub struct Stalled {
+    /// How long the transfer went without receiving anything.
+    pub idle: std::time::Duration,
+}
+
+impl std::fmt::Display for Stalled {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "transfer stalled: no data received for {}s",
+            self.idle.as_secs()
+        )
+    }
+}
+
+impl std::error::Error for Stalled {}
+
+/// The file is bigger than --max-size allows.
+#[derive(Debug)]
+pub struct TooLarge {
+    /// The size the server declared, or None if the body ran past the
+    /// limit without one.
+    pub size: Option<u64>,
+    pub limit: u64,
+}
+
+impl std::fmt::Display for TooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let limit = HumanBytes(self.limit);
+        match self.size {
+            Some(size) => write!(
+                f,
+                "file is {}, over the --max-size limit of {limit}",
+                HumanBytes(size)
+            ),
+            None => write!(f, "download passed the --max-size limit of {limit}"),
+        }
+    }
+}
+
+impl std::error::Error for TooLarge {}
+
 /// Downloads a file from `url` into the `output_dir` directory.
-/// Returns the full path to the downloaded file.
 ///
 /// # Errors
 /// Returns an error if the HTTP request fails, the server returns a non-success
@@ -16,45 +150,117 @@ use std::path::{Path, PathBuf};
 // `Box<dyn std::error::Error>` means "any error type" — it's a trait object
 // (like an interface pointer in OOP). This is the simple/lazy way to handle
 // errors. Production code often defines custom error types instead.
-pub fn download_file(url: &str, output_dir: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+pub fn download_file(
+    http: &HttpContext,
+    url: &str,
+    output_dir: &Path,
+    options: &DownloadOptions,
+) -> Result<Download, Box<dyn std::error::Error>> {
     // `pub` makes this function visible outside this module (from main.rs).
     // `&str` is a string reference (borrowed, read-only). `&Path` is similar but for paths.
     // The `&` means we're borrowing — we can read it but don't own it.
     // This is Rust's ownership system: only one owner at a time, others can borrow.
 
-    // Build an HTTP client with a User-Agent header.
-    // `?` at the end is the "try operator" — if the expression returns Err, immediately
-    // return that error from our function. It's shorthand for:
-    //   match result {
-    //     Ok(val) => val,
-    //     Err(e) => return Err(e.into()),
-    //   }
-    let client = Client::builder()
-        .user_agent(format!("dex/{}", env!("CARGO_PKG_VERSION")))
-        // ↑ `env!("CARGO_PKG_VERSION")` is a compile-time macro that reads the version
-        //   from Cargo.toml. The string "dex/0.1.0" is baked into the binary.
-        .build()?;
+    // Providers that fetch files themselves (Azure Artifacts) hand them over
+    // as file:// URLs. Nothing else gets to read local files this way.
+    if let Ok(parsed) = reqwest::Url::parse(url)
+        && parsed.scheme() == "file"
+    {
+        if !options.local_files {
+            return Err(format!("not reading a local file for {url}").into());
+        }
+        let path = parsed
+            .to_file_path()
+            .map_err(|()| format!("not a local file: {url}"))?;
+        return copy_local(&path, output_dir, options);
+    }
+
+    // Ask for the file as it is. Servers may compress it for the trip anyway,
+    // which is undone below.
+    let mut request = http
+        .get(url)
+        .header(reqwest::header::ACCEPT_ENCODING, "identity");
+    // With --if-newer, ask the server to skip the body if nothing changed.
+    if let Some(local) = options.if_newer {
+        for (name, value) in conditional_headers(local) {
+            request = request.header(name, value);
+        }
+    }
 
     // Send the GET request.
-    let response = client.get(url).send()?.error_for_status()?;
+    let started = std::time::Instant::now();
+    let response = http.send(request)?.error_for_status()?;
     // ↑ Chaining: send the request, then check the HTTP status code.
     //   `error_for_status()` converts 4xx/5xx responses into Err values.
-    //   Each `?` propagates errors upward.
+    //   `?` is the "try operator" — if the expression returns Err, immediately
+    //   return that error from our function. It's shorthand for:
+    //     match result {
+    //       Ok(val) => val,
+    //       Err(e) => return Err(e.into()),
+    //     }
 
-    // Try to figure out the filename from the URL (the last path segment).
-    // e.g., "https://example.com/files/archive.tar.gz" → "archive.tar.gz"
-    let filename = url
-        .rsplit('/') // Split from the right on '/'
-        .next() // Take the first segment (= last part of URL)
-        .unwrap_or("download")
-        .split('?') // Strip query params (e.g., from CDN redirect URLs)
-        .next()
-        .unwrap_or("download")
-        .to_string();
-    // ↑ `.to_string()` converts a `&str` (borrowed) into a `String` (owned).
-    //   We need ownership because we're building a PathBuf with it below.
+    // 304 is a success status, so it gets here rather than failing above.
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Err(NotModified.into());
+    }
+    // Kept for the next --if-newer run, but only saved once the caller has
+    // the whole file and has checked it.
+    let etag = response
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
+    // `response.url()` is where the redirects (if any) led.
+    let final_url = response.url().to_string();
+    if options.verbose {
+        if final_url != url {
+            eprintln!("Final URL: {final_url}");
+        }
+        eprintln!("Protocol: {:?}", response.version());
+    }
+
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
+    // Figure out the filename from the URL (the last path segment).
+    let name = download_name(url, &final_url);
+    let filename = match options.rename {
+        // An io::Error, so callers count it as a local problem rather than
+        // the network's.
+        Some(rename) => {
+            rename(&name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?
+        }
+        None => name.clone(),
+    };
+
+    let content_encoding = response
+        .headers()
+        .get(reqwest::header::CONTENT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let coding = match &content_encoding {
+        Some(encoding) => decoding(encoding, &name)?,
+        None => None,
+    };
+    if options.verbose
+        && let Some(encoding) = &content_encoding
+    {
+        let action = if coding.is_some() {
+            "decoding it"
+        } else {
+            "kept: the file itself is compressed"
+        };
+        eprintln!("Content-Encoding: {encoding} ({action})");
+    }
 
     let file_path = output_dir.join(&filename);
+    // The body streams into "<name>.part" and is renamed into place once
+    // complete, so an interrupted download never looks like a finished one.
+    let part_path = output_dir.join(format!("{filename}.part"));
     // ↑ `Path::join` concatenates paths with the correct separator.
     //   e.g., Path("./").join("file.tar.gz") → "./file.tar.gz"
 
@@ -62,9 +268,24 @@ pub fn download_file(url: &str, output_dir: &Path) -> Result<PathBuf, Box<dyn st
     fs::create_dir_all(output_dir)?;
 
     // Get the total file size from the Content-Length header (if the server provides it).
-    let total_size = response.content_length();
+    let declared_size = response.content_length();
     // ↑ Returns Option<u64> — Some(size) or None if the header is missing.
 
+    // Turn away a file that's declared too big before any of it is written.
+    if let (Some(size), Some(limit)) = (declared_size, options.max_size)
+        && size > limit
+    {
+        return Err(TooLarge {
+            size: Some(size),
+            limit,
+        }
+        .into());
+    }
+
+    // Content-Length counts the bytes sent, so it says nothing of a decoded
+    // file's size.
+    let total_size = declared_size.filter(|_| coding.is_none());
+
     // Set up the progress bar.
     let pb = match total_size {
         Some(size) => {
@@ -72,13 +293,13 @@ pub fn download_file(url: &str, output_dir: &Path) -> Result<PathBuf, Box<dyn st
             let pb = ProgressBar::new(size);
             pb.set_style(
                 ProgressStyle::default_bar()
-                    .template("{spinner:.green} [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta})")
+                    .template("{spinner:.green} [{bar:40.cyan/blue}] {bytes}/{total_bytes} {msg}")
                     // ↑ Template string for the progress bar appearance:
                     //   {spinner}     = spinning animation
                     //   {bar:40}      = 40-char wide progress bar
                     //   {bytes}       = downloaded so far
                     //   {total_bytes} = total file size
-                    //   {eta}         = estimated time remaining
+                    //   {msg}         = our own text: speed and time remaining
                     //   .green/.cyan/.blue = colors
                     .expect("invalid progress bar template")
                     .progress_chars("=> "),
@@ -91,31 +312,57 @@ pub fn download_file(url: &str, output_dir: &Path) -> Result<PathBuf, Box<dyn st
             let pb = ProgressBar::new_spinner();
             pb.set_style(
                 ProgressStyle::default_spinner()
-                    .template("{spinner:.green} {bytes} downloaded")
+                    .template("{spinner:.green} {bytes} downloaded {msg}")
                     .expect("invalid progress bar template"),
             );
             pb
         }
     };
 
+    // With concurrent downloads, each bar joins the shared group so they
+    // stack instead of overwriting each other.
+    let pb = match options.progress {
+        Some(multi) => multi.add(pb),
+        None => pb,
+    };
+
     // Read the response body in chunks and write to the file.
     // This streams the data instead of loading the entire file into memory.
-    let mut file = fs::File::create(&file_path)?;
+    let chunk_size = options.chunk_size();
+    let mut file = io::BufWriter::with_capacity(chunk_size, fs::File::create(&part_path)?);
     // ↑ `mut` = mutable. We need to write to this file, so it must be mutable.
     //   In Rust, variables are immutable by default (a safety feature).
+    //   `BufWriter` gathers the network's small reads into fewer, bigger writes.
 
     let mut downloaded: u64 = 0;
-    let mut reader = response;
+    let mut reader: Box<dyn io::Read> = match coding {
+        None => Box::new(response),
+        Some(Coding::Gzip) => Box::new(flate2::read::MultiGzDecoder::new(response)),
+        Some(Coding::Deflate) => Box::new(flate2::read::ZlibDecoder::new(response)),
+        Some(Coding::Brotli) => Box::new(brotli::Decompressor::new(response, 4096)),
+        Some(Coding::Zstd) => Box::new(zstd::Decoder::new(response)?),
+    };
+    let mut hashers: Vec<Hasher> = options.algorithms.iter().map(|&a| Hasher::new(a)).collect();
+    // indicatif's own speed estimate jumps around with every burst; the
+    // monitor averages over the last few seconds instead.
+    let mut monitor = TransferMonitor::new(std::time::Instant::now());
 
-    // We read in 8KB chunks — a good balance between memory usage and I/O efficiency.
-    let mut buffer = [0u8; 8192];
-    // ↑ `[0u8; 8192]` creates an array of 8192 bytes, all initialized to 0.
+    // We read in chunks (128 KB unless --chunk-size says otherwise).
+    let mut buffer = vec![0u8; chunk_size];
+    // ↑ `vec![0u8; n]` creates n bytes on the heap, all initialized to 0.
     //   `u8` is an unsigned 8-bit integer (= a byte).
 
     loop {
         // `io::Read::read` fills the buffer and returns how many bytes were read.
         // 0 bytes means we've reached the end of the response body.
-        let bytes_read = io::Read::read(&mut reader, &mut buffer)?;
+        let bytes_read = io::Read::read(&mut reader, &mut buffer).map_err(|e| {
+            if is_timeout(&e) {
+                let idle = monitor.idle(std::time::Instant::now());
+                Box::new(Stalled { idle }) as Box<dyn std::error::Error>
+            } else {
+                e.into()
+            }
+        })?;
         if bytes_read == 0 {
             break;
         }
@@ -123,15 +370,501 @@ pub fn download_file(url: &str, output_dir: &Path) -> Result<PathBuf, Box<dyn st
         file.write_all(&buffer[..bytes_read])?;
         // ↑ `&buffer[..bytes_read]` is a slice — a view into part of the array.
         //   `[..n]` means "from index 0 up to (not including) n".
+        for hasher in &mut hashers {
+            hasher.update(&buffer[..bytes_read]);
+        }
         downloaded += bytes_read as u64;
-        pb.set_position(downloaded);
+
+        // The header may be missing, or wrong: count what actually arrives.
+        if let Some(limit) = options.max_size
+            && downloaded > limit
+        {
+            pb.finish_and_clear();
+            drop(file);
+            let _ = fs::remove_file(&part_path);
+            return Err(TooLarge { size: None, limit }.into());
+        }
+
+        let now = std::time::Instant::now();
+        monitor.record(now, downloaded);
+        let remaining = total_size.map(|total| total.saturating_sub(downloaded));
+        // Redrawing the bar for every chunk costs more than the chunk; it's
+        // brought up to date a few times a second instead.
+        if let Some(status) = monitor.status(now, remaining) {
+            pb.set_position(downloaded);
+            pb.set_message(status);
+        }
     }
 
     pb.finish_and_clear();
     // ↑ Remove the progress bar from the terminal when done.
 
-    Ok(file_path)
-    // ↑ Return the path wrapped in Ok — the success variant of Result.
+    // Writing out what's still buffered can fail too (a full disk, say).
+    file.flush()?;
+    drop(file);
+    fs::rename(&part_path, &file_path)?;
+
+    Ok(Download {
+        path: file_path,
+        name,
+        digests: hashers.into_iter().map(Hasher::finalize).collect(),
+        content_type,
+        content_encoding,
+        etag,
+        bytes: downloaded,
+        elapsed: started.elapsed(),
+    })
+    // ↑ Return the result wrapped in Ok — the success variant of Result.
     //   Note: no `return` keyword and no semicolon. In Rust, the last expression
     //   in a function is its return value (like Ruby or Kotlin).
 }
+
+/// Copies a local file into `output_dir` the way `download_file` saves a
+/// download: renamed, size-limited and hashed as it goes.
+fn copy_local(
+    path: &Path,
+    output_dir: &Path,
+    options: &DownloadOptions,
+) -> Result<Download, Box<dyn std::error::Error>> {
+    let started = std::time::Instant::now();
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("not a file: {}", path.display()))?
+        .to_string_lossy()
+        .into_owned();
+    let filename = match options.rename {
+        Some(rename) => {
+            rename(&name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?
+        }
+        None => name.clone(),
+    };
+    let mut source = fs::File::open(path)?;
+    let size = source.metadata()?.len();
+    if let Some(limit) = options.max_size
+        && size > limit
+    {
+        return Err(TooLarge {
+            size: Some(size),
+            limit,
+        }
+        .into());
+    }
+
+    fs::create_dir_all(output_dir)?;
+    let file_path = output_dir.join(&filename);
+    let part_path = output_dir.join(format!("{filename}.part"));
+    let mut file = fs::File::create(&part_path)?;
+    let mut hashers: Vec<Hasher> = options.algorithms.iter().map(|&a| Hasher::new(a)).collect();
+    let mut buffer = vec![0u8; options.chunk_size()];
+    let mut copied: u64 = 0;
+    loop {
+        let n = io::Read::read(&mut source, &mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        file.write_all(&buffer[..n])?;
+        for hasher in &mut hashers {
+            hasher.update(&buffer[..n]);
+        }
+        copied += n as u64;
+    }
+    drop(file);
+    fs::rename(&part_path, &file_path)?;
+
+    Ok(Download {
+        path: file_path,
+        name,
+        digests: hashers.into_iter().map(Hasher::finalize).collect(),
+        content_type: None,
+        content_encoding: None,
+        etag: None,
+        bytes: copied,
+        elapsed: started.elapsed(),
+    })
+}
+
+/// A Content-Encoding dex undoes while saving.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Coding {
+    Gzip,
+    Deflate,
+    Brotli,
+    Zstd,
+}
+
+impl Coding {
+    /// File extensions of files already compressed this way.
+    fn extensions(self) -> &'static [&'static str] {
+        match self {
+            Coding::Gzip => &[".gz", ".tgz"],
+            Coding::Deflate => &[".zz", ".zlib"],
+            Coding::Brotli => &[".br"],
+            Coding::Zstd => &[".zst", ".tzst"],
+        }
+    }
+}
+
+/// How to save a response sent with `Content-Encoding: <encoding>`.
+///
+/// Servers label files that are compressed already ("tool.tar.gz" sent as
+/// gzip) the same way as ones they compressed for the trip ("tool.tar"
+/// sent as gzip). Only the second kind is decoded, so the bytes on disk are
+/// always what the file's name says. None means the bytes are saved as sent.
+fn decoding(encoding: &str, name: &str) -> Result<Option<Coding>, String> {
+    let coding = match encoding.trim().to_ascii_lowercase().as_str() {
+        "" | "identity" => return Ok(None),
+        "gzip" | "x-gzip" => Coding::Gzip,
+        "deflate" => Coding::Deflate,
+        "br" => Coding::Brotli,
+        "zstd" => Coding::Zstd,
+        other => return Err(format!("unsupported Content-Encoding '{other}'")),
+    };
+    let name = name.to_ascii_lowercase();
+    let compressed = coding.extensions().iter().any(|ext| name.ends_with(ext));
+    Ok((!compressed).then_some(coding))
+}
+
+/// Whether a failed body read was the client giving up waiting for data.
+fn is_timeout(error: &io::Error) -> bool {
+    // reqwest wraps its own error, which knows whether it was a timeout.
+    error.kind() == io::ErrorKind::TimedOut
+        || error
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<reqwest::Error>())
+            .is_some_and(reqwest::Error::is_timeout)
+}
+
+/// Request headers that make the server answer 304 if `local` is current:
+/// If-Modified-Since from its modification time, and If-None-Match from the
+/// ETag the last download saved for it. None if `local` doesn't exist.
+fn conditional_headers(local: &Path) -> Vec<(reqwest::header::HeaderName, String)> {
+    let mut headers = Vec::new();
+    if let Ok(modified) = fs::metadata(local).and_then(|m| m.modified()) {
+        headers.push((
+            reqwest::header::IF_MODIFIED_SINCE,
+            httpdate::fmt_http_date(modified),
+        ));
+        if let Some(etag) = etag_path(local).and_then(|p| fs::read_to_string(p).ok()) {
+            headers.push((reqwest::header::IF_NONE_MATCH, etag.trim().to_string()));
+        }
+    }
+    headers
+}
+
+/// Saves `etag` as the one to send with the next `--if-newer <local>`. Only
+/// call this once the download it came with is in place and verified, or a
+/// failed run would make the next one skip the new version as unchanged.
+pub fn remember_etag(local: &Path, etag: &str) -> io::Result<()> {
+    let Some(path) = etag_path(local) else {
+        return Ok(());
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, etag)
+}
+
+/// Where the ETag for `--if-newer <local>` is kept: in the cache directory,
+/// under a hash of the local file's absolute path (so mirror directories
+/// don't fill up with sidecar files).
+fn etag_path(local: &Path) -> Option<PathBuf> {
+    let absolute = std::path::absolute(local).ok()?;
+    let mut hasher = Hasher::new(Algorithm::Sha256);
+    hasher.update(absolute.as_os_str().as_encoded_bytes());
+    let key = hasher.finalize().hex;
+    Some(crate::dirs::cache_dir()?.join("etags").join(key))
+}
+
+/// Picks the name to save a download under. The final URL after redirects
+/// usually names the file better ("/download?id=5" → "tool-1.2.tar.gz"), but
+/// CDNs often serve from opaque object keys with no extension — then the
+/// requested URL's name is kept.
+fn download_name(url: &str, final_url: &str) -> String {
+    let name = filename_from_url(final_url);
+    if name.contains('.') {
+        name
+    } else {
+        filename_from_url(url)
+    }
+}
+
+/// Derives a file name from a URL's last path segment, dropping any query
+/// string and decoding percent-escapes.
+/// e.g., "https://example.com/files/my%20tool.tar.gz?viasf=1" → "my tool.tar.gz"
+pub fn filename_from_url(url: &str) -> String {
+    let segment = url
+        .split(['?', '#']) // Strip query params (e.g., from CDN redirect URLs)
+        .next()
+        .unwrap_or(url)
+        .rsplit('/') // Split from the right on '/'
+        .next() // Take the first segment (= last part of URL)
+        .filter(|s| !s.is_empty())
+        .unwrap_or("download");
+
+    // Decode %XX escapes; anything malformed is kept as-is.
+    let bytes = segment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    // Bytes that aren't UTF-8 keep their escapes, rather than become "�"s
+    // that would give different files the same name.
+    let Ok(name) = String::from_utf8(decoded) else {
+        return segment.to_string();
+    };
+    // A decoded slash or backslash must not turn into a path separator.
+    if name.contains(['/', '\\']) || name == ".." {
+        return segment.to_string();
+    }
+    name
+}
+
+/// What the server says about a file, from `dex probe`.
+#[derive(Serialize)]
+pub struct Probe {
+    /// Where the redirects (if any) led.
+    pub final_url: String,
+    /// Size in bytes, if the server said.
+    pub size: Option<u64>,
+    pub content_type: Option<String>,
+    pub last_modified: Option<String>,
+    pub etag: Option<String>,
+    /// Whether the server serves byte ranges, so downloads can resume.
+    pub ranges: bool,
+}
+
+/// A file already in `dir` that's the same as the one `url` would download,
+/// so the transfer can be skipped: one matching `expected`, when there's a
+/// digest to go by, or else one of the size the release listing
+/// (`known_size`) or the server reports. It's hashed with `options.algorithms`
+/// to stand in for a download.
+pub fn find_existing(
+    http: &HttpContext,
+    url: &str,
+    name: &str,
+    dir: &Path,
+    expected: Option<&Digest>,
+    known_size: Option<u64>,
+    options: &DownloadOptions,
+) -> Result<Option<Download>, Box<dyn std::error::Error>> {
+    let started = std::time::Instant::now();
+    let saved_name = match options.rename {
+        Some(rename) => rename(name)?,
+        None => name.to_string(),
+    };
+    let path = dir.join(&saved_name);
+    let Ok(metadata) = fs::metadata(&path) else {
+        return Ok(None);
+    };
+    if !metadata.is_file() {
+        return Ok(None);
+    }
+
+    // Sizes are compared first, so a file that can't match is never hashed.
+    if known_size.is_some_and(|size| size != metadata.len()) {
+        return Ok(None);
+    }
+    if expected.is_none() {
+        let size = known_size.or_else(|| probe(http, url).ok()?.size);
+        if size != Some(metadata.len()) {
+            return Ok(None);
+        }
+    }
+
+    let mut algorithms = options.algorithms.to_vec();
+    if let Some(expected) = expected
+        && !algorithms.contains(&expected.algorithm)
+    {
+        algorithms.push(expected.algorithm);
+    }
+    let digests = crate::verify::hash_file_all(&path, &algorithms)?;
+    if let Some(expected) = expected
+        && !digests
+            .iter()
+            .any(|d| d.algorithm == expected.algorithm && d.hex == expected.hex)
+    {
+        return Ok(None);
+    }
+    let how = match expected {
+        Some(expected) => format!("same {}", expected.algorithm.name()),
+        None => "same size".to_string(),
+    };
+    eprintln!(
+        "{} is already here ({how}); not downloading it again",
+        path.display()
+    );
+    Ok(Some(Download {
+        path,
+        name: name.to_string(),
+        digests: digests
+            .into_iter()
+            .filter(|d| options.algorithms.contains(&d.algorithm))
+            .collect(),
+        content_type: None,
+        content_encoding: None,
+        etag: None,
+        bytes: metadata.len(),
+        elapsed: started.elapsed(),
+    }))
+}
+
+/// Asks the server about `url` without downloading it: a HEAD request, or a
+/// GET for just the first byte when the server doesn't answer HEAD.
+pub fn probe(http: &HttpContext, url: &str) -> Result<Probe, Box<dyn std::error::Error>> {
+    let head = http.send(http.head(url))?;
+    // Some servers (S3 presigned URLs, a few CDNs) refuse HEAD outright.
+    let response = if head.status().is_success() {
+        head
+    } else {
+        http.send(http.get(url).header(reqwest::header::RANGE, "bytes=0-0"))?
+            .error_for_status()?
+    };
+    // The body of a ranged GET is never read; dropping the response closes it.
+
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v: &reqwest::header::HeaderValue| v.to_str().ok())
+            .map(str::to_string)
+    };
+    let partial = response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
+    // A 206's Content-Length is the one byte asked for; the full size is
+    // after the slash in "Content-Range: bytes 0-0/12345".
+    let size = if partial {
+        header(reqwest::header::CONTENT_RANGE)
+            .and_then(|range| range.rsplit('/').next()?.parse().ok())
+    } else {
+        header(reqwest::header::CONTENT_LENGTH).and_then(|len| len.parse().ok())
+    };
+
+    Ok(Probe {
+        final_url: response.url().to_string(),
+        size,
+        content_type: header(reqwest::header::CONTENT_TYPE),
+        last_modified: header(reqwest::header::LAST_MODIFIED),
+        etag: header(reqwest::header::ETAG),
+        ranges: partial || header(reqwest::header::ACCEPT_RANGES).is_some_and(|v| v == "bytes"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decoding() {
+        // Compressed for the trip: decoded.
+        assert_eq!(decoding("gzip", "tool.tar"), Ok(Some(Coding::Gzip)));
+        assert_eq!(decoding("br", "tool"), Ok(Some(Coding::Brotli)));
+        // Compressed already: saved as sent.
+        assert_eq!(decoding("gzip", "tool.tar.gz"), Ok(None));
+        assert_eq!(decoding("x-gzip", "Tool.TGZ"), Ok(None));
+        assert_eq!(decoding("zstd", "tool.tar.zst"), Ok(None));
+        assert_eq!(decoding("identity", "tool.tar"), Ok(None));
+        assert!(decoding("compress", "tool.tar").is_err());
+    }
+
+    #[test]
+    fn test_filename_from_url() {
+        assert_eq!(
+            filename_from_url("https://example.com/dl/caf%C3%A9.tar.gz?x=1"),
+            "café.tar.gz"
+        );
+        // Latin-1 "café" isn't UTF-8, and "caf%E8" mustn't collide with it.
+        assert_eq!(
+            filename_from_url("https://example.com/caf%E9.zip"),
+            "caf%E9.zip"
+        );
+        assert_eq!(
+            filename_from_url("https://example.com/a%2Fb.zip"),
+            "a%2Fb.zip"
+        );
+        assert_eq!(filename_from_url("https://example.com/"), "download");
+    }
+
+    #[test]
+    fn test_find_existing() {
+        let dir = std::env::temp_dir().join(format!("dex-test-existing-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("tool.tar.gz"), "tool").unwrap();
+        let http = HttpContext::new(&Default::default()).unwrap();
+        let url = "https://example.invalid/tool.tar.gz";
+        let sha256 = |data: &[u8]| {
+            let mut hasher = Hasher::new(Algorithm::Sha256);
+            hasher.update(data);
+            hasher.finalize()
+        };
+        let options = DownloadOptions {
+            algorithms: &[Algorithm::Sha256],
+            ..Default::default()
+        };
+        let find = |expected: Option<Digest>, size| {
+            find_existing(
+                &http,
+                url,
+                "tool.tar.gz",
+                &dir,
+                expected.as_ref(),
+                size,
+                &options,
+            )
+            .unwrap()
+        };
+
+        let found = find(Some(sha256(b"tool")), None).unwrap();
+        assert_eq!(found.bytes, 4);
+        assert_eq!(found.digests, [sha256(b"tool")]);
+        assert!(find(Some(sha256(b"tool 2.0")), None).is_none());
+        // Without a digest, the size listed for the asset decides.
+        assert!(find(None, Some(4)).is_some());
+        assert!(find(None, Some(5)).is_none());
+        assert!(find(Some(sha256(b"tool")), Some(5)).is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_download_local_file() {
+        let dir = std::env::temp_dir().join(format!("dex-test-local-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("tool.tar.gz");
+        fs::write(&source, "tool").unwrap();
+        let url = reqwest::Url::from_file_path(&source).unwrap().to_string();
+        let http = HttpContext::new(&Default::default()).unwrap();
+        let options = DownloadOptions {
+            algorithms: &[Algorithm::Sha256],
+            local_files: true,
+            ..Default::default()
+        };
+
+        // Only when a provider put the file there.
+        assert!(download_file(&http, &url, &dir.join("out"), &Default::default()).is_err());
+        let download = download_file(&http, &url, &dir.join("out"), &options).unwrap();
+        assert_eq!(download.name, "tool.tar.gz");
+        assert_eq!(fs::read(&download.path).unwrap(), b"tool");
+        assert_eq!(download.bytes, 4);
+        assert!(download.digest(Algorithm::Sha256).is_some());
+
+        let limited = DownloadOptions {
+            max_size: Some(3),
+            local_files: true,
+            ..Default::default()
+        };
+        assert!(download_file(&http, &url, &dir.join("out"), &limited).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
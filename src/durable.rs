@@ -0,0 +1,81 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Flushes `files` to disk (--fsync), along with the directories that name
+/// them: each file's parent, and for files under `root`, every directory up
+/// to `root` and `root`'s own entry, since any of them may be new.
+pub fn sync(files: &[PathBuf], root: &Path) -> io::Result<()> {
+    let mut dirs = BTreeSet::new();
+    for file in files {
+        sync_file(file)?;
+        let mut dir = file.parent();
+        while let Some(current) = dir {
+            dirs.insert(current);
+            if current == root || !current.starts_with(root) {
+                break;
+            }
+            dir = current.parent();
+        }
+        if file.starts_with(root)
+            && let Some(parent) = root.parent()
+        {
+            dirs.insert(parent);
+        }
+    }
+    for dir in dirs {
+        // "file" has the parent "", which is the working directory.
+        sync_dir(if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        })?;
+    }
+    Ok(())
+}
+
+fn sync_file(path: &Path) -> io::Result<()> {
+    // Windows only flushes a handle that may write; elsewhere a read-only
+    // handle does, and works on read-only files too.
+    fs::OpenOptions::new()
+        .read(true)
+        .write(cfg!(windows))
+        .open(path)?
+        .sync_all()
+}
+
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    fs::File::open(dir)?.sync_all()
+}
+
+// NTFS journals directory changes itself, and a directory can't be opened
+// like a file to flush it.
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sync() {
+        let root = std::env::temp_dir().join(format!("dex-test-durable-{}", std::process::id()));
+        let nested = root.join("tool/bin");
+        fs::create_dir_all(&nested).unwrap();
+        let files = [nested.join("tool"), root.join("README")];
+        for file in &files {
+            fs::write(file, "contents").unwrap();
+        }
+        let mut permissions = fs::metadata(&files[1]).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&files[1], permissions).unwrap();
+
+        sync(&files, &root).unwrap();
+        assert!(sync(&[root.join("missing")], &root).is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -1,92 +1,573 @@
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashSet};
+use std::ffi::OsStr;
 use std::fs;
 use std::io;
-use std::path::Path;
-
-// An enum (short for "enumeration") in Rust is more powerful than in most languages.
-// Each variant can hold data. Think of it as a "tagged union" — it's one of these
-// types, and the compiler forces you to handle all cases.
-// Unlike C/Java enums which are just named integers, Rust enums are full algebraic
-// data types (if that means anything to you — if not, just think "fancy enum").
-enum ArchiveType {
-    TarGz,
-    TarBz2,
-    TarXz,
-    TarZst,
-    Zip,
-    Gz,
-    Bz2,
-    Xz,
-    Zst,
-}
-
-/// Checks if a file path has a recognized archive extension.
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+/// What every extraction function returns: the files it created.
+type Extracted = Result<Vec<PathBuf>, Box<dyn std::error::Error>>;
+
+// A trait is Rust's version of an interface: a set of methods a type promises
+// to provide. `&dyn Extractor` ("some type that implements Extractor") lets
+// one list hold every archive format, however each is implemented.
+/// An archive format: how to recognize it, and how to unpack it.
+///
+/// The formats dex knows are listed in `BUILTIN`.
+pub trait Extractor: Sync {
+    // ↑ `: Sync` lets formats live in a `static`, shared across threads.
+
+    /// A short name for the format, e.g. "tar.gz".
+    fn name(&self) -> &'static str;
+
+    /// File name endings that mark the format, in lowercase (".tar.gz", ".tgz").
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// Signatures that mark the format, as (byte offset, bytes). Only checked
+    /// for files whose name has no extension at all.
+    fn magic(&self) -> &'static [(usize, &'static [u8])] {
+        &[]
+    }
+
+    /// MIME types servers label the format with ("application/zip"), for
+    /// downloads whose name has no archive extension.
+    fn content_types(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Unpacks the archive at `path` into `output_dir`, which already exists.
+    fn extract(&self, path: &Path, output_dir: &Path, options: &ExtractOptions) -> Extracted;
+}
+
+/// A built-in format: a table entry pointing at one of the functions below.
+struct Format {
+    name: &'static str,
+    extensions: &'static [&'static str],
+    magic: &'static [(usize, &'static [u8])],
+    content_types: &'static [&'static str],
+    extract: fn(&Path, &Path, &ExtractOptions) -> Extracted,
+}
+
+impl Extractor for Format {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        self.extensions
+    }
+
+    fn magic(&self) -> &'static [(usize, &'static [u8])] {
+        self.magic
+    }
+
+    fn content_types(&self) -> &'static [&'static str] {
+        self.content_types
+    }
+
+    fn extract(&self, path: &Path, output_dir: &Path, options: &ExtractOptions) -> Extracted {
+        (self.extract)(path, output_dir, options)
+    }
+}
+
+// Compressed tarballs have no signature of their own — they start like any
+// other .gz or .xz file — so without an extension (or a Content-Type that
+// says what they are) they're decompressed first, and the tar inside is
+// found on the next level of `extract_nested`.
+const TAR: Format = Format {
+    name: "tar",
+    extensions: &[".tar"],
+    magic: &[(257, b"ustar")],
+    content_types: &["application/x-tar"],
+    extract: extract_tar,
+};
+const TAR_GZ: Format = Format {
+    name: "tar.gz",
+    extensions: &[".tar.gz", ".tgz"],
+    magic: &[],
+    content_types: &[
+        "application/x-gtar",
+        "application/x-tgz",
+        "application/x-compressed-tar",
+    ],
+    extract: extract_tar_gz,
+};
+const TAR_BZ2: Format = Format {
+    name: "tar.bz2",
+    extensions: &[".tar.bz2", ".tbz2"],
+    magic: &[],
+    content_types: &[
+        "application/x-bzip-compressed-tar",
+        "application/x-bzip2-compressed-tar",
+    ],
+    extract: extract_tar_bz2,
+};
+const TAR_XZ: Format = Format {
+    name: "tar.xz",
+    extensions: &[".tar.xz", ".txz"],
+    magic: &[],
+    content_types: &["application/x-xz-compressed-tar"],
+    extract: extract_tar_xz,
+};
+const TAR_ZST: Format = Format {
+    name: "tar.zst",
+    extensions: &[".tar.zst", ".tzst"],
+    magic: &[],
+    content_types: &["application/x-zstd-compressed-tar"],
+    extract: extract_tar_zst,
+};
+const TAR_LZ4: Format = Format {
+    name: "tar.lz4",
+    extensions: &[".tar.lz4", ".tlz4"],
+    magic: &[],
+    content_types: &["application/x-lz4-compressed-tar"],
+    extract: extract_tar_lz4,
+};
+const TAR_BR: Format = Format {
+    name: "tar.br",
+    extensions: &[".tar.br"],
+    magic: &[],
+    content_types: &[],
+    extract: extract_tar_br,
+};
+const ZIP: Format = Format {
+    name: "zip",
+    extensions: &[".zip"],
+    magic: &[(0, b"PK\x03\x04")],
+    content_types: &["application/zip", "application/x-zip-compressed"],
+    extract: extract_zip,
+};
+const CAB: Format = Format {
+    name: "cab",
+    extensions: &[".cab"],
+    magic: &[(0, b"MSCF")],
+    content_types: &["application/vnd.ms-cab-compressed"],
+    extract: extract_cab,
+};
+const WIM: Format = Format {
+    name: "wim",
+    extensions: &[".wim"],
+    magic: &[(0, b"MSWIM\0\0\0")],
+    content_types: &["application/x-ms-wim"],
+    // No Rust crate reads WIM images; 7-Zip does.
+    extract: |path, output_dir, options| extract_external(&SEVEN_ZIP, path, output_dir, options),
+    // ↑ A closure that captures nothing can stand in for a plain `fn`.
+};
+const GZ: Format = Format {
+    name: "gz",
+    extensions: &[".gz"],
+    magic: &[(0, b"\x1f\x8b")],
+    content_types: &["application/gzip", "application/x-gzip"],
+    extract: |path, output_dir, options| extract_single_compressed(path, output_dir, "gz", options),
+};
+const BZ2: Format = Format {
+    name: "bz2",
+    extensions: &[".bz2"],
+    magic: &[(0, b"BZh")],
+    content_types: &["application/x-bzip2", "application/x-bzip"],
+    extract: |path, output_dir, options| {
+        extract_single_compressed(path, output_dir, "bz2", options)
+    },
+};
+const XZ: Format = Format {
+    name: "xz",
+    extensions: &[".xz"],
+    magic: &[(0, b"\xfd7zXZ\0")],
+    content_types: &["application/x-xz"],
+    extract: |path, output_dir, options| extract_single_compressed(path, output_dir, "xz", options),
+};
+const ZST: Format = Format {
+    name: "zst",
+    extensions: &[".zst"],
+    magic: &[(0, b"\x28\xb5\x2f\xfd")],
+    content_types: &["application/zstd"],
+    extract: |path, output_dir, options| {
+        extract_single_compressed(path, output_dir, "zst", options)
+    },
+};
+const LZ4: Format = Format {
+    name: "lz4",
+    extensions: &[".lz4"],
+    magic: &[(0, b"\x04\x22\x4d\x18")],
+    content_types: &["application/x-lz4"],
+    extract: |path, output_dir, options| {
+        extract_single_compressed(path, output_dir, "lz4", options)
+    },
+};
+const BR: Format = Format {
+    name: "br",
+    extensions: &[".br"],
+    // Brotli streams have no signature.
+    magic: &[],
+    content_types: &[],
+    extract: |path, output_dir, options| extract_single_compressed(path, output_dir, "br", options),
+};
+#[cfg(feature = "sevenz")]
+const SEVEN_Z: Format = Format {
+    name: "7z",
+    extensions: &[".7z"],
+    magic: &[(0, b"7z\xbc\xaf\x27\x1c")],
+    content_types: &["application/x-7z-compressed"],
+    extract: extract_7z,
+};
+#[cfg(feature = "rar")]
+const RAR: Format = Format {
+    name: "rar",
+    extensions: &[".rar"],
+    magic: &[(0, b"Rar!\x1a\x07")],
+    content_types: &["application/vnd.rar", "application/x-rar-compressed"],
+    extract: extract_rar,
+};
+#[cfg(feature = "deb")]
+const DEB: Format = Format {
+    name: "deb",
+    extensions: &[".deb"],
+    magic: &[(0, b"!<arch>\ndebian-binary")],
+    content_types: &[
+        "application/vnd.debian.binary-package",
+        "application/x-debian-package",
+    ],
+    extract: extract_deb,
+};
+
+/// The formats dex knows. Order doesn't matter for extensions — the longest
+/// match wins, so ".tar.gz" beats ".gz" — but the first signature that
+/// matches wins.
+const BUILTIN: &[&dyn Extractor] = &[
+    &TAR,
+    &TAR_GZ,
+    &TAR_BZ2,
+    &TAR_XZ,
+    &TAR_ZST,
+    &TAR_LZ4,
+    &TAR_BR,
+    &ZIP,
+    &CAB,
+    &WIM,
+    &GZ,
+    &BZ2,
+    &XZ,
+    &ZST,
+    &LZ4,
+    &BR,
+    // `#[cfg]` drops an element when the feature isn't enabled.
+    #[cfg(feature = "sevenz")]
+    &SEVEN_Z,
+    #[cfg(feature = "rar")]
+    &RAR,
+    #[cfg(feature = "deb")]
+    &DEB,
+];
+
+/// What to do when an extracted file would replace one that already exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Conflict {
+    /// Stop with an error.
+    Fail,
+    /// Replace the existing file.
+    #[default]
+    Overwrite,
+    /// Keep the existing file and don't extract that entry.
+    Skip,
+    /// Rename the existing file to `<name>.bak` (or `.bak.1`, `.bak.2`, ...) first.
+    Backup,
+}
+
+/// How to decode zip entry names that aren't marked as UTF-8.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EntryEncoding {
+    /// UTF-8 if the name is valid UTF-8, otherwise CP437 (the zip default).
+    #[default]
+    Auto,
+    /// IBM code page 437, which the zip format specifies for unmarked names.
+    Cp437,
+    /// Any other encoding, e.g. Shift_JIS from a Japanese Windows archiver.
+    Other(&'static encoding_rs::Encoding),
+}
+
+impl std::str::FromStr for EntryEncoding {
+    type Err = String;
+
+    /// Parses an encoding label like "shift_jis", "gbk", or "cp437".
+    fn from_str(label: &str) -> Result<Self, Self::Err> {
+        let lower = label.to_lowercase();
+        if matches!(lower.as_str(), "cp437" | "ibm437" | "437") {
+            return Ok(EntryEncoding::Cp437);
+        }
+        // `for_label` knows the WHATWG names and aliases ("sjis", "euc-kr", ...).
+        encoding_rs::Encoding::for_label(lower.as_bytes())
+            .map(EntryEncoding::Other)
+            .ok_or_else(|| format!("unknown encoding: {label}"))
+    }
+}
+
+/// An entry an extraction is about to write, as shown to
+/// [`ExtractOptions::on_entry`].
+#[derive(Debug)]
+pub struct Entry<'a> {
+    /// The entry's path inside the archive.
+    pub name: &'a Path,
+    /// Its uncompressed size, if the archive records one.
+    pub size: Option<u64>,
+    pub kind: EntryKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    /// Hard links, devices, and anything else a tar can hold.
+    Other,
+}
+
+/// Called with each entry before it's written, so that code embedding dex
+/// can log, filter, or show progress; returning false skips the entry.
+pub type OnEntry = Arc<dyn Fn(&Entry) -> bool + Send + Sync>;
+
+/// Options controlling extraction.
+#[derive(Clone)]
+pub struct ExtractOptions {
+    pub conflict: Conflict,
+    /// Used for zip entry names without the UTF-8 ("language encoding") flag.
+    pub entry_encoding: EntryEncoding,
+    /// Give extracted files the modification times stored in the archive
+    /// (otherwise they're stamped with the time of extraction).
+    pub preserve_mtime: bool,
+    /// Give extracted tar entries the owner and group recorded in the archive,
+    /// like `tar --same-owner`. Only root can do this.
+    pub same_owner: bool,
+    /// Give every extracted entry this user id, like `tar --owner`.
+    pub owner: Option<u32>,
+    /// Give every extracted entry this group id, like `tar --group`.
+    pub group: Option<u32>,
+    /// File name for what a single compressed file ("data.csv.gz") decompresses
+    /// to, instead of its own name without the extension.
+    pub name: Option<String>,
+    /// Name for what a single compressed file decompresses to when that is a
+    /// native executable ("tool_linux_amd64.gz" → "tool"); `.exe` is kept.
+    pub binary_name: Option<String>,
+    /// Sees every entry before it's written, and may skip it.
+    pub on_entry: Option<OnEntry>,
+    /// Threads to write zip entries on;
+    /// 0 for one per CPU.
+    pub threads: usize,
+    /// Give up with [`TimedOut`] once this passes (--extract-timeout). The
+    /// archive is then extracted into a staging directory first, so giving
+    /// up leaves nothing half-written in the output directory.
+    pub deadline: Option<Instant>,
+    /// Give every extracted file and directory the same modification time
+    /// ([`FIXED_MTIME`]) and a mode of 0755 or 0644 once extraction is done
+    /// (--deterministic), so the tree is the same whoever extracts it, and whenever.
+    pub deterministic: bool,
+}
+
+impl ExtractOptions {
+    /// Shows `entry` to the `on_entry` callback; returns whether to write it.
+    fn accept(&self, entry: &Entry) -> bool {
+        self.on_entry
+            .as_ref()
+            .is_none_or(|on_entry| on_entry(entry))
+    }
+
+    /// Fails with [`TimedOut`] once the deadline has passed.
+    fn check_deadline(&self) -> io::Result<()> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => {
+                Err(io::Error::new(io::ErrorKind::TimedOut, TimedOut))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Wraps a reader of archive data so that reading it fails once the
+    /// deadline passes: a single endless entry is cut short too.
+    fn bounded<R: io::Read>(&self, inner: R) -> Bounded<R> {
+        Bounded {
+            inner,
+            deadline: self.deadline,
+        }
+    }
+
+    /// How many threads extraction may use.
+    fn threads(&self) -> usize {
+        match self.threads {
+            0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+            threads => threads,
+        }
+    }
+}
+
+// `#[derive(Default)]` would make `preserve_mtime` false; spell out the defaults.
+impl Default for ExtractOptions {
+    fn default() -> Self {
+        ExtractOptions {
+            conflict: Conflict::default(),
+            entry_encoding: EntryEncoding::default(),
+            preserve_mtime: true,
+            same_owner: false,
+            owner: None,
+            group: None,
+            name: None,
+            binary_name: None,
+            on_entry: None,
+            threads: 0,
+            deadline: None,
+            deterministic: false,
+        }
+    }
+}
+
+/// The modification time --deterministic and --repack give everything:
+/// 1980-01-01 00:00 UTC, the earliest a zip archive can record.
+pub const FIXED_MTIME: u64 = 315_532_800;
+
+/// Extraction ran past its deadline (--extract-timeout).
+#[derive(Debug)]
+pub struct TimedOut;
+
+impl std::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "extraction timed out")
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// Whether an extraction failed by running out of time. The tar reader
+/// rewraps errors with the entry's path, keeping only their kind.
+pub fn is_timeout(e: &(dyn std::error::Error + 'static)) -> bool {
+    e.is::<TimedOut>()
+        || e.downcast_ref::<io::Error>()
+            .is_some_and(|e| e.kind() == io::ErrorKind::TimedOut)
+}
+
+/// A reader that fails once a deadline passes (see `ExtractOptions::bounded`).
+struct Bounded<R> {
+    inner: R,
+    deadline: Option<Instant>,
+}
+
+impl<R: io::Read> io::Read for Bounded<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, TimedOut));
+        }
+        self.inner.read(buf)
+    }
+}
+
+/// Checks if a file is an archive in a recognized format.
 pub fn is_extractable(path: &Path) -> bool {
-    detect_archive_type(path).is_some()
+    find_extractor(path).is_some()
     // ↑ `is_some()` returns true if the Option is Some (not None).
 }
 
-/// Detects the archive type from the file extension.
-/// Returns None if the extension isn't recognized.
-fn detect_archive_type(path: &Path) -> Option<ArchiveType> {
-    // No `pub` here — this is a private function, only visible within this module.
-
-    // Convert the path to a lowercase string for case-insensitive matching.
-    let path_str = path.to_string_lossy().to_lowercase();
-    // ↑ `to_string_lossy()` converts a Path to a string, replacing any invalid
-    //   Unicode characters with a replacement character. Paths on some OSes can
-    //   contain non-UTF-8 bytes, so this is the safe conversion.
-
-    // Check compound extensions first (e.g., .tar.gz before .gz).
-    // Order matters here — ".tar.gz" would also match ".gz" if we checked that first.
-    if path_str.ends_with(".tar.gz") || path_str.ends_with(".tgz") {
-        Some(ArchiveType::TarGz)
-    } else if path_str.ends_with(".tar.bz2") || path_str.ends_with(".tbz2") {
-        Some(ArchiveType::TarBz2)
-    } else if path_str.ends_with(".tar.xz") || path_str.ends_with(".txz") {
-        Some(ArchiveType::TarXz)
-    } else if path_str.ends_with(".tar.zst") || path_str.ends_with(".tzst") {
-        Some(ArchiveType::TarZst)
-    } else if path_str.ends_with(".zip") {
-        Some(ArchiveType::Zip)
-    } else if path_str.ends_with(".gz") {
-        Some(ArchiveType::Gz)
-    } else if path_str.ends_with(".bz2") {
-        Some(ArchiveType::Bz2)
-    } else if path_str.ends_with(".xz") {
-        Some(ArchiveType::Xz)
-    } else if path_str.ends_with(".zst") {
-        Some(ArchiveType::Zst)
-    } else {
-        None
+/// Finds the format of an archive from its file extension or, if the name
+/// has none, from its first bytes. Returns None if nothing recognizes it.
+pub fn find_extractor(path: &Path) -> Option<&'static dyn Extractor> {
+    find_extractor_for(path, None)
+}
+
+/// Like `find_extractor`, for a download the server labeled with
+/// `content_type`. The label is trusted over the file's first bytes, which
+/// can't tell a .tar.gz from a .gz, but not over an archive extension.
+pub fn find_extractor_for(
+    path: &Path,
+    content_type: Option<&str>,
+) -> Option<&'static dyn Extractor> {
+    let formats = || BUILTIN.iter().copied();
+
+    // The longest matching extension wins: "x.tar.gz" also ends with ".gz".
+    let mut best: Option<(usize, &'static dyn Extractor)> = None;
+    for format in formats() {
+        for extension in format.extensions() {
+            if has_extension(path, extension) && best.is_none_or(|(len, _)| extension.len() > len) {
+                best = Some((extension.len(), format));
+            }
+        }
+    }
+    if let Some((_, format)) = best {
+        return Some(format);
+    }
+
+    // "application/gzip; charset=binary" → "application/gzip"
+    let mime = content_type
+        .and_then(|t| t.split(';').next())
+        .map(|t| t.trim().to_lowercase());
+    if let Some(mime) = mime
+        && let Some(format) = formats().find(|f| f.content_types().contains(&mime.as_str()))
+    {
+        return Some(format);
     }
+
+    // Only sniff files with no extension: "app.jar" is a zip, but not one to unpack.
+    if path.extension().is_some() {
+        return None;
+    }
+    let header = read_header(path).ok()?;
+    formats().find(|format| {
+        format
+            .magic()
+            .iter()
+            .any(|(offset, bytes)| header.get(*offset..offset + bytes.len()) == Some(bytes))
+    })
+}
+
+/// Reads the start of a file, enough to check every signature.
+fn read_header(path: &Path) -> io::Result<Vec<u8>> {
+    use io::Read; // for `take` and `read_to_end`
+    let mut header = Vec::with_capacity(512);
+    fs::File::open(path)?.take(512).read_to_end(&mut header)?;
+    Ok(header)
 }
 
 /// Extracts an archive file into the given output directory.
+/// Returns the paths of the files it created (directories aren't listed).
 ///
 /// # Errors
 /// Returns an error if the archive can't be read or extracted.
-pub fn extract_file(path: &Path, output_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
-    // ↑ `Result<(), ...>` — the () is Rust's "unit type" (like void). It means
-    //   "on success, there's no meaningful return value."
-
-    fs::create_dir_all(output_dir)?;
+pub fn extract_file(
+    path: &Path,
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    // ↑ `Vec<PathBuf>` — a growable list of owned paths. Callers use it to find
+    //   nested archives, or to know exactly what an extraction touched.
 
-    // Detect the archive type; if unknown, return an error.
-    let archive_type = detect_archive_type(path).ok_or("Unknown archive format")?;
+    // Find the archive's format; if unknown, return an error.
+    let extractor = find_extractor(path).ok_or("Unknown archive format")?;
     // ↑ `ok_or` converts Option → Result: Some(v) → Ok(v), None → Err("message").
     //   Then `?` propagates the Err if it's None.
 
-    match archive_type {
-        ArchiveType::TarGz => extract_tar_gz(path, output_dir),
-        ArchiveType::TarBz2 => extract_tar_bz2(path, output_dir),
-        ArchiveType::TarXz => extract_tar_xz(path, output_dir),
-        ArchiveType::TarZst => extract_tar_zst(path, output_dir),
-        ArchiveType::Zip => extract_zip(path, output_dir),
-        ArchiveType::Gz => extract_single_compressed(path, output_dir, "gz"),
-        ArchiveType::Bz2 => extract_single_compressed(path, output_dir, "bz2"),
-        ArchiveType::Xz => extract_single_compressed(path, output_dir, "xz"),
-        ArchiveType::Zst => extract_single_compressed(path, output_dir, "zst"),
+    extract_as(extractor, path, output_dir, options)
+}
+
+/// Extracts an archive whose format is already known.
+fn extract_as(
+    extractor: &dyn Extractor,
+    path: &Path,
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> Extracted {
+    fs::create_dir_all(output_dir)?;
+    if options.deadline.is_none() {
+        return extractor.extract(path, output_dir, options);
     }
+    // Entries are shown to `on_entry` as they leave the staging directory.
+    let staged = ExtractOptions {
+        on_entry: None,
+        ..options.clone()
+    };
+    extract_staged(output_dir, options, |staging| {
+        extractor.extract(path, staging, &staged).map(drop)
+    })
 }
 
 // ========================================================================
@@ -94,192 +575,2064 @@ pub fn extract_file(path: &Path, output_dir: &Path) -> Result<(), Box<dyn std::e
 // All follow the same pattern: open file → wrap in decompressor → extract.
 // ========================================================================
 
+/// Extracts an uncompressed .tar archive.
+fn extract_tar(
+    path: &Path,
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let file = fs::File::open(path)?;
+    // No decompressor needed — the tar reader reads the file directly.
+    unpack_tar(file, output_dir, options)
+}
+
 /// Extracts a .tar.gz or .tgz archive.
-fn extract_tar_gz(path: &Path, output_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+fn extract_tar_gz(
+    path: &Path,
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
     let file = fs::File::open(path)?;
     // Wrap the file in a gzip decoder — it transparently decompresses as we read.
     let decoder = flate2::read::GzDecoder::new(file);
-    // Wrap the decompressed stream in a tar archive reader.
-    let mut archive = tar::Archive::new(decoder);
-    // `unpack` extracts all files in the archive into the output directory.
-    archive.unpack(output_dir)?;
-    Ok(())
-    // ↑ `Ok(())` = "success with no return value"
+    // Wrap the decompressed stream in a tar archive reader, then unpack it.
+    unpack_tar(decoder, output_dir, options)
 }
 
 /// Extracts a .tar.bz2 or .tbz2 archive.
-fn extract_tar_bz2(path: &Path, output_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+fn extract_tar_bz2(
+    path: &Path,
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
     let file = fs::File::open(path)?;
     let decoder = bzip2::read::BzDecoder::new(file);
-    let mut archive = tar::Archive::new(decoder);
-    archive.unpack(output_dir)?;
-    Ok(())
+    unpack_tar(decoder, output_dir, options)
 }
 
 /// Extracts a .tar.xz or .txz archive.
-fn extract_tar_xz(path: &Path, output_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+fn extract_tar_xz(
+    path: &Path,
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
     let file = fs::File::open(path)?;
     let decoder = xz2::read::XzDecoder::new(file);
-    let mut archive = tar::Archive::new(decoder);
-    archive.unpack(output_dir)?;
-    Ok(())
+    unpack_tar(decoder, output_dir, options)
 }
 
 /// Extracts a .tar.zst or .tzst archive.
-fn extract_tar_zst(path: &Path, output_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+fn extract_tar_zst(
+    path: &Path,
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
     let file = fs::File::open(path)?;
     // zstd::Decoder wraps a Read and decompresses on the fly.
     let decoder = zstd::Decoder::new(file)?;
-    let mut archive = tar::Archive::new(decoder);
-    archive.unpack(output_dir)?;
-    Ok(())
+    unpack_tar(decoder, output_dir, options)
+}
+
+/// Extracts a .tar.lz4 or .tlz4 archive.
+fn extract_tar_lz4(
+    path: &Path,
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let file = fs::File::open(path)?;
+    // lz4 files use the "frame" format (magic number + blocks), which
+    // FrameDecoder reads; the raw "block" format has no file extension.
+    let decoder = lz4_flex::frame::FrameDecoder::new(file);
+    unpack_tar(decoder, output_dir, options)
 }
 
-/// Extracts a .zip archive.
-fn extract_zip(path: &Path, output_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+/// Extracts a .tar.br archive.
+fn extract_tar_br(
+    path: &Path,
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
     let file = fs::File::open(path)?;
-    let mut archive = zip::ZipArchive::new(file)?;
+    // The second argument is the decompressor's internal buffer size in bytes.
+    let decoder = brotli::Decompressor::new(file, 4096);
+    unpack_tar(decoder, output_dir, options)
+}
+
+/// Unpacks every entry of a tar archive read from `reader` (whatever
+/// decompressor it's wrapped in) into `output_dir`, returning the regular
+/// files it created.
+fn unpack_tar<R: io::Read>(
+    reader: R,
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    // ↑ `<R: io::Read>` is a generic parameter: this works for any reader type,
+    //   so the gzip, bzip2, xz, and zstd variants can all share it.
+    let mut archive = tar::Archive::new(options.bounded(reader));
+    let mut files = Vec::new();
+    // Symlinks that have to be copies: (where, link target).
+    let mut copies = Vec::new();
+    archive.set_preserve_mtime(options.preserve_mtime);
+    archive.set_preserve_ownerships(options.same_owner);
+
+    // Unpacking entry by entry (instead of `archive.unpack`) lets us record
+    // what was created.
+    for entry in archive.entries()? {
+        options.check_deadline()?;
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+
+        // Paths that try to escape are left for `unpack_in` to reject; never
+        // touch (or back up) anything outside `output_dir` on their behalf.
+        let dest = entry_dest(output_dir, &entry_path);
+        let entry_type = entry.header().entry_type();
+        if let Some(dest) = &dest {
+            let kind = if entry_type.is_dir() {
+                EntryKind::Dir
+            } else if entry_type.is_symlink() {
+                EntryKind::Symlink
+            } else if entry_type.is_file() {
+                EntryKind::File
+            } else {
+                EntryKind::Other
+            };
+            let shown = Entry {
+                name: &entry_path,
+                size: entry.header().size().ok(),
+                kind,
+            };
+            if !options.accept(&shown)
+                || (kind != EntryKind::Dir && !resolve_conflict(dest, options.conflict)?)
+            {
+                continue;
+            }
+        }
+
+        // Where symlinks may have to be copies (see `links.rs`), make them
+        // here; a copy waits until the rest of the archive is out, since the
+        // target may come later.
+        if entry_type.is_symlink()
+            && crate::links::may_copy()
+            && let Some(dest) = &dest
+            && let Some(target) = entry.link_name()?
+        {
+            if !make_parent_inside(output_dir, dest)? {
+                continue;
+            }
+            crate::links::remove(dest)?;
+            if crate::links::try_symlink(&target, dest)? {
+                set_owner(dest, options)?;
+            } else {
+                copies.push((dest.clone(), target.into_owned()));
+            }
+            continue;
+        }
+
+        let unpacked = match &dest {
+            // The name had to change to be valid here (only ever on Windows),
+            // so `unpack_in`, which uses the name as-is, can't place it.
+            Some(dest) if *dest != output_dir.join(&entry_path) => {
+                unpack_renamed(&mut entry, output_dir, dest)?
+            }
+            // `unpack_in` refuses entries that would escape `output_dir`
+            // (e.g., "../../etc/passwd") and returns false for them.
+            _ => entry.unpack_in(output_dir)?,
+        };
+        if !unpacked {
+            continue;
+        }
+        let dest = dest.unwrap_or_else(|| output_dir.join(entry_path));
+        set_owner(&dest, options)?;
+        if entry.header().entry_type().is_file() {
+            files.push(dest);
+        }
+    }
+
+    for (dest, target) in copies {
+        let source = dest.parent().unwrap_or(output_dir).join(&target);
+        // Only ever copy the archive's own files, never one it points outside at.
+        let canonical = |path: &Path| fs::canonicalize(path).ok();
+        let inside = canonical(&source)
+            .zip(canonical(output_dir))
+            .is_some_and(|(source, root)| source.starts_with(root));
+        if !inside {
+            eprintln!(
+                "Warning: skipped {} (a link to {}, which isn't in the archive)",
+                dest.display(),
+                target.display()
+            );
+            continue;
+        }
+        crate::links::copy(&source, &dest)?;
+        if source.is_file() {
+            files.push(dest);
+        }
+    }
+
+    Ok(files)
+}
+
+/// Creates the parent directory of `dest`, a path under `output_dir`, and
+/// checks what `unpack_in` would: once symlinks are resolved, the parent
+/// must still be inside `output_dir`.
+fn make_parent_inside(output_dir: &Path, dest: &Path) -> io::Result<bool> {
+    let Some(parent) = dest.parent() else {
+        return Ok(true);
+    };
+    fs::create_dir_all(long_path(parent))?;
+    Ok(fs::canonicalize(parent)?.starts_with(fs::canonicalize(output_dir)?))
+}
+
+/// Unpacks a tar entry to `dest`, a renamed path under `output_dir`, with the
+/// check `unpack_in` would make: once symlinks are resolved, the parent
+/// directory must still be inside `output_dir`.
+fn unpack_renamed<R: io::Read>(
+    entry: &mut tar::Entry<R>,
+    output_dir: &Path,
+    dest: &Path,
+) -> io::Result<bool> {
+    if !make_parent_inside(output_dir, dest)? {
+        return Ok(false);
+    }
+    entry.unpack(long_path(dest))?;
+    Ok(true)
+}
+
+/// Extracts a .zip archive. Entries are gone through in order (checking
+/// names, creating directories, settling conflicts), and then the files are
+/// written by several threads at once.
+fn extract_zip(
+    path: &Path,
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    // Mapped into memory, the archive's directory is read once and shared
+    // by the threads; otherwise each opens the file and reads it again.
+    #[cfg(all(feature = "mmap", unix))]
+    if let Some(mapped) = crate::mmap::map(path) {
+        let archive = zip::ZipArchive::new(io::Cursor::new(&*mapped))?;
+        return unpack_zip(archive.clone(), output_dir, options, || Ok(archive.clone()));
+    }
+    let open = || zip::ZipArchive::new(fs::File::open(path)?);
+    unpack_zip(open()?, output_dir, options, open)
+}
+
+/// Unpacks a zip archive; `open` gives each writing thread a reader of its own.
+fn unpack_zip<R: io::Read + io::Seek>(
+    mut archive: zip::ZipArchive<R>,
+    output_dir: &Path,
+    options: &ExtractOptions,
+    open: impl Fn() -> zip::result::ZipResult<zip::ZipArchive<R>> + Sync,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut files = Vec::new();
+    // Files still to be written: (entry index, where to).
+    let mut pending: Vec<(usize, PathBuf)> = Vec::new();
+    let mut planned = HashSet::new();
 
     // Zip files need to be extracted entry by entry (unlike tar which has `unpack`).
     for i in 0..archive.len() {
-        let mut entry = archive.by_index(i)?;
+        options.check_deadline()?;
+        let entry = archive.by_index(i)?;
+
+        let name = entry_name(&entry, options.entry_encoding);
 
-        // `enclosed_name()` returns None if the entry path tries to escape the
+        // `entry_dest` returns None if the entry path tries to escape the
         // output directory (e.g., "../../../etc/passwd"). This is a security measure
         // against "zip slip" attacks.
-        let Some(entry_path) = entry.enclosed_name() else {
+        let Some(full_path) = entry_dest(output_dir, Path::new(&name)) else {
             // ↑ `let ... else` is Rust's way of doing "if this pattern doesn't match,
             //   execute the else block." The else block must diverge (return, break, continue).
             continue; // Skip malicious entries
         };
 
-        let full_path = output_dir.join(entry_path);
+        let kind = if entry.is_dir() {
+            EntryKind::Dir
+        } else if entry.is_symlink() {
+            EntryKind::Symlink
+        } else {
+            EntryKind::File
+        };
+        let shown = Entry {
+            name: Path::new(&name),
+            size: Some(entry.size()),
+            kind,
+        };
+        if !options.accept(&shown) {
+            continue;
+        }
 
         if entry.is_dir() {
-            fs::create_dir_all(&full_path)?;
+            fs::create_dir_all(long_path(&full_path))?;
+            set_owner(&full_path, options)?;
         } else {
+            // An archive can hold the same path twice; the second entry has
+            // to find the first one written, as it would one at a time.
+            if planned.contains(&full_path) {
+                files.extend(write_zip_files(&open, &pending, options)?);
+                pending.clear();
+                planned.clear();
+            }
+            // Leave existing files alone if the conflict policy says so.
+            if !resolve_conflict(&full_path, options.conflict)? {
+                continue;
+            }
             // Create parent directories if they don't exist.
             if let Some(parent) = full_path.parent() {
                 // ↑ `if let` is like `match` but for a single pattern.
                 //   "If full_path.parent() is Some(parent), do this block."
-                fs::create_dir_all(parent)?;
+                fs::create_dir_all(long_path(parent))?;
             }
-            let mut output_file = fs::File::create(&full_path)?;
-            io::copy(&mut entry, &mut output_file)?;
-            // ↑ `io::copy` streams bytes from a reader to a writer.
-            //   Efficient — doesn't load the whole file into memory.
+            planned.insert(full_path.clone());
+            pending.push((i, full_path));
         }
     }
 
-    Ok(())
+    files.extend(write_zip_files(&open, &pending, options)?);
+    Ok(files)
 }
 
-/// Extracts a single compressed file (not a tar archive).
-/// For example, "data.csv.gz" → decompresses to "data.csv".
-fn extract_single_compressed(
-    path: &Path,
-    output_dir: &Path,
-    format: &str,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Figure out the output filename by stripping the compression extension.
-    // e.g., "data.csv.gz" → "data.csv"
-    let stem = path
-        .file_name() // "data.csv.gz"
-        .and_then(|n| n.to_str()) // Convert OsStr → &str
-        // ↑ `and_then` chains Option operations: if None at any point, stays None.
-        .and_then(|n| n.strip_suffix(&format!(".{format}"))) // Remove ".gz"
-        .unwrap_or("decompressed"); // Fallback name if anything fails
+/// Writes the contents of zip entries to their destinations, whose
+/// directories exist, on up to `options.threads()` threads. A zip reader
+/// decompresses one entry at a time, so each thread has its own.
+fn write_zip_files<R: io::Read + io::Seek>(
+    open: &(impl Fn() -> zip::result::ZipResult<zip::ZipArchive<R>> + Sync),
+    files: &[(usize, PathBuf)],
+    options: &ExtractOptions,
+) -> io::Result<Vec<PathBuf>> {
+    // Starting a thread costs about as much as writing a few small files.
+    const FILES_PER_THREAD: usize = 32;
+    let threads = options
+        .threads()
+        .min(files.len().div_ceil(FILES_PER_THREAD));
+    // The index of the next file to write, shared by the threads.
+    let next = AtomicUsize::new(0);
+    let write = || -> io::Result<()> {
+        let mut archive = open()?;
+        while let Some((index, dest)) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+            let written = (|| {
+                let mut entry = archive.by_index(*index)?;
+                let mut output_file = fs::File::create(long_path(dest))?;
+                io::copy(&mut options.bounded(&mut entry), &mut output_file)?;
+                // ↑ `io::copy` streams bytes from a reader to a writer.
+                //   Efficient — doesn't load the whole file into memory.
+                if options.preserve_mtime
+                    && let Some(mtime) = zip_mtime(&entry)
+                {
+                    output_file.set_modified(mtime)?;
+                }
+                set_owner(dest, options)
+            })();
+            if written.is_err() {
+                // Stop the other threads too.
+                next.store(files.len(), Ordering::Relaxed);
+                return written;
+            }
+        }
+        Ok(())
+    };
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads).map(|_| scope.spawn(write)).collect();
+        workers.into_iter().try_for_each(|worker| {
+            worker
+                .join()
+                .unwrap_or_else(|e| std::panic::resume_unwind(e))
+        })
+    })?;
+    Ok(files.iter().map(|(_, dest)| dest.clone()).collect())
+}
 
-    let output_path = output_dir.join(stem);
-    let file = fs::File::open(path)?;
-    let mut output_file = fs::File::create(&output_path)?;
+/// Decodes a zip entry's name. Names flagged as UTF-8 are taken as-is; the
+/// rest are stored in whatever code page the archiver used, which the zip
+/// format says is CP437 but in practice is often the creator's locale.
+fn entry_name(entry: &zip::read::ZipFile, encoding: EntryEncoding) -> String {
+    let raw = entry.name_raw();
+    // The zip crate decodes flagged names as UTF-8 and unflagged ones as
+    // CP437, so a non-ASCII name that matches its UTF-8 reading was flagged.
+    let flagged = !raw.is_ascii() && std::str::from_utf8(raw) == Ok(entry.name());
+    if flagged || raw.is_ascii() {
+        return entry.name().to_string();
+    }
 
-    // Each format needs its own decompressor. We use `match` to pick the right one,
-    // then `io::copy` streams the decompressed data to the output file.
-    //
-    // The `&mut` in `io::copy(&mut decoder, ...)` is needed because reading
-    // consumes data (modifies the decoder's internal state), so we need a
-    // mutable reference.
-    match format {
-        "gz" => {
-            let mut decoder = flate2::read::GzDecoder::new(file);
-            io::copy(&mut decoder, &mut output_file)?;
+    match encoding {
+        // Many archivers write UTF-8 without setting the flag; CP437 text is
+        // almost never valid UTF-8, so prefer that reading when it fits.
+        EntryEncoding::Auto => match std::str::from_utf8(raw) {
+            Ok(name) => name.to_string(),
+            Err(_) => entry.name().to_string(),
+        },
+        EntryEncoding::Cp437 => entry.name().to_string(),
+        EntryEncoding::Other(encoding) => encoding.decode_without_bom_handling(raw).0.into_owned(),
+    }
+}
+
+/// Returns when a zip entry was last modified. The extended timestamp field
+/// holds a Unix time; without it there's only the DOS date and time, which
+/// have no time zone and are taken as UTC.
+fn zip_mtime(entry: &zip::read::ZipFile) -> Option<std::time::SystemTime> {
+    let unix_secs = entry
+        .extra_data_fields()
+        .find_map(|field| match field {
+            zip::ExtraField::ExtendedTimestamp(ts) => ts.mod_time().map(u64::from),
+            _ => None,
+        })
+        .or_else(|| {
+            let dos = entry.last_modified()?;
+            let days = days_since_epoch(dos.year().into(), dos.month().into(), dos.day().into());
+            let secs = u64::from(dos.hour()) * 3600
+                + u64::from(dos.minute()) * 60
+                + u64::from(dos.second());
+            Some(u64::try_from(days).ok()? * 86400 + secs)
+        })?;
+    Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(unix_secs))
+}
+
+/// Days from 1970-01-01 to the given date (proleptic Gregorian calendar).
+/// See http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_since_epoch(year: i64, month: i64, day: i64) -> i64 {
+    // Count from March so the leap day falls at the end of the year.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The names directly in `dir`, to tell afterwards what an extraction
+/// added. Empty if `dir` doesn't exist yet.
+fn top_level(dir: &Path) -> BTreeSet<std::ffi::OsString> {
+    fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| Some(entry.ok()?.file_name()))
+        .collect()
+}
+
+/// Adds `path` and, if it's a directory, everything below it to `paths`.
+fn walk(path: &Path, paths: &mut BTreeSet<PathBuf>) -> io::Result<()> {
+    if fs::symlink_metadata(path)?.is_dir() {
+        for entry in fs::read_dir(path)? {
+            walk(&entry?.path(), paths)?;
         }
-        "bz2" => {
-            let mut decoder = bzip2::read::BzDecoder::new(file);
-            io::copy(&mut decoder, &mut output_file)?;
+    }
+    paths.insert(path.to_path_buf());
+    Ok(())
+}
+
+/// Normalizes an extracted tree for --deterministic: every file in `files`,
+/// every directory between them and `output_dir`, and everything under the
+/// entries of `output_dir` not in `existed` (so empty directories too) gets
+/// [`FIXED_MTIME`], and a mode of 0755 (directories, and files with any
+/// execute bit) or 0644. Directories come last, since writing into one
+/// changes its time. Symlinks are left as they are; what they point to is
+/// normalized anyway.
+fn normalize(
+    output_dir: &Path,
+    files: &[PathBuf],
+    existed: &BTreeSet<std::ffi::OsString>,
+) -> io::Result<()> {
+    let mtime = std::time::UNIX_EPOCH + std::time::Duration::from_secs(FIXED_MTIME);
+    let mut paths: BTreeSet<PathBuf> = files.iter().cloned().collect();
+    for entry in fs::read_dir(output_dir)? {
+        let entry = entry?;
+        if !existed.contains(&entry.file_name()) {
+            walk(&entry.path(), &mut paths)?;
         }
-        "xz" => {
-            let mut decoder = xz2::read::XzDecoder::new(file);
-            io::copy(&mut decoder, &mut output_file)?;
+    }
+    let mut dirs = BTreeSet::new();
+    for file in &paths {
+        for dir in file.ancestors().skip(1) {
+            if dir == output_dir || !dir.starts_with(output_dir) {
+                break;
+            }
+            dirs.insert(dir.to_path_buf());
         }
-        "zst" => {
-            let mut decoder = zstd::Decoder::new(file)?;
-            io::copy(&mut decoder, &mut output_file)?;
+        let metadata = fs::symlink_metadata(file)?;
+        if metadata.is_dir() {
+            dirs.insert(file.clone());
+        } else if metadata.is_file() {
+            normalize_mode(file, &metadata)?;
+            fs::File::options()
+                .write(true)
+                .open(long_path(file))?
+                .set_modified(mtime)?;
         }
-        // `_` is the wildcard pattern — matches anything not covered above.
-        // `unreachable!()` panics with a message — it should genuinely never happen
-        // because we only call this function with known format strings.
-        _ => unreachable!("Unknown single compression format: {format}"),
     }
-
+    for dir in &dirs {
+        normalize_mode(dir, &fs::metadata(dir)?)?;
+        set_dir_mtime(dir, mtime)?;
+    }
     Ok(())
 }
 
-// ========================================================================
-// Tests module. `#[cfg(test)]` means this code is ONLY compiled when running
-// `cargo test` — it's stripped from release builds entirely.
-// ========================================================================
-#[cfg(test)]
-mod tests {
-    use super::*;
-    // ↑ `super::*` imports everything from the parent module (extract.rs itself).
-    //   This gives tests access to all functions, including private ones.
+#[cfg(unix)]
+fn normalize_mode(path: &Path, metadata: &fs::Metadata) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = match metadata.is_dir() || metadata.permissions().mode() & 0o111 != 0 {
+        true => 0o755,
+        false => 0o644,
+    };
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
 
-    #[test]
-    // ↑ `#[test]` marks this function as a test case. `cargo test` finds and runs all of these.
-    fn test_detect_tar_gz() {
-        assert!(is_extractable(Path::new("file.tar.gz")));
-        assert!(is_extractable(Path::new("file.tgz")));
-        assert!(is_extractable(Path::new("FILE.TAR.GZ"))); // case insensitive
+/// Windows has no mode bits; only a read-only flag, which is cleared.
+#[cfg(not(unix))]
+fn normalize_mode(path: &Path, metadata: &fs::Metadata) -> io::Result<()> {
+    let mut permissions = metadata.permissions();
+    if !permissions.readonly() {
+        return Ok(());
     }
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    fs::set_permissions(path, permissions)
+}
 
-    #[test]
-    fn test_detect_tar_bz2() {
-        assert!(is_extractable(Path::new("file.tar.bz2")));
-        assert!(is_extractable(Path::new("file.tbz2")));
-    }
+#[cfg(unix)]
+fn set_dir_mtime(dir: &Path, mtime: std::time::SystemTime) -> io::Result<()> {
+    fs::File::open(dir)?.set_modified(mtime)
+}
 
-    #[test]
-    fn test_detect_tar_xz() {
-        assert!(is_extractable(Path::new("file.tar.xz")));
-        assert!(is_extractable(Path::new("file.txz")));
+/// Opening a directory to change its times takes flags std doesn't expose
+/// on Windows, so directories keep theirs there.
+#[cfg(not(unix))]
+fn set_dir_mtime(_dir: &Path, _mtime: std::time::SystemTime) -> io::Result<()> {
+    Ok(())
+}
+
+/// Applies `--owner`/`--group` to an extracted file or directory.
+#[cfg(unix)]
+fn set_owner(path: &Path, options: &ExtractOptions) -> io::Result<()> {
+    if options.owner.is_none() && options.group.is_none() {
+        return Ok(());
     }
+    // `lchown` changes a symlink itself rather than what it points to.
+    std::os::unix::fs::lchown(path, options.owner, options.group).map_err(|e| {
+        if e.kind() == io::ErrorKind::PermissionDenied {
+            io::Error::new(
+                e.kind(),
+                format!("changing the owner of {} needs root", path.display()),
+            )
+        } else {
+            e
+        }
+    })
+}
 
-    #[test]
-    fn test_detect_tar_zst() {
-        assert!(is_extractable(Path::new("file.tar.zst")));
-        assert!(is_extractable(Path::new("file.tzst")));
+/// Applies `--owner`/`--group` to an extracted file or directory.
+#[cfg(not(unix))]
+fn set_owner(_path: &Path, options: &ExtractOptions) -> io::Result<()> {
+    if options.owner.is_none() && options.group.is_none() {
+        return Ok(());
     }
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--owner and --group are only supported on Unix",
+    ))
+}
 
-    #[test]
-    fn test_detect_zip() {
-        assert!(is_extractable(Path::new("file.zip")));
-        assert!(is_extractable(Path::new("FILE.ZIP")));
+/// Parses --name: a plain file name, without any directory.
+pub fn parse_file_name(name: &str) -> Result<String, String> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(format!("{name:?} is not a file name"));
     }
+    Ok(name.to_string())
+}
 
-    #[test]
-    fn test_detect_single_compressed() {
-        assert!(is_extractable(Path::new("file.gz")));
-        assert!(is_extractable(Path::new("file.bz2")));
-        assert!(is_extractable(Path::new("file.xz")));
-        assert!(is_extractable(Path::new("file.zst")));
+/// Parses a `--owner` value: a numeric user id, or a name from /etc/passwd.
+pub fn parse_user(spec: &str) -> Result<u32, String> {
+    lookup_id(spec, Path::new("/etc/passwd")).ok_or_else(|| format!("unknown user: {spec}"))
+}
+
+/// Parses a `--group` value: a numeric group id, or a name from /etc/group.
+pub fn parse_group(spec: &str) -> Result<u32, String> {
+    lookup_id(spec, Path::new("/etc/group")).ok_or_else(|| format!("unknown group: {spec}"))
+}
+
+/// Resolves a user or group to its id. Both /etc/passwd and /etc/group
+/// lines start "name:password:id:".
+fn lookup_id(spec: &str, database: &Path) -> Option<u32> {
+    if let Ok(id) = spec.parse() {
+        return Some(id);
     }
+    let contents = fs::read_to_string(database).ok()?;
+    contents.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        if fields.next()? != spec {
+            return None;
+        }
+        fields.nth(1)?.parse().ok()
+    })
+}
 
-    #[test]
+/// Extracts a Microsoft Cabinet (.cab) archive.
+fn extract_cab(
+    path: &Path,
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut cabinet = cab::Cabinet::new(fs::File::open(path)?)?;
+    let mut files = Vec::new();
+
+    // Collect the names first: listing borrows the cabinet, and reading a file
+    // needs to borrow it mutably.
+    let names: Vec<(String, u32)> = cabinet
+        .folder_entries()
+        .flat_map(|folder| folder.file_entries())
+        .map(|file| (file.name().to_string(), file.uncompressed_size()))
+        .collect();
+
+    for (name, size) in names {
+        // Cabinets use Windows separators ("bin\\tool.exe").
+        let entry_path = PathBuf::from(name.replace('\\', "/"));
+        // Like zip's `enclosed_name`: skip entries that would escape `output_dir`.
+        let Some(full_path) = entry_dest(output_dir, &entry_path) else {
+            continue;
+        };
+
+        let shown = Entry {
+            name: &entry_path,
+            size: Some(size.into()),
+            kind: EntryKind::File,
+        };
+        if !options.accept(&shown) || !resolve_conflict(&full_path, options.conflict)? {
+            continue;
+        }
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(long_path(parent))?;
+        }
+        let mut reader = cabinet.read_file(&name)?;
+        let mut output_file = fs::File::create(long_path(&full_path))?;
+        io::copy(&mut reader, &mut output_file)?;
+        files.push(full_path);
+    }
+
+    Ok(files)
+}
+
+/// An external extraction program, for formats no Rust crate handles.
+struct ExternalTool {
+    /// Executable names to look for on PATH, in order of preference.
+    programs: &'static [&'static str],
+    /// Builds the arguments to extract `archive` into `dir`.
+    args: fn(archive: &Path, dir: &Path) -> Vec<std::ffi::OsString>,
+    /// Shown when none of the programs is installed.
+    hint: &'static str,
+}
+
+// 7-Zip ships as "7z" (p7zip, Windows), "7zz" (official Linux/macOS builds), or "7za".
+const SEVEN_ZIP: ExternalTool = ExternalTool {
+    programs: &["7z", "7zz", "7za"],
+    args: |archive, dir| {
+        let mut out = std::ffi::OsString::from("-o");
+        out.push(dir);
+        vec!["x".into(), "-y".into(), out, archive.into()]
+    },
+    hint: "7-Zip (7z)",
+};
+
+const INNOEXTRACT: ExternalTool = ExternalTool {
+    programs: &["innoextract"],
+    args: |archive, dir| vec!["-s".into(), "-d".into(), dir.into(), archive.into()],
+    hint: "innoextract",
+};
+
+/// Finds a program on PATH, trying each name in turn.
+fn find_program(names: &[&str]) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    // `split_paths` knows the platform's PATH separator (":" or ";").
+    std::env::split_paths(&path).find_map(|dir| {
+        names.iter().find_map(|name| {
+            let candidate = dir.join(name);
+            let exe = candidate.with_extension("exe");
+            [candidate, exe].into_iter().find(|p| p.is_file())
+        })
+    })
+}
+
+/// Extracts with an external tool.
+fn extract_external(
+    tool: &ExternalTool,
+    path: &Path,
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let program = find_program(tool.programs)
+        .ok_or_else(|| format!("extracting {} needs {} on PATH", path.display(), tool.hint))?;
+
+    extract_staged(output_dir, options, |staging| {
+        let status = std::process::Command::new(&program)
+            .args((tool.args)(path, staging))
+            .stdout(std::process::Stdio::null())
+            .status()?;
+        if !status.success() {
+            return Err(format!("{} failed ({status})", program.display()).into());
+        }
+        Ok(())
+    })
+}
+
+/// Runs `unpack` on a staging directory, then moves what it wrote into
+/// `output_dir`. For extractors that write files themselves: the results
+/// still go through the same conflict policy (and get listed) like every
+/// built-in format.
+fn extract_staged(
+    output_dir: &Path,
+    options: &ExtractOptions,
+    unpack: impl FnOnce(&Path) -> Result<(), Box<dyn std::error::Error>>,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let staging = output_dir.join(format!(".dex-extract-{}", std::process::id()));
+    fs::create_dir_all(&staging)?;
+
+    let result = unpack(&staging).and_then(|()| adopt_staged(&staging, output_dir, options));
+
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+/// Extracts a 7-Zip (.7z) archive.
+#[cfg(feature = "sevenz")]
+fn extract_7z(
+    path: &Path,
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    // The crate writes the files itself (refusing "../" paths), so stage them.
+    extract_staged(output_dir, options, |staging| {
+        Ok(sevenz_rust2::decompress_file(path, staging)?)
+    })
+}
+
+/// Extracts a RAR (.rar) archive.
+#[cfg(feature = "rar")]
+fn extract_rar(
+    path: &Path,
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    extract_staged(output_dir, options, |staging| {
+        // The archive is read front to back: each header is followed by
+        // either extracting or skipping that entry.
+        let mut archive = unrar::Archive::new(path).open_for_processing()?;
+        while let Some(header) = archive.read_header()? {
+            archive = if header.entry().is_file() {
+                header.extract_with_base(staging)?
+            } else {
+                header.skip()?
+            };
+        }
+        Ok(())
+    })
+}
+
+/// Extracts the files a Debian package (.deb) installs. A .deb is an `ar`
+/// archive whose "data.tar" member (usually compressed) holds them.
+#[cfg(feature = "deb")]
+fn extract_deb(
+    path: &Path,
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut archive = ar::Archive::new(fs::File::open(path)?);
+    while let Some(entry) = archive.next_entry() {
+        let entry = entry?;
+        let name = String::from_utf8_lossy(entry.header().identifier()).into_owned();
+        // "data.tar", "data.tar.xz", "data.tar.zst", ...
+        let Some(compression) = name.strip_prefix("data.tar") else {
+            continue;
+        };
+        let reader: Box<dyn io::Read> = match compression.strip_prefix('.') {
+            Some(format) => decompressor(format, entry)?,
+            None => Box::new(entry),
+        };
+        return unpack_tar(reader, output_dir, options);
+    }
+    Err(format!("{} has no data.tar member", path.display()).into())
+}
+
+/// Moves everything in `staging` into `output_dir`, applying the conflict policy.
+fn adopt_staged(
+    staging: &Path,
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut staged = Vec::new();
+    walk_staged(staging, &mut staged)?;
+
+    let mut files = Vec::new();
+    for source in staged {
+        options.check_deadline()?;
+        let relative = source.strip_prefix(staging)?;
+        let dest = output_dir.join(relative);
+        let metadata = fs::symlink_metadata(&source)?;
+        let kind = match metadata.is_symlink() {
+            true => EntryKind::Symlink,
+            false => EntryKind::File,
+        };
+        let shown = Entry {
+            name: relative,
+            size: Some(metadata.len()).filter(|_| kind == EntryKind::File),
+            kind,
+        };
+        if !options.accept(&shown) || !resolve_conflict(&dest, options.conflict)? {
+            continue;
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(&source, &dest)?;
+        if kind == EntryKind::File {
+            files.push(dest);
+        }
+    }
+    Ok(files)
+}
+
+/// The files and symlinks under `dir`: what a staged extraction produced.
+fn walk_staged(dir: &Path, found: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            walk_staged(&entry.path(), found)?;
+        } else if file_type.is_file() || file_type.is_symlink() {
+            found.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// Kinds of self-extracting Windows installer, told apart by marker strings
+/// their stubs embed.
+const INSTALLER_MARKERS: &[(&[u8], &str)] = &[
+    (b"NullsoftInst", "NSIS"),
+    (b"Inno Setup Setup Data", "Inno Setup"),
+    (b"7z\xbc\xaf\x27\x1c", "7-Zip SFX"),
+];
+
+/// Identifies a self-extracting installer by scanning for its marker.
+fn detect_installer(path: &Path) -> io::Result<Option<&'static str>> {
+    let mut file = fs::File::open(path)?;
+    let longest = INSTALLER_MARKERS
+        .iter()
+        .map(|(m, _)| m.len())
+        .max()
+        .unwrap_or(0);
+
+    // Scan in chunks, carrying the tail of each chunk over so a marker split
+    // across a chunk boundary is still found.
+    let mut window: Vec<u8> = Vec::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let n = io::Read::read(&mut file, &mut buffer)?;
+        if n == 0 {
+            return Ok(None);
+        }
+        window.extend_from_slice(&buffer[..n]);
+        for (marker, kind) in INSTALLER_MARKERS {
+            if window.windows(marker.len()).any(|w| w == *marker) {
+                return Ok(Some(kind));
+            }
+        }
+        let keep = window.len().saturating_sub(longest);
+        window.drain(..keep);
+    }
+}
+
+/// Returns true for files `extract_installer` can be asked to unpack.
+pub fn is_installer(path: &Path) -> bool {
+    has_extension(path, ".exe")
+}
+
+/// Whether `path` ends with `extension` (".tar.gz"), ignoring ASCII case.
+///
+/// The comparison is on the path's raw bytes, so a name that isn't valid
+/// UTF-8 is matched as it is rather than through a lossy conversion.
+pub fn has_extension(path: &Path, extension: &str) -> bool {
+    // ↑ `as_encoded_bytes` gives the OS's bytes for the path (UTF-8 or
+    //   close to it everywhere), which any ASCII suffix can be compared against.
+    let bytes = path.as_os_str().as_encoded_bytes();
+    bytes.len() >= extension.len()
+        && bytes[bytes.len() - extension.len()..].eq_ignore_ascii_case(extension.as_bytes())
+}
+
+/// Pulls the payload out of a self-extracting installer (NSIS, Inno Setup,
+/// or 7-Zip SFX) without running it, using 7-Zip or innoextract.
+pub fn extract_installer(
+    path: &Path,
+    output_dir: &Path,
+    options: &ExtractOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    fs::create_dir_all(output_dir)?;
+    let existed = top_level(output_dir);
+    let files = match detect_installer(path)? {
+        Some("Inno Setup") => extract_external(&INNOEXTRACT, path, output_dir, options)?,
+        Some(_) => extract_external(&SEVEN_ZIP, path, output_dir, options)?,
+        None => {
+            return Err(format!(
+                "{} is not a recognized self-extracting installer (NSIS, Inno Setup, 7-Zip SFX)",
+                path.display()
+            )
+            .into());
+        }
+    };
+    if options.deterministic {
+        normalize(output_dir, &files, &existed)?;
+    }
+    Ok(files)
+}
+
+/// Extracts a single compressed file (not a tar archive).
+/// For example, "data.csv.gz" → decompresses to "data.csv".
+fn extract_single_compressed(
+    path: &Path,
+    output_dir: &Path,
+    format: &str,
+    options: &ExtractOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    // Figure out the output filename by stripping the compression extension.
+    // e.g., "data.csv.gz" → "data.csv". Names are kept as `OsStr`, so one
+    // that isn't valid UTF-8 comes out byte for byte.
+    let stem = path
+        .extension() // "gz"
+        .filter(|ext| ext.eq_ignore_ascii_case(format))
+        // ↑ `filter` turns Some into None when the test fails.
+        .and(path.file_stem()) // "data.csv"
+        .unwrap_or(OsStr::new("decompressed")); // Fallback name if anything fails
+
+    // Some projects publish just a compressed binary ("tool_linux_amd64.gz").
+    // A peek at the first decompressed bytes tells whether this is one.
+    let mut header = Vec::new();
+    io::Read::read_to_end(
+        &mut io::Read::take(decompressor(format, fs::File::open(path)?)?, 4),
+        &mut header,
+    )?;
+    let is_binary = crate::binaries::is_native(&header);
+    let name = match (&options.name, &options.binary_name) {
+        (Some(name), _) => name.into(),
+        // Windows executables keep their ".exe".
+        (None, Some(tool)) if is_binary && has_extension(Path::new(stem), ".exe") => {
+            format!("{tool}.exe").into()
+        }
+        (None, Some(tool)) if is_binary => tool.into(),
+        _ => stem.to_os_string(),
+    };
+
+    let output_path = output_dir.join(portable_name(&name));
+    let shown = Entry {
+        name: Path::new(&name),
+        size: None,
+        kind: EntryKind::File,
+    };
+    if !options.accept(&shown) || !resolve_conflict(&output_path, options.conflict)? {
+        return Ok(Vec::new());
+    }
+    let decoder = decompressor(format, fs::File::open(path)?)?;
+    let mut output_file = fs::File::create(long_path(&output_path))?;
+    // The `&mut` in `io::copy(&mut decoder, ...)` is needed because reading
+    // consumes data (modifies the decoder's internal state), so we need a
+    // mutable reference.
+    io::copy(&mut options.bounded(decoder), &mut output_file)?;
+    if is_binary {
+        crate::install::set_executable(&output_path)?;
+    }
+
+    Ok(vec![output_path])
+}
+
+/// Wraps a reader in the decompressor for `format` ("gz", "xz", ...), which
+/// decompresses as it's read.
+fn decompressor<'a>(
+    format: &str,
+    reader: impl io::Read + 'a,
+) -> io::Result<Box<dyn io::Read + 'a>> {
+    // ↑ `Box<dyn io::Read>` holds any reader type; each arm below returns a
+    //   different one. `'a` says the box lives no longer than `reader` may.
+    Ok(match format {
+        "gz" => Box::new(flate2::read::GzDecoder::new(reader)),
+        "bz2" => Box::new(bzip2::read::BzDecoder::new(reader)),
+        "xz" => Box::new(xz2::read::XzDecoder::new(reader)),
+        "zst" => Box::new(zstd::Decoder::new(reader)?),
+        "lz4" => Box::new(lz4_flex::frame::FrameDecoder::new(reader)),
+        "br" => Box::new(brotli::Decompressor::new(reader, 4096)),
+        // `_` is the wildcard pattern — matches anything not covered above.
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("unsupported compression: {format}"),
+            ));
+        }
+    })
+}
+
+/// Applies the conflict policy to a file about to be written at `path`.
+/// Returns whether to go ahead and write it.
+fn resolve_conflict(path: &Path, conflict: Conflict) -> io::Result<bool> {
+    // `symlink_metadata` doesn't follow symlinks, so a dangling link still
+    // counts as something in the way.
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return Ok(true); // Nothing there yet.
+    };
+    if metadata.is_dir() {
+        // A directory where a file should go can't be resolved by any policy
+        // here; let the extraction itself report it.
+        return Ok(true);
+    }
+
+    match conflict {
+        Conflict::Overwrite => Ok(true),
+        Conflict::Skip => Ok(false),
+        Conflict::Fail => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", path.display()),
+        )),
+        Conflict::Backup => {
+            fs::rename(path, backup_path(path))?;
+            Ok(true)
+        }
+    }
+}
+
+/// Picks an unused backup name: "file.bak", then "file.bak.1", "file.bak.2", ...
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".bak");
+    let first = PathBuf::from(&name);
+    if fs::symlink_metadata(&first).is_err() {
+        return first;
+    }
+    // `(1..)` is an endless range; `find` stops at the first free name.
+    (1..)
+        .map(|n| {
+            let mut numbered = name.clone();
+            numbered.push(format!(".{n}"));
+            PathBuf::from(numbered)
+        })
+        .find(|p| fs::symlink_metadata(p).is_err())
+        .expect("some backup name is free")
+}
+
+/// Maps an archive entry's path to where it lands under `output_dir`, or None
+/// if it would escape (e.g., "../../etc/passwd" or "/etc/passwd") or holds a
+/// NUL byte, which no filesystem accepts.
+fn entry_dest(output_dir: &Path, entry_path: &Path) -> Option<PathBuf> {
+    let mut dest = output_dir.to_path_buf();
+    for component in entry_path.components() {
+        match component {
+            Component::Normal(name) if !name.as_encoded_bytes().contains(&0) => {
+                dest.push(portable_name(name))
+            }
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(dest)
+}
+
+// ========================================================================
+// Windows path rules. Archives built on Unix can hold names Windows
+// refuses ("aux.h", "what?.txt") or paths longer than the 260-character
+// MAX_PATH limit. Elsewhere these helpers leave paths untouched.
+// ========================================================================
+
+/// Makes one path component valid on this platform.
+#[cfg(not(windows))]
+fn portable_name(name: &OsStr) -> Cow<'_, OsStr> {
+    Cow::Borrowed(name)
+}
+
+/// Makes one path component valid on this platform.
+#[cfg(windows)]
+fn portable_name(name: &OsStr) -> Cow<'_, OsStr> {
+    // Names that aren't valid Unicode are left as they are.
+    let Some(text) = name.to_str() else {
+        return Cow::Borrowed(name);
+    };
+    match sanitize_component(text) {
+        Cow::Borrowed(_) => Cow::Borrowed(name),
+        Cow::Owned(clean) => Cow::Owned(clean.into()),
+    }
+}
+
+// Device names Windows reserves in every directory, with or without an
+// extension: "con", "CON.txt", and "nul.tar.gz" all open the device.
+#[cfg(windows)]
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Rewrites a file name Windows can't create:
+///   what?.txt → what_.txt   (characters in <>:"|?* and control characters)
+///   notes.    → notes_      (trailing dots and spaces are silently dropped)
+///   aux.h     → aux_.h      (reserved device names)
+#[cfg(windows)]
+fn sanitize_component(name: &str) -> Cow<'_, str> {
+    let mut clean: String = name
+        .chars()
+        .map(|c| {
+            if c.is_control() || "<>:\"|?*".contains(c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+
+    // Windows would store "notes." as "notes", colliding with a real "notes".
+    let kept = clean.trim_end_matches(['.', ' ']).len();
+    if kept < clean.len() {
+        clean.truncate(kept);
+        clean.push('_');
+    }
+
+    // Only the part before the first dot decides whether a name is reserved.
+    let stem_len = clean.find('.').unwrap_or(clean.len());
+    let stem = clean[..stem_len].trim_end();
+    if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+        clean.insert(stem_len, '_');
+    }
+
+    if clean == name {
+        Cow::Borrowed(name)
+    } else {
+        Cow::Owned(clean)
+    }
+}
+
+/// Returns the form of `path` to hand to the filesystem.
+#[cfg(not(windows))]
+fn long_path(path: &Path) -> Cow<'_, Path> {
+    Cow::Borrowed(path)
+}
+
+/// Returns the form of `path` to hand to the filesystem: paths at or past
+/// MAX_PATH get the `\\?\` prefix (`\\?\UNC\` for network shares), which
+/// lifts the limit. The prefix also turns off Windows' own path cleanup, so
+/// it's only added to an absolute path.
+#[cfg(windows)]
+fn long_path(path: &Path) -> Cow<'_, Path> {
+    const MAX_PATH: usize = 260;
+    if path.as_os_str().len() < MAX_PATH {
+        return Cow::Borrowed(path);
+    }
+    // `absolute` resolves against the current directory and normalizes
+    // separators to backslashes, without touching the filesystem.
+    let Some(absolute) = std::path::absolute(path)
+        .ok()
+        .and_then(|p| p.to_str().map(String::from))
+    else {
+        return Cow::Borrowed(path);
+    };
+    if absolute.starts_with(r"\\?\") {
+        Cow::Owned(absolute.into())
+    } else if let Some(share) = absolute.strip_prefix(r"\\") {
+        Cow::Owned(format!(r"\\?\UNC\{share}").into())
+    } else {
+        Cow::Owned(format!(r"\\?\{absolute}").into())
+    }
+}
+
+/// Extracts an archive of the given format, then any archives found among
+/// its outputs, up to `max_depth` levels of nesting (1 extracts just the
+/// one). Nested archives are extracted next to where they landed and deleted
+/// afterwards unless `keep` is set. Returns the final list of extracted files,
+/// normalized if `options.deterministic` is set.
+pub fn extract_nested(
+    path: &Path,
+    format: &dyn Extractor,
+    output_dir: &Path,
+    max_depth: usize,
+    keep: bool,
+    options: &ExtractOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let existed = top_level(output_dir);
+    let mut files = extract_as(format, path, output_dir, options)?;
+
+    for _ in 1..max_depth {
+        // `partition` splits a list in two by a predicate — here, the files
+        // that are themselves archives vs. everything else.
+        let (nested, mut done): (Vec<PathBuf>, Vec<PathBuf>) =
+            files.into_iter().partition(|f| is_extractable(f));
+        if nested.is_empty() {
+            files = done;
+            break;
+        }
+
+        for archive in nested {
+            let dir = archive.parent().unwrap_or(output_dir);
+            done.extend(extract_file(&archive, dir, options)?);
+            if keep {
+                done.push(archive);
+            } else {
+                fs::remove_file(&archive)?;
+            }
+        }
+        files = done;
+    }
+
+    if options.deterministic {
+        normalize(output_dir, &files, &existed)?;
+    }
+    Ok(files)
+}
+
+// ========================================================================
+// Tests module. `#[cfg(test)]` means this code is ONLY compiled when running
+// `cargo test` — it's stripped from release builds entirely.
+// ========================================================================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    // ↑ `super::*` imports everything from the parent module (extract.rs itself).
+    //   This gives tests access to all functions, including private ones.
+
+    #[test]
+    // ↑ `#[test]` marks this function as a test case. `cargo test` finds and runs all of these.
+    fn test_detect_tar_gz() {
+        assert!(is_extractable(Path::new("file.tar.gz")));
+        assert!(is_extractable(Path::new("file.tgz")));
+        assert!(is_extractable(Path::new("FILE.TAR.GZ"))); // case insensitive
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_names() {
+        use std::os::unix::ffi::OsStrExt;
+        // "caf\xe9" is "café" in Latin-1.
+        let dir = temp_dir("non-utf8");
+        let archive = dir.join(OsStr::from_bytes(b"caf\xe9.txt.GZ"));
+        assert!(is_extractable(&archive));
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        io::Write::write_all(&mut encoder, b"menu").unwrap();
+        fs::write(&archive, encoder.finish().unwrap()).unwrap();
+
+        let files = extract_file(&archive, &dir, &Default::default()).unwrap();
+        assert_eq!(files, [dir.join(OsStr::from_bytes(b"caf\xe9.txt"))]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_detect_tar_bz2() {
+        assert!(is_extractable(Path::new("file.tar.bz2")));
+        assert!(is_extractable(Path::new("file.tbz2")));
+    }
+
+    #[test]
+    fn test_detect_tar_xz() {
+        assert!(is_extractable(Path::new("file.tar.xz")));
+        assert!(is_extractable(Path::new("file.txz")));
+    }
+
+    #[test]
+    fn test_detect_tar_zst() {
+        assert!(is_extractable(Path::new("file.tar.zst")));
+        assert!(is_extractable(Path::new("file.tzst")));
+    }
+
+    #[test]
+    fn test_detect_tar() {
+        assert!(is_extractable(Path::new("file.tar")));
+        assert_eq!(format_of("file.tar"), Some("tar"));
+        assert_eq!(format_of("file.tar.br"), Some("tar.br"));
+    }
+
+    #[test]
+    fn test_detect_tar_lz4() {
+        assert!(is_extractable(Path::new("file.tar.lz4")));
+        assert!(is_extractable(Path::new("file.tlz4")));
+    }
+
+    #[test]
+    fn test_detect_zip() {
+        assert!(is_extractable(Path::new("file.zip")));
+        assert!(is_extractable(Path::new("FILE.ZIP")));
+    }
+
+    #[test]
+    fn test_detect_windows_formats() {
+        assert_eq!(format_of("tools.cab"), Some("cab"));
+        assert_eq!(format_of("install.wim"), Some("wim"));
+        // Installers are only extracted on request.
+        assert!(!is_extractable(Path::new("setup.exe")));
+        assert!(is_installer(Path::new("Setup.EXE")));
+    }
+
+    #[test]
+    fn test_detect_single_compressed() {
+        assert!(is_extractable(Path::new("file.gz")));
+        assert!(is_extractable(Path::new("file.bz2")));
+        assert!(is_extractable(Path::new("file.xz")));
+        assert!(is_extractable(Path::new("file.zst")));
+        assert!(is_extractable(Path::new("file.lz4")));
+        assert!(is_extractable(Path::new("file.br")));
+    }
+
+    #[test]
     fn test_not_extractable() {
         assert!(!is_extractable(Path::new("file.txt")));
         assert!(!is_extractable(Path::new("file.pdf")));
         assert!(!is_extractable(Path::new("file")));
     }
+
+    /// The name of the format detected for a file name.
+    fn format_of(name: &str) -> Option<&'static str> {
+        find_extractor(Path::new(name)).map(|e| e.name())
+    }
+
+    #[test]
+    fn test_detect_by_magic() {
+        let dir = std::env::temp_dir().join(format!("dex-test-magic-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        // A name with no extension is identified by its contents...
+        let download = dir.join("download");
+        fs::write(&download, tar_gz(&[("tool", b"hi")])).unwrap();
+        assert_eq!(format_of(download.to_str().unwrap()), Some("gz"));
+        fs::write(&download, b"#!/bin/sh\necho hi\n").unwrap();
+        assert_eq!(format_of(download.to_str().unwrap()), None);
+
+        // ...but one with an extension isn't: a .jar is a zip, not an archive to unpack.
+        let jar = dir.join("app.jar");
+        fs::write(&jar, b"PK\x03\x04rest").unwrap();
+        assert_eq!(format_of(jar.to_str().unwrap()), None);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_detect_by_content_type() {
+        let dir = std::env::temp_dir().join(format!("dex-test-mime-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let download = dir.join("download");
+        fs::write(&download, tar_gz(&[("tool", b"hi")])).unwrap();
+        let format = |path: &Path, mime| find_extractor_for(path, Some(mime)).map(|e| e.name());
+
+        // The label tells a tarball from a plain .gz, which the bytes can't.
+        assert_eq!(
+            format(&download, "application/x-compressed-tar"),
+            Some("tar.gz")
+        );
+        assert_eq!(
+            format(&download, "Application/GZIP; charset=binary"),
+            Some("gz")
+        );
+        // An unknown label falls back to the bytes.
+        assert_eq!(format(&download, "application/octet-stream"), Some("gz"));
+        // A versioned name has an "extension" (".0") that blocks sniffing, but not the label.
+        assert_eq!(
+            format(Path::new("tool-1.0"), "application/zip"),
+            Some("zip")
+        );
+        // An archive extension wins over the label.
+        assert_eq!(
+            format(Path::new("tool.zip"), "application/x-tar"),
+            Some("zip")
+        );
+
+        let files = extract_nested(
+            &download,
+            &TAR_GZ,
+            &dir.join("out"),
+            1,
+            false,
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(files, [dir.join("out/tool")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Builds a .tar.gz in memory containing the given (path, contents) entries.
+    fn tar_gz(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        for (path, contents) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, *contents).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    /// A fresh, empty directory under the system temp dir.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dex-extract-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_extract_lists_files() {
+        let dir = temp_dir("list");
+        let archive = dir.join("a.tar.gz");
+        fs::write(
+            &archive,
+            tar_gz(&[("pkg/bin/tool", b"x"), ("pkg/README", b"y")]),
+        )
+        .unwrap();
+
+        let out = dir.join("out");
+        let mut files = extract_file(&archive, &out, &ExtractOptions::default()).unwrap();
+        files.sort();
+        assert_eq!(files, [out.join("pkg/README"), out.join("pkg/bin/tool")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_extract_timeout_leaves_nothing() {
+        let dir = temp_dir("timeout");
+        let archive = dir.join("a.tar.gz");
+        fs::write(
+            &archive,
+            tar_gz(&[("pkg/bin/tool", b"x"), ("pkg/README", b"y")]),
+        )
+        .unwrap();
+
+        let out = dir.join("out");
+        let expired = ExtractOptions {
+            deadline: Some(Instant::now()),
+            ..Default::default()
+        };
+        let e = extract_file(&archive, &out, &expired).unwrap_err();
+        assert!(is_timeout(&*e), "{e}");
+        assert_eq!(fs::read_dir(&out).unwrap().count(), 0);
+
+        // With time to spare, the staged files (and links) end up in place.
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(1);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "lib/libx.so.1", &b"x"[..])
+            .unwrap();
+        let mut link = tar::Header::new_gnu();
+        link.set_entry_type(tar::EntryType::Symlink);
+        link.set_size(0);
+        builder
+            .append_link(&mut link, "lib/libx.so", "libx.so.1")
+            .unwrap();
+        fs::write(dir.join("b.tar"), builder.into_inner().unwrap()).unwrap();
+        let generous = ExtractOptions {
+            deadline: Some(Instant::now() + std::time::Duration::from_secs(60)),
+            ..Default::default()
+        };
+        let files = extract_file(&dir.join("b.tar"), &out, &generous).unwrap();
+        assert_eq!(files, [out.join("lib/libx.so.1")]);
+        #[cfg(unix)]
+        assert_eq!(
+            fs::read_link(out.join("lib/libx.so")).unwrap(),
+            Path::new("libx.so.1")
+        );
+        assert!(
+            !out.join(format!(".dex-extract-{}", std::process::id()))
+                .exists()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// A digest of every path under `root` with its mode, modification
+    /// time, and contents.
+    fn tree_digest(root: &Path) -> String {
+        fn visit(dir: &Path, root: &Path, hasher: &mut crate::verify::Hasher) {
+            let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+                .unwrap()
+                .map(|e| e.unwrap().path())
+                .collect();
+            entries.sort();
+            for path in entries {
+                let metadata = fs::metadata(&path).unwrap();
+                let mtime = metadata.modified().unwrap();
+                let secs = mtime
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+                #[cfg(unix)]
+                let mode = std::os::unix::fs::PermissionsExt::mode(&metadata.permissions());
+                #[cfg(not(unix))]
+                let mode = 0;
+                let relative = path.strip_prefix(root).unwrap().to_string_lossy();
+                hasher.update(format!("{relative} {mode:o} {secs}\n").as_bytes());
+                if metadata.is_dir() {
+                    visit(&path, root, hasher);
+                } else {
+                    hasher.update(&fs::read(&path).unwrap());
+                }
+            }
+        }
+        let mut hasher = crate::verify::Hasher::new(crate::verify::Algorithm::Sha256);
+        visit(root, root, &mut hasher);
+        hasher.finalize().hex
+    }
+
+    #[test]
+    fn test_deterministic_tree() {
+        let dir = temp_dir("deterministic");
+        // The same files, archived at different times with different modes.
+        for (name, mtime, exec_mode, doc_mode) in [
+            ("a.tar", 1_600_000_000, 0o700, 0o600),
+            ("b.tar", 1_700_000_000, 0o775, 0o664),
+        ] {
+            let mut builder = tar::Builder::new(Vec::new());
+            // An empty directory: no file returned is under it.
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Directory);
+            header.set_size(0);
+            header.set_mode(exec_mode);
+            header.set_mtime(mtime);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, "pkg/empty/", io::empty())
+                .unwrap();
+            for (path, mode, contents) in [
+                ("pkg/bin/tool", exec_mode, &b"\x7fELF"[..]),
+                ("pkg/README", doc_mode, b"docs"),
+            ] {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(contents.len() as u64);
+                header.set_mode(mode);
+                header.set_mtime(mtime);
+                header.set_cksum();
+                builder.append_data(&mut header, path, contents).unwrap();
+            }
+            fs::write(dir.join(name), builder.into_inner().unwrap()).unwrap();
+        }
+
+        let extract = |name: &str, deterministic: bool| {
+            let out = dir.join(format!("{name}-{deterministic}"));
+            let options = ExtractOptions {
+                deterministic,
+                ..Default::default()
+            };
+            extract_nested(&dir.join(name), &TAR, &out, 1, false, &options).unwrap();
+            tree_digest(&out)
+        };
+        assert_ne!(extract("a.tar", false), extract("b.tar", false));
+        assert_eq!(extract("a.tar", true), extract("b.tar", true));
+
+        let out = dir.join("a.tar-true");
+        for path in ["pkg", "pkg/empty"] {
+            let mtime = fs::metadata(out.join(path)).unwrap().modified().unwrap();
+            assert_eq!(
+                mtime,
+                std::time::UNIX_EPOCH + std::time::Duration::from_secs(FIXED_MTIME)
+            );
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |p: &str| fs::metadata(out.join(p)).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode("pkg/bin/tool"), 0o755);
+            assert_eq!(mode("pkg/README"), 0o644);
+            assert_eq!(mode("pkg/bin"), 0o755);
+            assert_eq!(mode("pkg/empty"), 0o755);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// An uncompressed tar containing "hello.txt" → "hello".
+    fn hello_tar() -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "hello.txt", &b"hello"[..])
+            .unwrap();
+        builder.into_inner().unwrap()
+    }
+
+    #[test]
+    fn test_extract_tar() {
+        let dir = temp_dir("tar");
+        let archive = dir.join("a.tar");
+        fs::write(&archive, hello_tar()).unwrap();
+
+        let out = dir.join("out");
+        extract_file(&archive, &out, &ExtractOptions::default()).unwrap();
+        assert_eq!(fs::read_to_string(out.join("hello.txt")).unwrap(), "hello");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_extract_tar_br_and_br() {
+        let dir = temp_dir("br");
+        let compress = |bytes: &[u8]| {
+            let mut writer = brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22);
+            io::Write::write_all(&mut writer, bytes).unwrap();
+            writer.into_inner()
+        };
+        fs::write(dir.join("a.tar.br"), compress(&hello_tar())).unwrap();
+        fs::write(dir.join("notes.txt.br"), compress(b"notes")).unwrap();
+
+        let out = dir.join("out");
+        extract_file(&dir.join("a.tar.br"), &out, &ExtractOptions::default()).unwrap();
+        assert_eq!(fs::read_to_string(out.join("hello.txt")).unwrap(), "hello");
+        let files =
+            extract_file(&dir.join("notes.txt.br"), &out, &ExtractOptions::default()).unwrap();
+        assert_eq!(files, [out.join("notes.txt")]);
+        assert_eq!(fs::read_to_string(out.join("notes.txt")).unwrap(), "notes");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_extract_tar_lz4() {
+        let dir = temp_dir("lz4");
+        let tar_bytes = hello_tar();
+        let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+        io::Write::write_all(&mut encoder, &tar_bytes).unwrap();
+        let archive = dir.join("a.tar.lz4");
+        fs::write(&archive, encoder.finish().unwrap()).unwrap();
+
+        let out = dir.join("out");
+        extract_file(&archive, &out, &ExtractOptions::default()).unwrap();
+        assert_eq!(fs::read_to_string(out.join("hello.txt")).unwrap(), "hello");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Builds a zip holding one file whose stored name is the raw bytes `name`,
+    /// without the UTF-8 flag — like an archiver that writes its locale's code page.
+    fn zip_with_raw_name(name: &[u8]) -> Vec<u8> {
+        let placeholder = "X".repeat(name.len());
+        let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer
+            .start_file(
+                placeholder.as_str(),
+                zip::write::SimpleFileOptions::default(),
+            )
+            .unwrap();
+        io::Write::write_all(&mut writer, b"contents").unwrap();
+        let mut bytes = writer.finish().unwrap().into_inner();
+
+        // The name appears in the local header and the central directory.
+        let mut i = 0;
+        while let Some(at) = bytes[i..]
+            .windows(name.len())
+            .position(|w| w == placeholder.as_bytes())
+        {
+            bytes[i + at..i + at + name.len()].copy_from_slice(name);
+            i += at + name.len();
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_zip_entry_encoding() {
+        // "日本.txt" in Shift_JIS.
+        let sjis = b"\x93\xfa\x96\x7b.txt";
+        let dir = temp_dir("zip-encoding");
+        let archive = dir.join("a.zip");
+        fs::write(&archive, zip_with_raw_name(sjis)).unwrap();
+
+        let options = ExtractOptions {
+            entry_encoding: "shift_jis".parse().unwrap(),
+            ..Default::default()
+        };
+        let files = extract_file(&archive, &dir.join("sjis"), &options).unwrap();
+        assert_eq!(files, [dir.join("sjis/日本.txt")]);
+
+        // Without an override, unmarked names are read as CP437.
+        let files = extract_file(&archive, &dir.join("auto"), &Default::default()).unwrap();
+        assert_eq!(files, [dir.join("auto/ô·û{.txt")]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_zip_unflagged_utf8_name() {
+        let dir = temp_dir("zip-utf8");
+        let archive = dir.join("a.zip");
+        fs::write(&archive, zip_with_raw_name("café.txt".as_bytes())).unwrap();
+        let files = extract_file(&archive, &dir.join("out"), &Default::default()).unwrap();
+        assert_eq!(files, [dir.join("out/café.txt")]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    // ── Timestamps and ownership ────────────────────────────────────
+
+    fn mtime_secs(path: &Path) -> u64 {
+        let modified = fs::metadata(path).unwrap().modified().unwrap();
+        modified
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[test]
+    fn test_days_since_epoch() {
+        assert_eq!(days_since_epoch(1970, 1, 1), 0);
+        assert_eq!(days_since_epoch(2000, 3, 1), 11_017);
+        assert_eq!(days_since_epoch(2024, 2, 29), 19_782);
+    }
+
+    #[test]
+    fn test_tar_preserve_mtime() {
+        let dir = temp_dir("tar-mtime");
+        let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(4);
+        header.set_mode(0o644);
+        header.set_mtime(1_600_000_000);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "tool", &b"tool"[..])
+            .unwrap();
+        let archive = dir.join("a.tar.gz");
+        fs::write(&archive, builder.into_inner().unwrap().finish().unwrap()).unwrap();
+
+        extract_file(&archive, &dir.join("kept"), &Default::default()).unwrap();
+        assert_eq!(mtime_secs(&dir.join("kept/tool")), 1_600_000_000);
+
+        let options = ExtractOptions {
+            preserve_mtime: false,
+            ..Default::default()
+        };
+        extract_file(&archive, &dir.join("now"), &options).unwrap();
+        assert!(mtime_secs(&dir.join("now/tool")) > 1_600_000_000);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_zip_preserve_mtime() {
+        let dir = temp_dir("zip-mtime");
+        let stamp = zip::DateTime::from_date_and_time(2020, 9, 13, 12, 26, 40).unwrap();
+        let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        let file_options = zip::write::SimpleFileOptions::default().last_modified_time(stamp);
+        writer.start_file("tool", file_options).unwrap();
+        io::Write::write_all(&mut writer, b"tool").unwrap();
+        let archive = dir.join("a.zip");
+        fs::write(&archive, writer.finish().unwrap().into_inner()).unwrap();
+
+        extract_file(&archive, &dir.join("out"), &Default::default()).unwrap();
+        // 2020-09-13 12:26:40 UTC
+        assert_eq!(mtime_secs(&dir.join("out/tool")), 1_600_000_000);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_zip_many_files() {
+        let dir = temp_dir("zip-many");
+        let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        let file_options = zip::write::SimpleFileOptions::default();
+        writer.add_directory("data/", file_options).unwrap();
+        for i in 0..500 {
+            writer
+                .start_file(format!("data/{}/{i}.txt", i % 7), file_options)
+                .unwrap();
+            io::Write::write_all(&mut writer, i.to_string().as_bytes()).unwrap();
+        }
+        let archive = dir.join("a.zip");
+        fs::write(&archive, writer.finish().unwrap().into_inner()).unwrap();
+
+        let out = dir.join("out");
+        let files = extract_file(&archive, &out, &Default::default()).unwrap();
+        // Listed in the archive's order, whichever thread wrote them.
+        let expected: Vec<PathBuf> = (0..500)
+            .map(|i| out.join(format!("data/{}/{i}.txt", i % 7)))
+            .collect();
+        assert_eq!(files, expected);
+        for (i, file) in files.iter().enumerate() {
+            assert_eq!(fs::read_to_string(file).unwrap(), i.to_string());
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_lookup_id() {
+        let dir = temp_dir("lookup-id");
+        let passwd = dir.join("passwd");
+        fs::write(
+            &passwd,
+            "root:x:0:0:root:/root:/bin/sh\nbuild:x:1001:1001::/home/build:/bin/sh\n",
+        )
+        .unwrap();
+        assert_eq!(lookup_id("build", &passwd), Some(1001));
+        assert_eq!(lookup_id("42", &passwd), Some(42));
+        assert_eq!(lookup_id("nobody-here", &passwd), None);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_parse_entry_encoding() {
+        assert_eq!("CP437".parse(), Ok(EntryEncoding::Cp437));
+        assert_eq!(
+            "sjis".parse(),
+            Ok(EntryEncoding::Other(encoding_rs::SHIFT_JIS))
+        );
+        assert!("klingon".parse::<EntryEncoding>().is_err());
+    }
+
+    #[test]
+    fn test_extract_cab() {
+        let dir = temp_dir("cab");
+        let mut builder = cab::CabinetBuilder::new();
+        builder
+            .add_folder(cab::CompressionType::MsZip)
+            .add_file("bin\\tool.exe");
+        let mut writer = builder.build(io::Cursor::new(Vec::new())).unwrap();
+        while let Some(mut file) = writer.next_file().unwrap() {
+            io::Write::write_all(&mut file, b"MZ payload").unwrap();
+        }
+        let archive = dir.join("tools.cab");
+        fs::write(&archive, writer.finish().unwrap().into_inner()).unwrap();
+
+        let out = dir.join("out");
+        let files = extract_file(&archive, &out, &ExtractOptions::default()).unwrap();
+        assert_eq!(files, [out.join("bin/tool.exe")]);
+        assert_eq!(fs::read(out.join("bin/tool.exe")).unwrap(), b"MZ payload");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // ── Windows path rules ──────────────────────────────────────────
+
+    #[cfg(windows)]
+    #[test]
+    fn test_sanitize_component() {
+        assert_eq!(sanitize_component("tool.exe"), "tool.exe");
+        assert_eq!(sanitize_component("what?.txt"), "what_.txt");
+        assert_eq!(sanitize_component("a<b>:c"), "a_b__c");
+        assert_eq!(sanitize_component("notes. "), "notes_");
+        assert_eq!(sanitize_component("aux"), "aux_");
+        assert_eq!(sanitize_component("CON.txt"), "CON_.txt");
+        assert_eq!(sanitize_component("nul.tar.gz"), "nul_.tar.gz");
+        assert_eq!(sanitize_component("com10"), "com10");
+        assert_eq!(sanitize_component("console.log"), "console.log");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_long_path_prefix() {
+        let short = Path::new(r"C:\out\tool.exe");
+        assert_eq!(long_path(short), short);
+
+        let long = PathBuf::from(format!(r"C:\out\{}\tool.exe", "d".repeat(300)));
+        assert!(
+            long_path(&long)
+                .to_str()
+                .unwrap()
+                .starts_with(r"\\?\C:\out\")
+        );
+
+        let unc = PathBuf::from(format!(r"\\server\share\{}", "d".repeat(300)));
+        assert!(
+            long_path(&unc)
+                .to_str()
+                .unwrap()
+                .starts_with(r"\\?\UNC\server\share\")
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_extract_windows_names() {
+        let dir = temp_dir("windows-names");
+        let deep = format!("{}/tool.txt", ["segment"; 40].join("/"));
+        let entries: &[(&str, &[u8])] = &[("src/aux.h", b"aux"), (&deep, b"deep")];
+        let archive = dir.join("a.tar.gz");
+        fs::write(&archive, tar_gz(entries)).unwrap();
+
+        let out = dir.join("out");
+        let files = extract_file(&archive, &out, &ExtractOptions::default()).unwrap();
+        assert_eq!(files[0], out.join("src").join("aux_.h"));
+        assert_eq!(fs::read(long_path(&files[0])).unwrap(), b"aux");
+        assert!(files[1].as_os_str().len() > 260);
+        assert_eq!(fs::read(long_path(&files[1])).unwrap(), b"deep");
+        fs::remove_dir_all(long_path(&dir)).unwrap();
+    }
+
+    #[test]
+    fn test_detect_installer() {
+        let dir = temp_dir("installer");
+        let mut stub = vec![b'M', b'Z'];
+        stub.resize(100_000, 0);
+        // Straddle the 64 KiB chunk boundary.
+        stub.splice(65_530..65_530, b"NullsoftInst".iter().copied());
+        fs::write(dir.join("nsis.exe"), &stub).unwrap();
+        fs::write(dir.join("plain.exe"), b"MZ nothing here").unwrap();
+
+        assert_eq!(
+            detect_installer(&dir.join("nsis.exe")).unwrap(),
+            Some("NSIS")
+        );
+        assert_eq!(detect_installer(&dir.join("plain.exe")).unwrap(), None);
+        assert!(extract_installer(&dir.join("plain.exe"), &dir, &Default::default()).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_extract_nested() {
+        let dir = temp_dir("nested");
+        let inner = tar_gz(&[("tool", b"binary")]);
+        let archive = dir.join("outer.tar.gz");
+        fs::write(&archive, tar_gz(&[("dist/inner.tar.gz", &inner)])).unwrap();
+
+        let out = dir.join("out");
+        let files = extract_nested(&archive, &TAR_GZ, &out, 3, false, &Default::default()).unwrap();
+        assert_eq!(files, [out.join("dist/tool")]);
+        assert!(!out.join("dist/inner.tar.gz").exists());
+
+        // Depth 1 leaves the inner archive alone.
+        let out = dir.join("shallow");
+        let files = extract_nested(&archive, &TAR_GZ, &out, 1, false, &Default::default()).unwrap();
+        assert_eq!(files, [out.join("dist/inner.tar.gz")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // ── Conflict policies ───────────────────────────────────────────
+
+    /// Extracts an archive with "tool" → "new" over an existing "tool" → "old".
+    fn extract_over_existing(
+        name: &str,
+        conflict: Conflict,
+    ) -> (PathBuf, Result<Vec<PathBuf>, String>) {
+        let dir = temp_dir(name);
+        let archive = dir.join("a.tar.gz");
+        fs::write(&archive, tar_gz(&[("tool", b"new")])).unwrap();
+        let out = dir.join("out");
+        fs::create_dir_all(&out).unwrap();
+        fs::write(out.join("tool"), "old").unwrap();
+
+        let result = extract_file(
+            &archive,
+            &out,
+            &ExtractOptions {
+                conflict,
+                ..Default::default()
+            },
+        );
+        (dir, result.map_err(|e| e.to_string()))
+    }
+
+    #[test]
+    fn test_single_compressed_name() {
+        let dir = temp_dir("single-name");
+        let archive = dir.join("data.csv.gz");
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        io::Write::write_all(&mut encoder, b"a,b\n").unwrap();
+        fs::write(&archive, encoder.finish().unwrap()).unwrap();
+        fs::write(dir.join("data.csv"), "old").unwrap();
+
+        let options = ExtractOptions {
+            name: Some("today.csv".into()),
+            conflict: Conflict::Fail,
+            ..Default::default()
+        };
+        let files = extract_file(&archive, &dir, &options).unwrap();
+        assert_eq!(files, [dir.join("today.csv")]);
+        assert_eq!(fs::read_to_string(dir.join("today.csv")).unwrap(), "a,b\n");
+        assert_eq!(fs::read_to_string(dir.join("data.csv")).unwrap(), "old");
+
+        // The output goes through the conflict policy like any archive entry.
+        assert!(extract_file(&archive, &dir, &options).is_err());
+        let options = ExtractOptions {
+            conflict: Conflict::Skip,
+            ..Default::default()
+        };
+        assert!(extract_file(&archive, &dir, &options).unwrap().is_empty());
+        assert_eq!(fs::read_to_string(dir.join("data.csv")).unwrap(), "old");
+
+        assert!(parse_file_name("../etc/passwd").is_err());
+        assert!(parse_file_name("..").is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_single_compressed_binary() {
+        let dir = temp_dir("single-binary");
+        let archive = dir.join("tool_linux_amd64.gz");
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+        io::Write::write_all(&mut encoder, b"\x7fELF\x02\x01\x01\0").unwrap();
+        fs::write(&archive, encoder.finish().unwrap()).unwrap();
+
+        let files = extract_file(&archive, &dir.join("plain"), &Default::default()).unwrap();
+        assert_eq!(files, [dir.join("plain/tool_linux_amd64")]);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&files[0]).unwrap().permissions().mode();
+            assert_ne!(mode & 0o111, 0);
+        }
+
+        let options = ExtractOptions {
+            binary_name: Some("tool".into()),
+            ..Default::default()
+        };
+        let files = extract_file(&archive, &dir.join("named"), &options).unwrap();
+        assert_eq!(files, [dir.join("named/tool")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_on_entry_sees_and_filters() {
+        let dir = temp_dir("on-entry");
+        let archive = dir.join("a.tar.gz");
+        fs::write(&archive, tar_gz(&[("tool", b"bin"), ("README", b"docs!")])).unwrap();
+
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = seen.clone();
+        let options = ExtractOptions {
+            on_entry: Some(Arc::new(move |entry: &Entry| {
+                log.lock()
+                    .unwrap()
+                    .push((entry.name.to_path_buf(), entry.size, entry.kind));
+                entry.name != Path::new("README")
+            })),
+            ..Default::default()
+        };
+        let files = extract_file(&archive, &dir.join("out"), &options).unwrap();
+        assert_eq!(files, [dir.join("out/tool")]);
+        assert!(!dir.join("out/README").exists());
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                (PathBuf::from("tool"), Some(3), EntryKind::File),
+                (PathBuf::from("README"), Some(5), EntryKind::File),
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_conflict_overwrite() {
+        let (dir, result) = extract_over_existing("overwrite", Conflict::Overwrite);
+        assert_eq!(result.unwrap().len(), 1);
+        assert_eq!(fs::read_to_string(dir.join("out/tool")).unwrap(), "new");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_conflict_skip() {
+        let (dir, result) = extract_over_existing("skip", Conflict::Skip);
+        assert!(result.unwrap().is_empty());
+        assert_eq!(fs::read_to_string(dir.join("out/tool")).unwrap(), "old");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_conflict_fail() {
+        let (dir, result) = extract_over_existing("fail", Conflict::Fail);
+        assert!(result.unwrap_err().contains("already exists"));
+        assert_eq!(fs::read_to_string(dir.join("out/tool")).unwrap(), "old");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_conflict_backup() {
+        let (dir, result) = extract_over_existing("backup", Conflict::Backup);
+        assert_eq!(result.unwrap().len(), 1);
+        assert_eq!(fs::read_to_string(dir.join("out/tool")).unwrap(), "new");
+        assert_eq!(fs::read_to_string(dir.join("out/tool.bak")).unwrap(), "old");
+
+        // A second backup doesn't clobber the first.
+        fs::write(dir.join("out/tool"), "newer").unwrap();
+        assert_eq!(
+            backup_path(&dir.join("out/tool")),
+            dir.join("out/tool.bak.1")
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,103 @@
+use std::fs::{File, TryLockError};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+// How long to wait for another dex process, in seconds (--lock-timeout).
+static TIMEOUT_SECS: AtomicU64 = AtomicU64::new(300);
+
+// How often to try again while waiting.
+const POLL: Duration = Duration::from_millis(100);
+
+/// Sets how long `lock` waits for another process before giving up.
+pub fn set_timeout(timeout: Duration) {
+    TIMEOUT_SECS.store(timeout.as_secs(), Ordering::Relaxed);
+}
+
+/// An exclusive advisory lock, released when dropped.
+pub struct Guard {
+    _file: File,
+}
+
+/// Takes the lock guarding `path` — a cache entry, a state file, a tool's
+/// directory in the store — waiting while another dex process holds it.
+///
+/// The lock lives in a `<path>.lock` file beside it, which is left in place:
+/// deleting it would let a third process lock a new file while the second
+/// still waits on the old one. Locks are advisory, so only dex processes
+/// respect them.
+pub fn lock(path: &Path) -> io::Result<Guard> {
+    let timeout = Duration::from_secs(TIMEOUT_SECS.load(Ordering::Relaxed));
+    lock_within(path, timeout)
+}
+
+fn lock_within(path: &Path, timeout: Duration) -> io::Result<Guard> {
+    let lock_path = lock_path(path);
+    if let Some(parent) = lock_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)?;
+
+    let started = Instant::now();
+    let mut told = false;
+    loop {
+        match file.try_lock() {
+            Ok(()) => return Ok(Guard { _file: file }),
+            Err(TryLockError::WouldBlock) => {}
+            Err(TryLockError::Error(e)) => return Err(e),
+        }
+        if started.elapsed() >= timeout {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "gave up after {}s waiting for another dex process to release {}",
+                    timeout.as_secs(),
+                    path.display()
+                ),
+            ));
+        }
+        if !told {
+            eprintln!(
+                "Waiting for another dex process to finish with {}...",
+                path.display()
+            );
+            told = true;
+        }
+        std::thread::sleep(POLL);
+    }
+}
+
+fn lock_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".lock");
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_waits_then_times_out() {
+        let dir = std::env::temp_dir().join(format!("dex-test-filelock-{}", std::process::id()));
+        let path = dir.join("state.json");
+
+        let held = lock_within(&path, Duration::ZERO).unwrap();
+        assert!(dir.join("state.json.lock").exists());
+        // Locks are per open file, so a second handle in this process
+        // contends like another process would.
+        let error = lock_within(&path, Duration::from_millis(200))
+            .err()
+            .expect("the lock is held");
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+
+        drop(held);
+        assert!(lock_within(&path, Duration::ZERO).is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,5 +1,9 @@
-use crate::platform::{self, Asset};
-use reqwest::blocking::Client;
+use crate::apicache::{self, Fetched};
+use crate::attest::Bundle;
+use crate::http::HttpContext;
+use crate::platform::Asset;
+use crate::provider::{Latest, Provider, Release, ResolvedAsset, Selector};
+use reqwest::blocking::RequestBuilder;
 use serde::Deserialize;
 
 // Only the fields we need from the GitHub API response.
@@ -7,12 +11,32 @@ use serde::Deserialize;
 struct GitHubRelease {
     tag_name: String,
     assets: Vec<GitHubAsset>,
+    body: Option<String>,
+    #[serde(default)]
+    draft: bool,
+    #[serde(default)]
+    prerelease: bool,
 }
 
 #[derive(Deserialize)]
 struct GitHubAsset {
     name: String,
     browser_download_url: String,
+    /// "sha256:<hex>", computed by GitHub on upload; missing for old assets.
+    digest: Option<String>,
+    size: Option<u64>,
+    content_type: Option<String>,
+    updated_at: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AttestationList {
+    attestations: Vec<Attestation>,
+}
+
+#[derive(Deserialize)]
+struct Attestation {
+    bundle: Option<Bundle>,
 }
 
 /// Returns true if the URL looks like a GitHub repo or releases page.
@@ -20,6 +44,29 @@ pub fn is_github_release_url(url: &str) -> bool {
     parse_github_url(url).is_some()
 }
 
+/// Expands "owner/repo" or "owner/repo@tag" to the URL of that repository's
+/// latest release, or of the tagged one.
+pub fn shorthand_url(spec: &str) -> Option<String> {
+    let (repo, tag) = match spec.split_once('@') {
+        Some((repo, tag)) => (repo, Some(tag)),
+        None => (spec, None),
+    };
+    let (owner, name) = repo.split_once('/')?;
+    let valid = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+    };
+    if !valid(owner) || !valid(name) || tag == Some("") {
+        return None;
+    }
+    Some(match tag {
+        Some(tag) => format!("https://github.com/{owner}/{name}/releases/tag/{tag}"),
+        None => format!("https://github.com/{owner}/{name}/releases/latest"),
+    })
+}
+
 /// Parses a GitHub URL into (owner, repo, optional tag).
 ///
 /// Supported patterns:
@@ -48,56 +95,340 @@ fn parse_github_url(url: &str) -> Option<(&str, &str, Option<&str>)> {
     }
 }
 
-/// Resolves a GitHub release URL to a direct asset download URL.
-///
-/// Returns `(download_url, asset_filename)` for the best matching asset,
-/// or an error if no suitable asset is found.
-pub fn resolve_asset_url(
-    url: &str,
-    platform: &str,
-    arch: &str,
-) -> Result<(String, String), Box<dyn std::error::Error>> {
-    let (owner, repo, tag) = parse_github_url(url).ok_or("Not a valid GitHub release URL")?;
+/// The GitHub API refused a request because the rate limit is used up.
+#[derive(Debug)]
+pub struct RateLimited;
+
+impl std::fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "GitHub API rate limit exceeded (set GITHUB_TOKEN to raise it)"
+        )
+    }
+}
 
-    let client = Client::builder()
-        .user_agent(format!("dex/{}", env!("CARGO_PKG_VERSION")))
-        .build()?;
+impl std::error::Error for RateLimited {}
 
-    let api_url = match tag {
-        Some(t) => format!("https://api.github.com/repos/{owner}/{repo}/releases/tags/{t}"),
-        None => format!("https://api.github.com/repos/{owner}/{repo}/releases/latest"),
-    };
+/// Checks whether a response is GitHub's rate-limit rejection: a 429, or a 403
+/// with no requests remaining.
+fn is_rate_limited(response: &reqwest::blocking::Response) -> bool {
+    let status = response.status();
+    status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || (status == reqwest::StatusCode::FORBIDDEN
+            && response
+                .headers()
+                .get("x-ratelimit-remaining")
+                .is_some_and(|v| v == "0"))
+}
 
-    let mut request = client.get(&api_url);
+/// Builds a GitHub API GET request, authenticated with GITHUB_TOKEN if set.
+fn api_get(http: &HttpContext, url: &str) -> RequestBuilder {
+    let request = http.get(url);
 
     // Use GITHUB_TOKEN for higher rate limits if available.
-    if let Ok(token) = std::env::var("GITHUB_TOKEN") {
-        request = request.header("Authorization", format!("token {token}"));
+    match std::env::var("GITHUB_TOKEN") {
+        Ok(token) => request.header("Authorization", format!("token {token}")),
+        Err(_) => request,
+    }
+}
+
+/// GitHub releases, listed through the REST API.
+pub struct GitHub;
+
+impl Provider for GitHub {
+    fn name(&self) -> &'static str {
+        "GitHub release"
     }
 
-    let response = request.send()?.error_for_status()?;
-    let release: GitHubRelease = serde_json::from_reader(response)?;
+    fn matches(&self, url: &str) -> bool {
+        is_github_release_url(url)
+    }
 
-    // Convert GitHub assets into provider-agnostic Assets for matching.
+    fn repo_name<'u>(&self, url: &'u str) -> Option<&'u str> {
+        parse_github_url(url).map(|(_, repo, _)| repo)
+    }
+
+    fn fetch_release(
+        &self,
+        http: &HttpContext,
+        url: &str,
+        latest: Latest,
+    ) -> Result<Release, Box<dyn std::error::Error>> {
+        fetch_release(http, url, latest)
+    }
+
+    fn releases(
+        &self,
+        http: &HttpContext,
+        url: &str,
+    ) -> Result<Vec<Release>, Box<dyn std::error::Error>> {
+        list_releases(http, url)
+    }
+
+    /// If the API is rate-limited but the URL names a tag and an asset name
+    /// is given, the direct download URL is used instead. A tag with no
+    /// release has only its source tarball, for `source_fallback`.
+    fn resolve(
+        &self,
+        http: &HttpContext,
+        url: &str,
+        selector: &Selector,
+    ) -> Result<ResolvedAsset, Box<dyn std::error::Error>> {
+        let release = match fetch_release(http, url, selector.latest) {
+            Ok(release) => release,
+            Err(e) if e.is::<RateLimited>() => {
+                let (owner, repo, tag) =
+                    parse_github_url(url).ok_or("Not a valid GitHub release URL")?;
+                let (Some(tag), Some(asset_name)) = (tag, selector.asset_name) else {
+                    return Err(e);
+                };
+                eprintln!("Warning: {e}; downloading {asset_name} from {tag} without the API");
+                offline_release(owner, repo, tag, asset_name)
+            }
+            Err(e) if selector.source_fallback && is_not_found(&*e) => {
+                let (owner, repo, tag) =
+                    parse_github_url(url).ok_or("Not a valid GitHub release URL")?;
+                let Some(tag) = tag else {
+                    return Err(e);
+                };
+                eprintln!("{owner}/{repo} has no release for tag {tag}");
+                Release {
+                    owner: owner.to_string(),
+                    repo: repo.to_string(),
+                    tag: tag.to_string(),
+                    assets: Vec::new(),
+                    notes: None,
+                    source_tarball: Some(source_tarball(owner, repo, tag)),
+                }
+            }
+            Err(e) => return Err(e),
+        };
+
+        selector.select(release)
+    }
+}
+
+/// Fetches the release a GitHub URL points at (the latest one if no tag is given).
+fn fetch_release(
+    http: &HttpContext,
+    url: &str,
+    latest: Latest,
+) -> Result<Release, Box<dyn std::error::Error>> {
+    let (owner, repo, tag) = parse_github_url(url).ok_or("Not a valid GitHub release URL")?;
+
+    let release = match (tag, latest) {
+        (Some(t), _) => api_fetch(
+            http,
+            &format!("https://api.github.com/repos/{owner}/{repo}/releases/tags/{t}"),
+        )?,
+        (None, Latest::Date) => api_fetch(
+            http,
+            &format!("https://api.github.com/repos/{owner}/{repo}/releases/latest"),
+        )?,
+        (None, Latest::Semver) => highest_release(http, owner, repo)?,
+    };
+    Ok(convert(owner, repo, release))
+}
+
+/// The repository's published releases (not drafts or prereleases), newest
+/// first, from the 100 most recent.
+fn list_releases(
+    http: &HttpContext,
+    url: &str,
+) -> Result<Vec<Release>, Box<dyn std::error::Error>> {
+    let (owner, repo, _) = parse_github_url(url).ok_or("Not a valid GitHub release URL")?;
+    let releases: Vec<GitHubRelease> = api_fetch(
+        http,
+        &format!("https://api.github.com/repos/{owner}/{repo}/releases?per_page=100"),
+    )?;
+    Ok(releases
+        .into_iter()
+        .filter(|r| !r.draft && !r.prerelease)
+        .map(|r| convert(owner, repo, r))
+        .collect())
+}
+
+/// Converts a GitHub release into the provider-agnostic form used for matching.
+fn convert(owner: &str, repo: &str, release: GitHubRelease) -> Release {
     let assets: Vec<Asset> = release
         .assets
         .iter()
         .map(|a| Asset {
             name: a.name.clone(),
             url: a.browser_download_url.clone(),
+            digest: a.digest.clone(),
+            size: a.size,
+            content_type: a.content_type.clone(),
+            updated_at: a.updated_at.clone(),
         })
         .collect();
 
-    let asset = platform::select_best_asset(&assets, platform, arch).ok_or_else(|| {
-        format!(
-            "No matching asset for platform={platform}, arch={arch} in release {}",
-            release.tag_name
-        )
-    })?;
+    let source = source_tarball(owner, repo, &release.tag_name);
+    Release {
+        owner: owner.to_string(),
+        repo: repo.to_string(),
+        tag: release.tag_name,
+        assets,
+        notes: release.body.filter(|body| !body.trim().is_empty()),
+        source_tarball: Some(source),
+    }
+}
 
-    eprintln!("Found: {repo} {} → {}", release.tag_name, asset.name);
+/// Whether an API call failed because there's no such thing.
+fn is_not_found(e: &(dyn std::error::Error + 'static)) -> bool {
+    e.downcast_ref::<reqwest::Error>()
+        .and_then(reqwest::Error::status)
+        .is_some_and(|status| status == reqwest::StatusCode::NOT_FOUND)
+}
 
-    Ok((asset.url.clone(), asset.name.clone()))
+/// Fetches and parses a GitHub API response, by way of the API cache.
+fn api_fetch<T: serde::de::DeserializeOwned>(
+    http: &HttpContext,
+    api_url: &str,
+) -> Result<T, Box<dyn std::error::Error>> {
+    let response = match apicache::fetch(http, api_get(http, api_url))? {
+        Fetched::Body(body) => return Ok(serde_json::from_str(&body)?),
+        Fetched::Failed(response) => response,
+    };
+    if is_rate_limited(&response) {
+        return Err(RateLimited.into());
+    }
+    Err(response.error_for_status().err().map_or_else(
+        || "unexpected answer from the GitHub API".into(),
+        Into::into,
+    ))
+}
+
+/// The release with the highest version among a repository's 100 most
+/// recent, leaving out drafts, prereleases, and tags that aren't versions.
+/// GitHub's own "latest" is the most recently published, which for projects
+/// that backport fixes can be a patch to an older line.
+fn highest_release(
+    http: &HttpContext,
+    owner: &str,
+    repo: &str,
+) -> Result<GitHubRelease, Box<dyn std::error::Error>> {
+    let releases: Vec<GitHubRelease> = api_fetch(
+        http,
+        &format!("https://api.github.com/repos/{owner}/{repo}/releases?per_page=100"),
+    )?;
+    releases
+        .into_iter()
+        .filter(|r| !r.draft && !r.prerelease)
+        .filter_map(|r| Some((tag_version(&r.tag_name)?, r)))
+        .max_by(|(a, _), (b, _)| a.cmp(b))
+        .map(|(_, release)| release)
+        .ok_or_else(|| format!("{owner}/{repo} has no release tagged with a version").into())
+}
+
+/// Reads a release tag as a semantic version: "v1.2.3", "ripgrep-14.1.1",
+/// "1.2" (as 1.2.0). Returns None for tags that aren't versions.
+pub fn tag_version(tag: &str) -> Option<semver::Version> {
+    let version = tag.trim_start_matches(|c: char| !c.is_ascii_digit());
+    // Pad "1.2" out to "1.2.0", keeping any "-rc.1" or "+build" after it.
+    let (core, suffix) = version.split_at(version.find(['-', '+']).unwrap_or(version.len()));
+    let padding = ".0".repeat(2usize.saturating_sub(core.matches('.').count()));
+    semver::Version::parse(&format!("{core}{padding}{suffix}")).ok()
+}
+
+/// The releases after `installed` in a newest-first list: those with a
+/// greater version where the tags are versions, otherwise those listed
+/// before it.
+pub fn newer_releases(releases: Vec<Release>, installed: &str) -> Vec<Release> {
+    match tag_version(installed) {
+        Some(current) => releases
+            .into_iter()
+            .filter(|r| tag_version(&r.tag).is_some_and(|v| v > current))
+            .collect(),
+        None => releases
+            .into_iter()
+            .take_while(|r| r.tag != installed)
+            .collect(),
+    }
+}
+
+/// Where GitHub serves one file of a repository as it was at `tag`.
+pub fn raw_file_url(owner: &str, repo: &str, tag: &str, path: &str) -> String {
+    format!("https://raw.githubusercontent.com/{owner}/{repo}/{tag}/{path}")
+}
+
+/// Where GitHub serves the source code at a tag as a tarball.
+fn source_tarball(owner: &str, repo: &str, tag: &str) -> String {
+    format!("https://github.com/{owner}/{repo}/archive/refs/tags/{tag}.tar.gz")
+}
+
+/// Builds the release for a known tag and asset name without calling the API.
+/// GitHub serves release assets from a predictable URL, so this is enough to
+/// download — but there's no asset list, so no checksum or companion files.
+fn offline_release(owner: &str, repo: &str, tag: &str, asset_name: &str) -> Release {
+    Release {
+        owner: owner.to_string(),
+        repo: repo.to_string(),
+        tag: tag.to_string(),
+        assets: vec![Asset {
+            name: asset_name.to_string(),
+            url: format!("https://github.com/{owner}/{repo}/releases/download/{tag}/{asset_name}"),
+            digest: None,
+            size: None,
+            content_type: None,
+            updated_at: None,
+        }],
+        notes: None,
+        source_tarball: Some(source_tarball(owner, repo, tag)),
+    }
+}
+
+/// Fetches the artifact attestations GitHub stores for a file's SHA-256 digest.
+/// Returns an empty list if the repository has none for that digest.
+pub fn fetch_attestations(
+    http: &HttpContext,
+    owner: &str,
+    repo: &str,
+    sha256_hex: &str,
+) -> Result<Vec<Bundle>, Box<dyn std::error::Error>> {
+    let api_url =
+        format!("https://api.github.com/repos/{owner}/{repo}/attestations/sha256:{sha256_hex}");
+
+    let list: AttestationList = match api_fetch(http, &api_url) {
+        Err(e) if is_not_found(e.as_ref()) => return Ok(Vec::new()),
+        found => found?,
+    };
+    Ok(list
+        .attestations
+        .into_iter()
+        .filter_map(|a| a.bundle)
+        .collect())
+}
+
+#[derive(Deserialize)]
+struct RateLimits {
+    resources: RateLimitResources,
+}
+
+#[derive(Deserialize)]
+struct RateLimitResources {
+    core: RateLimit,
+}
+
+/// The REST API's rate limit for whoever is asking.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    pub limit: u64,
+    pub remaining: u64,
+    /// When the limit resets, in seconds since the Unix epoch.
+    pub reset: u64,
+}
+
+/// Asks GitHub how much of the API rate limit is left, for GITHUB_TOKEN's
+/// account if it's set. Asking doesn't count against the limit.
+pub fn rate_limit(http: &HttpContext) -> Result<RateLimit, Box<dyn std::error::Error>> {
+    let response = http.send(api_get(http, "https://api.github.com/rate_limit"))?;
+    if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+        return Err("GitHub rejected GITHUB_TOKEN: it's invalid, expired, or revoked".into());
+    }
+    let limits: RateLimits = serde_json::from_reader(response.error_for_status()?)?;
+    Ok(limits.resources.core)
 }
 
 #[cfg(test)]
@@ -152,6 +483,75 @@ mod tests {
         assert_eq!(result, Some(("owner", "repo", None)));
     }
 
+    #[test]
+    fn test_shorthand_url() {
+        assert_eq!(
+            shorthand_url("BurntSushi/ripgrep").as_deref(),
+            Some("https://github.com/BurntSushi/ripgrep/releases/latest")
+        );
+        assert_eq!(
+            shorthand_url("BurntSushi/ripgrep@14.1.1").as_deref(),
+            Some("https://github.com/BurntSushi/ripgrep/releases/tag/14.1.1")
+        );
+        assert!(shorthand_url("ripgrep").is_none());
+        assert!(shorthand_url("https://example.com/x").is_none());
+        assert!(shorthand_url("owner/repo@").is_none());
+    }
+
+    // ── Latest by version ───────────────────────────────────────────
+
+    #[test]
+    fn test_tag_version() {
+        let version = |tag| tag_version(tag).map(|v| v.to_string());
+        assert_eq!(version("v1.2.3").as_deref(), Some("1.2.3"));
+        assert_eq!(version("ripgrep-14.1.1").as_deref(), Some("14.1.1"));
+        assert_eq!(version("1.2").as_deref(), Some("1.2.0"));
+        assert_eq!(version("v2-rc.1").as_deref(), Some("2.0.0-rc.1"));
+        assert_eq!(version("nightly"), None);
+        assert!(tag_version("v1.10.0") > tag_version("v1.9.7"));
+    }
+
+    #[test]
+    fn test_newer_releases() {
+        let releases = |tags: &[&str]| -> Vec<Release> {
+            tags.iter()
+                .map(|tag| Release {
+                    owner: "o".into(),
+                    repo: "r".into(),
+                    tag: tag.to_string(),
+                    assets: vec![],
+                    notes: None,
+                    source_tarball: None,
+                })
+                .collect()
+        };
+        let tags = |releases: Vec<Release>| releases.into_iter().map(|r| r.tag).collect::<Vec<_>>();
+        let listed = releases(&["v1.10.0", "v1.9.0", "v1.2.0", "v1.1.0"]);
+        assert_eq!(
+            tags(newer_releases(listed, "v1.2.0")),
+            ["v1.10.0", "v1.9.0"]
+        );
+        let listed = releases(&["nightly-3", "nightly-2", "nightly-1"]);
+        assert_eq!(
+            tags(newer_releases(listed, "nightly")),
+            ["nightly-3", "nightly-2", "nightly-1"]
+        );
+        let listed = releases(&["c", "b", "a"]);
+        assert_eq!(tags(newer_releases(listed, "b")), ["c"]);
+    }
+
+    // ── Offline resolution ──────────────────────────────────────────
+
+    #[test]
+    fn test_offline_release_url() {
+        let release = offline_release("BurntSushi", "ripgrep", "14.1.1", "rg.tar.gz");
+        assert_eq!(release.tag, "14.1.1");
+        assert_eq!(
+            release.assets[0].url,
+            "https://github.com/BurntSushi/ripgrep/releases/download/14.1.1/rg.tar.gz"
+        );
+    }
+
     // ── is_github_release_url ───────────────────────────────────────
 
     #[test]
@@ -165,4 +565,26 @@ mod tests {
         ));
         assert!(!is_github_release_url("https://example.com/file.tar.gz"));
     }
+
+    // ── Rate limit ──────────────────────────────────────────────────
+
+    #[test]
+    fn test_parse_rate_limit() {
+        let json = r#"{
+            "resources": {
+                "core": {"limit": 5000, "used": 1, "remaining": 4999, "reset": 1700000000},
+                "search": {"limit": 30, "used": 0, "remaining": 30, "reset": 1700000000}
+            },
+            "rate": {"limit": 5000, "used": 1, "remaining": 4999, "reset": 1700000000}
+        }"#;
+        let limits: RateLimits = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            limits.resources.core,
+            RateLimit {
+                limit: 5000,
+                remaining: 4999,
+                reset: 1700000000,
+            }
+        );
+    }
 }
@@ -0,0 +1,445 @@
+use crate::config::HostConfig;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use reqwest::blocking::{Client, ClientBuilder, RequestBuilder, Response};
+use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderName, HeaderValue};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::sync::{Arc, OnceLock};
+
+/// How many redirects to follow before giving up, unless told otherwise.
+/// The same limit browsers and reqwest use.
+pub const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// Seconds without receiving anything before a transfer counts as stalled,
+/// unless told otherwise.
+pub const DEFAULT_STALL_TIMEOUT: u64 = 30;
+
+// The User-Agent to send instead of "dex/<version>" (--user-agent, or the
+// config's `user_agent`).
+static USER_AGENT: OnceLock<String> = OnceLock::new();
+
+pub fn set_user_agent(user_agent: String) {
+    let _ = USER_AGENT.set(user_agent);
+}
+
+/// The User-Agent header every request carries: "dex/0.1.0" unless set.
+fn user_agent() -> String {
+    USER_AGENT
+        .get()
+        .cloned()
+        .unwrap_or_else(|| format!("dex/{}", env!("CARGO_PKG_VERSION")))
+    // ↑ `env!("CARGO_PKG_VERSION")` is a compile-time macro that reads the version
+    //   from Cargo.toml. The string "dex/0.1.0" is baked into the binary.
+}
+
+/// An HTTP version to insist on, rather than negotiating one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HttpVersion {
+    /// HTTP/1.1 only — for proxies and middleboxes that break HTTP/2.
+    Http1,
+    /// HTTP/2 without falling back.
+    Http2,
+    /// HTTP/3 over QUIC (needs the `http3` cargo feature).
+    Http3,
+}
+
+impl std::str::FromStr for HttpVersion {
+    type Err = String;
+
+    /// Parses "1.1", "2", or "3".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1.1" | "1" => Ok(HttpVersion::Http1),
+            "2" => Ok(HttpVersion::Http2),
+            "3" => Ok(HttpVersion::Http3),
+            _ => Err(format!("unknown HTTP version {s} (expected 1.1, 2, or 3)")),
+        }
+    }
+}
+
+/// Settings shared by every request dex makes.
+pub struct HttpOptions {
+    /// Redirects to follow before failing.
+    pub max_redirects: usize,
+    /// Print each redirect as it's followed.
+    pub verbose: bool,
+    /// Protocol to force; `None` negotiates HTTP/2 or HTTP/1.1 with the server.
+    pub version: Option<HttpVersion>,
+    /// Refuse to send anything (--offline).
+    pub offline: bool,
+    /// Give up on a connection, response, or body read that makes no progress
+    /// for this long; `None` waits forever.
+    pub stall_timeout: Option<std::time::Duration>,
+    /// Per-host settings from the config's `[hosts]` sections.
+    pub hosts: BTreeMap<String, HostConfig>,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        HttpOptions {
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            verbose: false,
+            version: None,
+            offline: false,
+            stall_timeout: Some(std::time::Duration::from_secs(DEFAULT_STALL_TIMEOUT)),
+            hosts: BTreeMap::new(),
+        }
+    }
+}
+
+/// The HTTP client every provider and download goes through.
+///
+/// Building a `Client` sets up a connection pool and TLS configuration, so one
+/// is built per run and shared: several assets from the same host then reuse
+/// one kept-alive connection instead of handshaking for each. Cloning is cheap
+/// (the client is reference-counted inside).
+#[derive(Clone)]
+pub struct HttpContext {
+    client: Client,
+    offline: bool,
+    /// The config's `[hosts]` settings, most specific pattern first.
+    hosts: Arc<[Host]>,
+}
+
+/// A `[hosts]` section, ready to apply to requests.
+struct Host {
+    pattern: String,
+    /// A client of the host's own, when it needs a proxy or CA the rest don't.
+    client: Option<Client>,
+    /// Headers, authentication included, for requests that don't set them.
+    headers: HeaderMap,
+}
+
+impl HttpContext {
+    pub fn new(options: &HttpOptions) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut hosts = options
+            .hosts
+            .iter()
+            .map(|(pattern, config)| {
+                Host::new(pattern, config, options)
+                    .map_err(|e| format!("[hosts.\"{pattern}\"]: {e}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // "git.internal" before "*.internal": the longer pattern is more specific.
+        hosts.sort_by_key(|host| std::cmp::Reverse(host.pattern.len()));
+        Ok(HttpContext {
+            client: client_builder(options)?.build()?,
+            offline: options.offline,
+            hosts: hosts.into(),
+        })
+    }
+
+    pub fn get(&self, url: &str) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    pub fn head(&self, url: &str) -> RequestBuilder {
+        self.client.head(url)
+    }
+
+    pub fn post(&self, url: &str) -> RequestBuilder {
+        self.client.post(url)
+    }
+
+    /// Fetches a small text resource (e.g. a checksum file) into memory.
+    pub fn fetch_text(&self, url: &str) -> Result<String, Box<dyn std::error::Error>> {
+        read_text(self.send(self.get(url))?.error_for_status()?)
+    }
+
+    /// Sends a request, reporting a failed redirect by its cause. Every request
+    /// goes through here, so offline mode fails it before any connection is made,
+    /// and the `[hosts]` settings for its URL are applied.
+    pub fn send(&self, request: RequestBuilder) -> Result<Response, Box<dyn std::error::Error>> {
+        let (client, request) = request.build_split();
+        // ↑ Builds the request without sending it, to look at its URL.
+        if self.offline {
+            let url = request.map_or_else(|_| "this request".to_string(), |r| r.url().to_string());
+            return Err(Offline { url }.into());
+        }
+        let mut request = request?;
+        let client = match self
+            .hosts
+            .iter()
+            .find(|h| host_matches(&h.pattern, request.url()))
+        {
+            Some(host) => {
+                // Headers the caller set win, e.g. a GITHUB_TOKEN provider auth.
+                for (name, value) in &host.headers {
+                    request
+                        .headers_mut()
+                        .entry(name)
+                        .or_insert_with(|| value.clone());
+                }
+                host.client.clone().unwrap_or(client)
+            }
+            None => client,
+        };
+        client.execute(request).map_err(|e| {
+            // reqwest only says "error following redirect"; the loop or limit
+            // message from `redirect_policy` is the error's source.
+            match std::error::Error::source(&e) {
+                Some(cause) if e.is_redirect() => cause.to_string().into(),
+                _ => e.into(),
+            }
+        })
+    }
+}
+
+impl Host {
+    fn new(
+        pattern: &str,
+        config: &HostConfig,
+        options: &HttpOptions,
+    ) -> Result<Host, Box<dyn std::error::Error>> {
+        let client = if config.proxy.is_some() || config.ca_cert.is_some() {
+            let mut builder = client_builder(options)?;
+            if let Some(proxy) = &config.proxy {
+                builder = builder.proxy(
+                    reqwest::Proxy::all(proxy).map_err(|_| format!("invalid proxy URL {proxy}"))?,
+                );
+            }
+            if let Some(path) = &config.ca_cert {
+                let pem = std::fs::read(path).map_err(|e| format!("{}: {e}", path.display()))?;
+                let certs = reqwest::Certificate::from_pem_bundle(&pem)
+                    .ok()
+                    .filter(|certs| !certs.is_empty())
+                    .ok_or_else(|| format!("{}: no PEM certificates", path.display()))?;
+                for cert in certs {
+                    builder = builder.add_root_certificate(cert);
+                }
+            }
+            Some(builder.build()?)
+        } else {
+            None
+        };
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.headers {
+            headers.insert(
+                HeaderName::from_bytes(name.as_bytes())?,
+                HeaderValue::from_str(value)?,
+            );
+        }
+        if let Some(token) = config.token() {
+            let credentials = match &config.username {
+                Some(user) => format!("Basic {}", BASE64.encode(format!("{user}:{token}"))),
+                None => format!("Bearer {token}"),
+            };
+            let mut value = HeaderValue::from_str(&credentials)?;
+            value.set_sensitive(true);
+            // ↑ Keeps the token out of debug output.
+            headers.insert(AUTHORIZATION, value);
+        }
+
+        Ok(Host {
+            pattern: pattern.to_ascii_lowercase(),
+            client,
+            headers,
+        })
+    }
+}
+
+/// Whether a `[hosts]` pattern names the URL's host: exactly, together with
+/// its port ("git.internal:3000"), or as a parent domain ("*.example.com").
+fn host_matches(pattern: &str, url: &reqwest::Url) -> bool {
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    if let Some(domain) = pattern.strip_prefix("*.") {
+        return host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.ends_with('.'));
+    }
+    // rsplit, and only a number counts as a port: IPv6 hosts contain colons.
+    match pattern
+        .rsplit_once(':')
+        .filter(|(_, port)| port.parse::<u16>().is_ok())
+    {
+        Some((name, port)) => {
+            name == host
+                && url
+                    .port_or_known_default()
+                    .map(|p| p.to_string())
+                    .as_deref()
+                    == Some(port)
+        }
+        None => pattern == host,
+    }
+}
+
+/// The client settings shared by every host.
+fn client_builder(options: &HttpOptions) -> Result<ClientBuilder, Box<dyn std::error::Error>> {
+    let builder = Client::builder()
+        .user_agent(user_agent())
+        .redirect(redirect_policy(options.max_redirects, options.verbose))
+        // The blocking client applies this to each wait — connecting, the
+        // response headers, every read of the body — not the whole transfer,
+        // so a slow but steady download never hits it.
+        .timeout(options.stall_timeout);
+    Ok(match options.version {
+        None => builder,
+        Some(HttpVersion::Http1) => builder.http1_only(),
+        Some(HttpVersion::Http2) => builder.http2_prior_knowledge(),
+        #[cfg(feature = "http3")]
+        Some(HttpVersion::Http3) => builder.http3_prior_knowledge(),
+        #[cfg(not(feature = "http3"))]
+        Some(HttpVersion::Http3) => {
+            return Err("this dex was built without HTTP/3 support (the http3 feature)".into());
+        }
+    })
+}
+
+/// A request was about to go out while dex is offline (--offline).
+#[derive(Debug)]
+pub struct Offline {
+    pub url: String,
+}
+
+impl std::fmt::Display for Offline {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} requires network access (--offline)", self.url)
+    }
+}
+
+impl std::error::Error for Offline {}
+
+/// The most `read_text` takes into memory: plenty for sum files, index
+/// pages, and API answers, and a bound on what a broken or hostile server
+/// can make dex hold on to.
+pub const TEXT_LIMIT: u64 = 8 * 1024 * 1024;
+
+/// A response body was longer than dex reads into memory.
+#[derive(Debug)]
+pub struct TooLong {
+    pub url: String,
+    pub limit: u64,
+}
+
+impl std::fmt::Display for TooLong {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let limit = indicatif::HumanBytes(self.limit);
+        write!(f, "{} sent more than {limit}", self.url)
+    }
+}
+
+impl std::error::Error for TooLong {}
+
+/// Reads a text response body of up to `TEXT_LIMIT` bytes.
+pub fn read_text(response: Response) -> Result<String, Box<dyn std::error::Error>> {
+    let url = response.url().to_string();
+    let body = read_limited(response, &url, TEXT_LIMIT)?;
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Reads all of `body`, failing with `TooLong` past `limit` bytes rather
+/// than reading on.
+pub fn read_limited(
+    body: impl Read,
+    url: &str,
+    limit: u64,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut bytes = Vec::new();
+    body.take(limit + 1).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 > limit {
+        return Err(TooLong {
+            url: url.to_string(),
+            limit,
+        }
+        .into());
+    }
+    Ok(bytes)
+}
+
+/// Follows up to `max` redirects, failing with the whole chain on a loop or
+/// when the limit runs out, so a broken mirror shows where it sends you.
+fn redirect_policy(max: usize, verbose: bool) -> reqwest::redirect::Policy {
+    // `move` gives the closure its own copies of `max` and `verbose`: reqwest
+    // keeps the policy around longer than this function's stack frame.
+    reqwest::redirect::Policy::custom(move |attempt| {
+        // `previous()` starts with the original URL, so its length is the
+        // number of redirects followed so far, counting this one.
+        let chain = || {
+            attempt
+                .previous()
+                .iter()
+                .chain([attempt.url()])
+                .map(|u| u.as_str())
+                .collect::<Vec<_>>()
+                .join(" → ")
+        };
+        if attempt.previous().contains(attempt.url()) {
+            let message = format!("redirect loop: {}", chain());
+            return attempt.error(message);
+        }
+        if attempt.previous().len() > max {
+            let message = format!("more than {max} redirects: {}", chain());
+            return attempt.error(message);
+        }
+        if verbose {
+            eprintln!("Redirect ({}): {}", attempt.status(), attempt.url());
+        }
+        attempt.follow()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_limited() {
+        let body = |len| std::io::repeat(b'x').take(len);
+        assert_eq!(read_limited(body(10), "u", 10).unwrap().len(), 10);
+        let error = read_limited(body(11), "https://example.com/sums", 10).unwrap_err();
+        assert!(error.is::<TooLong>());
+        assert_eq!(
+            error.to_string(),
+            "https://example.com/sums sent more than 10 B"
+        );
+        // An endless body stops being read at the limit.
+        assert!(read_limited(std::io::repeat(0), "u", 1024).is_err());
+    }
+
+    #[test]
+    fn test_host_matches() {
+        let url = |s| reqwest::Url::parse(s).unwrap();
+        assert!(host_matches(
+            "git.internal",
+            &url("https://git.internal/api/v1")
+        ));
+        assert!(host_matches(
+            "git.internal",
+            &url("https://GIT.internal:3000/")
+        ));
+        assert!(!host_matches(
+            "git.internal",
+            &url("https://git.internal.evil.com/")
+        ));
+        assert!(host_matches(
+            "git.internal:3000",
+            &url("http://git.internal:3000/")
+        ));
+        assert!(!host_matches(
+            "git.internal:3000",
+            &url("https://git.internal/")
+        ));
+        assert!(host_matches(
+            "git.internal:443",
+            &url("https://git.internal/")
+        ));
+        assert!(host_matches(
+            "*.amazonaws.com",
+            &url("https://b.s3.amazonaws.com/k")
+        ));
+        assert!(!host_matches(
+            "*.amazonaws.com",
+            &url("https://amazonaws.com/")
+        ));
+        assert!(!host_matches(
+            "*.amazonaws.com",
+            &url("https://notamazonaws.com/")
+        ));
+        assert!(host_matches("[::1]:8080", &url("http://[::1]:8080/")));
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// Every message catalog, by language code. English comes first and is the
+/// fallback for anything a translation leaves out.
+const CATALOGS: &[(&str, &str)] = &[
+    ("en", include_str!("../locales/en.ftl")),
+    ("de", include_str!("../locales/de.ftl")),
+];
+
+/// Looks up a message in the user's language and fills in its arguments:
+/// `tr!("not-downloading", name = asset.name)`.
+macro_rules! tr {
+    ($key:literal $(, $arg:ident = $value:expr)* $(,)?) => {
+        $crate::i18n::message(
+            $key,
+            &[$((stringify!($arg), &$value as &dyn std::fmt::Display)),*],
+        )
+    };
+}
+pub(crate) use tr;
+
+/// The messages in use: the user's language over English, key → text.
+fn catalog() -> &'static HashMap<&'static str, &'static str> {
+    static CATALOG: OnceLock<HashMap<&str, &str>> = OnceLock::new();
+    CATALOG.get_or_init(|| {
+        let mut messages = parse(CATALOGS[0].1);
+        let language = requested_language();
+        if let Some((_, text)) = CATALOGS.iter().find(|(code, _)| Some(*code) == language) {
+            messages.extend(parse(text));
+        }
+        messages
+    })
+}
+
+/// The language asked for by the environment: `DEX_LANG`, or the usual
+/// locale variables (`LC_ALL`, `LC_MESSAGES`, `LANG`), in that order.
+fn requested_language() -> Option<&'static str> {
+    ["DEX_LANG", "LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .and_then(|value| language_of(&value))
+}
+
+/// The catalog for a locale name like "de_DE.UTF-8", if there's one.
+fn language_of(locale: &str) -> Option<&'static str> {
+    let language = locale
+        .split(['_', '-', '.', '@'])
+        .next()?
+        .to_ascii_lowercase();
+    CATALOGS
+        .iter()
+        .map(|(code, _)| *code)
+        .find(|code| *code == language)
+}
+
+/// Reads a catalog: `key = text` lines, skipping blanks and `#` comments.
+fn parse(text: &'static str) -> HashMap<&'static str, &'static str> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, message)| (key.trim(), message.trim()))
+        .collect()
+}
+
+/// The message `key` with each `{ $name }` replaced by its argument. An
+/// unknown key is shown as is, so a typo is visible rather than silent.
+pub fn message(key: &str, args: &[(&str, &dyn Display)]) -> String {
+    fill(catalog().get(key).copied().unwrap_or(key), args)
+}
+
+fn fill(template: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let name = rest[start + 1..start + end].trim().trim_start_matches('$');
+        match args.iter().find(|(arg, _)| *arg == name) {
+            Some((_, value)) => out.push_str(&value.to_string()),
+            None => out.push_str(&rest[start..=start + end]),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Whether an answer to a yes/no question means yes, in English or the
+/// user's language.
+pub fn is_yes(answer: &str) -> bool {
+    let answer = answer.trim().to_lowercase();
+    tr!("answer-yes")
+        .split(',')
+        .any(|word| word.trim() == answer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill() {
+        let count = 3;
+        assert_eq!(
+            fill("Install which one? [1-{ $count }]", &[("count", &count)]),
+            "Install which one? [1-3]"
+        );
+        // Unknown arguments stay as they are.
+        assert_eq!(fill("{ $who } {x", &[]), "{ $who } {x");
+    }
+
+    #[test]
+    fn test_language_of() {
+        assert_eq!(language_of("de_DE.UTF-8"), Some("de"));
+        assert_eq!(language_of("en"), Some("en"));
+        assert_eq!(language_of("C"), None);
+    }
+
+    #[test]
+    fn test_translations_match_english() {
+        let english = parse(CATALOGS[0].1);
+        let placeholders = |text: &str| {
+            let mut names: Vec<String> = text
+                .split('{')
+                .skip(1)
+                .filter_map(|part| Some(part.split_once('}')?.0.trim().to_string()))
+                .collect();
+            names.sort();
+            names
+        };
+        for (code, text) in &CATALOGS[1..] {
+            for (key, message) in parse(text) {
+                let original = english
+                    .get(key)
+                    .unwrap_or_else(|| panic!("{code}.ftl has {key}, which en.ftl doesn't"));
+                assert_eq!(
+                    placeholders(message),
+                    placeholders(original),
+                    "{code}.ftl: {key}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::download;
+use crate::http::HttpContext;
+use reqwest::Url;
+
+/// The files a plain HTTP directory index (nginx autoindex, Apache's
+/// mod_autoindex, `python -m http.server`) links to whose names match one of
+/// `patterns`, looking `depth` levels into its subdirectories. Only links
+/// below `url` are followed, so "Parent Directory" and sort links are left
+/// out.
+pub fn list(
+    http: &HttpContext,
+    url: &str,
+    patterns: &[String],
+    depth: usize,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    // Relative links resolve against the directory, so it needs its slash.
+    let base = if url.ends_with('/') {
+        Url::parse(url)?
+    } else {
+        Url::parse(&format!("{url}/"))?
+    };
+    let mut files = Vec::new();
+    let mut pending = vec![(base.clone(), 0)];
+    while let Some((dir, level)) = pending.pop() {
+        let page = http.fetch_text(dir.as_str())?;
+        for href in hrefs(&page) {
+            let Ok(link) = dir.join(&href) else {
+                continue;
+            };
+            if link.query().is_some()
+                || link.origin() != base.origin()
+                || !link.path().starts_with(dir.path())
+                || link.path() == dir.path()
+            {
+                continue;
+            }
+            let link = link.to_string();
+            if link.ends_with('/') {
+                if level < depth {
+                    pending.push((Url::parse(&link)?, level + 1));
+                }
+            } else if patterns
+                .iter()
+                .any(|p| glob_match(p, &download::filename_from_url(&link)))
+                && !files.contains(&link)
+            {
+                files.push(link);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// The targets of the page's `href` attributes, quoted or not, with the
+/// HTML entities index pages use decoded.
+pub fn hrefs(html: &str) -> Vec<String> {
+    let mut links = Vec::new();
+    // Lowercasing ASCII keeps every byte where it was.
+    let lower = html.to_ascii_lowercase();
+    for (at, _) in lower.match_indices("href") {
+        let Some(value) = html[at + "href".len()..].trim_start().strip_prefix('=') else {
+            continue;
+        };
+        let value = value.trim_start();
+        let link = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next(),
+            Some(_) => value.split([' ', '>', '\t', '\n']).next(),
+            None => None,
+        };
+        if let Some(link) = link.filter(|l| !l.is_empty()) {
+            links.push(
+                link.replace("&amp;", "&")
+                    .replace("&#39;", "'")
+                    .replace("&quot;", "\""),
+            );
+        }
+    }
+    links
+}
+
+/// Whether `name` matches a shell-style `pattern`: `*` matches any run of
+/// characters and `?` any one.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) =
+        (pattern.chars().collect(), name.chars().collect());
+    let (mut p, mut n) = (0, 0);
+    // Where the last `*` was, and how much of the name it had taken, to
+    // backtrack to when the rest stops matching.
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.tar.gz", "tool-1.0-linux.tar.gz"));
+        assert!(glob_match("tool-?.?-*", "tool-1.0-linux.tar.gz"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("*.zip", "tool.tar.gz"));
+        assert!(!glob_match("tool", "tool.exe"));
+        assert!(glob_match("*linux*amd64*", "x-linux-y-amd64.zip"));
+    }
+
+    #[test]
+    fn test_hrefs() {
+        let html = r#"<html><body><h1>Index of /releases/</h1>
+            <a href="../">../</a>
+            <a href="?C=M;O=A">Last modified</a>
+            <A HREF='tool-1.0.tar.gz'>tool-1.0.tar.gz</A>
+            <a href=nightly/>nightly/</a>
+            <a class="x" href = "a&amp;b.zip">a&amp;b.zip</a>
+            </body></html>"#;
+        assert_eq!(
+            hrefs(html),
+            ["../", "?C=M;O=A", "tool-1.0.tar.gz", "nightly/", "a&b.zip"]
+        );
+    }
+}
@@ -0,0 +1,379 @@
+use crate::binaries;
+use crate::dirs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Marks a file as executable (chmod +x). Does nothing on Windows, where
+/// executability comes from the file extension.
+pub fn set_executable(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = std::fs::metadata(path)?.permissions();
+        // Grant execute wherever read is granted, like chmod +x under a normal umask.
+        perms.set_mode(perms.mode() | ((perms.mode() & 0o444) >> 2));
+        std::fs::set_permissions(path, perms)?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// The name a downloaded binary is installed under.
+///
+/// Release binaries are usually named after their build
+/// ("tool-v1.2.3-linux-amd64", "Tool-1.0-x86_64.AppImage"), so the project
+/// name is used when known. Windows executables keep their `.exe`.
+pub fn install_name(file_name: &str, project: Option<&str>) -> String {
+    let is_exe = file_name.to_lowercase().ends_with(".exe");
+    let base = match project {
+        Some(project) => project.to_string(),
+        None if is_exe => file_name[..file_name.len() - 4].to_string(),
+        None => file_name.to_string(),
+    };
+    if is_exe { format!("{base}.exe") } else { base }
+}
+
+/// Guesses a tool's name from an archive file name, for when no project name
+/// is known: "ripgrep-14.1.1-x86_64-unknown-linux-musl.tar.gz" → "ripgrep".
+pub fn tool_name(file_name: &str) -> String {
+    let stem = crate::platform::strip_archive_ext(file_name);
+    stem.split(['-', '_'])
+        .next()
+        .filter(|s| !s.is_empty())
+        .unwrap_or(stem)
+        .to_string()
+}
+
+/// Moves a downloaded binary into `bin_dir` as `name`, replacing any existing
+/// file, and makes it executable. Returns the installed path.
+pub fn install_binary(
+    src: &Path,
+    bin_dir: &Path,
+    name: &str,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(bin_dir)?;
+    let dest = bin_dir.join(name);
+
+    // rename() fails across filesystems; fall back to copy + remove.
+    if std::fs::rename(src, &dest).is_err() {
+        std::fs::copy(src, &dest)?;
+        std::fs::remove_file(src)?;
+    }
+
+    set_executable(&dest)?;
+    Ok(dest)
+}
+
+/// Where --shim keeps every installed version: `<data dir>/store/<tool>/<version>`.
+pub fn store_dir() -> Option<PathBuf> {
+    Some(dirs::data_dir()?.join("store"))
+}
+
+/// Writes a shim into `bin_dir` that runs `target` with its arguments: a
+/// shell script named `name`, or `<name>.cmd` on Windows. Returns its path.
+pub fn write_shim(bin_dir: &Path, name: &str, target: &Path) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(bin_dir)?;
+    let target = target.display().to_string();
+    if cfg!(windows) {
+        let stem = name.strip_suffix(".exe").unwrap_or(name);
+        let shim = bin_dir.join(format!("{stem}.cmd"));
+        std::fs::write(&shim, format!("@\"{target}\" %*\r\n"))?;
+        return Ok(shim);
+    }
+
+    let shim = bin_dir.join(name);
+    // Written fresh, never through a binary or link an earlier install left here.
+    let _ = std::fs::remove_file(&shim);
+    let quoted = format!("'{}'", target.replace('\'', "'\\''"));
+    std::fs::write(
+        &shim,
+        format!(
+            "#!/bin/sh\n# Installed by dex; `dex use` picks the version.\nexec {quoted} \"$@\"\n"
+        ),
+    )?;
+    set_executable(&shim)?;
+    Ok(shim)
+}
+
+/// A shell with a completion script in a release archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Man pages and completion scripts found in an extracted tree.
+#[derive(Debug, Default)]
+pub struct Extras {
+    /// Man page path and its section ("1" for rg.1).
+    pub man_pages: Vec<(PathBuf, String)>,
+    pub completions: Vec<(PathBuf, Shell)>,
+}
+
+// Directory names release archives keep completion scripts under.
+const COMPLETION_DIRS: &[&str] = &["completions", "completion", "complete", "autocomplete"];
+
+/// Returns the section of a man page file name: "rg.1" / "rg.1.gz" → "1".
+fn man_section(name: &str) -> Option<String> {
+    let name = name.strip_suffix(".gz").unwrap_or(name);
+    let (stem, section) = name.rsplit_once('.')?;
+    let mut chars = section.chars();
+    let digit = chars.next().filter(|c| ('1'..='9').contains(c))?;
+    // Allow suffixed sections such as "3p" or "1ssl".
+    if stem.is_empty() || !chars.all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some(digit.to_string())
+}
+
+/// Checks that a file looks like roff source, so version-numbered files
+/// ("tool-1.0.1") aren't mistaken for man pages. Gzipped pages are trusted.
+fn looks_like_roff(path: &Path) -> bool {
+    if path.extension().is_some_and(|e| e == "gz") {
+        return true;
+    }
+    let mut first = [0u8; 1];
+    std::fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut first))
+        .is_ok_and(|_| first[0] == b'.' || first[0] == b'\'')
+}
+
+/// Decides which shell a completion script is for, from its name and the
+/// directories it sits in. Returns None for anything that isn't one.
+fn completion_shell(relative: &Path) -> Option<Shell> {
+    let name = relative.file_name()?.to_string_lossy().to_lowercase();
+    let dirs: Vec<String> = relative
+        .parent()
+        .map(|p| {
+            p.components()
+                .map(|c| c.as_os_str().to_string_lossy().to_lowercase())
+                .collect()
+        })
+        .unwrap_or_default();
+    let in_completion_dir = dirs.iter().any(|d| COMPLETION_DIRS.contains(&d.as_str()));
+
+    if name.ends_with(".fish") {
+        return Some(Shell::Fish);
+    }
+    if name.ends_with(".zsh") {
+        return Some(Shell::Zsh);
+    }
+    if name.ends_with(".bash") || name.ends_with(".bash-completion") {
+        return Some(Shell::Bash);
+    }
+    if !in_completion_dir || name.contains('.') {
+        return None;
+    }
+
+    // Extensionless scripts: zsh's convention is "_tool"; otherwise go by the
+    // shell-named directory (completions/bash/tool).
+    match dirs.last().map(String::as_str) {
+        _ if name.starts_with('_') => Some(Shell::Zsh),
+        Some("bash") => Some(Shell::Bash),
+        Some("zsh") => Some(Shell::Zsh),
+        Some("fish") => Some(Shell::Fish),
+        _ => None,
+    }
+}
+
+/// Scans an extracted tree for man pages and shell completion scripts.
+pub fn find_extras(root: &Path) -> std::io::Result<Extras> {
+    let mut files = Vec::new();
+    binaries::walk(root, &mut files)?;
+    files.sort();
+
+    let mut extras = Extras::default();
+    for path in files {
+        let relative = path.strip_prefix(root).unwrap_or(&path);
+        if let Some(shell) = completion_shell(relative) {
+            extras.completions.push((path, shell));
+            continue;
+        }
+        let name = relative
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if let Some(section) = man_section(&name)
+            && looks_like_roff(&path)
+        {
+            extras.man_pages.push((path, section));
+        }
+    }
+    Ok(extras)
+}
+
+/// Where a man page is installed: `~/.local/share/man/man<section>/<name>`.
+pub fn man_page_dest(name: &std::ffi::OsStr, section: &str) -> Option<PathBuf> {
+    Some(
+        dirs::user_data_dir()?
+            .join("man")
+            .join(format!("man{section}"))
+            .join(name),
+    )
+}
+
+/// Where a completion script for `tool` is installed, named the way each
+/// shell's loader expects:
+///   bash  ~/.local/share/bash-completion/completions/<tool>
+///   zsh   ~/.local/share/zsh/site-functions/_<tool>
+///   fish  ~/.config/fish/completions/<tool>.fish
+pub fn completion_dest(shell: Shell, tool: &str) -> Option<PathBuf> {
+    match shell {
+        Shell::Bash => Some(
+            dirs::user_data_dir()?
+                .join("bash-completion")
+                .join("completions")
+                .join(tool),
+        ),
+        Shell::Zsh => Some(
+            dirs::user_data_dir()?
+                .join("zsh")
+                .join("site-functions")
+                .join(format!("_{tool}")),
+        ),
+        Shell::Fish => Some(
+            dirs::user_config_dir()?
+                .join("fish")
+                .join("completions")
+                .join(format!("{tool}.fish")),
+        ),
+    }
+}
+
+/// Copies a man page or completion script to `dest`, creating its directory.
+pub fn install_file(src: &Path, dest: &Path) -> std::io::Result<()> {
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::copy(src, dest)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_install_name_uses_project() {
+        assert_eq!(
+            install_name("tool-v1.2.3-linux-amd64", Some("tool")),
+            "tool"
+        );
+        assert_eq!(
+            install_name("Tool-1.0-x86_64.AppImage", Some("tool")),
+            "tool"
+        );
+        assert_eq!(
+            install_name("tool-windows-amd64.exe", Some("tool")),
+            "tool.exe"
+        );
+    }
+
+    #[test]
+    fn test_install_name_without_project() {
+        assert_eq!(install_name("tool", None), "tool");
+        assert_eq!(install_name("tool.EXE", None), "tool.exe");
+    }
+
+    #[test]
+    fn test_tool_name() {
+        assert_eq!(
+            tool_name("ripgrep-14.1.1-x86_64-unknown-linux-musl.tar.gz"),
+            "ripgrep"
+        );
+        assert_eq!(tool_name("fd_v10_linux.zip"), "fd");
+        assert_eq!(tool_name("tool.tar.gz"), "tool");
+    }
+
+    // ── Man pages and completions ───────────────────────────────────
+
+    #[test]
+    fn test_man_section() {
+        assert_eq!(man_section("rg.1"), Some("1".into()));
+        assert_eq!(man_section("tool.8.gz"), Some("8".into()));
+        assert_eq!(man_section("Tool.3pm"), Some("3".into()));
+        assert_eq!(man_section("tool.md"), None);
+        assert_eq!(man_section("tool-1.0.10"), None);
+        assert_eq!(man_section(".1"), None);
+    }
+
+    #[test]
+    fn test_completion_shell() {
+        let shell = |p: &str| completion_shell(Path::new(p));
+        assert_eq!(shell("rg-14/complete/_rg"), Some(Shell::Zsh));
+        assert_eq!(shell("rg-14/complete/rg.bash"), Some(Shell::Bash));
+        assert_eq!(shell("rg-14/complete/rg.fish"), Some(Shell::Fish));
+        assert_eq!(shell("rg-14/complete/_rg.ps1"), None);
+        assert_eq!(shell("tool/completions/bash/tool"), Some(Shell::Bash));
+        assert_eq!(shell("tool/completions/zsh/tool"), Some(Shell::Zsh));
+        assert_eq!(shell("tool/_private"), None);
+        assert_eq!(shell("tool/bin/tool"), None);
+    }
+
+    #[test]
+    fn test_find_extras() {
+        let root = std::env::temp_dir().join(format!("dex-extras-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for (path, contents) in [
+            ("rg/doc/rg.1", ".TH RG 1\n"),
+            ("rg/doc/CHANGELOG.1", "not roff"),
+            ("rg/complete/_rg", "#compdef rg"),
+            ("rg/complete/rg.fish", "complete -c rg"),
+            ("rg/rg", "binary"),
+        ] {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+
+        let extras = find_extras(&root).unwrap();
+        assert_eq!(extras.man_pages.len(), 1);
+        assert!(extras.man_pages[0].0.ends_with("rg.1"));
+        assert_eq!(extras.man_pages[0].1, "1");
+        let shells: Vec<Shell> = extras.completions.iter().map(|c| c.1).collect();
+        assert_eq!(shells, [Shell::Zsh, Shell::Fish]);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_install_binary_sets_executable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("dex-install-test-{}", std::process::id()));
+        let src = dir.join("tool-linux-amd64");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&src, b"#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&src, std::fs::Permissions::from_mode(0o644)).unwrap();
+
+        let dest = install_binary(&src, &dir.join("bin"), "tool").unwrap();
+        assert_eq!(dest, dir.join("bin").join("tool"));
+        assert!(!src.exists());
+        let mode = std::fs::metadata(&dest).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_shim() {
+        let dir = std::env::temp_dir().join(format!("dex-shim-test-{}", std::process::id()));
+        let target = dir.join("store").join("tool").join("current").join("tool");
+        std::fs::create_dir_all(target.parent().unwrap()).unwrap();
+        std::fs::write(&target, "#!/bin/sh\necho \"version 2: $1\"\n").unwrap();
+        set_executable(&target).unwrap();
+
+        let shim = write_shim(&dir.join("bin"), "tool", &target).unwrap();
+        let output = std::process::Command::new(&shim)
+            .arg("hi")
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "version 2: hi\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
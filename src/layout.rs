@@ -0,0 +1,416 @@
+use std::path::{Path, PathBuf};
+
+// Name of the symlink pointing at the active version, and of the file
+// recording the order versions were activated in.
+const CURRENT: &str = "current";
+const HISTORY: &str = ".dex-history";
+
+/// Guesses a version from a download's file name, for when no release tag is
+/// known: "tool-1.4.2-linux-amd64.tar.gz" → "1.4.2", "tool_v2.0_linux.zip" → "v2.0".
+pub fn guess_version(file_name: &str) -> Option<String> {
+    let stem = crate::platform::strip_archive_ext(file_name);
+    stem.split(['-', '_'])
+        .find(|segment| {
+            let digits = segment.strip_prefix('v').unwrap_or(segment);
+            digits.starts_with(|c: char| c.is_ascii_digit())
+                && digits.contains('.')
+                && digits.chars().all(|c| c.is_ascii_digit() || c == '.')
+        })
+        .map(str::to_string)
+}
+
+/// Guesses the version of a file in a repository's folders from its path:
+/// the folder's name if that's a version ("tool/1.4.2/tool-linux.tar.gz" →
+/// "1.4.2"), otherwise the version in the file's name.
+pub fn guess_path_version(path: &str) -> Option<String> {
+    let (folder, name) = path.rsplit_once('/').unwrap_or(("", path));
+    let folder = folder.rsplit('/').next().unwrap_or_default();
+    if crate::github::tag_version(folder).is_some() {
+        return Some(folder.to_string());
+    }
+    guess_version(name)
+}
+
+/// The directory a version is extracted into: `<root>/<tool>/<version>`.
+pub fn version_dir(root: &Path, tool: &str, version: &str) -> PathBuf {
+    root.join(tool).join(version)
+}
+
+// Variables a --filename-template can use.
+const TEMPLATE_VARS: &[&str] = &["name", "stem", "ext", "repo", "tag", "platform", "arch"];
+
+// Variables a URL template can use.
+const URL_VARS: &[&str] = &["version", "platform", "arch"];
+
+/// What a --filename-template's release variables stand for. {name}, {stem},
+/// and {ext} come from the file name itself.
+#[derive(Default)]
+pub struct NameVars<'a> {
+    pub repo: Option<&'a str>,
+    pub tag: Option<&'a str>,
+    pub platform: Option<&'a str>,
+    pub arch: Option<&'a str>,
+}
+
+/// Checks a --filename-template for unknown variables and stray braces.
+pub fn parse_template(template: &str) -> Result<String, String> {
+    expand(template, TEMPLATE_VARS, "--filename-template", |_| {
+        Ok(String::new())
+    })?;
+    Ok(template.to_string())
+}
+
+/// Fills in a --filename-template for a download named `file_name`, e.g.
+/// "{repo}-{tag}-{platform}-{arch}.{ext}" → "ripgrep-14.1.1-linux-x86_64.tar.gz".
+/// Without a release, {repo} and {tag} are guessed from the file name.
+pub fn render_name(template: &str, file_name: &str, vars: &NameVars) -> Result<String, String> {
+    let (stem, ext) = crate::platform::split_ext(file_name);
+    let name = expand(template, TEMPLATE_VARS, "--filename-template", |var| {
+        let value = match var {
+            "name" => Some(file_name.to_string()),
+            "stem" => Some(stem.to_string()),
+            "ext" => Some(ext.to_string()),
+            "repo" => Some(
+                vars.repo
+                    .map_or_else(|| crate::install::tool_name(file_name), str::to_string),
+            ),
+            "tag" => vars
+                .tag
+                .map(str::to_string)
+                .or_else(|| guess_version(file_name)),
+            "platform" => vars.platform.map(str::to_string),
+            "arch" => vars.arch.map(str::to_string),
+            _ => unreachable!("expand checks variable names"),
+        };
+        value.ok_or_else(|| format!("can't tell the {{{var}}} of {file_name}"))
+    })?;
+
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(format!(
+            "--filename-template gives an invalid file name '{name}'"
+        ));
+    }
+    Ok(name)
+}
+
+/// Whether a download URL is a template, with variables to fill in.
+pub fn is_url_template(url: &str) -> bool {
+    url.contains('{')
+}
+
+/// What a URL template's variables stand for.
+pub struct UrlVars<'a> {
+    /// From --tool-version; a template using {version} needs it.
+    pub version: Option<&'a str>,
+    pub platform: &'a str,
+    pub arch: &'a str,
+}
+
+/// Fills in a vendor's URL template, e.g.
+/// "https://example.com/{version}/tool-{platform}-{arch:x86_64=amd64}.tar.gz" →
+/// "https://example.com/2.1/tool-linux-amd64.tar.gz".
+pub fn render_url(template: &str, vars: &UrlVars) -> Result<String, String> {
+    expand(template, URL_VARS, "URL template", |var| match var {
+        "version" => vars
+            .version
+            .map(str::to_string)
+            .ok_or_else(|| "the URL template uses {version}; pass --tool-version".to_string()),
+        "platform" => Ok(vars.platform.to_string()),
+        "arch" => Ok(vars.arch.to_string()),
+        _ => unreachable!("expand checks variable names"),
+    })
+}
+
+/// Replaces each `{var}` in `template` (one of `vars`) with `value(var)`.
+/// `{var:from=to,...}` renames values on the way, for vendors that spell
+/// them differently: `{arch:x86_64=amd64,arm64=aarch64}`. `what` names the
+/// template in errors.
+fn expand(
+    template: &str,
+    vars: &[&str],
+    what: &str,
+    mut value: impl FnMut(&str) -> Result<String, String>,
+) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        let close = rest[open..]
+            .find('}')
+            .ok_or_else(|| format!("unclosed '{{' in {what}"))?;
+        let (var, renames) = match rest[open + 1..open + close].split_once(':') {
+            Some((var, renames)) => (var, Some(renames)),
+            None => (&rest[open + 1..open + close], None),
+        };
+        if !vars.contains(&var) {
+            return Err(format!(
+                "unknown variable '{{{var}}}' in {what} (have: {})",
+                vars.join(", ")
+            ));
+        }
+        let renames = renames
+            .into_iter()
+            .flat_map(|r| r.split(','))
+            .map(|rename| {
+                rename
+                    .split_once('=')
+                    .ok_or_else(|| format!("'{rename}' in {what} should be from=to"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut filled = value(var)?;
+        if let Some((_, to)) = renames.iter().find(|(from, _)| *from == filled) {
+            filled = to.to_string();
+        }
+        expanded.push_str(&rest[..open]);
+        expanded.push_str(&filled);
+        rest = &rest[open + close + 1..];
+    }
+    if rest.contains('}') {
+        return Err(format!("unmatched '}}' in {what}"));
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// The `current` symlink of a tool, which always leads to its active version.
+pub fn current_dir(root: &Path, tool: &str) -> PathBuf {
+    root.join(tool).join(CURRENT)
+}
+
+/// Points `<root>/<tool>/current` at `version` and records it in the tool's history.
+pub fn activate(root: &Path, tool: &str, version: &str) -> std::io::Result<()> {
+    let tool_dir = root.join(tool);
+    let _guard = crate::filelock::lock(&tool_dir.join(HISTORY))?;
+    switch_current(&tool_dir, version)?;
+
+    let mut history = read_history(&tool_dir);
+    history.retain(|v| v != version);
+    history.push(version.to_string());
+    std::fs::write(tool_dir.join(HISTORY), history.join("\n") + "\n")
+}
+
+/// Switches `current` back to the previously active version, or to `to` if given.
+/// Returns the version now active.
+pub fn rollback(root: &Path, tool: &str, to: Option<&str>) -> Result<String, String> {
+    let tool_dir = root.join(tool);
+    if !tool_dir.is_dir() {
+        return Err(format!("{tool} is not installed in {}", root.display()));
+    }
+
+    let _guard = crate::filelock::lock(&tool_dir.join(HISTORY)).map_err(|e| e.to_string())?;
+    let mut history = read_history(&tool_dir);
+    // Versions deleted by hand can't be rolled back to.
+    history.retain(|v| tool_dir.join(v).is_dir());
+
+    let target = match to {
+        Some(version) => {
+            if !tool_dir.join(version).is_dir() {
+                return Err(format!(
+                    "{tool} {version} is not installed (have: {})",
+                    installed_versions(&tool_dir).join(", ")
+                ));
+            }
+            version.to_string()
+        }
+        None => {
+            if history.len() < 2 {
+                return Err(format!("no earlier version of {tool} to roll back to"));
+            }
+            history.pop();
+            history
+                .last()
+                .cloned()
+                .expect("history has an earlier entry")
+        }
+    };
+
+    switch_current(&tool_dir, &target).map_err(|e| e.to_string())?;
+
+    // Rolling back drops the abandoned version from the history, so a second
+    // rollback keeps going backwards instead of toggling.
+    history.retain(|v| *v != target);
+    history.push(target.clone());
+    std::fs::write(tool_dir.join(HISTORY), history.join("\n") + "\n").map_err(|e| e.to_string())?;
+
+    Ok(target)
+}
+
+/// Lists the version directories of a tool, sorted by name.
+fn installed_versions(tool_dir: &Path) -> Vec<String> {
+    let mut versions: Vec<String> = std::fs::read_dir(tool_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|e| e.file_name() != CURRENT && e.path().is_dir())
+        // Versions are release tags, so a name that isn't UTF-8 isn't one.
+        .filter_map(|e| e.file_name().into_string().ok())
+        .collect();
+    versions.sort();
+    versions
+}
+
+fn read_history(tool_dir: &Path) -> Vec<String> {
+    std::fs::read_to_string(tool_dir.join(HISTORY))
+        .unwrap_or_default()
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Replaces the `current` symlink. The new link is created beside the old one
+/// and renamed over it, so `current` never goes missing midway.
+fn switch_current(tool_dir: &Path, version: &str) -> std::io::Result<()> {
+    let link = tool_dir.join(CURRENT);
+    let staged = tool_dir.join(format!(".{CURRENT}.{}", std::process::id()));
+    let _ = crate::links::remove(&staged);
+
+    // Relative target, so the whole root can be moved.
+    crate::links::link_dir(Path::new(version), &staged)?;
+
+    // Nothing replaces a directory in one step — a copy, or on Windows any
+    // directory link — so the old one goes first.
+    if std::fs::rename(&staged, &link).is_err() {
+        crate::links::remove(&link)?;
+        std::fs::rename(&staged, &link)?;
+    }
+    Ok(())
+}
+
+/// The version a tool's `current` leads to: the link's target, or where it's
+/// a copy (see `links.rs`), the version last activated.
+pub fn current_version(root: &Path, tool: &str) -> Option<String> {
+    let link = current_dir(root, tool);
+    match std::fs::read_link(&link) {
+        Ok(target) => target.to_str().map(str::to_string),
+        Err(_) if link.is_dir() => read_history(&root.join(tool)).pop(),
+        Err(_) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guess_version() {
+        assert_eq!(
+            guess_version("tool-1.4.2-linux-amd64.tar.gz"),
+            Some("1.4.2".into())
+        );
+        assert_eq!(guess_version("tool_v2.0_linux.zip"), Some("v2.0".into()));
+        assert_eq!(guess_version("tool-linux-amd64.tar.gz"), None);
+        assert_eq!(guess_version("tool-x86_64.tar.gz"), None);
+    }
+
+    #[test]
+    fn test_guess_path_version() {
+        assert_eq!(
+            guess_path_version("team/tool/1.4.2/tool-linux-amd64.tar.gz"),
+            Some("1.4.2".into())
+        );
+        assert_eq!(
+            guess_path_version("tool/tool-2.0-linux.tar.gz"),
+            Some("2.0".into())
+        );
+        assert_eq!(guess_path_version("tool-linux.tar.gz"), None);
+    }
+
+    #[test]
+    fn test_render_name() {
+        let vars = NameVars {
+            repo: Some("ripgrep"),
+            tag: Some("14.1.1"),
+            platform: Some("linux"),
+            arch: Some("x86_64"),
+        };
+        let template = "{repo}-{tag}-{platform}-{arch}.{ext}";
+        assert_eq!(
+            render_name(
+                template,
+                "ripgrep-14.1.1-x86_64-unknown-linux-musl.tar.gz",
+                &vars
+            ),
+            Ok("ripgrep-14.1.1-linux-x86_64.tar.gz".into())
+        );
+
+        // Without a release, repo and tag come from the file name.
+        let plain = NameVars::default();
+        assert_eq!(
+            render_name("{repo}_{tag}.{ext}", "tool-1.2.0-linux.zip", &plain),
+            Ok("tool_1.2.0.zip".into())
+        );
+        assert!(render_name("{platform}.{ext}", "tool.zip", &plain).is_err());
+        assert!(render_name("../{name}", "tool.zip", &plain).is_err());
+    }
+
+    #[test]
+    fn test_parse_template() {
+        assert!(parse_template("{repo}-{tag}.{ext}").is_ok());
+        assert!(parse_template("plain-name.zip").is_ok());
+        assert!(parse_template("{version}.{ext}").is_err());
+        assert!(parse_template("{repo").is_err());
+        assert!(parse_template("repo}").is_err());
+        assert!(parse_template("{arch:amd64}").is_err());
+    }
+
+    #[test]
+    fn test_render_url() {
+        let vars = UrlVars {
+            version: Some("2.1.0"),
+            platform: "macos",
+            arch: "x86_64",
+        };
+        assert_eq!(
+            render_url(
+                "https://example.com/{version}/tool-{version}-{platform}-{arch}.tar.gz",
+                &vars
+            ),
+            Ok("https://example.com/2.1.0/tool-2.1.0-macos-x86_64.tar.gz".into())
+        );
+        assert_eq!(
+            render_url(
+                "https://example.com/tool_{platform:macos=darwin}_{arch:x86_64=amd64,arm64=arm64}.zip",
+                &vars
+            ),
+            Ok("https://example.com/tool_darwin_amd64.zip".into())
+        );
+        // Values with no rename pass through.
+        assert_eq!(
+            render_url("{platform:windows=win}", &vars),
+            Ok("macos".into())
+        );
+
+        let unversioned = UrlVars {
+            version: None,
+            ..vars
+        };
+        assert!(render_url("https://example.com/{version}/tool", &unversioned).is_err());
+        assert!(render_url("https://example.com/{tag}/tool", &vars).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_activate_and_rollback() {
+        let root = std::env::temp_dir().join(format!("dex-layout-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        for version in ["1.0", "1.1", "1.2"] {
+            std::fs::create_dir_all(version_dir(&root, "tool", version)).unwrap();
+            activate(&root, "tool", version).unwrap();
+        }
+        let current = || std::fs::read_link(root.join("tool").join(CURRENT)).unwrap();
+        assert_eq!(current(), PathBuf::from("1.2"));
+
+        assert_eq!(rollback(&root, "tool", None), Ok("1.1".into()));
+        assert_eq!(current(), PathBuf::from("1.1"));
+        assert_eq!(rollback(&root, "tool", None), Ok("1.0".into()));
+        assert!(rollback(&root, "tool", None).is_err());
+
+        assert_eq!(rollback(&root, "tool", Some("1.2")), Ok("1.2".into()));
+        assert_eq!(current(), PathBuf::from("1.2"));
+        assert!(rollback(&root, "tool", Some("9.9")).is_err());
+        assert!(rollback(&root, "missing", None).is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
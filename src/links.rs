@@ -0,0 +1,204 @@
+use serde::Deserialize;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+
+/// How dex makes symlinks: a version's `current` link, and symlink entries
+/// in archives. Windows only lets administrators and Developer Mode create
+/// them.
+///
+/// ```toml
+/// [symlinks]
+/// strategy = "copy"
+/// ```
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Strategy {
+    /// Symlinks where they can be made; otherwise a junction for `current`
+    /// on Windows, or a copy, with a warning.
+    #[default]
+    Auto,
+    /// Symlinks only: fail where they can't be made.
+    Symlink,
+    /// Always copy, e.g. for file systems or tools that mishandle links.
+    Copy,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct SymlinkConfig {
+    pub strategy: Strategy,
+}
+
+// The strategy in use, as a `Strategy` discriminant (set from the config).
+static STRATEGY: AtomicU8 = AtomicU8::new(Strategy::Auto as u8);
+
+// Whether the fallback warning has been shown; once a run is enough.
+static WARNED: AtomicBool = AtomicBool::new(false);
+
+pub fn set_strategy(strategy: Strategy) {
+    STRATEGY.store(strategy as u8, Ordering::Relaxed);
+}
+
+pub fn strategy() -> Strategy {
+    match STRATEGY.load(Ordering::Relaxed) {
+        s if s == Strategy::Symlink as u8 => Strategy::Symlink,
+        s if s == Strategy::Copy as u8 => Strategy::Copy,
+        _ => Strategy::Auto,
+    }
+}
+
+/// Whether symlink entries in archives may end up as copies, so extraction
+/// has to create them itself rather than leave them to the archive crate.
+pub fn may_copy() -> bool {
+    match strategy() {
+        Strategy::Copy => true,
+        Strategy::Auto => cfg!(windows),
+        Strategy::Symlink => false,
+    }
+}
+
+/// Links `link` to the directory `target` (relative to `link`'s parent):
+/// a symlink, or as the strategy allows, a junction or a copy.
+pub fn link_dir(target: &Path, link: &Path) -> io::Result<()> {
+    let strategy = strategy();
+    if strategy != Strategy::Copy {
+        match symlink(target, link, true) {
+            Ok(()) => return Ok(()),
+            Err(e) if strategy == Strategy::Auto && not_permitted(&e) => warn(link, &e),
+            Err(e) => return Err(e),
+        }
+        #[cfg(windows)]
+        if junction(target, link).is_ok() {
+            return Ok(());
+        }
+    }
+    copy(&link.parent().unwrap_or(Path::new("")).join(target), link)
+}
+
+/// Makes `link` a symlink to `target`, an archive entry's link target.
+/// Returns false if the strategy says to copy instead; the target may not be
+/// extracted yet, so that's left to the caller.
+pub fn try_symlink(target: &Path, link: &Path) -> io::Result<bool> {
+    let strategy = strategy();
+    if strategy == Strategy::Copy {
+        return Ok(false);
+    }
+    // A target that's there already tells a directory link from a file link
+    // (which Windows distinguishes); a missing one is taken for a file.
+    let resolved = link.parent().unwrap_or(Path::new("")).join(target);
+    match symlink(target, link, resolved.is_dir()) {
+        Ok(()) => Ok(true),
+        Err(e) if strategy == Strategy::Auto && not_permitted(&e) => {
+            warn(link, &e);
+            Ok(false)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Copies a file, or a directory and everything in it, to `dest`.
+pub fn copy(source: &Path, dest: &Path) -> io::Result<()> {
+    if !std::fs::metadata(source)?.is_dir() {
+        return std::fs::copy(source, dest).map(|_| ());
+    }
+    std::fs::create_dir_all(dest)?;
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        copy(&entry.path(), &dest.join(entry.file_name()))?;
+    }
+    Ok(())
+}
+
+/// Removes a link made by `link_dir` or `try_symlink`, whichever form it took.
+pub fn remove(link: &Path) -> io::Result<()> {
+    match std::fs::symlink_metadata(link) {
+        // A directory symlink or junction on Windows is removed as a directory.
+        Ok(metadata) if metadata.is_symlink() => {
+            std::fs::remove_file(link).or_else(|_| std::fs::remove_dir(link))
+        }
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(link),
+        Ok(_) => std::fs::remove_file(link),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path, _dir: bool) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path, dir: bool) -> io::Result<()> {
+    if dir {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+}
+
+/// An NTFS junction, which any user may create, though only to a directory
+/// and by absolute path.
+#[cfg(windows)]
+fn junction(target: &Path, link: &Path) -> io::Result<()> {
+    let target = std::fs::canonicalize(link.parent().unwrap_or(Path::new("")).join(target))?;
+    let status = std::process::Command::new("cmd")
+        .arg("/C")
+        .arg("mklink")
+        .arg("/J")
+        .arg(link)
+        .arg(target)
+        .stdout(std::process::Stdio::null())
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("mklink /J failed ({status})")));
+    }
+    Ok(())
+}
+
+/// Whether a symlink failed for want of the privilege to make one.
+fn not_permitted(e: &io::Error) -> bool {
+    // ERROR_PRIVILEGE_NOT_HELD
+    e.kind() == io::ErrorKind::PermissionDenied || (cfg!(windows) && e.raw_os_error() == Some(1314))
+}
+
+fn warn(link: &Path, e: &io::Error) {
+    if !WARNED.swap(true, Ordering::Relaxed) {
+        eprintln!(
+            "Warning: can't create symlinks here ({e}); {} and any others will be copies. \
+             Enable Developer Mode to allow symlinks, or set [symlinks] strategy = \"copy\" \
+             in the config to skip trying",
+            link.display()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_and_remove() {
+        let root = std::env::temp_dir().join(format!("dex-test-links-{}", std::process::id()));
+        let source = root.join("1.0");
+        std::fs::create_dir_all(source.join("bin")).unwrap();
+        std::fs::write(source.join("bin/tool"), "tool").unwrap();
+
+        // What `link_dir` falls back to where symlinks aren't allowed.
+        let current = root.join("current");
+        copy(&source, &current).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(current.join("bin/tool")).unwrap(),
+            "tool"
+        );
+        remove(&current).unwrap();
+        assert!(!current.exists() && source.join("bin/tool").exists());
+
+        link_dir(Path::new("1.0"), &current).unwrap();
+        assert!(current.join("bin/tool").is_file());
+        remove(&current).unwrap();
+        assert!(source.join("bin/tool").exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,17 +1,133 @@
+mod apicache;
+mod artifactory;
+mod attest;
+mod azure;
+mod batch;
+mod binaries;
+mod bitbucket;
+mod build;
+mod bundle;
+mod cache;
+mod color;
+mod config;
+mod delta;
+mod deploy;
+mod digestlog;
+mod dirs;
+mod doctor;
 mod download;
+mod durable;
 mod extract;
+mod filelock;
 mod github;
+mod http;
+mod i18n;
+mod index;
+mod install;
+mod layout;
+mod links;
+mod manifest;
+mod maven;
+mod mirror;
+#[cfg(all(feature = "mmap", unix))]
+mod mmap;
+mod nexus;
+mod notes;
+mod notify;
+mod oci;
+mod page;
 mod platform;
+mod progress;
+mod project;
+mod provider;
+mod receipt;
+mod repack;
+mod report;
+mod sbom;
+mod sourceforge;
+mod stats;
+mod verify;
 
-use clap::Parser;
+use batch::Failed;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use i18n::tr;
+use std::collections::HashMap;
+use std::io::{BufRead, IsTerminal, Write};
 use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by --json: stdout is reserved for the JSON report, so the result lines
+/// that normally go there ("Saved to ...") are printed to stderr instead.
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// `println!` for result lines, which move to stderr under --json.
+macro_rules! say {
+    ($($arg:tt)*) => {
+        if JSON_OUTPUT.load(Ordering::Relaxed) {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
 
 /// dex - download and extract
 #[derive(Parser)]
-#[command(version, about)]
+#[command(version, about, args_conflicts_with_subcommands = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Config file to use instead of ~/.config/dex/config.toml
+    #[arg(long, value_name = "PATH", global = true)]
+    config: Option<PathBuf>,
+
+    /// Seconds to wait for another dex process using the same cache entry,
+    /// install record, or lockfile before giving up
+    #[arg(long, value_name = "SECS", global = true, default_value_t = 300)]
+    lock_timeout: u64,
+
+    /// When to color output and progress bars: auto (at a terminal, unless
+    /// NO_COLOR is set or CLICOLOR_FORCE asks for it anyway), always, or never
+    #[arg(long, value_name = "WHEN", global = true, default_value = "auto")]
+    color: color::Choice,
+
+    /// User-Agent header to send with every request, instead of dex/<version>
+    #[arg(long, value_name = "STRING", global = true)]
+    user_agent: Option<String>,
+
+    /// With no subcommand, dex downloads: `dex <url>` is `dex get <url>`.
+    #[command(flatten)]
+    get: GetArgs,
+}
+
+/// What to download and what to do with it, for `dex get` (or plain `dex <url>`).
+#[derive(Args)]
+struct GetArgs {
     /// URL to download
-    url: String,
+    #[arg(required_unless_present = "input_file")]
+    url: Option<String>,
+
+    /// Treat the URL as a directory index page (like nginx's autoindex) and
+    /// download every file it lists whose name matches this glob ("*" and
+    /// "?"); may be given more than once
+    #[arg(short = 'A', long, value_name = "GLOB", conflicts_with_all = ["input_file", "all"])]
+    accept: Vec<String>,
+
+    /// Treat the URL as a vendor's download page and download the link on it
+    /// that best matches the platform, as for a release's assets
+    #[arg(long, conflicts_with_all = ["accept", "all"])]
+    page: bool,
+
+    /// How many levels of subdirectories of the index --accept looks into
+    #[arg(long, value_name = "N", default_value_t = 0, requires = "accept")]
+    index_depth: usize,
+
+    /// Download every URL listed in a file ("-" reads standard input).
+    /// URLs are separated by whitespace or newlines; "#" starts a comment
+    #[arg(short, long, value_name = "FILE", conflicts_with = "url")]
+    input_file: Option<PathBuf>,
 
     /// Output directory for extracted files (defaults to current directory)
     #[arg(short, long, default_value = ".")]
@@ -25,62 +141,3194 @@ struct Cli {
     #[arg(short, long)]
     no_extract: bool,
 
-    /// Override platform detection (e.g., linux, macos, windows)
+    /// Unpack a self-extracting installer (.exe: NSIS, Inno Setup, 7-Zip SFX) instead of
+    /// saving it. Needs 7-Zip or innoextract on PATH
+    #[arg(long, conflicts_with = "no_extract")]
+    extract: bool,
+
+    /// Also extract archives found inside the downloaded archive
+    #[arg(short, long)]
+    recursive: bool,
+
+    /// How many levels of nested archives --recursive extracts
+    #[arg(long, value_name = "N", default_value_t = 3, requires = "recursive")]
+    max_depth: usize,
+
+    /// Replace existing files when extracting (the default when not run from a terminal)
+    #[arg(long, conflicts_with_all = ["skip_existing", "backup"])]
+    overwrite: bool,
+
+    /// Keep existing files and skip those archive entries
+    #[arg(long, conflicts_with = "backup")]
+    skip_existing: bool,
+
+    /// Rename existing files to <name>.bak before extracting over them
+    #[arg(long)]
+    backup: bool,
+
+    /// Name the file a single compressed download (e.g. data.csv.gz) decompresses to
+    #[arg(long, value_name = "NAME", value_parser = extract::parse_file_name, conflicts_with_all = ["input_file", "all"])]
+    name: Option<String>,
+
+    /// Name a compressed bare binary (e.g. tool_linux_amd64.gz) after its tool
+    /// when decompressing it: the release's project, or the start of its file name
+    #[arg(long, conflicts_with = "name")]
+    rename_binary: bool,
+
+    /// Character encoding of zip entry names not marked as UTF-8 (e.g., shift_jis, gbk, cp437).
+    /// By default they're read as UTF-8 when valid, otherwise as CP437
+    #[arg(long, value_name = "ENCODING")]
+    entry_encoding: Option<extract::EntryEncoding>,
+
+    /// Stamp extracted files with the time of extraction instead of the
+    /// modification times stored in the archive
+    #[arg(long)]
+    no_preserve_mtime: bool,
+
+    /// Give extracted files the owner and group recorded in a tar archive (root only).
+    /// By default they belong to the user running dex, like `tar --no-same-owner`
+    #[arg(long, conflicts_with_all = ["owner", "group"])]
+    same_owner: bool,
+
+    /// Make extracted files belong to this user (name or id; root only)
+    #[arg(long, value_name = "USER", value_parser = extract::parse_user)]
+    owner: Option<u32>,
+
+    /// Make extracted files belong to this group (name or id; root only)
+    #[arg(long, value_name = "GROUP", value_parser = extract::parse_group)]
+    group: Option<u32>,
+
+    /// Make the extracted tree the same whoever extracts it, and whenever:
+    /// every file and directory is stamped 1980-01-01, modes become 0755 or
+    /// 0644, and files belong to the user running dex
+    #[arg(long, conflicts_with_all = ["same_owner", "owner", "group", "no_preserve_mtime"])]
+    deterministic: bool,
+
+    /// Threads to write .zip entries on
+    /// (default: one per CPU; 1 to use just one)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    threads: Option<u16>,
+
+    /// Flush the downloaded file (or the extracted or installed files) and
+    /// their directory entries to disk before finishing, so they survive a
+    /// power loss or an immediate reboot
+    #[arg(long)]
+    fsync: bool,
+
+    /// Write .dex-manifest.json listing every extracted file's size, mode, and SHA-256
+    #[arg(long)]
+    manifest: bool,
+
+    /// Override platform detection (e.g., linux, macos, windows, or "all")
     #[arg(long)]
     platform: Option<String>,
 
-    /// Override architecture detection (e.g., x86_64, arm64)
+    /// Override architecture detection (e.g., x86_64, arm64, or "all")
     #[arg(long)]
     arch: Option<String>,
+
+    /// Don't fall back to an arch the machine runs under emulation (x86_64
+    /// under Rosetta, say) when a release has no build for its own
+    #[arg(long)]
+    no_fallback_arch: bool,
+
+    /// Read the header of the binary that was downloaded (or the main one in
+    /// an archive) and warn if it's built for another platform or arch than
+    /// the one asked for, as mislabeled assets are
+    #[arg(long)]
+    check_arch: bool,
+
+    /// Like --check-arch, but fail on a mismatch instead of warning
+    #[arg(long)]
+    strict_arch: bool,
+
+    /// Prefer assets built against this C library (e.g., gnu, musl, msvc)
+    #[arg(long)]
+    libc: Option<String>,
+
+    /// Set platform, arch, and libc from a target triple (e.g., x86_64-unknown-linux-musl)
+    #[arg(long, value_name = "TRIPLE", conflicts_with_all = ["platform", "arch", "libc"])]
+    target: Option<String>,
+
+    /// Download the release asset with exactly this name instead of picking one.
+    /// Together with a tag URL, this also works when the GitHub API is rate-limited
+    #[arg(long, value_name = "NAME")]
+    asset_name: Option<String>,
+
+    /// Never pick a release asset whose name matches this glob (e.g.
+    /// '*-full-*'); may be given more than once
+    #[arg(long, value_name = "GLOB")]
+    exclude_asset: Vec<String>,
+
+    /// Only pick among release assets whose names match this glob; may be
+    /// given more than once
+    #[arg(long, value_name = "GLOB")]
+    only_asset: Vec<String>,
+
+    /// Which of equally good assets to pick, such as a "minimal" and a "full"
+    /// build: the smallest, the largest, or the one whose name contains a
+    /// word ("keyword=full")
+    #[arg(long, value_name = "POLICY", default_value = "smallest")]
+    variant_preference: platform::VariantPreference,
+
+    /// Print how each release asset was scored for selection, then exit
+    #[arg(long)]
+    explain: bool,
+
+    /// Version to put in a URL template's {version}, as in
+    /// https://example.com/{version}/tool-{version}-{platform}-{arch}.tar.gz
+    #[arg(long, value_name = "VERSION")]
+    tool_version: Option<String>,
+
+    /// Show the release notes before downloading (paged when interactive)
+    #[arg(long)]
+    notes: bool,
+
+    /// Download every asset of a GitHub release into a directory named after the tag
+    #[arg(long)]
+    all: bool,
+
+    /// Redirects to follow before giving up on a download
+    #[arg(long, value_name = "N", default_value_t = http::DEFAULT_MAX_REDIRECTS)]
+    max_redirects: usize,
+
+    /// Download only if the remote file changed since this local copy; exits
+    /// with code 6 and leaves it alone otherwise
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["input_file", "all"])]
+    if_newer: Option<PathBuf>,
+
+    /// Download even when the output directory already has the same file (by
+    /// checksum, or failing that by size)
+    #[arg(long)]
+    force: bool,
+
+    /// Save downloads as e.g. "{repo}-{tag}-{platform}-{arch}.{ext}" (also
+    /// {name}, {stem}); release values come from the provider, or are guessed
+    /// from the file name
+    #[arg(long, value_name = "TEMPLATE", value_parser = layout::parse_template)]
+    filename_template: Option<String>,
+
+    /// Reuse downloads kept in dex's cache directory, and keep new ones there;
+    /// a cached copy is checked against its recorded SHA-256 before each use
+    #[arg(long)]
+    cache: bool,
+
+    /// Never touch the network: serve release listings and downloads from the
+    /// cache (--cache), and fail right away for anything not in it
+    #[arg(long, conflicts_with = "if_newer")]
+    offline: bool,
+
+    /// When an earlier version of the file is cached and the server publishes
+    /// a zsync control file (<url>.zsync), fetch only the blocks that changed
+    #[arg(long, requires = "cache")]
+    delta: bool,
+
+    /// Refuse downloads bigger than this (e.g. 500M, 2G, 1.5GiB): by their
+    /// declared size up front, or once that many bytes have arrived
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    max_size: Option<u64>,
+
+    /// Bytes to read and write at a time while downloading (e.g. 64K, 1M;
+    /// 4K to 16M)
+    #[arg(long, value_name = "SIZE", value_parser = parse_chunk_size, default_value = "128K")]
+    chunk_size: usize,
+
+    /// At a terminal, ask before downloading a release asset the release
+    /// lists as bigger than this (0 never asks)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, default_value = "500M")]
+    confirm_above: u64,
+
+    /// When a release (or a tag with none) has nothing for the platform,
+    /// download the tag's source tarball instead of failing
+    #[arg(long, conflicts_with_all = ["asset_name", "all"])]
+    source_fallback: bool,
+
+    /// When a release has nothing for this machine, build the tag from
+    /// source with cargo install or go install (Rust and Go projects on GitHub)
+    #[arg(long, conflicts_with_all = ["asset_name", "all", "source_fallback"])]
+    build_fallback: bool,
+
+    /// Don't ask before big downloads
+    #[arg(short, long)]
+    yes: bool,
+
+    /// Abort a download when no data arrives for this many seconds (0 waits forever)
+    #[arg(long, value_name = "SECS", default_value_t = http::DEFAULT_STALL_TIMEOUT)]
+    stall_timeout: u64,
+
+    /// Give up extracting after this many seconds, leaving nothing
+    /// half-extracted behind, for archives that would otherwise run forever
+    #[arg(long, value_name = "SECS")]
+    extract_timeout: Option<u64>,
+
+    /// After extracting, keep only executables (by magic bytes or execute
+    /// bit) and delete docs, licenses, completion scripts, and the like
+    #[arg(long, conflicts_with_all = ["install", "no_extract"])]
+    binaries_only: bool,
+
+    /// Which release counts as the latest when the URL names none: the most
+    /// recently published ("date") or the highest version ("semver")
+    #[arg(long, value_name = "STRATEGY", default_value = "date")]
+    latest_strategy: provider::Latest,
+
+    /// Which of a download's [mirrors] to try first: the URL itself, then the
+    /// mirrors in order ("first"); the one that answers soonest ("fastest");
+    /// or any ("random"). The others are tried if it fails
+    #[arg(long, value_name = "STRATEGY", default_value = "first")]
+    mirror_strategy: mirror::Strategy,
+
+    /// Force an HTTP version (1.1, 2, or 3) instead of negotiating one
+    #[arg(long, value_name = "VERSION")]
+    http_version: Option<http::HttpVersion>,
+
+    /// Show redirects, the protocol used, and where each download ended up
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Number of downloads to run at once with --input-file, --all, --platform all, or --arch all
+    #[arg(short, long, default_value_t = 1)]
+    jobs: usize,
+
+    /// Print a summary line: bytes downloaded, average speed, time spent
+    /// downloading and extracting
+    #[arg(long)]
+    stats: bool,
+
+    /// Print a JSON report of each URL's outcome and the run's statistics to stdout
+    #[arg(long)]
+    json: bool,
+
+    /// Write a report of every URL handled — what it resolved to, digests,
+    /// durations, and failures — to this file: JUnit XML if it ends in .xml, else JSON
+    #[arg(long, value_name = "PATH")]
+    report: Option<PathBuf>,
+
+    /// When dex finishes, ring the terminal bell and/or show a desktop notification
+    #[arg(long, value_enum, value_name = "HOW", num_args = 0..=1, default_missing_value = "both")]
+    notify: Option<Notify>,
+
+    /// Stop a batch (--input-file, --all) at the first failed download
+    #[arg(long, overrides_with = "keep_going")]
+    fail_fast: bool,
+
+    /// Carry on with the rest of a batch after a download fails (the default)
+    #[arg(long, overrides_with = "fail_fast")]
+    keep_going: bool,
+
+    /// Skip checksum verification against published sum files
+    #[arg(long)]
+    no_verify: bool,
+
+    /// Require the download to have this SHA-256 digest, like a
+    /// "#sha256=<digest>" URL fragment; nothing is extracted if it doesn't
+    #[arg(long, value_name = "DIGEST", value_parser = parse_sha256, conflicts_with_all = ["input_file", "all", "no_verify"])]
+    sha256: Option<verify::Digest>,
+
+    /// Verify against the sum file at this URL (sha256sum, BSD, or bare-digest
+    /// form) instead of one published with the release. Not available with
+    /// --offline, which can't fetch it
+    #[arg(long, value_name = "URL", conflicts_with_all = ["all", "no_verify", "sha256", "offline"])]
+    checksum_url: Option<String>,
+
+    /// Check each download's SHA-256 against what the organization's digest
+    /// log recorded for it, recording it if nobody has downloaded it yet
+    #[arg(long, value_name = "URL")]
+    digest_log: Option<String>,
+
+    /// Check that a Sigstore attestation (GitHub artifact attestations or
+    /// cosign bundles) names the download's digest. Keyless signatures, which
+    /// all GitHub artifact attestations are, aren't verified
+    #[arg(long)]
+    verify_attestation: bool,
+
+    /// Fail unless an attestation signed with --attestation-key covers the
+    /// download (implies --verify-attestation). A bundle anyone could upload
+    /// next to the asset proves nothing without a signature check, so the key
+    /// is required; keyless attestations never satisfy it
+    #[arg(long, requires = "attestation_key")]
+    require_attestation: bool,
+
+    /// Public key (PEM, e.g. cosign.pub) that attestation signatures must verify against
+    #[arg(long, value_name = "PATH")]
+    attestation_key: Option<PathBuf>,
+
+    /// Also fetch the release's SBOM for the selected asset
+    #[arg(long, value_name = "MODE")]
+    sbom: Option<SbomMode>,
+
+    /// Install the downloaded binary, or the one found inside the archive, into --bin-dir
+    #[arg(long)]
+    install: bool,
+
+    /// How downloads are arranged under the output directory
+    #[arg(long, value_name = "LAYOUT", default_value = "flat", conflicts_with_all = ["install", "all"])]
+    layout: Layout,
+
+    /// Directory for --install (defaults to ~/.local/bin)
+    #[arg(long, value_name = "DIR", requires = "install")]
+    bin_dir: Option<PathBuf>,
+
+    /// With --install, also install man pages from the archive into ~/.local/share/man
+    #[arg(long, requires = "install")]
+    with_man: bool,
+
+    /// With --install, also install bash/zsh/fish completion scripts from the archive
+    #[arg(long, requires = "install")]
+    with_completions: bool,
+
+    /// With --install, keep each version in dex's store and put a shim in
+    /// --bin-dir that runs the active one, switched with `dex use`
+    #[arg(long, requires = "install")]
+    shim: bool,
+
+    /// Copy every extracted file into DIR, for bundles of config files,
+    /// themes, or completions: permissions are kept, files already there
+    /// are moved aside to <file>.dex-bak, and `dex uninstall` takes it all back out
+    #[arg(long, value_name = "DIR", conflicts_with_all = ["install", "no_extract", "keep", "layout"])]
+    install_to: Option<PathBuf>,
+
+    /// Package the extracted files as an OCI image layer tarball at FILE,
+    /// rooted at the image's `/`, for buildah, crane, and the like
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["install", "install_to", "no_extract", "keep", "layout"])]
+    oci_layer: Option<PathBuf>,
+
+    /// With --oci-layer, also write a minimal image config and manifest next
+    /// to the layer, for an image made of just it
+    #[arg(long, requires = "oci_layer")]
+    oci_manifest: bool,
+
+    /// Re-archive what was extracted as tar.gz, tar.zst, or zip, into the
+    /// output directory; entries are sorted and timestamps fixed, so the same
+    /// files always make the same archive
+    #[arg(long, value_name = "FORMAT", conflicts_with_all = ["install", "install_to", "oci_layer", "no_extract", "keep", "layout"])]
+    repack: Option<repack::Format>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Download a URL and extract it (the default when no subcommand is given)
+    Get(Box<GetArgs>),
+    /// Extract an archive that's already on disk
+    Extract {
+        /// Archive to extract
+        archive: PathBuf,
+
+        /// Output directory for extracted files
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+    },
+    /// List a release's assets, marking the one dex would download
+    List {
+        /// Release URL (GitHub releases, Bitbucket downloads, Maven, Azure Artifacts)
+        url: String,
+
+        /// Override platform detection (e.g., linux, macos, windows)
+        #[arg(long)]
+        platform: Option<String>,
+
+        /// Override architecture detection (e.g., x86_64, arm64)
+        #[arg(long)]
+        arch: Option<String>,
+
+        /// Prefer assets built against this C library (e.g., gnu, musl, msvc)
+        #[arg(long)]
+        libc: Option<String>,
+    },
+    /// Show a release's notes
+    Notes {
+        /// owner/repo, owner/repo@tag, or a release URL
+        release: String,
+    },
+    /// Install the tools listed in the project's dex.toml into .dex/bin
+    ///
+    /// dex.toml is looked for in the current directory and its parents.
+    /// What gets installed is recorded in dex.lock beside it, and later
+    /// syncs install exactly those files. Tools already installed from the
+    /// locked file are skipped.
+    Sync {
+        /// Install every tool again, even those already up to date
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print shell code that adds the project's .dex/bin to PATH
+    ///
+    /// For `eval "$(dex env)"` in a shell profile or a direnv .envrc.
+    Env {
+        /// Shell to write for (defaults to the one in $SHELL)
+        #[arg(long)]
+        shell: Option<clap_complete::Shell>,
+    },
+    /// Pack the files a dex.lock pins into one archive, or install from one offline
+    Bundle {
+        #[command(subcommand)]
+        action: BundleAction,
+    },
+    /// Show or empty the download cache (--cache)
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Print a shell completion script
+    Completions {
+        /// Shell to complete for
+        shell: clap_complete::Shell,
+    },
+    /// Print the binary an installed tool runs, and the release it came from
+    Which {
+        /// Tool name, as installed (e.g. rg)
+        tool: String,
+    },
+    /// Show everything recorded about an installed tool
+    Info {
+        /// Tool name, as installed (e.g. rg)
+        tool: String,
+    },
+    /// Switch the version a --shim install runs, e.g. `dex use rg@14.1.0`
+    Use {
+        /// Tool and version, as tool@version
+        #[arg(value_name = "TOOL@VERSION")]
+        tool: String,
+    },
+    /// Install the latest release of a tool installed from GitHub, where it
+    /// was installed before
+    Update {
+        /// Tool name, as installed (e.g. rg)
+        tool: String,
+
+        /// Show the release notes of every release since the installed one
+        /// instead of updating
+        #[arg(long)]
+        preview: bool,
+    },
+    /// Remove the files --install-to copied into a directory, putting back
+    /// the ones they replaced
+    ///
+    /// Files edited since they were installed are left where they are.
+    Uninstall {
+        /// Bundle name, as installed (the release's repository name)
+        name: String,
+
+        /// Only the copy in this directory, when it was installed into several
+        #[arg(long, value_name = "DIR")]
+        from: Option<PathBuf>,
+    },
+    /// Point a tool's `current` symlink back at its previous version (--layout versioned)
+    Rollback {
+        /// Tool name, as in <root>/<tool>/<version>
+        tool: String,
+
+        /// Switch to this version instead of the previous one
+        #[arg(long, value_name = "VERSION")]
+        to: Option<String>,
+
+        /// Root directory the tool was downloaded into
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+    },
+    /// Re-hash files extracted with --manifest and report anything missing or
+    /// changed, or check a file against a checksum with --sha256
+    Verify {
+        /// Directory holding a .dex-manifest.json, or a tool name under --output
+        /// (--layout versioned); with --sha256, the file to check
+        target: PathBuf,
+
+        /// Root directory tools were downloaded into
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+
+        /// Check `target` against this digest, or the one a sum file (a path or
+        /// URL) lists for it; a sum file's own algorithm is used
+        #[arg(long, value_name = "DIGEST|SUMFILE|URL")]
+        sha256: Option<String>,
+    },
+    /// Check the network, proxy, GitHub token, and directories dex uses, and
+    /// suggest fixes for anything wrong
+    Doctor,
+    /// Show a download's size, content type, final URL, and range support without downloading it
+    Probe {
+        /// URL to probe; release URLs are resolved to the asset dex would pick
+        url: String,
+
+        /// Override platform detection (e.g., linux, macos, windows)
+        #[arg(long)]
+        platform: Option<String>,
+
+        /// Override architecture detection (e.g., x86_64, arm64)
+        #[arg(long)]
+        arch: Option<String>,
+
+        /// Prefer assets built against this C library (e.g., gnu, musl, msvc)
+        #[arg(long)]
+        libc: Option<String>,
+
+        /// Probe the release asset with this exact name
+        #[arg(long, value_name = "NAME")]
+        asset_name: Option<String>,
+
+        /// Print the result as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// What `dex bundle` does.
+#[derive(Subcommand)]
+enum BundleAction {
+    /// Download every file in a lockfile, for every platform, into a tar
+    Create {
+        /// Lockfile written by `dex sync`
+        lockfile: PathBuf,
+        /// Bundle to write
+        bundle: PathBuf,
+    },
+    /// Install this machine's build of every tool in a bundle, without the network
+    Install {
+        bundle: PathBuf,
+
+        /// Directory to install into (defaults to ~/.local/bin)
+        #[arg(long, value_name = "DIR")]
+        bin_dir: Option<PathBuf>,
+    },
+}
+
+/// What `dex cache` does.
+#[derive(Subcommand)]
+enum CacheAction {
+    /// Print the cache directory
+    Dir,
+    /// List cached downloads
+    List,
+    /// Delete everything in the cache
+    Clear,
+}
+
+/// How downloads are arranged under the output directory.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Layout {
+    /// Straight into the output directory
+    Flat,
+    /// Into <output>/<tool>/<version>/, with a `current` symlink to the latest
+    Versioned,
+}
+
+/// How --notify announces that dex has finished.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Notify {
+    /// Ring the terminal bell
+    Bell,
+    /// Show a desktop notification
+    Desktop,
+    /// Both
+    Both,
+}
+
+/// What to do with a release asset's SBOM.
+#[derive(Clone, Copy, ValueEnum)]
+enum SbomMode {
+    /// Save it next to the download
+    Save,
+    /// Print its component and license counts
+    Summary,
 }
 
 fn main() {
-    let cli = Cli::parse();
+    // clap exits with 2 on bad arguments, but 2 means partial failure here.
+    let Cli {
+        command,
+        config: config_path,
+        lock_timeout,
+        color,
+        user_agent,
+        get: args,
+    } = Cli::try_parse().unwrap_or_else(|e| {
+        let _ = e.print();
+        std::process::exit(if e.use_stderr() { 1 } else { 0 });
+    });
+    filelock::set_timeout(std::time::Duration::from_secs(lock_timeout));
+    color::init(color);
+    let config = load_config(config_path.as_deref());
+    links::set_strategy(config.symlinks.strategy);
+    if let Some(user_agent) = user_agent.or(config.user_agent) {
+        http::set_user_agent(user_agent);
+    }
 
-    // Determine the effective URL — resolve GitHub release URLs to direct asset URLs.
-    let url = if github::is_github_release_url(&cli.url) {
-        let platform = cli
-            .platform
-            .as_deref()
-            .unwrap_or_else(|| platform::normalize_platform(std::env::consts::OS));
-        let arch = cli
-            .arch
-            .as_deref()
-            .unwrap_or_else(|| platform::normalize_arch(std::env::consts::ARCH));
+    let mut cli = match command {
+        None => args,
+        Some(Command::Get(args)) => *args,
+        Some(command) => {
+            run_command(command, config_path.as_deref());
+            return;
+        }
+    };
+    cli.digest_log = cli.digest_log.or(config.digest_log);
+
+    let mut rules = platform::Rules::default();
+    rules.extend(&config.selection);
+    rules.filter_names(&cli.only_asset, &cli.exclude_asset);
+    rules.set_variant_preference(cli.variant_preference.clone());
+
+    let target = cli.target.as_deref().map(|triple| {
+        platform::parse_target(triple).unwrap_or_else(|| {
+            eprintln!("Error: unrecognized target triple '{triple}'");
+            std::process::exit(1);
+        })
+    });
+
+    JSON_OUTPUT.store(cli.json, Ordering::Relaxed);
 
-        let (asset_url, _filename) = github::resolve_asset_url(&cli.url, platform, arch)
-            .unwrap_or_else(|e| {
-                eprintln!("Error resolving GitHub release: {e}");
+    let http = http::HttpContext::new(&http::HttpOptions {
+        max_redirects: cli.max_redirects,
+        verbose: cli.verbose,
+        version: cli.http_version,
+        offline: cli.offline,
+        stall_timeout: (cli.stall_timeout > 0)
+            .then(|| std::time::Duration::from_secs(cli.stall_timeout)),
+        hosts: config.hosts,
+    })
+    .unwrap_or_else(|e| {
+        eprintln!("Error setting up HTTP client: {e}");
+        std::process::exit(1);
+    });
+
+    let mirrors = mirror::Mirrors::new(config.mirrors);
+    // Batches draw one progress bar per concurrent download.
+    let multi = indicatif::MultiProgress::new();
+    let context = Context {
+        cli: &cli,
+        http,
+        rules,
+        target,
+        progress: (cli.input_file.is_some() || !cli.accept.is_empty()).then_some(&multi),
+        stats: Mutex::default(),
+        installed: Mutex::default(),
+        report: Mutex::default(),
+        mirrors: &mirrors,
+    };
+
+    let started = std::time::Instant::now();
+    let (label, urls, outcomes) = match &cli.input_file {
+        Some(list) => {
+            let urls = batch::read_url_list(list).unwrap_or_else(|e| {
+                eprintln!("Error reading {}: {e}", list.display());
                 std::process::exit(1);
             });
+            let outcomes = get_all(&context, &urls);
+            (list.display().to_string(), urls, outcomes)
+        }
+        None if !cli.accept.is_empty() => {
+            let index = cli.url.clone().expect("clap requires a URL");
+            let urls = index::list(&context.http, &index, &cli.accept, cli.index_depth)
+                .unwrap_or_else(|e| {
+                    eprintln!("Error listing {index}: {e}");
+                    std::process::exit(Failed::classify(&*e).exit_code());
+                });
+            if urls.is_empty() {
+                eprintln!(
+                    "Error: {index} lists no files matching {}",
+                    cli.accept.join(", ")
+                );
+                std::process::exit(1);
+            }
+            let s = if urls.len() == 1 { "" } else { "s" };
+            eprintln!("{} file{s} to download from {index}", urls.len());
+            let outcomes = get_all(&context, &urls);
+            (index, urls, outcomes)
+        }
+        None => {
+            let url = cli.url.clone().expect("clap requires a URL");
+            let outcome = get_timed(&context, &url);
+            (tool_label(&url), vec![url], vec![Some(outcome)])
+        }
+    };
+    let result = batch::overall(&outcomes);
+    let stats = context.stats.into_inner().unwrap();
 
-        asset_url
-    } else {
-        cli.url.clone()
+    if cli.stats {
+        eprintln!("{}", stats.summary());
+    }
+    if cli.json {
+        println!("{:#}", batch::json_report(&urls, &outcomes, &stats));
+    }
+    if let Some(path) = &cli.report {
+        let mut noted = context.report.into_inner().unwrap();
+        let items: Vec<report::Item> = urls
+            .iter()
+            .zip(&outcomes)
+            .map(|(url, outcome)| report::Item {
+                url: url.clone(),
+                status: batch::status(outcome).to_string(),
+                ..noted.remove(url).unwrap_or_default()
+            })
+            .collect();
+        let exit_code = result.err().map_or(0, Failed::exit_code);
+        if let Err(e) = report::write(path, &items, exit_code, started.elapsed(), &stats) {
+            eprintln!(
+                "Warning: could not write the report to {}: {e}",
+                path.display()
+            );
+        }
+    }
+    if let Some(how) = cli.notify {
+        announce(how, &label, result.is_ok(), started.elapsed());
+    }
+    if let Err(failed) = result {
+        std::process::exit(failed.exit_code());
+    }
+}
+
+/// Parses the value of --sha256.
+fn parse_sha256(hex: &str) -> Result<verify::Digest, String> {
+    verify::parse_pinned(verify::Algorithm::Sha256, hex)
+}
+
+/// Parses the value of --max-size: a number of bytes, optionally with a
+/// K/M/G/T suffix (powers of 1024; "KB", "MiB", etc. are accepted too).
+fn parse_size(size: &str) -> Result<u64, String> {
+    let digits = size.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let unit = size[digits.len()..].to_ascii_uppercase();
+    let shift = match unit.trim_end_matches('B').trim_end_matches('I') {
+        "" => 0,
+        "K" => 10,
+        "M" => 20,
+        "G" => 30,
+        "T" => 40,
+        _ => return Err(format!("unknown size unit '{unit}' (use K, M, G, or T)")),
     };
+    let number: f64 = digits
+        .parse()
+        .ok()
+        .filter(|n: &f64| n.is_finite() && *n >= 0.0)
+        .ok_or_else(|| format!("'{size}' is not a size"))?;
+    Ok((number * (1u64 << shift) as f64) as u64)
+}
+
+/// Parses the value of --chunk-size: a size as for --max-size, between
+/// `download::MIN_CHUNK_SIZE` and `download::MAX_CHUNK_SIZE`.
+fn parse_chunk_size(size: &str) -> Result<usize, String> {
+    let range = download::MIN_CHUNK_SIZE..=download::MAX_CHUNK_SIZE;
+    usize::try_from(parse_size(size)?)
+        .ok()
+        .filter(|bytes| range.contains(bytes))
+        .ok_or_else(|| format!("'{size}' is out of range (use 4K to 16M)"))
+}
 
-    let downloaded_path = download::download_file(&url, &cli.output).unwrap_or_else(|e| {
-        eprintln!("Error downloading: {e}");
+/// Reads the config file and returns the built-in asset selection tables,
+/// extended with the user's naming conventions. Exits if the config is bad.
+fn load_config(config_path: Option<&std::path::Path>) -> config::Config {
+    config::load(config_path).unwrap_or_else(|e| {
+        eprintln!("Error reading config: {e}");
         std::process::exit(1);
-    });
+    })
+}
 
-    if cli.no_extract {
-        println!("Saved to {}", downloaded_path.display());
-        return;
+fn load_rules(config_path: Option<&std::path::Path>) -> platform::Rules {
+    let mut rules = platform::Rules::default();
+    rules.extend(&load_config(config_path).selection);
+    rules
+}
+
+/// An HTTP client for a subcommand, with the config's `[hosts]` settings.
+fn http_client(
+    config_path: Option<&std::path::Path>,
+) -> Result<http::HttpContext, Box<dyn std::error::Error>> {
+    http::HttpContext::new(&http::HttpOptions {
+        hosts: load_config(config_path).hosts,
+        ..Default::default()
+    })
+}
+
+/// Runs a subcommand other than `get`, exiting non-zero if it fails.
+fn run_command(command: Command, config_path: Option<&std::path::Path>) {
+    let result = match command {
+        Command::Get(_) => unreachable!("`get` is handled by main"),
+        Command::Which { tool } => which(&tool),
+        Command::Info { tool } => show_info(&tool),
+        Command::Use { tool } => use_version(&tool),
+        Command::Update { tool, preview } => update(&tool, preview, config_path),
+        Command::Doctor => doctor(config_path),
+        Command::Uninstall { name, from } => uninstall(&name, from.as_deref()),
+        Command::Rollback { tool, to, output } => layout::rollback(&output, &tool, to.as_deref())
+            .map(|version| println!("{tool} is now at {version}"))
+            .map_err(Into::into),
+        Command::Verify {
+            target,
+            output,
+            sha256,
+        } => {
+            match sha256 {
+                Some(expected) => verify_file(&target, &expected, config_path),
+                None => verify_tree(&target, &output),
+            }
+            Ok(())
+        }
+        Command::Probe {
+            url,
+            platform,
+            arch,
+            libc,
+            asset_name,
+            json,
+        } => {
+            let selection = (platform.as_deref(), arch.as_deref(), libc.as_deref());
+            let rules = load_rules(config_path);
+            http_client(config_path)
+                .and_then(|http| probe(&http, &url, &rules, selection, asset_name.as_deref(), json))
+        }
+        Command::Extract { archive, output } => {
+            extract::extract_file(&archive, &output, &Default::default())
+                .map(|_| say!("Extracted to {}", output.display()))
+        }
+        Command::List {
+            url,
+            platform,
+            arch,
+            libc,
+        } => {
+            let selection = (platform.as_deref(), arch.as_deref(), libc.as_deref());
+            http_client(config_path)
+                .and_then(|http| list_assets(&http, &url, &load_rules(config_path), selection))
+        }
+        Command::Notes { release } => {
+            let url = github::shorthand_url(&release).unwrap_or(release);
+            let provider =
+                provider::find(&url).ok_or("not a release (owner/repo or a release URL)");
+            provider.map_err(Into::into).and_then(|provider| {
+                let http = http_client(config_path)?;
+                show_notes(&provider.fetch_release(&http, &url, Default::default())?);
+                Ok(())
+            })
+        }
+        Command::Sync { force } => sync(config_path, force),
+        Command::Env { shell } => print_env(shell),
+        Command::Bundle {
+            action: BundleAction::Create { lockfile, bundle },
+        } => http_client(config_path)
+            .and_then(|http| bundle::create(&http, &lockfile, &bundle))
+            .map(|count| {
+                let s = if count == 1 { "" } else { "s" };
+                say!("Packed {count} file{s} into {}", bundle.display())
+            }),
+        Command::Bundle {
+            action: BundleAction::Install { bundle, bin_dir },
+        } => install_bundle(&bundle, bin_dir),
+        Command::Cache { action } => manage_cache(action),
+        Command::Completions { shell } => {
+            // Generated into a buffer: clap_complete panics on write errors,
+            // such as a closed pipe.
+            let mut script = Vec::new();
+            clap_complete::generate(shell, &mut Cli::command(), "dex", &mut script);
+            std::io::Write::write_all(&mut std::io::stdout(), &script).map_err(Into::into)
+        }
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {e}");
+        std::process::exit(Failed::classify(&*e).exit_code());
     }
+}
 
-    if extract::is_extractable(&downloaded_path) {
-        extract::extract_file(&downloaded_path, &cli.output).unwrap_or_else(|e| {
-            eprintln!("Error extracting: {e}");
-            std::process::exit(1);
-        });
+/// A short name for what a URL downloads: the repository of a release URL,
+/// otherwise the tool named in the file name.
+fn tool_label(url: &str) -> String {
+    provider::find(url)
+        .and_then(|p| p.repo_name(url))
+        .map(String::from)
+        .unwrap_or_else(|| install::tool_name(&download::filename_from_url(url)))
+}
 
-        if !cli.keep {
-            let _ = std::fs::remove_file(&downloaded_path);
+/// Tells the user a run has finished, for --notify.
+fn announce(how: Notify, label: &str, ok: bool, elapsed: std::time::Duration) {
+    if how != Notify::Desktop {
+        notify::bell();
+    }
+    if how != Notify::Bell {
+        let outcome = if ok { "finished" } else { "failed" };
+        let summary = format!("dex: {label} {outcome}");
+        let body = format!("after {}", stats::format_elapsed(elapsed));
+        if let Err(e) = notify::desktop(&summary, &body) {
+            eprintln!("Warning: could not show a notification: {e}");
+        }
+    }
+}
+
+/// Settings shared by every URL handled in one run.
+struct Context<'a> {
+    cli: &'a GetArgs,
+    /// The one HTTP client every request of the run goes through.
+    http: http::HttpContext,
+    rules: platform::Rules,
+    target: Option<platform::Target>,
+    /// Progress bar group to draw into when several URLs download at once.
+    progress: Option<&'a indicatif::MultiProgress>,
+    /// Totals across every URL, for --stats and --json.
+    stats: Mutex<stats::Stats>,
+    /// Every file --install put in place, for `dex sync` to lock.
+    installed: Mutex<Vec<Installed>>,
+    /// What --report says about each URL, by the URL as given.
+    report: Mutex<HashMap<String, report::Item>>,
+    /// The config's `[mirrors]`, with what probing them found.
+    mirrors: &'a mirror::Mirrors,
+}
+
+/// One file put in place by --install.
+struct Installed {
+    /// Direct download URL of the asset.
+    url: String,
+    tag: Option<String>,
+    /// File name of the downloaded asset.
+    asset: String,
+    sha256: String,
+    /// What users run: the binary, or its --shim.
+    command: std::path::PathBuf,
+}
+
+impl Context<'_> {
+    /// Adds one download's (or extraction's) numbers to the run's totals.
+    fn record(&self, stats: stats::Stats) {
+        *self.stats.lock().unwrap() += stats;
+    }
+
+    /// Updates what --report says about `url`.
+    fn note(&self, url: &str, update: impl FnOnce(&mut report::Item)) {
+        if self.cli.report.is_some() {
+            update(
+                self.report
+                    .lock()
+                    .unwrap()
+                    .entry(url.to_string())
+                    .or_default(),
+            );
+        }
+    }
+}
+
+/// Downloads one URL and does everything the flags ask for with it:
+/// verify, install, or extract.
+fn get(context: &Context, source_url: &str) -> Result<(), Failed> {
+    let cli = context.cli;
+    let given_url = source_url;
+    let rules = &context.rules;
+    let target = context.target.as_ref();
+
+    // A digest in the URL's fragment ("#sha256=...") pins what the download
+    // must hash to, as --sha256 does.
+    let (source_url, pinned) = verify::split_pinned(source_url).map_err(|e| {
+        eprintln!("Error: {e}");
+        Failed::Other
+    })?;
+    let pinned = match (pinned, &cli.sha256) {
+        (Some(fragment), Some(flag)) if fragment != *flag => {
+            eprintln!("Error: the URL's digest ({fragment}) and --sha256 ({flag}) disagree");
+            return Err(Failed::Other);
+        }
+        (pinned, flag) => pinned.or_else(|| flag.clone()),
+    };
+
+    let platform = cli
+        .platform
+        .as_deref()
+        .or(target.map(|t| t.platform.as_str()))
+        .unwrap_or_else(|| platform::normalize_platform(std::env::consts::OS));
+    let arch = cli
+        .arch
+        .as_deref()
+        .or(target.map(|t| t.arch.as_str()))
+        .unwrap_or_else(|| platform::normalize_arch(std::env::consts::ARCH));
+    // Only this machine's own arch gets substitutes; a chosen one is meant.
+    let host = cli.platform.is_none() && cli.arch.is_none() && target.is_none();
+    let libc = cli
+        .libc
+        .as_deref()
+        .or(target.and_then(|t| t.libc.as_deref()))
+        .or(if host { platform::host_libc() } else { None });
+    let fallback_arches = if host && !cli.no_fallback_arch {
+        platform::emulated_arches()
+    } else {
+        Vec::new()
+    };
+    let wanted = Wanted {
+        platform,
+        arches: std::iter::once(arch)
+            .chain(fallback_arches.iter().copied())
+            .collect(),
+    };
+
+    // Vendors with predictable download links are given as URL templates:
+    // fill in the version, platform, and architecture.
+    let expanded;
+    let source_url = if layout::is_url_template(source_url) {
+        let vars = layout::UrlVars {
+            version: cli.tool_version.as_deref(),
+            platform,
+            arch,
+        };
+        expanded = layout::render_url(source_url, &vars).map_err(|e| {
+            eprintln!("Error: {e}");
+            Failed::Other
+        })?;
+        eprintln!("URL: {expanded}");
+        &expanded
+    } else {
+        source_url
+    };
+
+    // SourceForge links only reveal the real file (and its name) after a
+    // redirect chain through their mirror network.
+    let source_url = if sourceforge::is_sourceforge_url(source_url) {
+        &sourceforge::resolve(&context.http, source_url).map_err(|e| {
+            eprintln!("Error resolving SourceForge download: {e}");
+            Failed::Network
+        })?
+    } else {
+        source_url
+    };
+
+    let page_link;
+    let source_url = if cli.page {
+        let selection = (platform, arch, libc);
+        page_link = page::resolve(
+            &context.http,
+            source_url,
+            rules,
+            selection,
+            &fallback_arches,
+        )
+        .map_err(|e| {
+            eprintln!("Error reading download page {source_url}: {e}");
+            Failed::classify(&*e)
+        })?;
+        &page_link
+    } else {
+        source_url
+    };
+
+    // --if-newer asks the server whether a file changed; a cached copy can't answer that.
+    let cache = ((cli.cache || cli.offline) && cli.if_newer.is_none())
+        .then(cache::Cache::open)
+        .flatten();
+
+    // Determine the effective URL — resolve release URLs (GitHub releases,
+    // Bitbucket downloads, Maven and Azure Artifacts packages) to direct
+    // asset URLs. Releases may also publish a
+    // checksum file we can verify against.
+    let provider = provider::find(source_url);
+    let resolve_error = |e: Box<dyn std::error::Error>| {
+        let name = provider.map_or("release", |p| p.name());
+        eprintln!("Error resolving {name}: {e}");
+        Failed::classify(&*e)
+    };
+    // Offline, releases are listed from the cache; online, --cache keeps
+    // each listing for later.
+    let cached_release = || {
+        cache
+            .as_ref()
+            .and_then(|c| c.release(source_url))
+            .ok_or_else(|| {
+                resolve_error(Box::new(http::Offline {
+                    url: source_url.to_string(),
+                }))
+            })
+    };
+    let keep_release = |release: &provider::Release| {
+        if let Some(cache) = cache.as_ref().filter(|_| cli.cache)
+            && let Err(e) = cache.insert_release(source_url, release)
+        {
+            eprintln!("Warning: could not cache the release listing: {e}");
+        }
+    };
+
+    let resolved = if let Some(provider) = provider {
+        let fetch_release = || {
+            if cli.offline {
+                return cached_release();
+            }
+            let release = provider
+                .fetch_release(&context.http, source_url, cli.latest_strategy)
+                .map_err(resolve_error)?;
+            keep_release(&release);
+            Ok(release)
+        };
+        if cli.explain {
+            let release = fetch_release()?;
+            explain_selection(rules, &release, platform, arch, &fallback_arches, libc);
+            return Ok(());
+        }
+
+        // Mirroring modes bypass single-best selection entirely.
+        if cli.all || platform == "all" || arch == "all" {
+            let release = fetch_release()?;
+            let assets: Vec<&platform::Asset> = if cli.all {
+                release.assets.iter().collect()
+            } else {
+                rules.select_matching_assets(
+                    &release.assets,
+                    Some(platform).filter(|p| *p != "all"),
+                    Some(arch).filter(|a| *a != "all"),
+                )
+            };
+            return download_all(context, &release, &assets, provider.local_assets());
+        }
+
+        let selector = provider::Selector {
+            rules,
+            platform,
+            arch,
+            fallback_arches: &fallback_arches,
+            libc,
+            asset_name: cli.asset_name.as_deref(),
+            latest: cli.latest_strategy,
+            source_fallback: cli.source_fallback || cli.build_fallback,
+        };
+        let resolved = if cli.offline {
+            selector.select(cached_release()?)
+        } else {
+            provider.resolve(&context.http, source_url, &selector)
+        };
+        let resolved = resolved.map_err(resolve_error)?;
+        keep_release(&resolved.release);
+        if cli.notes {
+            show_notes(&resolved.release);
         }
+        if cli.build_fallback && resolved.is_source() {
+            return build_from_source(context, &resolved.release, (platform, arch));
+        }
+        Some(resolved)
+    } else {
+        if cli.all {
+            eprintln!(
+                "Error: --all is only supported for release URLs (GitHub, Bitbucket, Maven, Azure Artifacts)"
+            );
+            return Err(Failed::Other);
+        }
+        None
+    };
+
+    // A sum file given with --checksum-url must list the download; it takes
+    // the place of any the release publishes.
+    let out_of_band = match &cli.checksum_url {
+        Some(sum_url) if pinned.is_none() => {
+            let file_name = match &resolved {
+                Some(resolved) => resolved.asset.name.clone(),
+                None => download::filename_from_url(source_url),
+            };
+            let sum_name = download::filename_from_url(sum_url);
+            let digest = verify::fetch_sum_file(&context.http, sum_url, &sum_name, &file_name)
+                .map_err(|e| {
+                    eprintln!("Error fetching checksum file {sum_url}: {e}");
+                    Failed::classify(&*e)
+                })?;
+            let Some(digest) = digest else {
+                eprintln!("Error: {sum_url} does not list {file_name}");
+                return Err(Failed::Verification);
+            };
+            Some(digest)
+        }
+        _ => None,
+    };
+
+    let (url, expected_digest) = match &resolved {
+        Some(resolved) => {
+            let is_pinned = pinned.is_some();
+            let expected_digest = match &resolved.checksum {
+                // A pinned digest is all the checking needed.
+                _ if pinned.is_some() => pinned,
+                _ if out_of_band.is_some() => out_of_band,
+                // Only verified downloads are cached, and a cached copy is
+                // checked against its own digest, so offline there's no need
+                // for the checksum file.
+                Some(sum_asset) if !cli.no_verify && !cli.offline => {
+                    let digest = verify::fetch_expected_digest(
+                        &context.http,
+                        sum_asset,
+                        &resolved.asset.name,
+                    )
+                    .map_err(|e| {
+                        eprintln!("Error fetching checksum file {}: {e}", sum_asset.name);
+                        Failed::classify(&*e)
+                    })?;
+                    if digest.is_none() {
+                        eprintln!(
+                            "Warning: {} does not list {}, skipping verification",
+                            sum_asset.name, resolved.asset.name
+                        );
+                    }
+                    digest
+                }
+                _ => None,
+            };
 
-        println!("Extracted to {}", cli.output.display());
+            // GitHub publishes each asset's digest too: it's used when there's
+            // no checksum file, and cross-checked against the file when there is.
+            let published = resolved
+                .asset
+                .digest
+                .as_deref()
+                .and_then(verify::parse_labeled)
+                .filter(|_| !cli.no_verify);
+            let expected_digest = match (expected_digest, published) {
+                (Some(listed), Some(published))
+                    if !is_pinned
+                        && listed.algorithm == published.algorithm
+                        && listed != published =>
+                {
+                    eprintln!(
+                        "Error: the checksum file and the release disagree on {} ({} vs {})",
+                        resolved.asset.name, listed.hex, published.hex
+                    );
+                    return Err(Failed::Verification);
+                }
+                (None, published) => published,
+                (listed, _) => listed,
+            };
+
+            (resolved.asset.url.clone(), expected_digest)
+        }
+        None => (source_url.to_string(), pinned.or(out_of_band)),
+    };
+
+    context.note(given_url, |item| {
+        item.platform = Some(platform.to_string());
+        item.arch = Some(arch.to_string());
+        item.libc = libc.map(str::to_string);
+        if let Some(resolved) = &resolved {
+            let release = &resolved.release;
+            item.repo = Some(format!("{}/{}", release.owner, release.repo));
+            item.tag = Some(release.tag.clone());
+            item.asset = Some(resolved.asset.name.clone());
+        }
+        item.download_url = Some(url.clone());
+    });
+
+    // With --layout versioned, everything for this download lives in <output>/<tool>/<version>.
+    let versioned = if cli.layout == Layout::Versioned {
+        let file_name = download::filename_from_url(&url);
+        let (tool, version) = match &resolved {
+            Some(r) => (r.release.repo.clone(), Some(r.release.tag.clone())),
+            None => (
+                install::tool_name(&file_name),
+                layout::guess_version(&file_name),
+            ),
+        };
+        let Some(version) = version else {
+            eprintln!("Error: can't tell the version of {file_name} for --layout versioned");
+            return Err(Failed::Other);
+        };
+        Some((tool, version))
     } else {
-        println!("Saved to {}", downloaded_path.display());
+        None
+    };
+    // --install-to, --oci-layer, and --repack download and extract into a
+    // scratch directory, and copy or pack from there.
+    let scratch = cli.install_to.is_some() || cli.oci_layer.is_some() || cli.repack.is_some();
+    let output = match &versioned {
+        Some((tool, version)) => layout::version_dir(&cli.output, tool, version),
+        None if scratch => std::env::temp_dir().join(format!(
+            "dex-{}-{}-{}",
+            match (&cli.install_to, &cli.oci_layer) {
+                (Some(_), _) => "install-to",
+                (None, Some(_)) => "oci-layer",
+                (None, None) => "repack",
+            },
+            std::process::id(),
+            download::filename_from_url(&url)
+        )),
+        None => cli.output.clone(),
+    };
+
+    let check_attestation =
+        cli.verify_attestation || cli.require_attestation || cli.attestation_key.is_some();
+
+    // Hash with the sum file's algorithm, plus SHA-256 if attestations,
+    // the cache, or a `dex sync` lock need it.
+    let mut algorithms: Vec<verify::Algorithm> =
+        expected_digest.iter().map(|d| d.algorithm).collect();
+    if (check_attestation
+        || cli.cache
+        || cli.install
+        || cli.report.is_some()
+        || cli.digest_log.is_some())
+        && !algorithms.contains(&verify::Algorithm::Sha256)
+    {
+        algorithms.push(verify::Algorithm::Sha256);
+    }
+
+    // --filename-template variables: the release's, and the platform and
+    // arch the asset's name mentions (or the ones it was picked for).
+    let rename = |name: &str| {
+        let template = cli.filename_template.as_deref().unwrap_or("{name}");
+        let (named_platform, named_arch) = rules.identify(name);
+        let vars = layout::NameVars {
+            repo: resolved.as_ref().map(|r| r.release.repo.as_str()),
+            tag: resolved.as_ref().map(|r| r.release.tag.as_str()),
+            platform: named_platform.or(Some(platform)),
+            arch: named_arch.or(Some(arch)),
+        };
+        layout::render_name(template, name, &vars)
+    };
+
+    let options = download::DownloadOptions {
+        algorithms: &algorithms,
+        progress: context.progress,
+        verbose: cli.verbose,
+        if_newer: cli.if_newer.as_deref(),
+        rename: cli.filename_template.is_some().then_some(&rename),
+        max_size: cli.max_size,
+        chunk_size: Some(cli.chunk_size),
+        local_files: provider.is_some_and(|p| p.local_assets()),
+    };
+    let cached = match &cache {
+        Some(cache) => cache.restore(&url, &output, &options).map_err(|e| {
+            eprintln!("Error reading the cache: {e}");
+            Failed::Other
+        })?,
+        None => None,
+    };
+    let from_cache = cached.is_some();
+
+    // A re-run can find the file it downloaded last time still there.
+    let existing = if from_cache || cli.force || cli.if_newer.is_some() {
+        None
+    } else {
+        let name = match &resolved {
+            Some(resolved) => resolved.asset.name.clone(),
+            None => download::filename_from_url(&url),
+        };
+        let known_size = resolved.as_ref().and_then(|r| r.asset.size);
+        download::find_existing(
+            &context.http,
+            &url,
+            &name,
+            &output,
+            expected_digest.as_ref(),
+            known_size,
+            &options,
+        )
+        .unwrap_or_else(|e| {
+            eprintln!("Warning: could not check for an existing {name}: {e}");
+            None
+        })
+    };
+
+    if !from_cache
+        && existing.is_none()
+        && let Some(asset) = resolved.as_ref().map(|r| &r.asset)
+        && let Some(size) = asset.size
+        && !confirm_size(context, &asset.name, size)
+    {
+        eprintln!("{}", tr!("not-downloading", name = asset.name));
+        return Err(Failed::Other);
+    }
+
+    let download = match (cached, existing) {
+        (Some(download), _) => {
+            eprintln!("Using cached {}", download.name);
+            context.record(stats::Stats {
+                cache_hits: 1,
+                ..Default::default()
+            });
+            download
+        }
+        (None, Some(download)) => download,
+        (None, None) => {
+            let seed = cache
+                .as_ref()
+                .filter(|_| cli.delta && !cli.offline)
+                .and_then(|cache| cache.seed_for(&url));
+            let delta = match seed
+                .map(|seed| delta::download(&context.http, &url, &seed, &output, &options))
+            {
+                // Too big is too big however it's downloaded.
+                Some(Err(e)) if e.is::<download::TooLarge>() => Err(e),
+                Some(Err(e)) => {
+                    eprintln!("Warning: delta download failed ({e}); downloading the whole file");
+                    Ok(None)
+                }
+                Some(Ok(found)) => Ok(found),
+                None => Ok(None),
+            };
+            let download = match delta {
+                Ok(Some(download)) => Ok(download),
+                Ok(None) => download_from_mirrors(context, &url, &output, &options),
+                Err(e) => Err(e),
+            };
+            let download = download.map_err(|e| {
+                if e.is::<download::NotModified>() {
+                    let local = cli.if_newer.as_deref().unwrap_or(&output);
+                    eprintln!("Not modified: {} is up to date", local.display());
+                    return Failed::Unchanged;
+                }
+                eprintln!("Error downloading: {e}");
+                // Failing to write the file, or refusing it, is a local
+                // problem; anything else is the network's.
+                if e.is::<std::io::Error>() || e.is::<download::TooLarge>() {
+                    Failed::Other
+                } else {
+                    Failed::Network
+                }
+            })?;
+            context.record(stats::Stats {
+                downloads: 1,
+                bytes: download.bytes,
+                download_time: download.elapsed,
+                ..Default::default()
+            });
+            download
+        }
+    };
+    let downloaded_path = download.path.clone();
+    context.note(given_url, |item| {
+        item.bytes = Some(download.bytes);
+        item.content_encoding = download.content_encoding.clone();
+        item.sha256 = download
+            .digest(verify::Algorithm::Sha256)
+            .map(|d| d.hex.clone());
+        item.from_cache = from_cache;
+    });
+
+    // Refuse to go any further with bytes that don't match the published checksum.
+    if let Some(expected) = &expected_digest
+        && let Some(actual) = download.digest(expected.algorithm)
+    {
+        if let Err(e) = verify::check(expected, actual) {
+            let _ = std::fs::remove_file(&downloaded_path);
+            eprintln!("Error verifying {}: {e}", downloaded_path.display());
+            return Err(Failed::Verification);
+        }
+        eprintln!("Verified {} checksum", expected.algorithm.name());
+        context.note(given_url, |item| {
+            item.verified = Some(expected.algorithm.name().to_string())
+        });
+    }
+
+    if let Some(log) = &cli.digest_log
+        && !cli.offline
+    {
+        let sha256 = download
+            .digest(verify::Algorithm::Sha256)
+            .expect("sha256 is always computed for the digest log");
+        let key = digestlog::key(resolved.as_ref(), &url);
+        let log = digestlog::DigestLog { url: log };
+        match log.observe(&context.http, &key, &sha256.hex) {
+            Ok(digestlog::Observation::Recorded) => {
+                eprintln!("Recorded {key} in the digest log")
+            }
+            Ok(digestlog::Observation::Matches) => eprintln!("Matches the digest log"),
+            Ok(digestlog::Observation::Differs(recorded)) => eprintln!(
+                "Warning: {key} has changed since it was first downloaded: the digest log \
+                 has sha256 {recorded}, this download is {}",
+                sha256.hex
+            ),
+            Err(e) => eprintln!("Warning: could not check the digest log: {e}"),
+        }
+    }
+
+    // Only cache what passed verification, so a bad download isn't reused.
+    if let Some(cache) = cache.as_ref().filter(|_| cli.cache)
+        && !from_cache
+        && let Err(e) = cache.insert(&url, &download)
+    {
+        eprintln!("Warning: could not cache {}: {e}", download.name);
+    }
+    // The ETag is saved only once nothing can reject the download any more
+    // (attestations, --check-arch, installing or extracting it), so a failed
+    // run doesn't make the next --if-newer skip a version it never kept.
+    let remember_etag = || {
+        if let Some(local) = cli.if_newer.as_deref()
+            && let Some(etag) = &download.etag
+            && let Err(e) = download::remember_etag(local, etag)
+        {
+            eprintln!(
+                "Warning: could not save the ETag for {}: {e}",
+                local.display()
+            );
+        }
+    };
+
+    if check_attestation {
+        let sha256 = download
+            .digest(verify::Algorithm::Sha256)
+            .expect("sha256 is always computed when checking attestations");
+        match check_attestations(context, source_url, resolved.as_ref(), &sha256.hex) {
+            Ok(attest::Verdict::Signed) => eprintln!("Verified attestation signature"),
+            Ok(attest::Verdict::DigestOnly) => eprintln!(
+                "Attestation covers this file (signature not checked; pass --attestation-key to verify it)"
+            ),
+            Err(e) if cli.require_attestation => {
+                let _ = std::fs::remove_file(&downloaded_path);
+                eprintln!("Error verifying attestation: {e}");
+                return Err(Failed::Verification);
+            }
+            Err(e) => eprintln!("Warning: attestation check failed: {e}"),
+        }
+    }
+
+    if let Some(mode) = cli.sbom {
+        match &resolved {
+            Some(resolved) => handle_sbom(&context.http, mode, resolved, &output),
+            None => eprintln!("Warning: --sbom is only supported for GitHub releases"),
+        }
+    }
+
+    // Standalone binaries are ready to run as soon as they land.
+    let file_name = downloaded_path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    // The name first, then the server's Content-Type, then the first bytes.
+    let format = extract::find_extractor_for(&downloaded_path, download.content_type.as_deref());
+    let is_binary =
+        format.is_none() && platform::asset_kind(&file_name) == platform::AssetKind::Binary;
+    if is_binary && let Err(e) = install::set_executable(&downloaded_path) {
+        eprintln!(
+            "Warning: could not make {} executable: {e}",
+            downloaded_path.display()
+        );
+    }
+
+    if cli.install {
+        let project = resolved.as_ref().map(|r| r.release.repo.as_str());
+        let tag = resolved.as_ref().map(|r| r.release.tag.as_str());
+        let installed = install_download(
+            cli,
+            &downloaded_path,
+            &file_name,
+            format,
+            project,
+            tag,
+            &wanted,
+        )
+        .map_err(|e| {
+            eprintln!("Error installing: {e}");
+            match e.is::<binaries::ArchMismatch>() {
+                true => Failed::Verification,
+                false => Failed::Other,
+            }
+        })?;
+        if cli.fsync {
+            let mut placed = vec![installed.command.clone(), installed.binary.clone()];
+            placed.extend(installed.extras.iter().cloned());
+            let bin_dir = installed.command.parent().unwrap_or(&cli.output);
+            sync_to_disk(&placed, bin_dir)?;
+        }
+        say!("Installed to {}", installed.command.display());
+        let command = installed.command.clone();
+        let sha256 = download
+            .digest(verify::Algorithm::Sha256)
+            .expect("sha256 is always computed for --install")
+            .hex
+            .clone();
+        let repo = resolved
+            .as_ref()
+            .map(|r| format!("{}/{}", r.release.owner, r.release.repo));
+        record_install(cli, installed, &url, repo, tag, &sha256);
+        context.installed.lock().unwrap().push(Installed {
+            url: url.clone(),
+            tag: tag.map(str::to_string),
+            asset: download.name.clone(),
+            sha256,
+            command,
+        });
+        remember_etag();
+        return Ok(());
+    }
+
+    let unpack_installer = cli.extract && extract::is_installer(&downloaded_path);
+    let created = if unpack_installer || (!cli.no_extract && format.is_some()) {
+        let depth = if cli.recursive { cli.max_depth } else { 1 };
+        let options = extract::ExtractOptions {
+            conflict: conflict_policy(cli),
+            entry_encoding: cli.entry_encoding.unwrap_or_default(),
+            preserve_mtime: !cli.no_preserve_mtime,
+            same_owner: cli.same_owner,
+            owner: cli.owner,
+            group: cli.group,
+            name: cli.name.clone(),
+            binary_name: cli.rename_binary.then(|| match &resolved {
+                Some(resolved) => resolved.release.repo.clone(),
+                None => install::tool_name(&file_name),
+            }),
+            on_entry: cli
+                .verbose
+                .then(|| -> extract::OnEntry { std::sync::Arc::new(list_entry) }),
+            threads: cli.threads.map_or(0, usize::from),
+            deadline: cli
+                .extract_timeout
+                .map(|secs| std::time::Instant::now() + std::time::Duration::from_secs(secs)),
+            deterministic: cli.deterministic,
+        };
+        if cli.verbose
+            && !unpack_installer
+            && let Some(format) = format
+        {
+            eprintln!("Format: {}", format.name());
+        }
+        let extract_started = std::time::Instant::now();
+        let extracted = match format {
+            Some(format) if !unpack_installer => extract::extract_nested(
+                &downloaded_path,
+                format,
+                &output,
+                depth,
+                cli.keep,
+                &options,
+            ),
+            _ => extract::extract_installer(&downloaded_path, &output, &options),
+        };
+        let files = extracted.map_err(|e| {
+            if extract::is_timeout(&*e) {
+                eprintln!(
+                    "Error extracting: gave up after --extract-timeout {}s",
+                    cli.extract_timeout.unwrap_or_default()
+                );
+                return Failed::Extraction;
+            }
+            eprintln!("Error extracting: {e}");
+            let exists = e
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(|e| e.kind() == std::io::ErrorKind::AlreadyExists);
+            if exists {
+                eprintln!(
+                    "Pass --overwrite, --skip-existing, or --backup to extract over existing files"
+                );
+            }
+            Failed::Extraction
+        })?;
+        context.record(stats::Stats {
+            extract_time: extract_started.elapsed(),
+            ..Default::default()
+        });
+        let files = if cli.binaries_only {
+            let (kept, removed) = binaries::keep_executables(&output, files).map_err(|e| {
+                eprintln!("Error removing non-executables: {e}");
+                Failed::Extraction
+            })?;
+            eprintln!(
+                "Kept {} executables, removed {removed} other files",
+                kept.iter().filter(|f| !f.is_dir()).count()
+            );
+            kept
+        } else {
+            files
+        };
+
+        if !cli.keep {
+            let _ = std::fs::remove_file(&downloaded_path);
+        }
+
+        if !scratch {
+            say!("Extracted to {}", output.display());
+        }
+        files
+    } else {
+        if !scratch {
+            say!("Saved to {}", downloaded_path.display());
+        }
+        vec![downloaded_path.clone()]
+    };
+    if cli.check_arch || cli.strict_arch {
+        let tool = match &resolved {
+            Some(resolved) => resolved.release.repo.clone(),
+            None => install::tool_name(&file_name),
+        };
+        // The binary the download is for: the best-ranked native one.
+        let main = binaries::rank(&output, created.clone(), &tool)
+            .into_iter()
+            .find(|c| {
+                matches!(
+                    c.format,
+                    binaries::Format::Elf | binaries::Format::MachO | binaries::Format::Pe
+                )
+            });
+        if let Some(main) = main
+            && let Err(e) = check_arch(cli, &main.path, &wanted)
+        {
+            eprintln!("Error: {e}");
+            if scratch {
+                let _ = std::fs::remove_dir_all(&output);
+            }
+            return Err(Failed::Verification);
+        }
+    }
+    if let Some(target) = &cli.install_to {
+        let name = match &resolved {
+            Some(resolved) => resolved.release.repo.clone(),
+            None => install::tool_name(&file_name),
+        };
+        let files = deploy::relative_files(&output, &created);
+        let installed = deploy::install(&name, &url, &files, target);
+        let _ = std::fs::remove_dir_all(&output);
+        let outcome = installed.map_err(|e| {
+            eprintln!("Error installing to {}: {e}", target.display());
+            Failed::Other
+        })?;
+        for backup in &outcome.backups {
+            eprintln!("Moved aside {}", target.join(backup).display());
+        }
+        for kept in &outcome.kept {
+            eprintln!(
+                "Left {}: it was edited after being installed",
+                target.join(kept).display()
+            );
+        }
+        if cli.fsync {
+            let placed: Vec<PathBuf> = files.iter().map(|(_, r)| target.join(r)).collect();
+            sync_to_disk(&placed, target)?;
+        }
+        say!("Installed {} files to {}", outcome.files, target.display());
+        remember_etag();
+        return Ok(());
+    }
+    if let Some(layer_path) = &cli.oci_layer {
+        let files = deploy::relative_files(&output, &created);
+        let written = oci::write_layer(&files, layer_path).and_then(|layer| {
+            if !cli.oci_manifest {
+                return Ok((layer, None));
+            }
+            // The binary a container of just this layer runs, if one stands out.
+            let tool = match &resolved {
+                Some(resolved) => resolved.release.repo.clone(),
+                None => install::tool_name(&file_name),
+            };
+            let candidates = binaries::find_candidates(&output, &tool)?;
+            let entrypoint = match candidates.as_slice() {
+                [best] => Some(best),
+                [best, next, ..] if best.score > next.score => Some(best),
+                _ => None,
+            }
+            .and_then(|best| files.iter().find(|(source, _)| *source == best.path))
+            .map(|(_, relative)| format!("/{}", relative.to_string_lossy().replace('\\', "/")));
+            let (named_platform, named_arch) = rules.identify(&file_name);
+            let written = oci::write_manifest(
+                layer_path,
+                &layer,
+                named_platform.unwrap_or(platform),
+                named_arch.unwrap_or(arch),
+                entrypoint.as_deref(),
+            )?;
+            Ok((layer, Some(written)))
+        });
+        let _ = std::fs::remove_dir_all(&output);
+        let (layer, manifest) = written.map_err(|e| {
+            eprintln!("Error writing {}: {e}", layer_path.display());
+            Failed::Other
+        })?;
+        for skipped in &layer.skipped {
+            eprintln!(
+                "Left out {}: OCI layers read names starting with .wh. as deletions",
+                skipped.display()
+            );
+        }
+        if let Some((config, manifest)) = manifest {
+            eprintln!("Wrote {} and {}", config.display(), manifest.display());
+        }
+        if cli.fsync {
+            sync_to_disk(std::slice::from_ref(layer_path), &cli.output)?;
+        }
+        say!(
+            "Wrote OCI layer {} ({})",
+            layer_path.display(),
+            layer.digest
+        );
+        remember_etag();
+        return Ok(());
+    }
+    if let Some(format) = cli.repack {
+        let name = platform::strip_archive_ext(&file_name);
+        let archive = cli.output.join(format!("{name}.{}", format.extension()));
+        let packed = std::fs::create_dir_all(&cli.output)
+            .map_err(Into::into)
+            .and_then(|()| repack::repack(&output, format, &archive));
+        let _ = std::fs::remove_dir_all(&output);
+        let entries = packed.map_err(|e| {
+            eprintln!("Error repacking into {}: {e}", archive.display());
+            Failed::Other
+        })?;
+        if cli.fsync {
+            sync_to_disk(std::slice::from_ref(&archive), &cli.output)?;
+        }
+        say!("Repacked {entries} entries into {}", archive.display());
+        remember_etag();
+        return Ok(());
+    }
+    if cli.fsync {
+        let mut written = created.clone();
+        if cli.keep && !written.contains(&downloaded_path) {
+            written.push(downloaded_path.clone());
+        }
+        sync_to_disk(&written, &output)?;
+    }
+
+    if cli.manifest {
+        let written = manifest::build(&url, &output, &created)
+            .map_err(|e| e.into())
+            .and_then(|m| manifest::write(&m, &output));
+        match written {
+            Ok(path) => eprintln!("Wrote manifest {}", path.display()),
+            Err(e) => eprintln!("Warning: could not write manifest: {e}"),
+        }
+    }
+
+    if let Some((tool, version)) = &versioned {
+        layout::activate(&cli.output, tool, version).map_err(|e| {
+            eprintln!("Error updating {tool}/current: {e}");
+            Failed::Other
+        })?;
+        eprintln!("Activated {tool} {version}");
+    }
+
+    remember_etag();
+    Ok(())
+}
+
+/// Flushes files dex wrote to disk for --fsync (see `durable::sync`).
+fn sync_to_disk(files: &[PathBuf], root: &std::path::Path) -> Result<(), Failed> {
+    durable::sync(files, root).map_err(|e| {
+        eprintln!("Error flushing files to disk: {e}");
+        Failed::Other
+    })
+}
+
+/// Downloads `url`, or the same file from one of its `[mirrors]`, trying
+/// each in the order --mirror-strategy picks until one succeeds. Failures
+/// that no other mirror would fix end the attempt.
+fn download_from_mirrors(
+    context: &Context,
+    url: &str,
+    output: &std::path::Path,
+    options: &download::DownloadOptions,
+) -> Result<download::Download, Box<dyn std::error::Error>> {
+    let cli = context.cli;
+    let urls = context
+        .mirrors
+        .order(&context.http, url, cli.mirror_strategy, cli.verbose);
+    let mut urls = urls.iter().peekable();
+    loop {
+        let mirror = urls.next().expect("a URL is always its own candidate");
+        if mirror != url {
+            eprintln!("Downloading from mirror {mirror}");
+        }
+        match download::download_file(&context.http, mirror, output, options) {
+            Err(e)
+                if urls.peek().is_some()
+                    && !e.is::<std::io::Error>()
+                    && !e.is::<download::TooLarge>()
+                    && !e.is::<download::NotModified>() =>
+            {
+                eprintln!("Warning: {mirror} failed ({e}); trying the next mirror");
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Resolves `url` the way a download would — SourceForge mirrors, release
+/// asset selection — and prints what the server reports about the file.
+fn probe(
+    http: &http::HttpContext,
+    url: &str,
+    rules: &platform::Rules,
+    (platform, arch, libc): (Option<&str>, Option<&str>, Option<&str>),
+    asset_name: Option<&str>,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let url = if sourceforge::is_sourceforge_url(url) {
+        sourceforge::resolve(http, url)?
+    } else {
+        url.to_string()
+    };
+
+    // The machine's own platform may settle for an emulated arch, and
+    // prefers builds for its libc.
+    let host = platform.is_none() && arch.is_none();
+    let fallback_arches = if host {
+        platform::emulated_arches()
+    } else {
+        Vec::new()
+    };
+    let libc = libc.or(if host { platform::host_libc() } else { None });
+    let platform = platform.unwrap_or_else(|| platform::normalize_platform(std::env::consts::OS));
+    let arch = arch.unwrap_or_else(|| platform::normalize_arch(std::env::consts::ARCH));
+    let selector = provider::Selector {
+        rules,
+        platform,
+        arch,
+        fallback_arches: &fallback_arches,
+        libc,
+        asset_name,
+        latest: Default::default(),
+        source_fallback: false,
+    };
+    let resolved = match provider::find(&url) {
+        Some(provider) => Some(provider.resolve(http, &url, &selector)?),
+        None => None,
+    };
+    let asset = resolved.map(|r| r.asset);
+    let asset_url = asset.as_ref().map_or(&url, |a| &a.url);
+
+    let probe = download::probe(http, asset_url)?;
+
+    if json {
+        let mut report = serde_json::to_value(&probe)?;
+        report["url"] = asset_url.as_str().into();
+        report["asset"] = asset.map(|a| a.name).into();
+        println!("{report:#}");
+        return Ok(());
+    }
+
+    let or_unknown = |value: Option<String>| value.unwrap_or_else(|| "unknown".to_string());
+    if let Some(asset) = &asset {
+        println!("Asset:          {}", asset.name);
+        if let Some(updated) = &asset.updated_at {
+            println!("Uploaded:       {updated}");
+        }
+    }
+    println!("URL:            {asset_url}");
+    if probe.final_url != *asset_url {
+        println!("Final URL:      {}", probe.final_url);
+    }
+    let size = probe
+        .size
+        .map(|n| format!("{} ({n} bytes)", indicatif::HumanBytes(n)));
+    println!("Size:           {}", or_unknown(size));
+    println!("Content type:   {}", or_unknown(probe.content_type));
+    println!("Last modified:  {}", or_unknown(probe.last_modified));
+    if let Some(etag) = probe.etag {
+        println!("ETag:           {etag}");
+    }
+    println!(
+        "Ranges:         {}",
+        if probe.ranges { "yes" } else { "no" }
+    );
+    Ok(())
+}
+
+/// Prints every asset of the release `url` points at, starring the one a
+/// download would pick for the platform.
+fn list_assets(
+    http: &http::HttpContext,
+    url: &str,
+    rules: &platform::Rules,
+    (platform, arch, libc): (Option<&str>, Option<&str>, Option<&str>),
+) -> Result<(), Box<dyn std::error::Error>> {
+    let provider = provider::find(url).ok_or(
+        "not a release URL (GitHub releases, Bitbucket downloads, Maven, Azure Artifacts)",
+    )?;
+    let release = provider.fetch_release(http, url, Default::default())?;
+
+    // The machine's own platform may settle for an emulated arch, and
+    // prefers builds for its libc.
+    let host = platform.is_none() && arch.is_none();
+    let fallback_arches = if host {
+        platform::emulated_arches()
+    } else {
+        Vec::new()
+    };
+    let libc = libc.or(if host { platform::host_libc() } else { None });
+    let platform = platform.unwrap_or_else(|| platform::normalize_platform(std::env::consts::OS));
+    let arch = arch.unwrap_or_else(|| platform::normalize_arch(std::env::consts::ARCH));
+    let best = rules
+        .select_best_asset_or_fallback(&release.assets, platform, arch, &fallback_arches, libc)
+        .map(|(a, _)| a.name.clone());
+
+    println!("{} {}", release.repo, release.tag);
+    for asset in &release.assets {
+        let marker = if best.as_ref() == Some(&asset.name) {
+            "*"
+        } else {
+            " "
+        };
+        match asset.size {
+            Some(size) => println!("{marker} {} ({})", asset.name, indicatif::HumanBytes(size)),
+            None => println!("{marker} {}", asset.name),
+        }
+    }
+    Ok(())
+}
+
+/// Shows a release's notes, or says it has none.
+fn show_notes(release: &provider::Release) {
+    let title = format!("{} {}", release.repo, release.tag);
+    match &release.notes {
+        Some(notes) => notes::show(&title, notes),
+        None => eprintln!("{title} has no release notes"),
+    }
+}
+
+/// Records an install for `dex which` and `dex info`; failing to is only a warning.
+fn record_install(
+    cli: &GetArgs,
+    placed: Placed,
+    url: &str,
+    repo: Option<String>,
+    tag: Option<&str>,
+    sha256: &str,
+) {
+    let tool = placed.tool();
+    let install = receipt::Install {
+        version: placed.version,
+        url: url.to_string(),
+        repo,
+        tag: tag.map(str::to_string),
+        sha256: sha256.to_string(),
+        installed_at: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        binary: placed.binary,
+        files: placed.extras,
+    };
+    if let Err(e) = receipt::record(&tool, &placed.command, cli.shim, install) {
+        eprintln!("Warning: could not record the install of {tool}: {e}");
+    }
+}
+
+/// The platform and arches a download was picked for, which --check-arch
+/// holds its binary to: the arch asked for, and those the machine emulates
+/// when dex may fall back to them.
+struct Wanted<'a> {
+    platform: &'a str,
+    arches: Vec<&'a str>,
+}
+
+impl Wanted<'static> {
+    /// This machine's own platform and arches.
+    fn host() -> Self {
+        Wanted {
+            platform: platform::normalize_platform(std::env::consts::OS),
+            arches: std::iter::once(platform::normalize_arch(std::env::consts::ARCH))
+                .chain(platform::emulated_arches())
+                .collect(),
+        }
+    }
+}
+
+/// --check-arch: reads `binary`'s header and compares it with what was
+/// wanted. A mismatch is a warning, or with --strict-arch an error. Binaries
+/// dex can't read, and `--platform all` or `--arch all`, pass.
+fn check_arch(
+    cli: &GetArgs,
+    binary: &std::path::Path,
+    wanted: &Wanted,
+) -> Result<(), binaries::ArchMismatch> {
+    if !(cli.check_arch || cli.strict_arch)
+        || wanted.platform == "all"
+        || wanted.arches.contains(&"all")
+    {
+        return Ok(());
+    }
+    let Some(machine) = binaries::machine(binary) else {
+        return Ok(());
+    };
+    let arches: Vec<&str> = wanted
+        .arches
+        .iter()
+        .map(|a| platform::canonical_arch(a))
+        .collect();
+    let platform = platform::canonical_platform(wanted.platform);
+    if machine.fits(platform, &arches) {
+        return Ok(());
+    }
+    let message = format!(
+        "{} is {}, but {platform} {} was asked for; the asset may be mislabeled",
+        binary.file_name().unwrap_or_default().to_string_lossy(),
+        machine.describe(),
+        arches[0]
+    );
+    if cli.strict_arch {
+        return Err(binaries::ArchMismatch(message));
+    }
+    eprintln!("Warning: {message}");
+    Ok(())
+}
+
+/// Where --install put a download's files.
+struct Placed {
+    /// What users run: the binary, or its --shim.
+    command: PathBuf,
+    /// The binary itself.
+    binary: PathBuf,
+    /// Man pages and completion scripts installed with it.
+    extras: Vec<PathBuf>,
+    version: Option<String>,
+}
+
+impl Placed {
+    /// The tool's name: its binary's, without `.exe`.
+    fn tool(&self) -> String {
+        let name = self
+            .binary
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        name.strip_suffix(".exe").unwrap_or(&name).to_string()
+    }
+}
+
+/// Moves an installed binary into place: straight into `bin_dir`, or for
+/// --shim into the store as `<tool>/<version>/<name>`, activated, with a shim
+/// in `bin_dir` running whatever version is active.
+fn place_binary(
+    cli: &GetArgs,
+    src: &std::path::Path,
+    bin_dir: &std::path::Path,
+    name: &str,
+    version: Option<&str>,
+) -> Result<(PathBuf, PathBuf), Box<dyn std::error::Error>> {
+    if !cli.shim {
+        let installed = install::install_binary(src, bin_dir, name)?;
+        return Ok((installed.clone(), installed));
+    }
+    let version = version.ok_or_else(|| format!("can't tell the version of {name} for --shim"))?;
+    let store = install::store_dir().ok_or("could not determine dex's data directory")?;
+    let tool = name.strip_suffix(".exe").unwrap_or(name);
+    let binary = install::install_binary(src, &layout::version_dir(&store, tool, version), name)?;
+    layout::activate(&store, tool, version)?;
+    let target = layout::current_dir(&store, tool).join(name);
+    Ok((install::write_shim(bin_dir, name, &target)?, binary))
+}
+
+/// Runs `dex doctor`'s checks and prints what they found, failing if any did.
+fn doctor(config_path: Option<&std::path::Path>) -> Result<(), Box<dyn std::error::Error>> {
+    let config = load_config(config_path);
+    let http = http::HttpContext::new(&http::HttpOptions {
+        hosts: config.hosts.clone(),
+        ..Default::default()
+    })?;
+    let checks = doctor::run(&http, &config.hosts);
+    for check in &checks {
+        let mark = match check.status {
+            doctor::Status::Ok => "ok",
+            doctor::Status::Warning => "warn",
+            doctor::Status::Failed => "FAIL",
+        };
+        say!("[{mark:>4}] {}: {}", check.name, check.detail);
+        if let Some(fix) = &check.fix {
+            say!("       → {fix}");
+        }
+    }
+    let failed = checks
+        .iter()
+        .filter(|c| c.status == doctor::Status::Failed)
+        .count();
+    if failed > 0 {
+        return Err(format!("{failed} check(s) failed").into());
+    }
+    Ok(())
+}
+
+/// Removes a bundle --install-to copied, for `dex uninstall`.
+fn uninstall(name: &str, from: Option<&std::path::Path>) -> Result<(), Box<dyn std::error::Error>> {
+    for (target, outcome) in deploy::uninstall(name, from)? {
+        for kept in &outcome.kept {
+            eprintln!(
+                "Left {}: it was edited after being installed",
+                target.join(kept).display()
+            );
+        }
+        for backup in &outcome.backups {
+            eprintln!("Restored {}", target.join(backup).display());
+        }
+        say!("Removed {} files from {}", outcome.files, target.display());
+    }
+    Ok(())
+}
+
+/// The receipt for the copy of `tool` a shell would run, and its active version.
+fn installed_tool(
+    tool: &str,
+) -> Result<(receipt::Receipt, receipt::Install), Box<dyn std::error::Error>> {
+    let receipt = receipt::on_path(receipt::load(tool)?)
+        .ok_or_else(|| format!("{tool} wasn't installed by dex --install"))?;
+    let install = receipt
+        .active()
+        .ok_or_else(|| format!("{tool}'s active version is missing from the store"))?
+        .clone();
+    Ok((receipt, install))
+}
+
+/// Prints where an installed tool's command leads, for `dex which`.
+fn which(tool: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let (_, install) = installed_tool(tool)?;
+    let origin = match (&install.repo, &install.tag) {
+        (Some(repo), Some(tag)) => format!("{repo} {tag}"),
+        _ => install.url.clone(),
+    };
+    say!("{} ({origin})", install.binary.display());
+    Ok(())
+}
+
+/// Prints an installed tool's receipt, for `dex info`.
+fn show_info(tool: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let (receipt, install) = installed_tool(tool)?;
+    let installed_at = std::time::UNIX_EPOCH + std::time::Duration::from_secs(install.installed_at);
+
+    say!(
+        "{tool} {}",
+        install.version.as_deref().unwrap_or("(unknown version)")
+    );
+    let shim = if receipt.shim { " (shim)" } else { "" };
+    say!("  Command:   {}{shim}", receipt.command.display());
+    say!("  Binary:    {}", install.binary.display());
+    if let (Some(repo), Some(tag)) = (&install.repo, &install.tag) {
+        say!("  Release:   {repo} {tag}");
+    }
+    say!("  URL:       {}", install.url);
+    say!("  SHA-256:   {}", install.sha256);
+    say!("  Installed: {}", httpdate::fmt_http_date(installed_at));
+    if !install.files.is_empty() {
+        say!("  Files:");
+        for file in &install.files {
+            say!("    {}", file.display());
+        }
+    }
+    let others: Vec<&str> = receipt
+        .installs
+        .iter()
+        .filter(|i| i.version != install.version)
+        .filter_map(|i| i.version.as_deref())
+        .collect();
+    if !others.is_empty() {
+        say!("  Also installed: {}", others.join(", "));
+    }
+    Ok(())
+}
+
+/// Makes a --shim install run another of its installed versions, for `dex use`.
+fn use_version(spec: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let (tool, version) = spec
+        .split_once('@')
+        .ok_or_else(|| format!("'{spec}' should be tool@version"))?;
+    let store = install::store_dir().ok_or("could not determine dex's data directory")?;
+    let version = layout::rollback(&store, tool, Some(version))?;
+    say!("{tool} is now at {version}");
+    Ok(())
+}
+
+/// Updates an installed tool to its repository's latest release, for `dex
+/// update`; with `preview`, shows what changed since the installed release.
+fn update(
+    tool: &str,
+    preview: bool,
+    config_path: Option<&std::path::Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (receipt, install) = installed_tool(tool)?;
+    let (Some(repo), Some(installed)) = (&install.repo, &install.tag) else {
+        return Err(format!("{tool} wasn't installed from a release").into());
+    };
+    let url = github::shorthand_url(repo)
+        .filter(|_| install.url.starts_with("https://github.com/"))
+        .ok_or_else(|| {
+            format!("{tool} came from {repo}, and only GitHub releases can be updated")
+        })?;
+    let provider = provider::find(&url).ok_or("not a release URL")?;
+    let http = http_client(config_path)?;
+
+    if preview {
+        let newer = github::newer_releases(provider.releases(&http, &url)?, installed);
+        let Some(latest) = newer.first() else {
+            say!("{tool} is up to date ({installed})");
+            return Ok(());
+        };
+        let s = if newer.len() == 1 { "" } else { "s" };
+        let title = format!(
+            "{tool} {installed} → {} ({} release{s})",
+            latest.tag,
+            newer.len()
+        );
+        let mut notes = String::new();
+        for release in &newer {
+            let body = release.notes.as_deref().unwrap_or("(no release notes)");
+            notes.push_str(&format!("# {}\n\n{body}\n\n", release.tag));
+        }
+        notes::show(&title, &notes);
+        return Ok(());
+    }
+
+    let latest = provider.fetch_release(&http, &url, Default::default())?;
+    if latest.tag == *installed {
+        say!("{tool} is up to date ({installed})");
+        return Ok(());
+    }
+    eprintln!("{tool}: {installed} → {}", latest.tag);
+    let bin_dir = receipt
+        .command
+        .parent()
+        .ok_or("the installed command has no directory")?;
+    let scratch = std::env::temp_dir().join(format!("dex-update-{}", std::process::id()));
+    let release_url = format!("https://github.com/{repo}/releases/tag/{}", latest.tag);
+    let mut argv: Vec<std::ffi::OsString> = vec!["dex".into(), release_url.clone().into()];
+    argv.extend(["--install".into(), "--bin-dir".into(), bin_dir.into()]);
+    argv.extend(["--output".into(), scratch.clone().into()]);
+    if receipt.shim {
+        argv.push("--shim".into());
+    }
+    let config = load_config(config_path);
+    let mut cli = get_args(argv)?;
+    cli.digest_log = config.digest_log;
+    let mirrors = mirror::Mirrors::new(config.mirrors);
+    let context = Context {
+        cli: &cli,
+        http,
+        rules: load_rules(config_path),
+        target: None,
+        progress: None,
+        stats: Mutex::default(),
+        installed: Mutex::default(),
+        report: Mutex::default(),
+        mirrors: &mirrors,
+    };
+    let result = get(&context, &release_url);
+    let _ = std::fs::remove_dir_all(&scratch);
+    result.map_err(|failed| {
+        format!("updating {tool} failed (exit code {})", failed.exit_code()).into()
+    })
+}
+
+/// `get` options parsed from an argument list, for subcommands that download
+/// the way `get` does.
+fn get_args(argv: Vec<std::ffi::OsString>) -> Result<GetArgs, clap::Error> {
+    let matches = GetArgs::augment_args(clap::Command::new("dex")).try_get_matches_from(argv)?;
+    GetArgs::from_arg_matches(&matches)
+}
+
+/// The dex.toml governing the current directory.
+fn find_manifest() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let manifest = project::find(&std::env::current_dir()?);
+    Ok(manifest
+        .ok_or_else(|| format!("no {} here or in any parent directory", project::MANIFEST))?)
+}
+
+/// Installs the tools in the nearest dex.toml into its `.dex/bin`, pinning
+/// each to its dex.lock entry, and locks any tool that doesn't have one yet.
+fn sync(
+    config_path: Option<&std::path::Path>,
+    force: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest_path = find_manifest()?;
+    let root = manifest_path
+        .parent()
+        .expect("a found manifest has a directory");
+    let manifest = project::Manifest::load(&manifest_path)?;
+    let lock_path = root.join(project::LOCKFILE);
+    // One sync of a project at a time; another waits for this one. The lock
+    // is kept in .dex, out of version control.
+    let _guard = filelock::lock(&root.join(".dex").join("sync"))?;
+    let mut lock = project::Lockfile::load(&lock_path)?;
+    let bin_dir = project::bin_dir(root);
+    let scratch = root.join(".dex").join("downloads");
+    let state_path = project::state_path(root);
+    let mut state = project::SyncState::load(&state_path);
+
+    let build = project::host_build();
+    let http = http_client(config_path)?;
+    let rules = load_rules(config_path);
+    let config = load_config(config_path);
+    let mirrors = mirror::Mirrors::new(config.mirrors);
+    let mut failed = 0;
+    let mut current = 0;
+    for (name, tool) in &manifest.tools {
+        // A changed manifest entry is resolved afresh, for every build.
+        let entry = lock.tools.entry(name.clone()).or_default();
+        if entry.source != tool.url() {
+            *entry = project::LockedTool {
+                source: tool.url().to_string(),
+                ..Default::default()
+            };
+        }
+        // A locked tool is fetched by its exact URL and must hash to the
+        // locked digest.
+        let locked = entry.builds.get(&build);
+        if let Some(locked) = locked
+            && !force
+            && state.tools.get(name).is_some_and(|s| s.is_current(locked))
+        {
+            let version = locked.tag.as_deref().unwrap_or(&locked.asset);
+            eprintln!("{}", tr!("sync-up-to-date", tool = name, version = version));
+            current += 1;
+            continue;
+        }
+        let url = match locked {
+            Some(locked) => format!("{}#sha256={}", locked.url, locked.sha256),
+            None => github::shorthand_url(tool.url()).unwrap_or_else(|| tool.url().to_string()),
+        };
+        let mut argv: Vec<std::ffi::OsString> = vec!["dex".into(), url.clone().into()];
+        argv.extend([
+            "--install".into(),
+            "--bin-dir".into(),
+            bin_dir.clone().into(),
+        ]);
+        argv.extend(["--output".into(), scratch.clone().into()]);
+        if let Some(asset) = tool.asset().filter(|_| locked.is_none()) {
+            argv.extend(["--asset-name".into(), asset.into()]);
+        }
+        let mut cli = get_args(argv)?;
+        cli.digest_log = config.digest_log.clone();
+
+        eprintln!("{name}: {}", tool.url());
+        let context = Context {
+            cli: &cli,
+            http: http.clone(),
+            rules: rules.clone(),
+            target: None,
+            progress: None,
+            stats: Mutex::default(),
+            installed: Mutex::default(),
+            report: Mutex::default(),
+            mirrors: &mirrors,
+        };
+        if get(&context, &url).is_err() {
+            failed += 1;
+            state.tools.remove(name);
+            continue;
+        }
+        let Some(installed) = context.installed.into_inner().unwrap().pop() else {
+            continue;
+        };
+        let locked = entry
+            .builds
+            .entry(build.clone())
+            .or_insert_with(|| project::Locked {
+                tag: installed.tag,
+                asset: installed.asset,
+                url: installed.url,
+                sha256: installed.sha256,
+            });
+        state.tools.insert(
+            name.clone(),
+            project::Synced {
+                tag: locked.tag.clone(),
+                sha256: locked.sha256.clone(),
+                command: installed.command,
+            },
+        );
+    }
+    // Tools dropped from the manifest drop out of the lock.
+    lock.tools
+        .retain(|name, _| manifest.tools.contains_key(name));
+    lock.save(&lock_path)?;
+    state
+        .tools
+        .retain(|name, _| manifest.tools.contains_key(name));
+    state.save(&state_path)?;
+    let _ = std::fs::remove_dir(&scratch);
+
+    if failed > 0 {
+        return Err(format!(
+            "{failed} of {} tools failed to install",
+            manifest.tools.len()
+        )
+        .into());
+    }
+    let count = manifest.tools.len() - current;
+    let s = if count == 1 { "" } else { "s" };
+    if current == 0 {
+        say!("{count} tool{s} installed in {}", bin_dir.display());
+    } else {
+        say!(
+            "{count} tool{s} installed, {current} up to date in {}",
+            bin_dir.display()
+        );
+    }
+    Ok(())
+}
+
+/// Prints the PATH setup for the nearest project's `.dex/bin`, for `dex env`.
+fn print_env(shell: Option<clap_complete::Shell>) -> Result<(), Box<dyn std::error::Error>> {
+    let shell = shell
+        .or_else(clap_complete::Shell::from_env)
+        .ok_or("can't tell the shell from $SHELL; pass --shell")?;
+    let manifest_path = find_manifest()?;
+    let root = manifest_path
+        .parent()
+        .expect("a found manifest has a directory");
+    print!(
+        "{}",
+        project::activation_script(shell, &project::bin_dir(root))
+    );
+    Ok(())
+}
+
+/// Installs this machine's build of each tool in a bundle, checking every
+/// file against the bundle's lockfile, for `dex bundle install`.
+fn install_bundle(
+    bundle_path: &std::path::Path,
+    bin_dir: Option<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let scratch = std::env::temp_dir().join(format!("dex-bundle-{}", std::process::id()));
+    // The URL is never fetched; `get` just requires one.
+    let mut argv: Vec<std::ffi::OsString> =
+        vec!["dex".into(), bundle_path.into(), "--install".into()];
+    if let Some(dir) = bin_dir {
+        argv.extend(["--bin-dir".into(), dir.into()]);
+    }
+    argv.extend(["--output".into(), scratch.clone().into()]);
+    let cli = get_args(argv)?;
+
+    let result = (|| {
+        let lock = bundle::unpack(bundle_path, &scratch)?;
+        let build = project::host_build();
+        let mut installed = 0;
+        for (tool, locked_tool) in &lock.tools {
+            let Some(locked) = locked_tool.builds.get(&build) else {
+                eprintln!("Warning: the bundle has no {build} build of {tool}");
+                continue;
+            };
+            let path = scratch.join(bundle::asset_path(tool, &build, &locked.asset));
+            let actual = verify::hash_file(&path, verify::Algorithm::Sha256)?;
+            verify::check(&bundle::expected(locked)?, &actual)
+                .map_err(|e| format!("{}: {e}", locked.asset))?;
+
+            let format = extract::find_extractor(&path);
+            let placed = install_download(
+                &cli,
+                &path,
+                &locked.asset,
+                format,
+                Some(tool),
+                locked.tag.as_deref(),
+                &Wanted::host(),
+            )
+            .map_err(|e| format!("installing {tool}: {e}"))?;
+            say!("Installed to {}", placed.command.display());
+            record_install(
+                &cli,
+                placed,
+                &locked.url,
+                None,
+                locked.tag.as_deref(),
+                &actual.hex,
+            );
+            installed += 1;
+        }
+        Ok::<_, Box<dyn std::error::Error>>(installed)
+    })();
+    let _ = std::fs::remove_dir_all(&scratch);
+
+    let installed = result?;
+    let s = if installed == 1 { "" } else { "s" };
+    say!(
+        "{installed} tool{s} installed from {}",
+        bundle_path.display()
+    );
+    Ok(())
+}
+
+/// Carries out `dex cache <action>`.
+fn manage_cache(action: CacheAction) -> Result<(), Box<dyn std::error::Error>> {
+    let cache = cache::Cache::open().ok_or("no cache directory (is HOME set?)")?;
+    match action {
+        CacheAction::Dir => println!("{}", cache.root().display()),
+        CacheAction::List => {
+            let files = cache.list();
+            for file in &files {
+                println!(
+                    "{:>10}  {}  {}",
+                    indicatif::HumanBytes(file.size).to_string(),
+                    file.name,
+                    file.url
+                );
+            }
+            let total = files.iter().map(|f| f.size).sum();
+            eprintln!("{} files, {}", files.len(), indicatif::HumanBytes(total));
+        }
+        CacheAction::Clear => {
+            cache.clear()?;
+            eprintln!("Cleared {}", cache.root().display());
+        }
+    }
+    Ok(())
+}
+
+/// Checks a file against an expected digest, exiting with the verification
+/// failure code if it doesn't match. `expected` is a hex digest, or a sum
+/// file (a local path or a URL) listing the file.
+fn verify_file(path: &std::path::Path, expected: &str, config_path: Option<&std::path::Path>) {
+    let digest = if let Ok(digest) = verify::parse_pinned(verify::Algorithm::Sha256, expected) {
+        Ok(digest)
+    } else if let Some(file_name) = path.file_name().unwrap_or_default().to_str() {
+        let contents: Result<String, Box<dyn std::error::Error>> =
+            if expected.starts_with("https://") || expected.starts_with("http://") {
+                http_client(config_path).and_then(|h| h.fetch_text(expected))
+            } else {
+                std::fs::read_to_string(expected).map_err(|e| format!("{expected}: {e}").into())
+            };
+        let hint = verify::Algorithm::from_sum_filename(&download::filename_from_url(expected));
+        contents.and_then(|contents| {
+            verify::parse_sum_file(&contents, file_name, hint)
+                .ok_or_else(|| format!("{expected} does not list {file_name}").into())
+        })
+    } else {
+        Err(format!(
+            "{}: a sum file can't list a name that isn't valid UTF-8; give the digest instead",
+            path.display()
+        )
+        .into())
+    };
+
+    let result = digest.and_then(|digest| {
+        let actual = verify::hash_file(path, digest.algorithm)?;
+        Ok((verify::check(&digest, &actual), digest))
+    });
+    match result {
+        Ok((Ok(()), digest)) => println!("OK: {} matches {digest}", path.display()),
+        Ok((Err(mismatch), _)) => {
+            eprintln!("Error verifying {}: {mismatch}", path.display());
+            std::process::exit(Failed::Verification.exit_code());
+        }
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(Failed::classify(&*e).exit_code());
+        }
+    }
+}
+
+/// Checks an extracted tree against its manifest and exits non-zero if
+/// anything was changed. `target` is either the tree itself or the name of a
+/// tool kept under `root` with --layout versioned.
+fn verify_tree(target: &std::path::Path, root: &std::path::Path) {
+    let dir = if target.join(manifest::MANIFEST_NAME).is_file() {
+        target.to_path_buf()
+    } else {
+        root.join(target).join("current")
+    };
+
+    let result = manifest::read(&dir).and_then(|m| Ok((manifest::check(&dir, &m)?, m.files.len())));
+    match result {
+        Ok((problems, _)) if !problems.is_empty() => {
+            for problem in &problems {
+                println!("{problem}");
+            }
+            eprintln!(
+                "Error: {} problem(s) found in {}",
+                problems.len(),
+                dir.display()
+            );
+            std::process::exit(1);
+        }
+        Ok((_, count)) => println!("OK: {count} files verified in {}", dir.display()),
+        Err(e) => {
+            eprintln!("Error: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Lists an extracted file for --verbose.
+fn list_entry(entry: &extract::Entry) -> bool {
+    if entry.kind != extract::EntryKind::Dir {
+        match entry.size {
+            Some(size) => eprintln!(
+                "  {} ({})",
+                entry.name.display(),
+                indicatif::HumanBytes(size)
+            ),
+            None => eprintln!("  {}", entry.name.display()),
+        }
+    }
+    true
+}
+
+/// --build-fallback: builds a release that has no asset for this machine
+/// from its source, into the output directory (or with --install, the bin
+/// directory).
+fn build_from_source(
+    context: &Context,
+    release: &provider::Release,
+    (platform, arch): (&str, &str),
+) -> Result<(), Failed> {
+    let cli = context.cli;
+    if platform != platform::normalize_platform(std::env::consts::OS)
+        || arch != platform::normalize_arch(std::env::consts::ARCH)
+    {
+        eprintln!(
+            "Error: {} {} has no build for platform={platform}, arch={arch}, and \
+             --build-fallback only builds for this machine",
+            release.repo, release.tag
+        );
+        return Err(Failed::Other);
+    }
+    let Some(toolchain) = build::detect(&context.http, release) else {
+        eprintln!(
+            "Error: {} {} has no build for this machine, and no Cargo.toml or go.mod to build from",
+            release.repo, release.tag
+        );
+        return Err(Failed::Other);
+    };
+    let dest = if cli.install {
+        cli.bin_dir.clone().or_else(dirs::bin_dir)
+    } else {
+        Some(cli.output.clone())
+    };
+    let Some(dest) = dest else {
+        eprintln!("Error: could not determine an install directory; pass --bin-dir");
+        return Err(Failed::Other);
+    };
+    eprintln!(
+        "No release build for this machine: building {} {} from source with {toolchain} \
+         (this can take a while)",
+        release.repo, release.tag
+    );
+    let binaries = build::build(&toolchain, release, &dest).map_err(|e| {
+        eprintln!("Error building {} {}: {e}", release.repo, release.tag);
+        Failed::Other
+    })?;
+    for binary in binaries {
+        say!("Built {}", binary.display());
+    }
+    Ok(())
+}
+
+/// Asks whether to go ahead with a download of `size` bytes, if it's over
+/// --confirm-above and a person is at the terminal to answer.
+fn confirm_size(context: &Context, name: &str, size: u64) -> bool {
+    let cli = context.cli;
+    if cli.yes
+        || cli.confirm_above == 0
+        || size <= cli.confirm_above
+        || !std::io::stdin().is_terminal()
+    {
+        return true;
+    }
+    // One question at a time, with progress bars out of the way.
+    static ASKING: Mutex<()> = Mutex::new(());
+    let _asking = ASKING.lock().unwrap();
+    let ask = || {
+        let size = indicatif::HumanBytes(size);
+        eprint!("{} ", tr!("size-prompt", name = name, size = size));
+        let _ = std::io::stderr().flush();
+        let mut answer = String::new();
+        let _ = std::io::stdin().lock().read_line(&mut answer);
+        i18n::is_yes(&answer)
+    };
+    match context.progress {
+        Some(multi) => multi.suspend(ask),
+        None => ask(),
+    }
+}
+
+/// The extraction conflict policy from the flags. With none given, existing
+/// files are protected when a person is at the terminal, but scripts keep the
+/// long-standing overwrite behavior.
+fn conflict_policy(cli: &GetArgs) -> extract::Conflict {
+    if cli.overwrite {
+        extract::Conflict::Overwrite
+    } else if cli.skip_existing {
+        extract::Conflict::Skip
+    } else if cli.backup {
+        extract::Conflict::Backup
+    } else if std::io::stdin().is_terminal() {
+        extract::Conflict::Fail
+    } else {
+        extract::Conflict::Overwrite
+    }
+}
+
+/// Installs a download into the bin directory. Standalone binaries are moved
+/// there directly; archives are extracted to a scratch directory first and the
+/// binary is picked out of the extracted tree.
+fn install_download(
+    cli: &GetArgs,
+    downloaded_path: &std::path::Path,
+    file_name: &str,
+    format: Option<&dyn extract::Extractor>,
+    project: Option<&str>,
+    tag: Option<&str>,
+    wanted: &Wanted,
+) -> Result<Placed, Box<dyn std::error::Error>> {
+    let bin_dir = cli
+        .bin_dir
+        .clone()
+        .or_else(dirs::bin_dir)
+        .ok_or("could not determine an install directory; pass --bin-dir")?;
+    let version = tag
+        .map(str::to_string)
+        .or_else(|| layout::guess_version(file_name));
+
+    let Some(format) = format else {
+        if platform::asset_kind(file_name) == platform::AssetKind::Binary {
+            let name = install::install_name(file_name, project);
+            check_arch(cli, downloaded_path, wanted)?;
+            let (command, binary) =
+                place_binary(cli, downloaded_path, &bin_dir, &name, version.as_deref())?;
+            return Ok(Placed {
+                command,
+                binary,
+                extras: Vec::new(),
+                version,
+            });
+        }
+        return Err(format!("{file_name} is neither an archive nor an executable").into());
+    };
+
+    let scratch = cli
+        .output
+        .join(format!(".dex-install-{}", std::process::id()));
+    let result = (|| {
+        let depth = if cli.recursive { cli.max_depth } else { 1 };
+        extract::extract_nested(
+            downloaded_path,
+            format,
+            &scratch,
+            depth,
+            false,
+            &Default::default(),
+        )?;
+
+        let tool = project
+            .map(str::to_string)
+            .unwrap_or_else(|| install::tool_name(file_name));
+        let candidates = binaries::find_candidates(&scratch, &tool)?;
+        let stdin = std::io::stdin();
+        let interactive = stdin.is_terminal();
+        let chosen =
+            &candidates[binaries::choose(&candidates, &scratch, interactive, stdin.lock())?];
+        check_arch(cli, &chosen.path, wanted)?;
+
+        // The name is recorded in receipts and shims, which need it as text.
+        let name = chosen
+            .path
+            .file_name()
+            .ok_or("binary has no file name")?
+            .to_str()
+            .ok_or_else(|| format!("{}: name is not valid UTF-8", chosen.path.display()))?
+            .to_string();
+        let (command, binary) =
+            place_binary(cli, &chosen.path, &bin_dir, &name, version.as_deref())?;
+
+        let extras = if cli.with_man || cli.with_completions {
+            let tool = name.strip_suffix(".exe").unwrap_or(&name);
+            install_extras(cli, &scratch, tool)?
+        } else {
+            Vec::new()
+        };
+        Ok(Placed {
+            command,
+            binary,
+            extras,
+            version: version.clone(),
+        })
+    })();
+
+    let _ = std::fs::remove_dir_all(&scratch);
+    if result.is_ok() && !cli.keep {
+        let _ = std::fs::remove_file(downloaded_path);
+    }
+    result
+}
+
+/// Installs the man pages and completion scripts found in an extracted
+/// archive, as requested by --with-man / --with-completions.
+fn install_extras(
+    cli: &GetArgs,
+    root: &std::path::Path,
+    tool: &str,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let extras = install::find_extras(root)?;
+
+    let mut files = Vec::new();
+    if cli.with_man {
+        if extras.man_pages.is_empty() {
+            eprintln!("Warning: no man pages found in the archive");
+        }
+        for (path, section) in &extras.man_pages {
+            let name = path.file_name().ok_or("man page has no file name")?;
+            let dest = install::man_page_dest(name, section);
+            files.push((path, dest));
+        }
+    }
+    if cli.with_completions {
+        if extras.completions.is_empty() {
+            eprintln!("Warning: no shell completions found in the archive");
+        }
+        for (path, shell) in &extras.completions {
+            files.push((path, install::completion_dest(*shell, tool)));
+        }
+    }
+
+    let mut installed = Vec::new();
+    for (src, dest) in files {
+        let dest = dest.ok_or("could not determine the user data directory")?;
+        install::install_file(src, &dest)?;
+        say!("Installed {}", dest.display());
+        installed.push(dest);
+    }
+    Ok(installed)
+}
+
+/// Collects attestations for the downloaded asset — GitHub artifact attestations
+/// plus any Sigstore bundle published next to it — and checks them.
+/// `source_url` is the release URL being downloaded, which may be one of
+/// several (--input-file).
+fn check_attestations(
+    context: &Context,
+    source_url: &str,
+    resolved: Option<&provider::ResolvedAsset>,
+    sha256_hex: &str,
+) -> Result<attest::Verdict, String> {
+    let cli = context.cli;
+    let resolved = resolved.ok_or("attestations are only looked up for release URLs")?;
+
+    let key = match &cli.attestation_key {
+        Some(path) => Some(attest::load_key(path).map_err(|e| e.to_string())?),
+        None => None,
+    };
+
+    // Artifact attestations are a GitHub API; other providers can still
+    // publish Sigstore bundles as files.
+    let mut bundles = if github::is_github_release_url(source_url) {
+        let (owner, repo) = (&resolved.release.owner, &resolved.release.repo);
+        github::fetch_attestations(&context.http, owner, repo, sha256_hex)
+            .map_err(|e| format!("fetching attestations: {e}"))?
+    } else {
+        Vec::new()
+    };
+    if let Some(bundle_asset) =
+        attest::find_bundle_asset(&resolved.release.assets, &resolved.asset.name)
+    {
+        bundles.push(attest::fetch_bundle(&context.http, bundle_asset).map_err(|e| e.to_string())?);
+    }
+
+    attest::evaluate(&bundles, sha256_hex, key.as_ref())
+}
+
+/// Saves or summarizes the SBOM published for the resolved asset.
+/// A missing or unreadable SBOM is a warning, not a failure.
+fn handle_sbom(
+    http: &http::HttpContext,
+    mode: SbomMode,
+    resolved: &provider::ResolvedAsset,
+    output: &std::path::Path,
+) {
+    let Some(sbom_asset) = sbom::find_sbom_asset(&resolved.release.assets, &resolved.asset.name)
+    else {
+        eprintln!("Warning: no SBOM found for {}", resolved.asset.name);
+        return;
+    };
+
+    match mode {
+        SbomMode::Save => {
+            match download::download_file(http, &sbom_asset.url, output, &Default::default()) {
+                Ok(saved) => say!("Saved SBOM to {}", saved.path.display()),
+                Err(e) => eprintln!("Warning: downloading SBOM {}: {e}", sbom_asset.name),
+            }
+        }
+        SbomMode::Summary => {
+            let summary = http
+                .fetch_text(&sbom_asset.url)
+                .map_err(|e| e.to_string())
+                .and_then(|json| sbom::summarize(&json));
+            match summary {
+                Ok(summary) => sbom::print_summary(&sbom_asset.name, &summary),
+                Err(e) => eprintln!("Warning: reading SBOM {}: {e}", sbom_asset.name),
+            }
+        }
+    }
+}
+
+/// Downloads every URL in a list, `cli.jobs` at a time, then prints which
+/// succeeded.
+fn get_all(context: &Context, urls: &[String]) -> Vec<batch::Outcome> {
+    let cli = context.cli;
+    let outcomes = batch::run(urls, cli.jobs, fail_fast(cli), |url| {
+        get_timed(context, url)
+    });
+
+    batch::print_summary(urls, &outcomes);
+    outcomes
+}
+
+/// Runs `get`, noting how long it took for --report.
+fn get_timed(context: &Context, url: &str) -> Result<(), Failed> {
+    let started = std::time::Instant::now();
+    let result = get(context, url);
+    let elapsed = started.elapsed().as_secs_f64();
+    context.note(url, |item| item.duration_secs = elapsed);
+    result
+}
+
+/// Whether a batch stops at the first failure (--fail-fast) or carries on
+/// with the rest (--keep-going, the default).
+fn fail_fast(cli: &GetArgs) -> bool {
+    cli.fail_fast && !cli.keep_going
+}
+
+/// Downloads several release assets into `<output>/<tag>/`, `cli.jobs` at a time.
+/// Assets are verified against the release's sum files where listed, never extracted.
+fn download_all(
+    context: &Context,
+    release: &provider::Release,
+    assets: &[&platform::Asset],
+    local_files: bool,
+) -> Result<(), Failed> {
+    let cli = context.cli;
+    if assets.is_empty() {
+        eprintln!("Error: no matching assets in release {}", release.tag);
+        return Err(Failed::Other);
+    }
+
+    let dir = cli.output.join(&release.tag);
+    eprintln!(
+        "Downloading {} assets from {} {} into {}",
+        assets.len(),
+        release.repo,
+        release.tag,
+        dir.display()
+    );
+
+    let multi = indicatif::MultiProgress::new();
+    let outcomes = batch::run(
+        assets,
+        cli.jobs,
+        fail_fast(cli),
+        |asset| match download_one(context, release, asset, &dir, &multi, local_files) {
+            Ok(download) => {
+                context.record(stats::Stats {
+                    downloads: 1,
+                    bytes: download.bytes,
+                    download_time: download.elapsed,
+                    ..Default::default()
+                });
+                multi.suspend(|| say!("Saved to {}", download.path.display()));
+                Ok(())
+            }
+            Err((failed, e)) => {
+                multi.suspend(|| eprintln!("Error downloading {}: {e}", asset.name));
+                Err(failed)
+            }
+        },
+    );
+    batch::overall(&outcomes)
+}
+
+/// Downloads one asset of a multi-asset run, verifying it if a sum file lists it.
+fn download_one(
+    context: &Context,
+    release: &provider::Release,
+    asset: &platform::Asset,
+    dir: &std::path::Path,
+    multi: &indicatif::MultiProgress,
+    local_files: bool,
+) -> Result<download::Download, (Failed, String)> {
+    let cli = context.cli;
+    let expected = match verify::find_checksum_asset(&release.assets, &asset.name) {
+        Some(sum_asset) if !cli.no_verify => {
+            verify::fetch_expected_digest(&context.http, sum_asset, &asset.name).map_err(|e| {
+                let message = format!("fetching checksum file {}: {e}", sum_asset.name);
+                (Failed::classify(&*e), message)
+            })?
+        }
+        _ => None,
+    };
+    let expected = expected.or_else(|| {
+        asset
+            .digest
+            .as_deref()
+            .and_then(verify::parse_labeled)
+            .filter(|_| !cli.no_verify)
+    });
+
+    let algorithms: Vec<verify::Algorithm> = expected.iter().map(|d| d.algorithm).collect();
+    let rename = |name: &str| {
+        let template = cli.filename_template.as_deref().unwrap_or("{name}");
+        let (platform, arch) = context.rules.identify(name);
+        let vars = layout::NameVars {
+            repo: Some(&release.repo),
+            tag: Some(&release.tag),
+            platform,
+            arch,
+        };
+        layout::render_name(template, name, &vars)
+    };
+    let options = download::DownloadOptions {
+        algorithms: &algorithms,
+        progress: Some(multi),
+        verbose: cli.verbose,
+        if_newer: None,
+        rename: cli.filename_template.is_some().then_some(&rename),
+        max_size: cli.max_size,
+        chunk_size: Some(cli.chunk_size),
+        local_files,
+    };
+    let download =
+        download::download_file(&context.http, &asset.url, dir, &options).map_err(|e| {
+            let failed = if e.is::<download::TooLarge>() {
+                Failed::Other
+            } else {
+                Failed::Network
+            };
+            (failed, e.to_string())
+        })?;
+
+    if let Some(expected) = &expected
+        && let Some(actual) = download.digest(expected.algorithm)
+        && let Err(e) = verify::check(expected, actual)
+    {
+        let _ = std::fs::remove_file(&download.path);
+        return Err((Failed::Verification, e));
+    }
+    if cli.fsync {
+        durable::sync(std::slice::from_ref(&download.path), dir)
+            .map_err(|e| (Failed::Other, format!("flushing to disk: {e}")))?;
+    }
+
+    Ok(download)
+}
+
+/// Prints every asset's score and the rules behind it, best candidates first.
+fn explain_selection(
+    rules: &platform::Rules,
+    release: &provider::Release,
+    platform: &str,
+    arch: &str,
+    fallback_arches: &[&str],
+    libc: Option<&str>,
+) {
+    // Scores for the arch a download would settle for, if not the native one.
+    let picked = rules
+        .select_best_asset_or_fallback(&release.assets, platform, arch, fallback_arches, libc)
+        .map_or(arch, |(_, picked)| picked);
+    let arch_label = if picked == arch {
+        arch.to_string()
+    } else {
+        format!("{picked} (no {arch} build; runs under emulation)")
+    };
+    let arch = picked;
+    let scored = rules.score_assets(&release.assets, platform, arch, libc);
+    let best = rules.best_of(&scored).map(|s| s.asset.name.clone());
+
+    let mut rows: Vec<&platform::ScoredAsset> = scored.iter().collect();
+    // Eligible candidates first, then near-misses, then skipped files.
+    rows.sort_by_key(|s| {
+        (
+            !s.is_eligible(),
+            s.skipped.is_some(),
+            std::cmp::Reverse(s.score),
+        )
+    });
+
+    println!(
+        "{} {}: {} assets, platform={platform} arch={arch_label}{}",
+        release.repo,
+        release.tag,
+        scored.len(),
+        libc.map(|l| format!(" libc={l}")).unwrap_or_default()
+    );
+
+    let width = rows.iter().map(|s| s.asset.name.len()).max().unwrap_or(0);
+    for row in rows {
+        let marker = if best.as_deref() == Some(row.asset.name.as_str()) {
+            "*"
+        } else {
+            " "
+        };
+        let score = if row.skipped.is_some() {
+            "-".to_string()
+        } else {
+            row.score.to_string()
+        };
+        println!("{marker} {score:>3}  {:<width$}  {row}", row.asset.name);
+    }
+
+    match best {
+        Some(name) => println!("Selected: {name}"),
+        None => println!("Selected: none (no asset matches both platform and arch)"),
     }
 }
@@ -0,0 +1,180 @@
+use crate::verify::{self, Algorithm};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// File name of the manifest written into an extraction's output directory.
+pub const MANIFEST_NAME: &str = ".dex-manifest.json";
+
+/// A record of everything one extraction created.
+#[derive(Serialize, Deserialize)]
+pub struct Manifest {
+    /// URL the archive was downloaded from.
+    pub source: String,
+    pub files: Vec<Entry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// Path relative to the manifest's directory, with `/` separators.
+    pub path: String,
+    pub size: u64,
+    /// Unix permission bits; absent on platforms without them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+    pub sha256: String,
+}
+
+#[cfg(unix)]
+fn file_mode(metadata: &std::fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn file_mode(_metadata: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
+fn relative_path(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Hashes the extracted `files` and records them, relative to `root`.
+pub fn build(source: &str, root: &Path, files: &[PathBuf]) -> std::io::Result<Manifest> {
+    let mut entries = Vec::with_capacity(files.len());
+    for path in files {
+        let metadata = std::fs::metadata(path)?;
+        entries.push(Entry {
+            path: relative_path(root, path),
+            size: metadata.len(),
+            mode: file_mode(&metadata),
+            sha256: verify::hash_file(path, Algorithm::Sha256)?.hex,
+        });
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(Manifest {
+        source: source.to_string(),
+        files: entries,
+    })
+}
+
+/// Writes the manifest into `root`, returning its path.
+pub fn write(manifest: &Manifest, root: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let path = root.join(MANIFEST_NAME);
+    std::fs::write(&path, serde_json::to_string_pretty(manifest)? + "\n")?;
+    Ok(path)
+}
+
+/// Reads the manifest in `root`.
+pub fn read(root: &Path) -> Result<Manifest, Box<dyn std::error::Error>> {
+    let path = root.join(MANIFEST_NAME);
+    let json =
+        std::fs::read_to_string(&path).map_err(|e| format!("reading {}: {e}", path.display()))?;
+    serde_json::from_str(&json).map_err(|e| format!("invalid {}: {e}", path.display()).into())
+}
+
+/// A way an installed file no longer matches its manifest entry.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Problem {
+    Missing(String),
+    Modified(String),
+    ModeChanged {
+        path: String,
+        expected: u32,
+        actual: u32,
+    },
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Problem::Missing(path) => write!(f, "missing: {path}"),
+            Problem::Modified(path) => write!(f, "modified: {path}"),
+            Problem::ModeChanged {
+                path,
+                expected,
+                actual,
+            } => write!(f, "mode changed: {path} ({expected:o} → {actual:o})"),
+        }
+    }
+}
+
+/// Re-hashes every file listed in the manifest in `root` and reports the ones
+/// that are gone or differ. Files added since extraction aren't reported.
+pub fn check(root: &Path, manifest: &Manifest) -> std::io::Result<Vec<Problem>> {
+    let mut problems = Vec::new();
+    for entry in &manifest.files {
+        let path = root.join(&entry.path);
+        let Ok(metadata) = std::fs::metadata(&path) else {
+            problems.push(Problem::Missing(entry.path.clone()));
+            continue;
+        };
+
+        // A size difference settles it without reading the file.
+        if metadata.len() != entry.size
+            || verify::hash_file(&path, Algorithm::Sha256)?.hex != entry.sha256
+        {
+            problems.push(Problem::Modified(entry.path.clone()));
+        }
+
+        if let (Some(expected), Some(actual)) = (entry.mode, file_mode(&metadata))
+            && expected != actual
+        {
+            problems.push(Problem::ModeChanged {
+                path: entry.path.clone(),
+                expected,
+                actual,
+            });
+        }
+    }
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_write_and_check() {
+        let root = std::env::temp_dir().join(format!("dex-manifest-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("bin")).unwrap();
+        let files = [root.join("bin").join("tool"), root.join("README")];
+        std::fs::write(&files[0], "binary").unwrap();
+        std::fs::write(&files[1], "hello").unwrap();
+
+        let manifest = build("https://example.com/tool.tar.gz", &root, &files).unwrap();
+        assert_eq!(manifest.files[0].path, "README");
+        assert_eq!(manifest.files[0].size, 5);
+        // sha256("hello")
+        assert_eq!(
+            manifest.files[0].sha256,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(manifest.files[1].path, "bin/tool");
+
+        write(&manifest, &root).unwrap();
+        let manifest = read(&root).unwrap();
+        assert!(check(&root, &manifest).unwrap().is_empty());
+
+        // Same size, different bytes.
+        std::fs::write(&files[1], "jello").unwrap();
+        std::fs::remove_file(&files[0]).unwrap();
+        let problems = check(&root, &manifest).unwrap();
+        assert_eq!(
+            problems,
+            [
+                Problem::Modified("README".into()),
+                Problem::Missing("bin/tool".into())
+            ]
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,309 @@
+use crate::apicache;
+use crate::download;
+use crate::github;
+use crate::http::HttpContext;
+use crate::index;
+use crate::platform::Asset;
+use crate::provider::{Latest, Provider, Release, ResolvedAsset, Selector};
+
+/// A package in a Maven repository, named by its coordinates.
+#[derive(Debug, PartialEq, Eq)]
+struct Coordinates<'u> {
+    /// The repository's base URL: https:// in place of maven://, or http://
+    /// in place of maven+http://.
+    repository: String,
+    group: &'u str,
+    artifact: &'u str,
+    version: Option<&'u str>,
+}
+
+impl Coordinates<'_> {
+    /// The directory holding every version of the artifact.
+    fn artifact_url(&self) -> String {
+        format!(
+            "{}/{}/{}",
+            self.repository,
+            self.group.replace('.', "/"),
+            self.artifact
+        )
+    }
+}
+
+/// Parses a Maven URL: the repository with `maven://` for its scheme, then
+/// the coordinates as the last path segment. `maven+http://` is for
+/// repositories served over plain HTTP.
+///
+///   maven://repo1.maven.org/maven2/com.acme:tool
+///   maven://pkgs.dev.azure.com/org/_packaging/feed/maven/v1/com.acme:tool@1.2.0
+///   maven+http://nexus.internal:8081/repository/releases/com.acme:tool
+fn parse_maven_url(url: &str) -> Option<Coordinates<'_>> {
+    let (scheme, path) = match url.strip_prefix("maven://") {
+        Some(path) => ("https", path),
+        None => ("http", url.strip_prefix("maven+http://")?),
+    };
+    let (repository, coordinates) = path.trim_end_matches('/').rsplit_once('/')?;
+    let (coordinates, version) = match coordinates.split_once('@') {
+        Some((coordinates, version)) => (coordinates, Some(version).filter(|v| !v.is_empty())),
+        None => (coordinates, None),
+    };
+    let (group, artifact) = coordinates.split_once(':')?;
+    if group.is_empty() || artifact.is_empty() || artifact.contains(':') {
+        return None;
+    }
+    Some(Coordinates {
+        repository: format!("{scheme}://{repository}"),
+        group,
+        artifact,
+        version,
+    })
+}
+
+/// The contents of each `<tag>` element in `xml`, in order. maven-metadata.xml
+/// is simple enough not to need a parser: no attributes, entities or CDATA.
+fn elements<'x>(xml: &'x str, tag: &str) -> Vec<&'x str> {
+    let (open, close) = (format!("<{tag}>"), format!("</{tag}>"));
+    let mut values = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        let Some(end) = rest.find(&close) else {
+            break;
+        };
+        values.push(rest[..end].trim());
+        rest = &rest[end + close.len()..];
+    }
+    values
+}
+
+/// The version an artifact's maven-metadata.xml calls latest: the newest
+/// release it records (by `latest`), skipping snapshots when there's
+/// anything else.
+fn latest_version(metadata: &str, latest: Latest) -> Option<String> {
+    let versions = elements(metadata, "version");
+    if latest == Latest::Semver {
+        let released = versions.iter().filter(|v| !v.ends_with("-SNAPSHOT"));
+        if let Some(best) = released
+            .filter_map(|v| Some((github::tag_version(v)?, v)))
+            .max()
+        {
+            return Some(best.1.to_string());
+        }
+    }
+    // <release> is the newest non-snapshot deployed; <latest> may be a
+    // snapshot; the versions are listed oldest first.
+    ["release", "latest"]
+        .iter()
+        .find_map(|tag| elements(metadata, tag).into_iter().find(|v| !v.is_empty()))
+        .or_else(|| versions.last().copied())
+        .map(str::to_string)
+}
+
+/// Whether a file in a version's directory is one to offer for download.
+/// POMs and Maven's own metadata describe the artifact, and SHA-1 sums
+/// can't be checked; the other checksum and signature files are kept for
+/// verification.
+fn is_artifact_file(name: &str) -> bool {
+    ![".pom", ".module", ".sha1", ".pom.asc", ".module.asc"]
+        .iter()
+        .any(|suffix| name.ends_with(suffix))
+        && !name.starts_with("maven-metadata")
+}
+
+/// An artifact in a Maven repository (Maven Central, Nexus, Artifactory,
+/// Azure Artifacts, GitHub Packages), its version's files listed from the
+/// repository's directory index.
+pub struct Maven;
+
+impl Provider for Maven {
+    fn name(&self) -> &'static str {
+        "Maven artifact"
+    }
+
+    fn matches(&self, url: &str) -> bool {
+        parse_maven_url(url).is_some()
+    }
+
+    fn repo_name<'u>(&self, url: &'u str) -> Option<&'u str> {
+        parse_maven_url(url).map(|c| c.artifact)
+    }
+
+    fn fetch_release(
+        &self,
+        http: &HttpContext,
+        url: &str,
+        latest: Latest,
+    ) -> Result<Release, Box<dyn std::error::Error>> {
+        let coordinates = parse_maven_url(url).ok_or("Not a valid Maven URL")?;
+        let version = version(http, &coordinates, latest)?;
+        let dir = format!("{}/{version}/", coordinates.artifact_url());
+        let pattern = format!("{}-{version}*", coordinates.artifact);
+        let assets = index::list(http, &dir, &[pattern], 0)
+            .map_err(|e| {
+                format!(
+                    "can't list {dir} ({e}); the repository may not serve directory \
+                     listings, in which case name the file with --asset-name"
+                )
+            })?
+            .into_iter()
+            .map(|url| Asset {
+                name: download::filename_from_url(&url),
+                url,
+                digest: None,
+                size: None,
+                content_type: None,
+                updated_at: None,
+            })
+            .filter(|a| is_artifact_file(&a.name))
+            .collect();
+        Ok(release(&coordinates, version, assets))
+    }
+
+    /// With --asset-name, the file's URL follows from the coordinates, so a
+    /// repository without directory listings works too.
+    fn resolve(
+        &self,
+        http: &HttpContext,
+        url: &str,
+        selector: &Selector,
+    ) -> Result<ResolvedAsset, Box<dyn std::error::Error>> {
+        let Some(name) = selector.asset_name else {
+            return selector.select(self.fetch_release(http, url, selector.latest)?);
+        };
+        let coordinates = parse_maven_url(url).ok_or("Not a valid Maven URL")?;
+        let version = version(http, &coordinates, selector.latest)?;
+        let dir = format!("{}/{version}", coordinates.artifact_url());
+        let asset = |name: String| Asset {
+            url: format!("{dir}/{name}"),
+            name,
+            digest: None,
+            size: None,
+            content_type: None,
+            updated_at: None,
+        };
+        // Maven repositories keep a checksum beside every file; SHA-256 is
+        // optional, so it's only checked if it's there.
+        let sum = asset(format!("{name}.sha256"));
+        let checksum = http
+            .send(http.head(&sum.url))
+            .is_ok_and(|r| r.status().is_success())
+            .then_some(sum);
+        let asset = asset(name.to_string());
+        eprintln!("Found: {} {version} → {name}", coordinates.artifact);
+        Ok(ResolvedAsset {
+            release: release(&coordinates, version, vec![asset.clone()]),
+            asset,
+            checksum,
+        })
+    }
+}
+
+/// The version the URL names, or the latest in the artifact's metadata.
+fn version(
+    http: &HttpContext,
+    coordinates: &Coordinates,
+    latest: Latest,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if let Some(version) = coordinates.version {
+        return Ok(version.to_string());
+    }
+    let metadata = apicache::fetch_text(
+        http,
+        http.get(&format!(
+            "{}/maven-metadata.xml",
+            coordinates.artifact_url()
+        )),
+    )?;
+    latest_version(&metadata, latest).ok_or_else(|| {
+        format!(
+            "{}:{} has no versions in its maven-metadata.xml",
+            coordinates.group, coordinates.artifact
+        )
+        .into()
+    })
+}
+
+fn release(coordinates: &Coordinates, version: String, assets: Vec<Asset>) -> Release {
+    Release {
+        owner: coordinates.group.to_string(),
+        repo: coordinates.artifact.to_string(),
+        tag: version,
+        assets,
+        notes: None,
+        source_tarball: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_maven_url() {
+        assert_eq!(
+            parse_maven_url("maven://repo1.maven.org/maven2/com.acme.tools:tool@1.2.0"),
+            Some(Coordinates {
+                repository: "https://repo1.maven.org/maven2".into(),
+                group: "com.acme.tools",
+                artifact: "tool",
+                version: Some("1.2.0"),
+            })
+        );
+        let azure = parse_maven_url(
+            "maven://pkgs.dev.azure.com/org/_packaging/feed/maven/v1/com.acme:tool/",
+        )
+        .unwrap();
+        assert_eq!(azure.version, None);
+        assert_eq!(
+            azure.artifact_url(),
+            "https://pkgs.dev.azure.com/org/_packaging/feed/maven/v1/com/acme/tool"
+        );
+        assert_eq!(
+            parse_maven_url("maven+http://nexus:8081/releases/com.acme:tool")
+                .unwrap()
+                .repository,
+            "http://nexus:8081/releases"
+        );
+        assert!(parse_maven_url("maven://repo.example.com/com.acme").is_none());
+        assert!(parse_maven_url("maven://repo.example.com/a:b:c").is_none());
+        assert!(parse_maven_url("https://repo.example.com/com.acme:tool").is_none());
+    }
+
+    #[test]
+    fn test_latest_version() {
+        let metadata = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <metadata>
+              <groupId>com.acme</groupId>
+              <artifactId>tool</artifactId>
+              <versioning>
+                <latest>2.1.0-SNAPSHOT</latest>
+                <release>1.4.2</release>
+                <versions>
+                  <version>1.10.0</version>
+                  <version>1.4.2</version>
+                  <version>2.1.0-SNAPSHOT</version>
+                </versions>
+              </versioning>
+            </metadata>"#;
+        assert_eq!(
+            latest_version(metadata, Latest::Date).as_deref(),
+            Some("1.4.2")
+        );
+        assert_eq!(
+            latest_version(metadata, Latest::Semver).as_deref(),
+            Some("1.10.0")
+        );
+        let bare = "<metadata><versioning><versions><version>0.9</version>\
+                    <version>1.0</version></versions></versioning></metadata>";
+        assert_eq!(latest_version(bare, Latest::Date).as_deref(), Some("1.0"));
+        assert_eq!(latest_version("<metadata/>", Latest::Date), None);
+    }
+
+    #[test]
+    fn test_is_artifact_file() {
+        assert!(is_artifact_file("tool-1.0-linux-x86_64.tar.gz"));
+        assert!(is_artifact_file("tool-1.0-linux-x86_64.tar.gz.sha256"));
+        assert!(!is_artifact_file("tool-1.0.pom"));
+        assert!(!is_artifact_file("tool-1.0.jar.sha1"));
+        assert!(!is_artifact_file("maven-metadata.xml"));
+    }
+}
@@ -0,0 +1,200 @@
+use crate::http::HttpContext;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, RandomState};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The order to try a download's mirrors in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Strategy {
+    /// The URL itself, then each mirror as configured.
+    #[default]
+    First,
+    /// Whichever answers a HEAD request soonest; unreachable ones go last.
+    Fastest,
+    /// A random order, to spread load across them.
+    Random,
+}
+
+impl std::str::FromStr for Strategy {
+    type Err = String;
+
+    /// Parses "first", "fastest", or "random".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first" => Ok(Strategy::First),
+            "fastest" => Ok(Strategy::Fastest),
+            "random" => Ok(Strategy::Random),
+            _ => Err(format!(
+                "unknown mirror strategy {s} (expected first, fastest, or random)"
+            )),
+        }
+    }
+}
+
+/// The config's `[mirrors]`: URL prefix → prefixes serving the same files.
+///
+/// ```toml
+/// [mirrors]
+/// "https://github.com/" = ["https://gh.mirror.internal/github.com/"]
+/// ```
+pub struct Mirrors {
+    /// Longest prefix first, so the most specific one applies.
+    prefixes: Vec<(String, Vec<String>)>,
+    /// How long each host took to answer a probe (None: it didn't), so a
+    /// batch probes a host once.
+    latency: Mutex<HashMap<String, Option<Duration>>>,
+}
+
+impl Mirrors {
+    pub fn new(config: BTreeMap<String, Vec<String>>) -> Self {
+        let mut prefixes: Vec<_> = config.into_iter().collect();
+        prefixes.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        Mirrors {
+            prefixes,
+            latency: Mutex::default(),
+        }
+    }
+
+    /// `url` and its copies on each mirror, as configured.
+    pub fn candidates(&self, url: &str) -> Vec<String> {
+        let mut urls = vec![url.to_string()];
+        if let Some((prefix, mirrors)) = self.prefixes.iter().find(|(p, _)| url.starts_with(p)) {
+            let rest = &url[prefix.len()..];
+            urls.extend(mirrors.iter().map(|mirror| format!("{mirror}{rest}")));
+        }
+        urls
+    }
+
+    /// The URLs to try for `url`, in the order `strategy` picks.
+    pub fn order(
+        &self,
+        http: &HttpContext,
+        url: &str,
+        strategy: Strategy,
+        verbose: bool,
+    ) -> Vec<String> {
+        let mut urls = self.candidates(url);
+        if urls.len() < 2 {
+            return urls;
+        }
+        match strategy {
+            Strategy::First => {}
+            Strategy::Random => shuffle(&mut urls),
+            Strategy::Fastest => {
+                let known = self.probe(http, &urls);
+                let latency = |url: &str| known.get(&host(url)).copied().flatten();
+                // A stable sort: unreachable mirrors keep their configured order.
+                urls.sort_by_key(|url| latency(url).unwrap_or(Duration::MAX));
+                if verbose {
+                    for url in &urls {
+                        match latency(url) {
+                            Some(time) => eprintln!("Mirror {url}: {}ms", time.as_millis()),
+                            None => eprintln!("Mirror {url}: unreachable"),
+                        }
+                    }
+                }
+            }
+        }
+        urls
+    }
+
+    /// HEADs every URL whose host hasn't been probed yet, all at once, and
+    /// returns the latency of every host probed so far.
+    fn probe(&self, http: &HttpContext, urls: &[String]) -> HashMap<String, Option<Duration>> {
+        let unprobed: Vec<&String> = {
+            let latency = self.latency.lock().unwrap();
+            urls.iter()
+                .filter(|url| !latency.contains_key(&host(url)))
+                .collect()
+        };
+        let results: Vec<(String, Option<Duration>)> = std::thread::scope(|scope| {
+            let probes: Vec<_> = unprobed
+                .iter()
+                .map(|url| scope.spawn(|| (host(url), head_latency(http, url))))
+                .collect();
+            probes
+                .into_iter()
+                .filter_map(|probe| probe.join().ok())
+                .collect()
+        });
+
+        let mut latency = self.latency.lock().unwrap();
+        latency.extend(results);
+        latency.clone()
+    }
+}
+
+/// How long `url` takes to answer a HEAD request, if it answers with success.
+fn head_latency(http: &HttpContext, url: &str) -> Option<Duration> {
+    // Probes shouldn't hold up the download for long.
+    const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+    let started = Instant::now();
+    let response = http.send(http.head(url).timeout(PROBE_TIMEOUT)).ok()?;
+    response.status().is_success().then(|| started.elapsed())
+}
+
+/// What latency is remembered by: a URL's scheme, host, and port.
+fn host(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) => parsed.origin().ascii_serialization(),
+        Err(_) => url.to_string(),
+    }
+}
+
+/// Fisher–Yates, seeded by the standard library's per-process random keys.
+fn shuffle<T>(items: &mut [T]) {
+    let random = RandomState::new();
+    for i in (1..items.len()).rev() {
+        let j = (random.hash_one(i) % (i as u64 + 1)) as usize;
+        items.swap(i, j);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates_use_longest_prefix() {
+        let mirrors = Mirrors::new(BTreeMap::from([
+            (
+                "https://example.com/".to_string(),
+                vec!["https://a.mirror/".to_string()],
+            ),
+            (
+                "https://example.com/releases/".to_string(),
+                vec![
+                    "https://b.mirror/r/".to_string(),
+                    "https://c.mirror/".to_string(),
+                ],
+            ),
+        ]));
+        assert_eq!(
+            mirrors.candidates("https://example.com/releases/tool.tar.gz"),
+            [
+                "https://example.com/releases/tool.tar.gz",
+                "https://b.mirror/r/tool.tar.gz",
+                "https://c.mirror/tool.tar.gz",
+            ]
+        );
+        assert_eq!(
+            mirrors.candidates("https://other.org/tool.zip"),
+            ["https://other.org/tool.zip"]
+        );
+    }
+
+    #[test]
+    fn test_shuffle_keeps_items() {
+        let mut items: Vec<u32> = (0..20).collect();
+        shuffle(&mut items);
+        items.sort();
+        assert_eq!(items, (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_parse_strategy() {
+        assert_eq!("fastest".parse(), Ok(Strategy::Fastest));
+        assert!("slowest".parse::<Strategy>().is_err());
+    }
+}
@@ -0,0 +1,81 @@
+use std::fs;
+use std::path::Path;
+
+/// A file mapped into memory, read-only, for hashing and unzipping large
+/// local files without copying them through a buffer (`--features mmap`).
+///
+/// The mapping reads the file as it is on disk: should something truncate
+/// the file while it's mapped, touching the lost pages kills the process
+/// (SIGBUS). dex only maps files it just downloaded or was pointed at, and
+/// the feature is off by default.
+pub struct Mapped {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+// The mapping is read-only, so sharing it between threads is as safe as
+// sharing a `&[u8]`.
+unsafe impl Send for Mapped {}
+unsafe impl Sync for Mapped {}
+
+/// Maps the file at `path`, or returns None if it can't be (it's empty, or
+/// the system refuses), in which case the caller reads it the usual way.
+pub fn map(path: &Path) -> Option<Mapped> {
+    use std::os::fd::AsRawFd;
+    let file = fs::File::open(path).ok()?;
+    let len = usize::try_from(file.metadata().ok()?.len()).ok()?;
+    if len == 0 {
+        return None;
+    }
+    // SAFETY: a fresh read-only, private mapping of a file we hold open;
+    // the result is checked before use.
+    let ptr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_PRIVATE,
+            file.as_raw_fd(),
+            0,
+        )
+    };
+    // The mapping outlives the file descriptor, which can close here.
+    (ptr != libc::MAP_FAILED).then_some(Mapped { ptr, len })
+}
+
+impl std::ops::Deref for Mapped {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: `ptr` is a live mapping of `len` readable bytes until drop.
+        unsafe { std::slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mapped {
+    fn drop(&mut self) {
+        // SAFETY: unmaps exactly what `map` mapped, once.
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_map() {
+        let dir = std::env::temp_dir().join(format!("dex-test-mmap-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data");
+        fs::write(&path, b"mapped bytes").unwrap();
+        assert_eq!(&*map(&path).unwrap(), b"mapped bytes");
+
+        fs::write(&path, b"").unwrap();
+        assert!(map(&path).is_none());
+        assert!(map(&dir.join("missing")).is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,346 @@
+use crate::apicache;
+use crate::github;
+use crate::http::HttpContext;
+use crate::index;
+use crate::layout;
+use crate::platform::Asset;
+use crate::provider::{Latest, Provider, Release};
+use reqwest::Url;
+use serde::Deserialize;
+use std::collections::HashSet;
+
+/// How many pages of search results (50 files each) dex reads before
+/// giving up and asking for a narrower pattern.
+const MAX_PAGES: usize = 100;
+
+// Only the fields we need from a page of the asset search.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchPage {
+    items: Vec<NexusAsset>,
+    /// Passed back for the next page; absent on the last one.
+    continuation_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NexusAsset {
+    download_url: String,
+    /// Where the file is in the repository: "mytool/1.2.0/mytool-linux.tar.gz".
+    path: String,
+    #[serde(default)]
+    checksum: Checksums,
+    content_type: Option<String>,
+    last_modified: Option<String>,
+    /// Only reported by Nexus 3.39 and later.
+    file_size: Option<u64>,
+}
+
+#[derive(Deserialize, Default)]
+struct Checksums {
+    sha256: Option<String>,
+}
+
+/// Files in a Sonatype Nexus repository, named by a path pattern.
+#[derive(Debug, PartialEq, Eq)]
+struct Location<'u> {
+    /// The server's base URL, with its context path if it has one ("/nexus").
+    base: String,
+    repository: &'u str,
+    /// The files' paths in the repository: a glob, or a folder to look in.
+    /// Empty for the whole repository.
+    pattern: &'u str,
+}
+
+/// Parses a Nexus URL: the repository's browse URL with `nexus://` for its
+/// scheme (`nexus+http://` for plain HTTP), then a path pattern.
+///
+///   nexus://nexus.example.com/repository/tools/mytool
+///   nexus://repo.internal/nexus/repository/raw-hosted/mytool/*/mytool-*.tar.gz
+fn parse_nexus_url(url: &str) -> Option<Location<'_>> {
+    let (scheme, rest) = match url.strip_prefix("nexus://") {
+        Some(rest) => ("https", rest),
+        None => ("http", url.strip_prefix("nexus+http://")?),
+    };
+    let (server, rest) = rest.split_once("/repository/")?;
+    let (repository, pattern) = rest.split_once('/').unwrap_or((rest, ""));
+    if server.is_empty() || repository.is_empty() {
+        return None;
+    }
+    Some(Location {
+        base: format!("{scheme}://{server}"),
+        repository,
+        pattern: pattern.trim_matches('/'),
+    })
+}
+
+impl Location<'_> {
+    /// Whether a file at `path` in the repository is one the URL asks for.
+    /// A pattern without wildcards names a folder, searched all the way down.
+    fn includes(&self, path: &str) -> bool {
+        let path = path.trim_start_matches('/');
+        if self.pattern.is_empty() {
+            true
+        } else if self.pattern.contains(['*', '?']) {
+            index::glob_match(self.pattern, path)
+        } else {
+            path.starts_with(&format!("{}/", self.pattern))
+        }
+    }
+
+    /// The search API's `name` filter for the pattern. Raw repositories name
+    /// a file by its path, so there the server leaves out most of what
+    /// `includes` would. None when there's no pattern, or it starts with a
+    /// wildcard, which the search doesn't accept.
+    fn search_name(&self) -> Option<String> {
+        if self.pattern.is_empty() || self.pattern.starts_with(['*', '?']) {
+            None
+        } else if self.pattern.contains(['*', '?']) {
+            Some(self.pattern.to_string())
+        } else {
+            Some(format!("{}/*", self.pattern))
+        }
+    }
+}
+
+/// The files the URL asks for, paging through the search API with `name` as
+/// its filter (or none). Fails rather than loop if the server repeats a
+/// continuation token or the results run past [`MAX_PAGES`].
+fn search(
+    http: &HttpContext,
+    location: &Location,
+    name: Option<&str>,
+) -> Result<Vec<NexusAsset>, Box<dyn std::error::Error>> {
+    let mut found = Vec::new();
+    let mut seen = HashSet::new();
+    let mut token: Option<String> = None;
+    for _ in 0..MAX_PAGES {
+        let mut page_url = Url::parse(&format!("{}/service/rest/v1/search/assets", location.base))?;
+        page_url
+            .query_pairs_mut()
+            .append_pair("repository", location.repository);
+        if let Some(name) = name {
+            page_url.query_pairs_mut().append_pair("name", name);
+        }
+        if let Some(token) = &token {
+            page_url
+                .query_pairs_mut()
+                .append_pair("continuationToken", token);
+        }
+        let body = apicache::fetch_text(http, http.get(page_url.as_str()))?;
+        let page: SearchPage = serde_json::from_str(&body)?;
+        found.extend(
+            page.items
+                .into_iter()
+                .filter(|a| location.includes(&a.path)),
+        );
+        token = match page.continuation_token {
+            None => return Ok(found),
+            Some(next) if !seen.insert(next.clone()) => {
+                return Err(
+                    format!("{} repeated a search continuation token", location.base).into(),
+                );
+            }
+            next => next,
+        };
+    }
+    Err(format!(
+        "more than {MAX_PAGES} pages of files in the {} repository; name fewer with a narrower path",
+        location.repository
+    )
+    .into())
+}
+
+/// Picks the latest version among `(version, last modified)` pairs: the one
+/// modified most recently, or by `latest` the highest.
+fn latest_version(versions: &[(String, Option<&str>)], latest: Latest) -> Option<String> {
+    let best = match latest {
+        Latest::Date => versions.iter().max_by_key(|(_, modified)| *modified),
+        Latest::Semver => versions
+            .iter()
+            .filter_map(|v| Some((github::tag_version(&v.0)?, v)))
+            .max_by(|a, b| a.0.cmp(&b.0))
+            .map(|(_, v)| v),
+    };
+    best.map(|(version, _)| version.clone())
+}
+
+/// Files in a Sonatype Nexus Repository (3.x) repository of any format.
+/// There are no releases, so the assets the search API finds are grouped by
+/// the version in their folder or name, and the latest version's are
+/// offered. Credentials come from the config's `[hosts]` section.
+pub struct Nexus;
+
+impl Provider for Nexus {
+    fn name(&self) -> &'static str {
+        "Nexus repository"
+    }
+
+    fn matches(&self, url: &str) -> bool {
+        parse_nexus_url(url).is_some()
+    }
+
+    fn repo_name<'u>(&self, url: &'u str) -> Option<&'u str> {
+        let location = parse_nexus_url(url)?;
+        // The deepest folder before any wildcard, e.g. "mytool" in
+        // "team/mytool/*/mytool-*.tar.gz"; the repository if there's none.
+        location
+            .pattern
+            .split('/')
+            .take_while(|s| !s.contains(['*', '?']))
+            .filter(|s| !s.is_empty())
+            .last()
+            .or(Some(location.repository))
+    }
+
+    fn fetch_release(
+        &self,
+        http: &HttpContext,
+        url: &str,
+        latest: Latest,
+    ) -> Result<Release, Box<dyn std::error::Error>> {
+        let location = parse_nexus_url(url).ok_or("Not a valid Nexus URL")?;
+        let name = location.search_name();
+        let mut found = search(http, &location, name.as_deref())?;
+        // Other formats name files by their package (a Maven artifact ID,
+        // say), which the path filter never matches; search them unfiltered.
+        if found.is_empty() && name.is_some() {
+            found = search(http, &location, None)?;
+        }
+        if found.is_empty() {
+            return Err(format!(
+                "no files matching {:?} in the {} repository",
+                location.pattern, location.repository
+            )
+            .into());
+        }
+
+        let versions: Vec<(String, Option<&str>)> = found
+            .iter()
+            .filter_map(|a| {
+                let version = layout::guess_path_version(&a.path)?;
+                Some((version, a.last_modified.as_deref()))
+            })
+            .collect();
+        // No tag when no path carries a version: all the files found are
+        // offered, under "latest".
+        let tag = latest_version(&versions, latest);
+        let assets = found
+            .iter()
+            .filter(|a| tag.is_none() || layout::guess_path_version(&a.path) == tag)
+            .map(|a| Asset {
+                name: a.path.rsplit('/').next().unwrap_or(&a.path).to_string(),
+                url: a.download_url.clone(),
+                digest: a
+                    .checksum
+                    .sha256
+                    .as_ref()
+                    .map(|hex| format!("sha256:{hex}")),
+                size: a.file_size,
+                content_type: a.content_type.clone(),
+                updated_at: a.last_modified.clone(),
+            })
+            .collect();
+        Ok(Release {
+            owner: location.repository.to_string(),
+            repo: self
+                .repo_name(url)
+                .unwrap_or(location.repository)
+                .to_string(),
+            tag: tag.unwrap_or_else(|| "latest".to_string()),
+            assets,
+            notes: None,
+            source_tarball: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nexus_url() {
+        assert_eq!(
+            parse_nexus_url("nexus://repo.internal/nexus/repository/raw-hosted/mytool/"),
+            Some(Location {
+                base: "https://repo.internal/nexus".into(),
+                repository: "raw-hosted",
+                pattern: "mytool",
+            })
+        );
+        let http = parse_nexus_url("nexus+http://nexus:8081/repository/tools").unwrap();
+        assert_eq!(http.base, "http://nexus:8081");
+        assert_eq!((http.repository, http.pattern), ("tools", ""));
+        assert!(parse_nexus_url("nexus://nexus.example.com/tools/mytool").is_none());
+        assert!(parse_nexus_url("https://nexus.example.com/repository/tools").is_none());
+
+        let name = |url| Nexus.repo_name(url);
+        assert_eq!(
+            name("nexus://n.io/repository/tools/team/mytool/*/mytool-*.tar.gz"),
+            Some("mytool")
+        );
+        assert_eq!(name("nexus://n.io/repository/tools"), Some("tools"));
+    }
+
+    #[test]
+    fn test_includes() {
+        let folder = parse_nexus_url("nexus://n.io/repository/tools/mytool").unwrap();
+        assert!(folder.includes("mytool/1.2.0/mytool-linux.tar.gz"));
+        assert!(folder.includes("/mytool/mytool-1.2.0-linux.tar.gz"));
+        assert!(!folder.includes("mytool2/1.0/mytool2.tar.gz"));
+        let glob = parse_nexus_url("nexus://n.io/repository/tools/mytool/*/*.zip").unwrap();
+        assert!(glob.includes("mytool/1.2.0/mytool-windows.zip"));
+        assert!(!glob.includes("mytool/1.2.0/mytool-linux.tar.gz"));
+    }
+
+    #[test]
+    fn test_search_name() {
+        let name = |url| parse_nexus_url(url).unwrap().search_name();
+        assert_eq!(
+            name("nexus://n.io/repository/tools/mytool/").as_deref(),
+            Some("mytool/*")
+        );
+        assert_eq!(
+            name("nexus://n.io/repository/tools/mytool/*/*.zip").as_deref(),
+            Some("mytool/*/*.zip")
+        );
+        assert_eq!(name("nexus://n.io/repository/tools/*/mytool.zip"), None);
+        assert_eq!(name("nexus://n.io/repository/tools"), None);
+    }
+
+    #[test]
+    fn test_parse_search_page() {
+        let json = r#"{
+            "items": [{
+                "downloadUrl": "https://n.io/repository/tools/mytool/1.2.0/mytool-linux-amd64.tar.gz",
+                "path": "mytool/1.2.0/mytool-linux-amd64.tar.gz",
+                "id": "dG9vbHM6YWJj",
+                "repository": "tools",
+                "format": "raw",
+                "checksum": {"sha1": "aa", "sha256": "bb", "md5": "cc"},
+                "contentType": "application/x-gzip",
+                "lastModified": "2024-05-02T10:00:00.000+00:00",
+                "fileSize": 1024
+            }],
+            "continuationToken": "88491cd1d185dd13"
+        }"#;
+        let page: SearchPage = serde_json::from_str(json).unwrap();
+        assert_eq!(page.items[0].checksum.sha256.as_deref(), Some("bb"));
+        assert_eq!(page.items[0].file_size, Some(1024));
+        assert!(page.continuation_token.is_some());
+
+        let versions = [
+            ("1.10.0".to_string(), Some("2024-04-01T00:00:00.000+00:00")),
+            ("1.9.3".to_string(), Some("2024-05-01T00:00:00.000+00:00")),
+        ];
+        assert_eq!(
+            latest_version(&versions, Latest::Date).as_deref(),
+            Some("1.9.3")
+        );
+        assert_eq!(
+            latest_version(&versions, Latest::Semver).as_deref(),
+            Some("1.10.0")
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::io::{IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+// ANSI styles for the terminal rendering.
+const BOLD: &str = "\x1b[1m";
+const UNDERLINE: &str = "\x1b[4m";
+const DIM: &str = "\x1b[2m";
+const CYAN: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// Shows a release's notes: paged through $PAGER when standard output is a
+/// terminal, and rendered with styling when `--color` allows.
+pub fn show(title: &str, markdown: &str) {
+    let interactive = std::io::stdout().is_terminal();
+    let styled = crate::color::stdout();
+    let mut text = if styled {
+        format!("{BOLD}{UNDERLINE}{title}{RESET}\n\n")
+    } else {
+        format!("{title}\n\n")
+    };
+    text.push_str(&render(markdown, styled));
+
+    if !interactive || page(&text).is_err() {
+        print!("{text}");
+    }
+}
+
+/// Sends `text` through the user's pager ($PAGER, or `less -R`), waiting
+/// until it's closed.
+fn page(text: &str) -> std::io::Result<()> {
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| {
+        if cfg!(windows) {
+            "more".to_string()
+        } else {
+            "less -R".to_string()
+        }
+    });
+    let mut words = pager.split_whitespace();
+    let program = words.next().ok_or(std::io::ErrorKind::NotFound)?;
+    let mut child = Command::new(program)
+        .args(words)
+        .stdin(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The user may quit the pager before reading everything.
+        let _ = stdin.write_all(text.as_bytes());
+    }
+    child.wait()?;
+    Ok(())
+}
+
+/// Renders markdown for the terminal: headings in bold, list bullets as "•",
+/// code in color, and links as "text (url)". Without `styled`, only the
+/// markup characters are tidied away.
+pub fn render(markdown: &str, styled: bool) -> String {
+    let style = |code: &'static str| if styled { code } else { "" };
+    let mut out = String::new();
+    let mut in_fence = false;
+
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            out.push_str(&format!("    {}{line}{}\n", style(DIM), style(RESET)));
+            continue;
+        }
+
+        let heading = trimmed
+            .find(|c| c != '#')
+            .filter(|&level| (1..=6).contains(&level) && trimmed[level..].starts_with(' '));
+        if let Some(level) = heading {
+            let text = inline(trimmed[level..].trim(), styled);
+            let underline = if level == 1 { style(UNDERLINE) } else { "" };
+            out.push_str(&format!(
+                "{}{underline}{text}{}\n",
+                style(BOLD),
+                style(RESET)
+            ));
+        } else if let Some(item) = ["- ", "* ", "+ "]
+            .iter()
+            .find_map(|bullet| trimmed.strip_prefix(bullet))
+        {
+            let indent = &line[..line.len() - trimmed.len()];
+            out.push_str(&format!("{indent}• {}\n", inline(item, styled)));
+        } else {
+            out.push_str(&inline(line, styled));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Renders the inline markup of one line: **bold**, `code`, and [links](url).
+fn inline(text: &str, styled: bool) -> String {
+    let style = |code: &'static str| if styled { code } else { "" };
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(['*', '`', '[']) {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start..];
+
+        if let Some(bold) = tail.strip_prefix("**")
+            && let Some(end) = bold.find("**")
+        {
+            out.push_str(&format!("{}{}{}", style(BOLD), &bold[..end], style(RESET)));
+            rest = &bold[end + 2..];
+        } else if let Some(code) = tail.strip_prefix('`')
+            && let Some(end) = code.find('`')
+        {
+            out.push_str(&format!("{}{}{}", style(CYAN), &code[..end], style(RESET)));
+            rest = &code[end + 1..];
+        } else if let Some(label) = tail.strip_prefix('[')
+            && let Some((label, after)) = label.split_once("](")
+            && let Some((url, after)) = after.split_once(')')
+        {
+            out.push_str(&format!(
+                "{label} ({}{url}{})",
+                style(UNDERLINE),
+                style(RESET)
+            ));
+            rest = after;
+        } else {
+            out.push_str(&tail[..1]);
+            rest = &tail[1..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_plain() {
+        let markdown = "## What's Changed\n\
+                        * Fix **crash** in `--json` by @someone in [#12](https://example.com/12)\n\
+                        \n\
+                        ```\n\
+                        cargo install tool\n\
+                        ```";
+        assert_eq!(
+            render(markdown, false),
+            "What's Changed\n\
+             • Fix crash in --json by @someone in #12 (https://example.com/12)\n\
+             \n    cargo install tool\n"
+        );
+    }
+
+    #[test]
+    fn test_render_styled() {
+        assert_eq!(render("# v1.0", true), "\x1b[1m\x1b[4mv1.0\x1b[0m\n");
+        assert_eq!(render("use `dex`", true), "use \x1b[36mdex\x1b[0m\n");
+        // Stray markup characters are left as they are.
+        assert_eq!(render("2 * 3 [x", true), "2 * 3 [x\n");
+    }
+}
@@ -0,0 +1,16 @@
+/// Rings the terminal bell. It goes to stderr, which is still the terminal
+/// when stdout is piped somewhere.
+pub fn bell() {
+    eprint!("\x07");
+}
+
+/// Shows a desktop notification (D-Bus on Linux and BSD, Notification
+/// Center on macOS, toasts on Windows).
+pub fn desktop(summary: &str, body: &str) -> Result<(), Box<dyn std::error::Error>> {
+    notify_rust::Notification::new()
+        .appname("dex")
+        .summary(summary)
+        .body(body)
+        .show()?;
+    Ok(())
+}
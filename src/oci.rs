@@ -0,0 +1,315 @@
+use crate::verify::{self, Algorithm, Hasher};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// File names starting with this mark deletions in an OCI layer, so a file
+/// really called that can't be put in one.
+const WHITEOUT_PREFIX: &str = ".wh.";
+
+/// An OCI layer tarball --oci-layer wrote.
+#[derive(Debug)]
+pub struct Layer {
+    /// `sha256:<hex>` of the tarball, which is both its digest and its
+    /// "diff ID", since the layer isn't compressed.
+    pub digest: String,
+    pub size: u64,
+    /// Files left out because their names would read as whiteouts.
+    pub skipped: Vec<PathBuf>,
+}
+
+/// What goes in the layer at one path.
+enum Item {
+    Dir(Option<PathBuf>),
+    File(PathBuf),
+}
+
+fn slash_path(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(unix)]
+fn mode(_path: &Path, metadata: &std::fs::Metadata) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    metadata.permissions().mode() & 0o7777
+}
+
+/// Windows has no mode bits to copy; executables get 0755 like they would
+/// from a Unix archive.
+#[cfg(not(unix))]
+fn mode(path: &Path, metadata: &std::fs::Metadata) -> u32 {
+    match metadata.is_dir() || crate::binaries::is_executable(path) {
+        true => 0o755,
+        false => 0o644,
+    }
+}
+
+fn mtime(metadata: &std::fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Packs `files` (from `deploy::relative_files`) into the uncompressed tar
+/// `out`, as a layer for an OCI image: paths are relative to the image's
+/// root, every directory gets an entry before its contents, entries are
+/// sorted, and everything is owned by root. Modes, modification times, and
+/// symlinks are kept. The tarball is written under a temporary name and
+/// renamed into place, so a failure leaves no half-written layer.
+pub fn write_layer(
+    files: &[(PathBuf, PathBuf)],
+    out: &Path,
+) -> Result<Layer, Box<dyn std::error::Error>> {
+    let mut items: BTreeMap<String, Item> = BTreeMap::new();
+    let mut skipped = Vec::new();
+    for (source, relative) in files {
+        let whiteout = relative
+            .components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with(WHITEOUT_PREFIX));
+        if whiteout {
+            skipped.push(relative.clone());
+            continue;
+        }
+        for parent in relative.ancestors().skip(1) {
+            if parent.as_os_str().is_empty() {
+                break;
+            }
+            // The extracted directory it came from, to take its mode and mtime.
+            let depth = relative.components().count() - parent.components().count();
+            let dir = source.ancestors().nth(depth).map(Path::to_path_buf);
+            items.entry(slash_path(parent)).or_insert(Item::Dir(dir));
+        }
+        let item = match source.is_dir() && !source.is_symlink() {
+            true => Item::Dir(Some(source.clone())),
+            false => Item::File(source.clone()),
+        };
+        items.insert(slash_path(relative), item);
+    }
+
+    let partial = out.with_extension(format!("tar.{}", std::process::id()));
+    let written = (|| -> Result<(), Box<dyn std::error::Error>> {
+        let mut builder = tar::Builder::new(std::fs::File::create(&partial)?);
+        for (path, item) in &items {
+            let mut header = tar::Header::new_gnu();
+            header.set_uid(0);
+            header.set_gid(0);
+            match item {
+                Item::Dir(source) => {
+                    let found = source
+                        .as_deref()
+                        .and_then(|s| Some((s, std::fs::metadata(s).ok()?)));
+                    header.set_entry_type(tar::EntryType::Directory);
+                    header.set_mode(found.as_ref().map_or(0o755, |(s, m)| mode(s, m)));
+                    header.set_mtime(found.as_ref().map_or(0, |(_, m)| mtime(m)));
+                    header.set_size(0);
+                    builder.append_data(&mut header, format!("{path}/"), std::io::empty())?;
+                }
+                Item::File(source) => {
+                    let metadata = std::fs::symlink_metadata(source)?;
+                    header.set_mtime(mtime(&metadata));
+                    if metadata.is_symlink() {
+                        header.set_entry_type(tar::EntryType::Symlink);
+                        header.set_mode(0o777);
+                        header.set_size(0);
+                        builder.append_link(&mut header, path, std::fs::read_link(source)?)?;
+                    } else {
+                        header.set_entry_type(tar::EntryType::Regular);
+                        header.set_mode(mode(source, &metadata));
+                        header.set_size(metadata.len());
+                        builder.append_data(&mut header, path, std::fs::File::open(source)?)?;
+                    }
+                }
+            }
+        }
+        builder.into_inner()?.sync_all()?;
+        std::fs::rename(&partial, out)?;
+        Ok(())
+    })();
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&partial);
+        return Err(e);
+    }
+
+    let digest = verify::hash_file(out, Algorithm::Sha256)?;
+    Ok(Layer {
+        digest: format!("sha256:{}", digest.hex),
+        size: std::fs::metadata(out)?.len(),
+        skipped,
+    })
+}
+
+/// The name OCI image configs use for a dex platform (`macos` → `darwin`).
+fn oci_os(platform: &str) -> &str {
+    match platform {
+        "macos" => "darwin",
+        other => other,
+    }
+}
+
+/// The name OCI image configs use for a dex arch, Go's (`x86_64` → `amd64`).
+fn oci_arch(arch: &str) -> &str {
+    match arch {
+        "x86_64" | "amd64" => "amd64",
+        "arm64" | "aarch64" => "arm64",
+        "x86" | "i386" | "i686" => "386",
+        "armv7" | "arm" => "arm",
+        "powerpc64le" | "ppc64le" => "ppc64le",
+        "riscv64" | "riscv64gc" => "riscv64",
+        other => other,
+    }
+}
+
+/// Writes a minimal image config and manifest for an image made of just
+/// `layer`, next to it as `<name>.config.json` and `<name>.manifest.json`,
+/// and returns their paths. `entrypoint` is the path in the image the
+/// container runs, if one binary stood out.
+pub fn write_manifest(
+    layer_path: &Path,
+    layer: &Layer,
+    platform: &str,
+    arch: &str,
+    entrypoint: Option<&str>,
+) -> Result<(PathBuf, PathBuf), Box<dyn std::error::Error>> {
+    let mut config = serde_json::json!({
+        "architecture": oci_arch(arch),
+        "os": oci_os(platform),
+        "rootfs": { "type": "layers", "diff_ids": [layer.digest] },
+        "config": {},
+    });
+    if let Some(entrypoint) = entrypoint {
+        config["config"]["Entrypoint"] = serde_json::json!([entrypoint]);
+    }
+    let config = serde_json::to_vec_pretty(&config)?;
+    let mut hasher = Hasher::new(Algorithm::Sha256);
+    hasher.update(&config);
+    let manifest = serde_json::json!({
+        "schemaVersion": 2,
+        "mediaType": "application/vnd.oci.image.manifest.v1+json",
+        "config": {
+            "mediaType": "application/vnd.oci.image.config.v1+json",
+            "digest": format!("sha256:{}", hasher.finalize().hex),
+            "size": config.len(),
+        },
+        "layers": [{
+            "mediaType": "application/vnd.oci.image.layer.v1.tar",
+            "digest": layer.digest,
+            "size": layer.size,
+        }],
+    });
+
+    let stem = layer_path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    let stem = stem.strip_suffix(".tar").unwrap_or(&stem).to_string();
+    let config_path = layer_path.with_file_name(format!("{stem}.config.json"));
+    let manifest_path = layer_path.with_file_name(format!("{stem}.manifest.json"));
+    std::fs::write(&config_path, config)?;
+    std::fs::write(&manifest_path, serde_json::to_vec_pretty(&manifest)?)?;
+    Ok((config_path, manifest_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("dex-test-oci-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_write_layer() {
+        let dir = temp_dir("layer");
+        let root = dir.join("out");
+        std::fs::create_dir_all(root.join("tool-1.0/bin")).unwrap();
+        std::fs::write(root.join("tool-1.0/bin/tool"), b"\x7fELF").unwrap();
+        std::fs::write(root.join("tool-1.0/.wh.hidden"), b"").unwrap();
+        std::fs::write(root.join("tool-1.0/README"), b"hi").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let exec = std::fs::Permissions::from_mode(0o755);
+            std::fs::set_permissions(root.join("tool-1.0/bin/tool"), exec).unwrap();
+        }
+        let files = crate::deploy::relative_files(
+            &root,
+            &[
+                root.join("tool-1.0/bin/tool"),
+                root.join("tool-1.0/.wh.hidden"),
+                root.join("tool-1.0/README"),
+            ],
+        );
+        let out = dir.join("layer.tar");
+        let layer = write_layer(&files, &out).unwrap();
+        assert_eq!(layer.skipped, vec![PathBuf::from(".wh.hidden")]);
+        assert_eq!(
+            layer.digest,
+            format!(
+                "sha256:{}",
+                verify::hash_file(&out, Algorithm::Sha256).unwrap().hex
+            )
+        );
+
+        let mut archive = tar::Archive::new(std::fs::File::open(&out).unwrap());
+        let entries: Vec<(String, u32, u64)> = archive
+            .entries()
+            .unwrap()
+            .map(|e| {
+                let e = e.unwrap();
+                let header = e.header();
+                assert_eq!((header.uid().unwrap(), header.gid().unwrap()), (0, 0));
+                (
+                    e.path().unwrap().to_string_lossy().into_owned(),
+                    header.mode().unwrap(),
+                    header.size().unwrap(),
+                )
+            })
+            .collect();
+        let paths: Vec<&str> = entries.iter().map(|e| e.0.as_str()).collect();
+        assert_eq!(paths, ["README", "bin/", "bin/tool"]);
+        assert_eq!(entries[2].2, 4);
+        #[cfg(unix)]
+        assert_eq!(entries[2].1, 0o755);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_write_manifest() {
+        let dir = temp_dir("manifest");
+        let layer_path = dir.join("tool.tar");
+        let layer = Layer {
+            digest: "sha256:abc".into(),
+            size: 1024,
+            skipped: Vec::new(),
+        };
+        let (config_path, manifest_path) =
+            write_manifest(&layer_path, &layer, "linux", "x86_64", Some("/bin/tool")).unwrap();
+        assert_eq!(config_path, dir.join("tool.config.json"));
+
+        let config: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&config_path).unwrap()).unwrap();
+        assert_eq!(config["architecture"], "amd64");
+        assert_eq!(config["os"], "linux");
+        assert_eq!(config["rootfs"]["diff_ids"][0], "sha256:abc");
+        assert_eq!(config["config"]["Entrypoint"][0], "/bin/tool");
+
+        let manifest: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(&manifest_path).unwrap()).unwrap();
+        let config_digest = verify::hash_file(&config_path, Algorithm::Sha256).unwrap();
+        assert_eq!(
+            manifest["config"]["digest"],
+            format!("sha256:{}", config_digest.hex)
+        );
+        assert_eq!(manifest["layers"][0]["digest"], "sha256:abc");
+        assert_eq!(manifest["layers"][0]["size"], 1024);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,105 @@
+use crate::download;
+use crate::http::HttpContext;
+use crate::index;
+use crate::platform::{self, Asset, AssetKind};
+use reqwest::Url;
+
+/// Finds the download for the platform on a vendor's web page (`--page`):
+/// the links to archives and executables are scored like a release's
+/// assets, and the best one's URL is returned.
+pub fn resolve(
+    http: &HttpContext,
+    url: &str,
+    rules: &platform::Rules,
+    (platform, arch, libc): (&str, &str, Option<&str>),
+    fallback_arches: &[&str],
+) -> Result<String, Box<dyn std::error::Error>> {
+    let page = Url::parse(url)?;
+    let assets = candidates(&page, &http.fetch_text(url)?);
+    if assets.is_empty() {
+        return Err(format!("{url} links to no archives or executables").into());
+    }
+    let (asset, _) = rules
+        .select_best_asset_or_fallback(&assets, platform, arch, fallback_arches, libc)
+        .ok_or_else(|| {
+            format!(
+                "none of the {} downloads on {url} is for platform={platform}, arch={arch}",
+                assets.len()
+            )
+        })?;
+    eprintln!("Found: {} on {url}", asset.name);
+    Ok(asset.url.clone())
+}
+
+/// The page's links to files worth downloading, as assets to score.
+fn candidates(page: &Url, html: &str) -> Vec<Asset> {
+    let mut assets: Vec<Asset> = Vec::new();
+    for href in index::hrefs(html) {
+        let Ok(link) = page.join(&href) else {
+            continue;
+        };
+        if !matches!(link.scheme(), "http" | "https") {
+            continue;
+        }
+        let url = link.to_string();
+        let name = download::filename_from_url(&url);
+        if is_download(&name) && !assets.iter().any(|a| a.url == url) {
+            assets.push(Asset {
+                name,
+                url,
+                digest: None,
+                size: None,
+                content_type: None,
+                updated_at: None,
+            });
+        }
+    }
+    assets
+}
+
+/// Whether a link's file name says it's a download rather than a page:
+/// an archive, or an executable by its extension. A page's other links
+/// ("/docs", "/pricing") would pass for extensionless binaries.
+fn is_download(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    match platform::asset_kind(name) {
+        AssetKind::Archive => true,
+        AssetKind::Binary => lower.ends_with(".exe") || lower.ends_with(".appimage"),
+        AssetKind::Other => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates_and_pick() {
+        let html = r#"
+            <a href="/docs">Docs</a>
+            <a href="https://cdn.example.com/tool/2.1/tool-2.1-linux-x86_64.tar.gz">Linux</a>
+            <a href="dl/tool-2.1-macos-arm64.zip">macOS</a>
+            <a href="dl/tool-2.1-windows-x64.exe">Windows</a>
+            <a href="dl/tool-2.1-windows-x64.exe">Windows (again)</a>
+            <a href="mailto:sales@example.com">Contact</a>"#;
+        let page = Url::parse("https://example.com/download/").unwrap();
+        let assets = candidates(&page, html);
+        let names: Vec<&str> = assets.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "tool-2.1-linux-x86_64.tar.gz",
+                "tool-2.1-macos-arm64.zip",
+                "tool-2.1-windows-x64.exe"
+            ]
+        );
+        assert_eq!(
+            assets[1].url,
+            "https://example.com/download/dl/tool-2.1-macos-arm64.zip"
+        );
+
+        let rules = platform::Rules::default();
+        let best = rules.select_best_asset(&assets, "macos", "arm64", None);
+        assert_eq!(best.unwrap().name, "tool-2.1-macos-arm64.zip");
+    }
+}
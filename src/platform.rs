@@ -1,8 +1,28 @@
+use crate::config::SelectionConfig;
+use crate::index::glob_match;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
 /// A downloadable asset with a name and URL.
 /// Provider-agnostic — GitHub, GitLab, or any other source can produce these.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Asset {
     pub name: String,
     pub url: String,
+    /// The digest the site publishes for the file ("sha256:…"), if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    /// The file's size in bytes, if the site lists it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// The MIME type the file was uploaded with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    /// When the file was last uploaded, as the site gives it (RFC 3339).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<String>,
 }
 
 /// Normalizes a Rust platform constant to the user-facing name.
@@ -24,9 +44,43 @@ pub fn normalize_arch(arch: &str) -> &str {
     }
 }
 
+/// Present on Macs where Rosetta 2 is installed.
+const ROSETTA_RUNTIME: &str = "/Library/Apple/usr/libexec/oah/libRosettaRuntime";
+
+/// Architectures this machine can also run through emulation, in order of
+/// preference: x86_64 under Rosetta 2 on Apple Silicon or Windows' x64
+/// emulation on ARM, and whatever qemu-user is registered for on Linux.
+pub fn emulated_arches() -> Vec<&'static str> {
+    let native = normalize_arch(std::env::consts::ARCH);
+    match std::env::consts::OS {
+        "macos" if native == "arm64" && Path::new(ROSETTA_RUNTIME).exists() => vec!["x86_64"],
+        "windows" if native == "arm64" => vec!["x86_64"],
+        "linux" => [("qemu-x86_64", "x86_64"), ("qemu-aarch64", "arm64")]
+            .into_iter()
+            .filter(|(handler, arch)| {
+                *arch != native && Path::new("/proc/sys/fs/binfmt_misc").join(handler).exists()
+            })
+            .map(|(_, arch)| arch)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
 // Extensions that indicate non-downloadable files (checksums, signatures, etc.)
 const SKIP_EXTENSIONS: &[&str] = &[
-    ".sha256", ".sha512", ".sig", ".asc", ".sbom", ".json", ".txt", ".md",
+    ".sha256",
+    ".sha512",
+    ".md5",
+    ".b3",
+    ".blake3",
+    ".sig",
+    ".asc",
+    ".pem",
+    ".sigstore",
+    ".sbom",
+    ".json",
+    ".txt",
+    ".md",
 ];
 
 // Words in the filename that indicate source archives (not binaries).
@@ -45,107 +99,693 @@ const PLATFORM_ALIASES: &[(&str, &[&str])] = &[
 // Architecture alias groups: (canonical, &[aliases])
 const ARCH_ALIASES: &[(&str, &[&str])] = &[
     ("x86_64", &["x86_64", "x86-64", "amd64", "x64"]),
-    ("arm64", &["arm64", "aarch64"]),
+    // ARM64EC builds are Windows-on-ARM binaries that can load x64 code.
+    ("arm64", &["arm64", "aarch64", "arm64ec"]),
+    // The 32-bit ones. Their canonical names aren't aliases, since "x86" and
+    // "arm" are also part of "x86_64" and "arm64" in asset names.
+    ("x86", &["i386", "i686", "386"]),
+    ("arm", &["armv7", "armhf", "armv6"]),
+    ("ppc64le", &["ppc64le", "powerpc64le"]),
+    // Fat binaries for several arches (macOS x86_64 + arm64); see `score_asset`.
+    ("universal", &["universal2", "universal"]),
 ];
 
-/// Selects the best matching asset from a list for the given platform and arch.
+/// The canonical name for `alias` in `table`, or `alias` itself if no
+/// group lists it.
+fn canonical<'a>(table: &[(&'static str, &[&str])], alias: &'a str) -> &'a str {
+    let lower = alias.to_lowercase();
+    table
+        .iter()
+        .find(|(canonical, aliases)| *canonical == lower || aliases.contains(&lower.as_str()))
+        .map_or(alias, |(canonical, _)| canonical)
+}
+
+/// The canonical name of a platform given as any of its aliases ("darwin" → "macos").
+pub fn canonical_platform(platform: &str) -> &str {
+    canonical(PLATFORM_ALIASES, platform)
+}
+
+/// The canonical name of an arch given as any of its aliases ("amd64" → "x86_64").
+pub fn canonical_arch(arch: &str) -> &str {
+    canonical(ARCH_ALIASES, arch)
+}
+
+/// The arch group whose assets stand in for any arch.
+const UNIVERSAL_ARCH: &str = "universal";
+
+// C library alias groups: (canonical, &[aliases])
+const LIBC_ALIASES: &[(&str, &[&str])] = &[
+    ("gnu", &["gnu", "glibc"]),
+    ("musl", &["musl"]),
+    ("msvc", &["msvc"]),
+];
+
+// Words that mark a build as musl in all but name: static binaries are
+// usually linked against musl, and Alpine is musl-based.
+const MUSL_HINTS: &[&str] = &["static", "alpine"];
+
+/// The C library of the machine dex runs on, when it isn't the platform's
+/// usual one: "musl" on Alpine and other musl-based Linux distributions.
+pub fn host_libc() -> Option<&'static str> {
+    if std::env::consts::OS != "linux" {
+        return None;
+    }
+    // musl's dynamic loader is /lib/ld-musl-<arch>.so.1.
+    let musl = std::fs::read_dir("/lib")
+        .ok()?
+        .flatten()
+        .any(|entry| entry.file_name().to_string_lossy().starts_with("ld-musl-"));
+    musl.then_some("musl")
+}
+
+/// A platform/arch/libc combination parsed from a Rust-style target triple.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Target {
+    pub platform: String,
+    pub arch: String,
+    pub libc: Option<String>,
+}
+
+/// Parses a target triple like `x86_64-unknown-linux-musl` or `aarch64-apple-darwin`.
 ///
-/// Scoring: +10 platform match, +5 arch match, +2 preferred format, +1 any extractable.
-/// Returns `None` if no asset matches both platform and arch.
-pub fn select_best_asset<'a>(assets: &'a [Asset], platform: &str, arch: &str) -> Option<&'a Asset> {
-    let mut best: Option<(&Asset, i32)> = None;
+/// The first component is the arch; the OS is found by name among the rest,
+/// and anything after it is the environment (libc). ABI suffixes such as
+/// `gnueabihf` reduce to their libc (`gnu`).
+pub fn parse_target(triple: &str) -> Option<Target> {
+    let parts: Vec<&str> = triple.split('-').collect();
+    let (arch, rest) = parts.split_first()?;
+
+    let os_index = rest
+        .iter()
+        .position(|p| matches!(*p, "linux" | "darwin" | "macos" | "windows" | "freebsd"))?;
+    let platform = normalize_platform(rest[os_index]);
+
+    let libc = rest.get(os_index + 1).map(|env| {
+        LIBC_ALIASES
+            .iter()
+            .find(|(canonical, _)| env.starts_with(canonical))
+            .map_or(*env, |(canonical, _)| canonical)
+            .to_string()
+    });
+
+    Some(Target {
+        platform: platform.to_string(),
+        arch: normalize_arch(arch).to_string(),
+        libc,
+    })
+}
 
-    for asset in assets {
-        let name_lower = asset.name.to_lowercase();
+/// How a single asset fared in scoring. Produced for every asset by
+/// `Rules::score_assets`, so callers can show why one was (or wasn't) picked.
+pub struct ScoredAsset<'a> {
+    pub asset: &'a Asset,
+    /// Why the asset was excluded before scoring (checksum, source archive, ...).
+    pub skipped: Option<String>,
+    /// The alias from the platform group found in the name, if any.
+    pub platform_match: Option<String>,
+    /// The alias from the arch group found in the name, if any.
+    pub arch_match: Option<String>,
+    /// The alias from the libc group found in the name, if a libc was requested.
+    pub libc_match: Option<String>,
+    /// Bonus for the archive format (+2 preferred, +1 any extractable).
+    pub format_bonus: i32,
+    pub score: i32,
+}
+
+impl ScoredAsset<'_> {
+    /// An asset is a candidate only if it wasn't skipped and matches both platform and arch.
+    pub fn is_eligible(&self) -> bool {
+        self.skipped.is_none() && self.platform_match.is_some() && self.arch_match.is_some()
+    }
+}
 
-        // Skip non-downloadable files.
-        if SKIP_EXTENSIONS.iter().any(|ext| name_lower.ends_with(ext)) {
-            continue;
+impl fmt::Display for ScoredAsset<'_> {
+    /// One-line explanation of the score, e.g. "platform=linux arch=amd64 format+2".
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(reason) = &self.skipped {
+            return write!(f, "skipped: {reason}");
         }
 
-        // Skip source archives.
-        if SKIP_KEYWORDS.iter().any(|kw| name_lower.contains(kw)) {
-            continue;
+        let mut parts = Vec::new();
+        match &self.platform_match {
+            Some(alias) => parts.push(format!("platform={alias}")),
+            None => parts.push("no platform match".to_string()),
+        }
+        match &self.arch_match {
+            Some(alias) => parts.push(format!("arch={alias}")),
+            None => parts.push("no arch match".to_string()),
+        }
+        if let Some(alias) = &self.libc_match {
+            parts.push(format!("libc={alias}"));
         }
+        if self.format_bonus > 0 {
+            parts.push(format!("format+{}", self.format_bonus));
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
 
-        let mut score = 0;
+/// An alias group: a canonical name and the substrings that identify it in filenames.
+type AliasGroup = (String, Vec<String>);
+
+fn to_groups(table: &[(&str, &[&str])]) -> Vec<AliasGroup> {
+    table
+        .iter()
+        .map(|(canonical, aliases)| {
+            (
+                canonical.to_string(),
+                aliases.iter().map(|a| a.to_string()).collect(),
+            )
+        })
+        .collect()
+}
 
-        // Platform matching (+10)
-        let platform_match = matches_alias_group(&name_lower, platform, PLATFORM_ALIASES);
-        if platform_match {
-            score += 10;
+/// How to choose between assets that score the same for the platform — a
+/// "minimal" and a "full" build, say (--variant-preference).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum VariantPreference {
+    /// The smallest: often the same build packed more tightly.
+    #[default]
+    Smallest,
+    /// The largest, for the variant with everything in it.
+    Largest,
+    /// The one whose name contains this word, then the smallest.
+    Keyword(String),
+}
+
+impl std::str::FromStr for VariantPreference {
+    type Err = String;
+
+    /// Parses "smallest", "largest", or "keyword=<word>".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "smallest" => Ok(VariantPreference::Smallest),
+            "largest" => Ok(VariantPreference::Largest),
+            _ => match s.strip_prefix("keyword=") {
+                Some(word) if !word.is_empty() => {
+                    Ok(VariantPreference::Keyword(word.to_lowercase()))
+                }
+                _ => Err(format!(
+                    "unknown variant preference {s} (expected smallest, largest, or keyword=<word>)"
+                )),
+            },
         }
+    }
+}
 
-        // Architecture matching (+5)
-        let arch_match = matches_alias_group(&name_lower, arch, ARCH_ALIASES);
-        if arch_match {
-            score += 5;
+impl VariantPreference {
+    /// Whether `a` is preferred to `b`, an asset that scored the same.
+    fn prefers(&self, a: &Asset, b: &Asset) -> bool {
+        match self {
+            VariantPreference::Smallest => is_smaller(a, b),
+            // An unknown size never wins, whichever way sizes are preferred.
+            VariantPreference::Largest => match (a.size, b.size) {
+                (Some(a), Some(b)) => a > b,
+                (Some(_), None) => true,
+                (None, _) => false,
+            },
+            VariantPreference::Keyword(word) => {
+                let has = |asset: &Asset| asset.name.to_lowercase().contains(word.as_str());
+                match (has(a), has(b)) {
+                    (true, false) => true,
+                    (false, true) => false,
+                    _ => is_smaller(a, b),
+                }
+            }
         }
+    }
+}
 
-        // Must match both platform and arch to be considered.
-        if !platform_match || !arch_match {
-            continue;
+/// The tables that drive asset selection.
+///
+/// `Rules::default()` holds the built-in tables above; `extend` merges in
+/// additions from the user's config file at runtime.
+#[derive(Clone)]
+pub struct Rules {
+    platform_aliases: Vec<AliasGroup>,
+    arch_aliases: Vec<AliasGroup>,
+    libc_aliases: Vec<AliasGroup>,
+    skip_extensions: Vec<String>,
+    skip_keywords: Vec<String>,
+    /// Extensions earning the +2 format bonus. `None` keeps the built-in
+    /// preference: .zip on Windows, .tar.gz elsewhere.
+    preferred_formats: Option<Vec<String>>,
+    /// Name globs an asset must match one of to be picked (--only-asset);
+    /// empty allows every name.
+    only: Vec<String>,
+    /// Name globs that rule an asset out (--exclude-asset).
+    exclude: Vec<String>,
+    /// Which of equally good assets wins.
+    variant: VariantPreference,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            platform_aliases: to_groups(PLATFORM_ALIASES),
+            arch_aliases: to_groups(ARCH_ALIASES),
+            libc_aliases: to_groups(LIBC_ALIASES),
+            skip_extensions: SKIP_EXTENSIONS.iter().map(|s| s.to_string()).collect(),
+            skip_keywords: SKIP_KEYWORDS.iter().map(|s| s.to_string()).collect(),
+            preferred_formats: None,
+            only: Vec::new(),
+            exclude: Vec::new(),
+            variant: VariantPreference::default(),
         }
+    }
+}
 
-        // Prefer archive formats (+2 for preferred, +1 for any extractable)
-        let is_windows = platform_aliases_contain(platform, "windows");
-        if is_windows {
-            if name_lower.ends_with(".zip") {
-                score += 2;
-            } else if is_extractable_ext(&name_lower) {
-                score += 1;
+impl Rules {
+    /// Merges user-defined selection rules into these tables.
+    ///
+    /// Aliases are added to the group whose canonical name (or an existing alias)
+    /// matches the config key; unknown keys start a new group. Skip lists are
+    /// appended, and `preferred_formats` replaces the built-in format preference.
+    pub fn extend(&mut self, config: &SelectionConfig) {
+        merge_groups(&mut self.platform_aliases, &config.platform_aliases);
+        merge_groups(&mut self.arch_aliases, &config.arch_aliases);
+        merge_groups(&mut self.libc_aliases, &config.libc_aliases);
+        self.skip_extensions
+            .extend(config.skip_extensions.iter().map(|s| s.to_lowercase()));
+        self.skip_keywords
+            .extend(config.skip_keywords.iter().map(|s| s.to_lowercase()));
+        if let Some(formats) = &config.preferred_formats {
+            self.preferred_formats = Some(formats.iter().map(|f| f.to_lowercase()).collect());
+        }
+    }
+
+    /// Limits selection to assets whose names match one of `only` (if any
+    /// are given) and none of `exclude`: shell-style globs, ignoring case.
+    pub fn filter_names(&mut self, only: &[String], exclude: &[String]) {
+        self.only = only.iter().map(|g| g.to_lowercase()).collect();
+        self.exclude = exclude.iter().map(|g| g.to_lowercase()).collect();
+    }
+
+    /// Sets how ties between equally good assets are broken.
+    pub fn set_variant_preference(&mut self, variant: VariantPreference) {
+        self.variant = variant;
+    }
+
+    /// Scores every asset for the given platform, arch, and (optional) libc.
+    ///
+    /// Scoring: +10 platform match, +5 arch match (+1 for a universal binary instead),
+    /// +3 libc match (when one is requested; a static or Alpine build counts
+    /// for musl), +2 preferred format, +1 any extractable. Skipped assets
+    /// score 0.
+    /// Results are in the same order as `assets`.
+    pub fn score_assets<'a>(
+        &self,
+        assets: &'a [Asset],
+        platform: &str,
+        arch: &str,
+        libc: Option<&str>,
+    ) -> Vec<ScoredAsset<'a>> {
+        assets
+            .iter()
+            .map(|asset| self.score_asset(asset, platform, arch, libc))
+            .collect()
+    }
+
+    fn score_asset<'a>(
+        &self,
+        asset: &'a Asset,
+        platform: &str,
+        arch: &str,
+        libc: Option<&str>,
+    ) -> ScoredAsset<'a> {
+        let name_lower = asset.name.to_lowercase();
+
+        let mut scored = ScoredAsset {
+            asset,
+            skipped: self.skip_reason(&name_lower),
+            platform_match: None,
+            arch_match: None,
+            libc_match: None,
+            format_bonus: 0,
+            score: 0,
+        };
+        if scored.skipped.is_some() {
+            return scored;
+        }
+        if let Some(ext) = WINDOWS_ONLY_EXTENSIONS
+            .iter()
+            .find(|ext| name_lower.ends_with(*ext))
+            && !self.platform_in_group(platform, "windows")
+        {
+            scored.skipped = Some(format!("Windows-only format {ext}"));
+            return scored;
+        }
+
+        // Platform matching (+10). AppImages rarely say "linux" in their
+        // name, but they only ever run on Linux.
+        scored.platform_match = matching_alias(&name_lower, platform, &self.platform_aliases)
+            .or_else(|| {
+                (name_lower.ends_with(".appimage") && self.platform_in_group(platform, "linux"))
+                    .then(|| "appimage".to_string())
+            });
+        if scored.platform_match.is_some() {
+            scored.score += 10;
+        }
+
+        // Architecture matching (+5). A universal binary runs on any arch, but
+        // scores low enough (+1) that a build for the exact arch wins if there is one.
+        scored.arch_match = matching_alias(&name_lower, arch, &self.arch_aliases);
+        if scored.arch_match.is_some() {
+            scored.score += 5;
+        } else {
+            scored.arch_match = matching_alias(&name_lower, UNIVERSAL_ARCH, &self.arch_aliases);
+            if scored.arch_match.is_some() {
+                scored.score += 1;
             }
-        } else if name_lower.ends_with(".tar.gz") || name_lower.ends_with(".tgz") {
-            score += 2;
+        }
+
+        // libc matching (+3) — outweighs archive format preferences, since a
+        // glibc build won't run on a musl system regardless of how it's packaged.
+        scored.libc_match = libc.and_then(|l| matching_alias(&name_lower, l, &self.libc_aliases));
+        if scored.libc_match.is_some() {
+            scored.score += 3;
+        } else if let Some(libc) = libc
+            && in_group(&self.libc_aliases, libc, "musl")
+            && let Some(hint) = MUSL_HINTS.iter().find(|h| name_lower.contains(*h))
+        {
+            scored.libc_match = Some(hint.to_string());
+            scored.score += 3;
+        }
+
+        // Prefer archive formats (+2 for preferred, +1 for any extractable)
+        scored.format_bonus = if self.is_preferred_format(&name_lower, platform) {
+            2
         } else if is_extractable_ext(&name_lower) {
-            score += 1;
+            1
+        } else {
+            0
+        };
+        scored.score += scored.format_bonus;
+
+        scored
+    }
+
+    fn is_preferred_format(&self, name_lower: &str, platform: &str) -> bool {
+        if let Some(formats) = &self.preferred_formats {
+            return formats.iter().any(|f| name_lower.ends_with(f.as_str()));
+        }
+        if self.platform_in_group(platform, "windows") {
+            name_lower.ends_with(".zip")
+        } else {
+            name_lower.ends_with(".tar.gz") || name_lower.ends_with(".tgz")
         }
+    }
+
+    /// Selects the best matching asset from a list for the given platform and arch.
+    ///
+    /// Uses the scores from `score_assets`; ties are broken as in `best_of`.
+    /// Returns `None` if no asset matches both platform and arch.
+    pub fn select_best_asset<'a>(
+        &self,
+        assets: &'a [Asset],
+        platform: &str,
+        arch: &str,
+        libc: Option<&str>,
+    ) -> Option<&'a Asset> {
+        self.best_of(&self.score_assets(assets, platform, arch, libc))
+            .map(|s| s.asset)
+    }
+
+    /// Like `select_best_asset`, with a second pass: when nothing matches
+    /// `arch`, each of the `fallbacks` is tried in turn. Returns the asset and
+    /// the arch it was picked for.
+    pub fn select_best_asset_or_fallback<'a, 's>(
+        &self,
+        assets: &'a [Asset],
+        platform: &str,
+        arch: &'s str,
+        fallbacks: &[&'s str],
+        libc: Option<&str>,
+    ) -> Option<(&'a Asset, &'s str)> {
+        std::iter::once(arch)
+            .chain(fallbacks.iter().copied())
+            .find_map(|arch| Some((self.select_best_asset(assets, platform, arch, libc)?, arch)))
+    }
 
-        match &best {
-            Some((_, best_score)) if score <= *best_score => {}
-            _ => best = Some((asset, score)),
+    /// Returns every downloadable asset matching the given platform and arch.
+    /// `None` for either dimension means "any" (used by `--platform all` / `--arch all`).
+    ///
+    /// Checksums, signatures, and source archives are skipped, as in `select_best_asset`.
+    pub fn select_matching_assets<'a>(
+        &self,
+        assets: &'a [Asset],
+        platform: Option<&str>,
+        arch: Option<&str>,
+    ) -> Vec<&'a Asset> {
+        assets
+            .iter()
+            .filter(|asset| {
+                let name_lower = asset.name.to_lowercase();
+                self.skip_reason(&name_lower).is_none()
+                    && platform.is_none_or(|p| {
+                        matching_alias(&name_lower, p, &self.platform_aliases).is_some()
+                    })
+                    && arch.is_none_or(|a| {
+                        matching_alias(&name_lower, a, &self.arch_aliases).is_some()
+                    })
+            })
+            .collect()
+    }
+
+    /// The canonical platform and arch an asset's name mentions, e.g.
+    /// "tool-aarch64-apple-darwin.tar.gz" → (Some("macos"), Some("arm64")).
+    pub fn identify(&self, name: &str) -> (Option<&str>, Option<&str>) {
+        let name_lower = name.to_lowercase();
+        (
+            named_group(&name_lower, &self.platform_aliases),
+            named_group(&name_lower, &self.arch_aliases),
+        )
+    }
+
+    /// Explains why an asset is a non-downloadable file (checksum, signature) or a
+    /// source archive. Returns `None` for assets that should be scored.
+    fn skip_reason(&self, name_lower: &str) -> Option<String> {
+        if let Some(glob) = self.exclude.iter().find(|g| glob_match(g, name_lower)) {
+            return Some(format!("--exclude-asset {glob}"));
+        }
+        if !self.only.is_empty() && !self.only.iter().any(|g| glob_match(g, name_lower)) {
+            return Some("no --only-asset match".to_string());
+        }
+        if let Some(ext) = self
+            .skip_extensions
+            .iter()
+            .find(|ext| name_lower.ends_with(ext.as_str()))
+        {
+            return Some(format!("extension {ext}"));
+        }
+        self.skip_keywords
+            .iter()
+            .find(|kw| name_lower.contains(kw.as_str()))
+            .map(|kw| format!("source keyword '{kw}'"))
+    }
+
+    /// Picks the highest-scoring eligible asset from a scored list. Of equally
+    /// good ones, the variant preference decides (the smallest, by default:
+    /// the same build packed more tightly); otherwise the earliest wins.
+    pub fn best_of<'s, 'a>(&self, scored: &'s [ScoredAsset<'a>]) -> Option<&'s ScoredAsset<'a>> {
+        let mut best: Option<&ScoredAsset> = None;
+        for candidate in scored.iter().filter(|s| s.is_eligible()) {
+            match best {
+                Some(b) if candidate.score < b.score => {}
+                Some(b)
+                    if candidate.score == b.score
+                        && !self.variant.prefers(candidate.asset, b.asset) => {}
+                _ => best = Some(candidate),
+            }
+        }
+        best
+    }
+
+    /// Checks if a platform value belongs to the group with the given canonical name.
+    fn platform_in_group(&self, platform: &str, canonical: &str) -> bool {
+        in_group(&self.platform_aliases, platform, canonical)
+    }
+}
+
+/// Checks if a value belongs to the group with the given canonical name.
+fn in_group(groups: &[AliasGroup], value: &str, canonical: &str) -> bool {
+    let value_lower = value.to_lowercase();
+    groups
+        .iter()
+        .find(|(c, _)| c == canonical)
+        .is_some_and(|(c, aliases)| *c == value_lower || aliases.contains(&value_lower))
+}
+
+/// Adds user aliases to matching groups, or creates new groups.
+fn merge_groups(groups: &mut Vec<AliasGroup>, additions: &BTreeMap<String, Vec<String>>) {
+    for (key, aliases) in additions {
+        let key = key.to_lowercase();
+        let aliases = aliases.iter().map(|a| a.to_lowercase());
+
+        let existing = groups
+            .iter_mut()
+            .find(|(canonical, group)| *canonical == key || group.contains(&key));
+        match existing {
+            Some((_, group)) => {
+                for alias in aliases {
+                    if !group.contains(&alias) {
+                        group.push(alias);
+                    }
+                }
+            }
+            None => {
+                let mut group = vec![key.clone()];
+                group.extend(aliases);
+                groups.push((key, group));
+            }
         }
     }
+}
+
+/// Whether `a` is smaller than `b`, an unknown size counting as the largest.
+fn is_smaller(a: &Asset, b: &Asset) -> bool {
+    match (a.size, b.size) {
+        (Some(a), Some(b)) => a < b,
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
 
-    best.map(|(asset, _)| asset)
+/// The canonical name of the first group with an alias in the filename.
+fn named_group<'g>(name_lower: &str, groups: &'g [AliasGroup]) -> Option<&'g str> {
+    groups
+        .iter()
+        .find(|(_, aliases)| aliases.iter().any(|a| name_lower.contains(a.as_str())))
+        .map(|(canonical, _)| canonical.as_str())
 }
 
-/// Checks if a filename matches any alias in the group for the given canonical name.
-fn matches_alias_group(name_lower: &str, value: &str, groups: &[(&str, &[&str])]) -> bool {
+/// Finds which alias of the group containing `value` appears in the filename.
+/// Returns the matched alias, or `None` if the filename has none of them.
+fn matching_alias(name_lower: &str, value: &str, groups: &[AliasGroup]) -> Option<String> {
     let value_lower = value.to_lowercase();
 
     // Find the alias group that contains our value.
-    for &(canonical, aliases) in groups {
-        let is_in_group = canonical == value_lower || aliases.iter().any(|a| *a == value_lower);
+    for (canonical, aliases) in groups {
+        let is_in_group = *canonical == value_lower || aliases.contains(&value_lower);
 
         if is_in_group {
             // Check if the filename contains any alias from this group.
-            return aliases.iter().any(|alias| name_lower.contains(alias));
+            return aliases
+                .iter()
+                .find(|alias| name_lower.contains(alias.as_str()))
+                .cloned();
         }
     }
 
     // If the value doesn't belong to any known group, do a direct substring match.
-    name_lower.contains(&value_lower)
+    name_lower.contains(&value_lower).then_some(value_lower)
+}
+
+// Extractable archive extensions. Compound extensions come first so that
+// stripping "tool.tar.gz" removes ".tar.gz" rather than just ".gz".
+const ARCHIVE_EXTENSIONS: &[&str] = &[
+    ".tar.gz", ".tgz", ".tar.bz2", ".tbz2", ".tar.xz", ".txz", ".tar.zst", ".tzst", ".tar.lz4",
+    ".tlz4", ".tar.br", ".tar", ".zip", ".gz", ".bz2", ".xz", ".zst", ".lz4", ".br", ".cab",
+    ".wim",
+];
+
+// Formats that only make sense when targeting Windows: cabinets, WIM images,
+// and executables (including self-extracting installers).
+const WINDOWS_ONLY_EXTENSIONS: &[&str] = &[".cab", ".wim", ".exe", ".msi"];
+
+/// Checks if a filename has a recognized extractable archive extension.
+fn is_extractable_ext(name: &str) -> bool {
+    ARCHIVE_EXTENSIONS.iter().any(|ext| name.ends_with(ext))
+}
+
+// Installer and package formats: not archives dex extracts, and not runnable binaries.
+const PACKAGE_EXTENSIONS: &[&str] = &[
+    ".deb", ".rpm", ".apk", ".msi", ".msix", ".dmg", ".pkg", ".snap", ".flatpak",
+];
+
+/// What kind of file an asset is, judged by its name.
+#[derive(Debug, PartialEq, Eq)]
+pub enum AssetKind {
+    /// An archive dex can extract.
+    Archive,
+    /// A directly runnable file: an AppImage, a `.exe`, or an extensionless binary.
+    Binary,
+    /// Anything else (packages, documents, ...).
+    Other,
+}
+
+/// Classifies an asset by name. Release binaries often carry dots in their
+/// version ("tool-v1.2.3-linux-amd64"), so a "extension" that isn't short and
+/// alphanumeric is treated as no extension at all.
+pub fn asset_kind(name: &str) -> AssetKind {
+    let lower = name.to_lowercase();
+    if is_extractable_ext(&lower) {
+        return AssetKind::Archive;
+    }
+    if lower.ends_with(".appimage") || lower.ends_with(".exe") {
+        return AssetKind::Binary;
+    }
+    if PACKAGE_EXTENSIONS
+        .iter()
+        .chain(SKIP_EXTENSIONS)
+        .any(|ext| lower.ends_with(ext))
+    {
+        return AssetKind::Other;
+    }
+
+    match lower.rsplit_once('.') {
+        Some((_, ext)) if ext.len() <= 4 && ext.chars().all(|c| c.is_ascii_alphanumeric()) => {
+            AssetKind::Other
+        }
+        _ => AssetKind::Binary,
+    }
 }
 
-/// Checks if a platform value belongs to the "windows" group.
-fn platform_aliases_contain(platform: &str, target: &str) -> bool {
-    let platform_lower = platform.to_lowercase();
-    for &(canonical, aliases) in PLATFORM_ALIASES {
-        if canonical == target {
-            return canonical == platform_lower || aliases.iter().any(|a| *a == platform_lower);
+/// Strips a recognized archive extension, e.g. "tool-linux.tar.gz" → "tool-linux".
+pub fn strip_archive_ext(name: &str) -> &str {
+    let lower = name.to_lowercase();
+    ARCHIVE_EXTENSIONS
+        .iter()
+        .find(|ext| lower.ends_with(*ext))
+        .map_or(name, |ext| &name[..name.len() - ext.len()])
+}
+
+/// Splits a file name into stem and extension (without the dot), keeping
+/// compound archive extensions whole: "tool.tar.gz" → ("tool", "tar.gz").
+/// Names whose last dot isn't followed by a short extension, like
+/// "tool-v1.2.3-linux", have none.
+pub fn split_ext(name: &str) -> (&str, &str) {
+    let stem = strip_archive_ext(name);
+    if stem.len() < name.len() {
+        return (stem, &name[stem.len() + 1..]);
+    }
+    match name.rsplit_once('.') {
+        Some((stem, ext))
+            if !stem.is_empty()
+                && ext.len() <= 8
+                && ext.chars().all(|c| c.is_ascii_alphanumeric())
+                && ext.contains(|c: char| c.is_ascii_alphabetic()) =>
+        {
+            (stem, ext)
         }
+        _ => (name, ""),
     }
-    false
 }
 
-/// Checks if a filename has a recognized extractable archive extension.
-fn is_extractable_ext(name: &str) -> bool {
-    let extractable = [
-        ".tar.gz", ".tgz", ".tar.bz2", ".tbz2", ".tar.xz", ".txz", ".tar.zst", ".tzst", ".zip",
-        ".gz", ".bz2", ".xz", ".zst",
-    ];
-    extractable.iter().any(|ext| name.ends_with(ext))
+/// Assets with just a name, at `https://example.com/<name>`, for tests.
+#[cfg(test)]
+pub(crate) fn make_assets(names: &[&str]) -> Vec<Asset> {
+    names
+        .iter()
+        .map(|n| Asset {
+            name: n.to_string(),
+            url: format!("https://example.com/{n}"),
+            digest: None,
+            size: None,
+            content_type: None,
+            updated_at: None,
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -170,18 +810,53 @@ mod tests {
         assert_eq!(normalize_arch("riscv64"), "riscv64");
     }
 
-    // ── Asset matching ──────────────────────────────────────────────
+    #[test]
+    fn test_canonical_names() {
+        assert_eq!(canonical_arch("amd64"), "x86_64");
+        assert_eq!(canonical_arch("AArch64"), "arm64");
+        assert_eq!(canonical_arch("riscv64"), "riscv64");
+        assert_eq!(canonical_arch("i686"), "x86");
+        assert_eq!(canonical_arch("armv7"), "arm");
+        assert_eq!(canonical_arch("powerpc64le"), "ppc64le");
+        assert_eq!(canonical_platform("darwin"), "macos");
+        assert_eq!(canonical_platform("freebsd"), "freebsd");
+    }
 
-    fn make_assets(names: &[&str]) -> Vec<Asset> {
-        names
-            .iter()
-            .map(|n| Asset {
-                name: n.to_string(),
-                url: format!("https://example.com/{n}"),
-            })
-            .collect()
+    #[test]
+    fn test_strip_archive_ext() {
+        assert_eq!(strip_archive_ext("tool-linux.tar.gz"), "tool-linux");
+        assert_eq!(strip_archive_ext("Tool.ZIP"), "Tool");
+        assert_eq!(strip_archive_ext("tool.exe"), "tool.exe");
     }
 
+    #[test]
+    fn test_split_ext() {
+        assert_eq!(split_ext("tool-linux.tar.gz"), ("tool-linux", "tar.gz"));
+        assert_eq!(split_ext("tool.exe"), ("tool", "exe"));
+        assert_eq!(
+            split_ext("Tool-x86_64.AppImage"),
+            ("Tool-x86_64", "AppImage")
+        );
+        assert_eq!(split_ext("tool-v1.2.3-linux"), ("tool-v1.2.3-linux", ""));
+        assert_eq!(split_ext("tool-1.2"), ("tool-1.2", ""));
+    }
+
+    #[test]
+    fn test_identify() {
+        let rules = Rules::default();
+        assert_eq!(
+            rules.identify("tool-aarch64-apple-darwin.tar.gz"),
+            (Some("macos"), Some("arm64"))
+        );
+        assert_eq!(
+            rules.identify("tool_Linux_x86_64.zip"),
+            (Some("linux"), Some("x86_64"))
+        );
+        assert_eq!(rules.identify("tool.tar.gz"), (None, None));
+    }
+
+    // ── Asset matching ──────────────────────────────────────────────
+
     #[test]
     fn test_ripgrep_linux_x86_64() {
         let assets = make_assets(&[
@@ -193,7 +868,7 @@ mod tests {
             "ripgrep-14.1.1.sha256",
         ]);
 
-        let result = select_best_asset(&assets, "linux", "x86_64");
+        let result = Rules::default().select_best_asset(&assets, "linux", "x86_64", None);
         assert_eq!(
             result.unwrap().name,
             "ripgrep-14.1.1-x86_64-unknown-linux-musl.tar.gz"
@@ -210,7 +885,7 @@ mod tests {
             "ripgrep-14.1.1-x86_64-apple-darwin.tar.gz",
         ]);
 
-        let result = select_best_asset(&assets, "macos", "arm64");
+        let result = Rules::default().select_best_asset(&assets, "macos", "arm64", None);
         assert_eq!(
             result.unwrap().name,
             "ripgrep-14.1.1-aarch64-apple-darwin.tar.gz"
@@ -225,7 +900,7 @@ mod tests {
             "tool-1.0-x86_64-unknown-linux-musl.tar.gz",
         ]);
 
-        let result = select_best_asset(&assets, "windows", "x86_64");
+        let result = Rules::default().select_best_asset(&assets, "windows", "x86_64", None);
         assert_eq!(result.unwrap().name, "tool-1.0-x86_64-pc-windows-msvc.zip");
     }
 
@@ -236,7 +911,7 @@ mod tests {
             "tool-1.0-x86_64-unknown-linux-musl.tar.gz",
         ]);
 
-        let result = select_best_asset(&assets, "linux", "x86_64");
+        let result = Rules::default().select_best_asset(&assets, "linux", "x86_64", None);
         assert_eq!(
             result.unwrap().name,
             "tool-1.0-x86_64-unknown-linux-musl.tar.gz"
@@ -251,7 +926,7 @@ mod tests {
             "tool-1.0-linux-amd64.tar.gz.sig",
         ]);
 
-        let result = select_best_asset(&assets, "linux", "x86_64");
+        let result = Rules::default().select_best_asset(&assets, "linux", "x86_64", None);
         assert_eq!(result.unwrap().name, "tool-1.0-linux-amd64.tar.gz");
     }
 
@@ -263,7 +938,7 @@ mod tests {
             "tool-1.0-linux-amd64.tar.gz",
         ]);
 
-        let result = select_best_asset(&assets, "linux", "x86_64");
+        let result = Rules::default().select_best_asset(&assets, "linux", "x86_64", None);
         assert_eq!(result.unwrap().name, "tool-1.0-linux-amd64.tar.gz");
     }
 
@@ -271,7 +946,7 @@ mod tests {
     fn test_amd64_alias_matches_x86_64() {
         let assets = make_assets(&["tool-1.0-linux-amd64.tar.gz", "tool-1.0-linux-arm64.tar.gz"]);
 
-        let result = select_best_asset(&assets, "linux", "x86_64");
+        let result = Rules::default().select_best_asset(&assets, "linux", "x86_64", None);
         assert_eq!(result.unwrap().name, "tool-1.0-linux-amd64.tar.gz");
     }
 
@@ -282,15 +957,69 @@ mod tests {
             "tool-1.0-linux-arm64.tar.gz",
         ]);
 
-        let result = select_best_asset(&assets, "macos", "arm64");
+        let result = Rules::default().select_best_asset(&assets, "macos", "arm64", None);
         assert_eq!(result.unwrap().name, "tool-1.0-darwin-arm64.tar.gz");
     }
 
+    #[test]
+    fn test_universal_binary_fallback() {
+        let assets = make_assets(&[
+            "tool-1.0-macos-universal2.tar.gz",
+            "tool-1.0-linux-amd64.tar.gz",
+        ]);
+        let result = Rules::default().select_best_asset(&assets, "macos", "arm64", None);
+        assert_eq!(result.unwrap().name, "tool-1.0-macos-universal2.tar.gz");
+
+        // An exact arch match beats a universal binary, whatever its format.
+        let assets = make_assets(&["tool-1.0-macos-universal.tar.gz", "tool-1.0-macos-arm64"]);
+        let result = Rules::default().select_best_asset(&assets, "macos", "arm64", None);
+        assert_eq!(result.unwrap().name, "tool-1.0-macos-arm64");
+    }
+
+    #[test]
+    fn test_fallback_arch() {
+        let assets = make_assets(&[
+            "tool-1.0-darwin-amd64.tar.gz",
+            "tool-1.0-linux-arm64.tar.gz",
+        ]);
+        let rules = Rules::default();
+        let pick = |fallbacks: &[&'static str]| {
+            rules
+                .select_best_asset_or_fallback(&assets, "macos", "arm64", fallbacks, None)
+                .map(|(asset, arch)| (asset.name.as_str(), arch))
+        };
+        assert_eq!(
+            pick(&["x86_64"]),
+            Some(("tool-1.0-darwin-amd64.tar.gz", "x86_64"))
+        );
+        assert_eq!(pick(&[]), None);
+
+        // The native arch comes first.
+        let assets = make_assets(&[
+            "tool-1.0-darwin-amd64.tar.gz",
+            "tool-1.0-darwin-arm64.tar.gz",
+        ]);
+        let result =
+            rules.select_best_asset_or_fallback(&assets, "macos", "arm64", &["x86_64"], None);
+        assert_eq!(result.unwrap().1, "arm64");
+    }
+
+    #[test]
+    fn test_windows_arm64() {
+        let assets = make_assets(&[
+            "tool-1.0-windows-x64.zip",
+            "tool-1.0-windows-arm64ec.zip",
+            "tool-1.0-linux-arm64.tar.gz",
+        ]);
+        let result = Rules::default().select_best_asset(&assets, "windows", "arm64", None);
+        assert_eq!(result.unwrap().name, "tool-1.0-windows-arm64ec.zip");
+    }
+
     #[test]
     fn test_no_matching_asset() {
         let assets = make_assets(&["tool-1.0-linux-amd64.tar.gz", "tool-1.0-linux-arm64.tar.gz"]);
 
-        let result = select_best_asset(&assets, "windows", "x86_64");
+        let result = Rules::default().select_best_asset(&assets, "windows", "x86_64", None);
         assert!(result.is_none());
     }
 
@@ -298,7 +1027,7 @@ mod tests {
     fn test_all_filtered_out() {
         let assets = make_assets(&["checksums.sha256", "checksums.txt", "source.tar.gz"]);
 
-        let result = select_best_asset(&assets, "linux", "x86_64");
+        let result = Rules::default().select_best_asset(&assets, "linux", "x86_64", None);
         assert!(result.is_none());
     }
 
@@ -319,7 +1048,7 @@ mod tests {
             "fd-v10.2.0-x86_64-unknown-linux-musl.tar.gz",
         ]);
 
-        let result = select_best_asset(&assets, "macos", "x86_64");
+        let result = Rules::default().select_best_asset(&assets, "macos", "x86_64", None);
         assert_eq!(
             result.unwrap().name,
             "fd-v10.2.0-x86_64-apple-darwin.tar.gz"
@@ -337,7 +1066,7 @@ mod tests {
             "gh_2.60.0_checksums.txt",
         ]);
 
-        let result = select_best_asset(&assets, "linux", "arm64");
+        let result = Rules::default().select_best_asset(&assets, "linux", "arm64", None);
         assert_eq!(result.unwrap().name, "gh_2.60.0_linux_arm64.tar.gz");
     }
 
@@ -353,7 +1082,7 @@ mod tests {
             "terraform_1.9.0_SHA256SUMS.sig",
         ]);
 
-        let result = select_best_asset(&assets, "linux", "x86_64");
+        let result = Rules::default().select_best_asset(&assets, "linux", "x86_64", None);
         assert_eq!(result.unwrap().name, "terraform_1.9.0_linux_amd64.zip");
     }
 
@@ -364,7 +1093,7 @@ mod tests {
             "Tool-1.0-Darwin-ARM64.tar.gz",
         ]);
 
-        let result = select_best_asset(&assets, "linux", "x86_64");
+        let result = Rules::default().select_best_asset(&assets, "linux", "x86_64", None);
         assert_eq!(result.unwrap().name, "Tool-1.0-Linux-X86_64.tar.gz");
     }
 
@@ -372,7 +1101,7 @@ mod tests {
     fn test_user_passes_aarch64_directly() {
         let assets = make_assets(&["tool-1.0-linux-amd64.tar.gz", "tool-1.0-linux-arm64.tar.gz"]);
 
-        let result = select_best_asset(&assets, "linux", "aarch64");
+        let result = Rules::default().select_best_asset(&assets, "linux", "aarch64", None);
         assert_eq!(result.unwrap().name, "tool-1.0-linux-arm64.tar.gz");
     }
 
@@ -383,7 +1112,7 @@ mod tests {
             "tool-1.0-linux-arm64.tar.gz",
         ]);
 
-        let result = select_best_asset(&assets, "darwin", "arm64");
+        let result = Rules::default().select_best_asset(&assets, "darwin", "arm64", None);
         assert_eq!(result.unwrap().name, "tool-1.0-darwin-arm64.tar.gz");
     }
 
@@ -391,15 +1120,344 @@ mod tests {
     fn test_osx_alias() {
         let assets = make_assets(&["tool-1.0-osx-x86_64.tar.gz", "tool-1.0-linux-x86_64.tar.gz"]);
 
-        let result = select_best_asset(&assets, "macos", "x86_64");
+        let result = Rules::default().select_best_asset(&assets, "macos", "x86_64", None);
         assert_eq!(result.unwrap().name, "tool-1.0-osx-x86_64.tar.gz");
     }
 
+    #[test]
+    fn test_select_matching_all_arches() {
+        let assets = make_assets(&[
+            "tool-1.0-linux-amd64.tar.gz",
+            "tool-1.0-linux-arm64.tar.gz",
+            "tool-1.0-darwin-arm64.tar.gz",
+            "tool-1.0-linux-amd64.tar.gz.sha256",
+        ]);
+
+        let names: Vec<&str> = Rules::default()
+            .select_matching_assets(&assets, Some("linux"), None)
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            ["tool-1.0-linux-amd64.tar.gz", "tool-1.0-linux-arm64.tar.gz"]
+        );
+    }
+
+    #[test]
+    fn test_select_matching_everything() {
+        let assets = make_assets(&[
+            "tool-1.0-linux-amd64.tar.gz",
+            "tool-1.0-darwin-arm64.tar.gz",
+            "source.tar.gz",
+            "checksums.txt",
+        ]);
+
+        assert_eq!(
+            Rules::default()
+                .select_matching_assets(&assets, None, None)
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_libc_preference() {
+        let assets = make_assets(&[
+            "tool-1.0-x86_64-unknown-linux-gnu.tar.gz",
+            "tool-1.0-x86_64-unknown-linux-musl.zip",
+        ]);
+
+        let result = Rules::default().select_best_asset(&assets, "linux", "x86_64", Some("musl"));
+        assert_eq!(
+            result.unwrap().name,
+            "tool-1.0-x86_64-unknown-linux-musl.zip"
+        );
+
+        let result = Rules::default().select_best_asset(&assets, "linux", "x86_64", Some("gnu"));
+        assert_eq!(
+            result.unwrap().name,
+            "tool-1.0-x86_64-unknown-linux-gnu.tar.gz"
+        );
+    }
+
+    // ── Score explanations ──────────────────────────────────────────
+
+    #[test]
+    fn test_score_assets_reports_every_asset() {
+        let assets = make_assets(&[
+            "tool-1.0-linux-amd64.tar.gz",
+            "tool-1.0-linux-arm64.tar.gz",
+            "tool-1.0-linux-amd64.tar.gz.sha256",
+            "tool-1.0-src.tar.gz",
+        ]);
+
+        let scored = Rules::default().score_assets(&assets, "linux", "x86_64", None);
+        assert_eq!(scored.len(), 4);
+
+        assert!(scored[0].is_eligible());
+        assert_eq!(scored[0].score, 17);
+        assert_eq!(scored[0].to_string(), "platform=linux arch=amd64 format+2");
+
+        assert!(!scored[1].is_eligible());
+        assert_eq!(
+            scored[1].to_string(),
+            "platform=linux no arch match format+2"
+        );
+
+        assert_eq!(scored[2].skipped.as_deref(), Some("extension .sha256"));
+        assert_eq!(scored[3].skipped.as_deref(), Some("source keyword 'src'"));
+    }
+
+    #[test]
+    fn test_best_of_prefers_earliest_on_tie() {
+        let assets = make_assets(&["a-linux-amd64.tar.gz", "b-linux-amd64.tar.gz"]);
+        let rules = Rules::default();
+        let scored = rules.score_assets(&assets, "linux", "x86_64", None);
+        assert_eq!(
+            rules.best_of(&scored).unwrap().asset.name,
+            "a-linux-amd64.tar.gz"
+        );
+    }
+
+    #[test]
+    fn test_best_of_prefers_smaller_on_tie() {
+        let mut assets = make_assets(&[
+            "a-linux-amd64.tar.gz",
+            "b-linux-amd64.tar.gz",
+            "c-linux-amd64.tar.gz",
+        ]);
+        assets[0].size = Some(9_000_000);
+        assets[1].size = Some(4_000_000);
+        let rules = Rules::default();
+        let best = rules.select_best_asset(&assets, "linux", "x86_64", None);
+        assert_eq!(best.unwrap().name, "b-linux-amd64.tar.gz");
+
+        // An unknown size counts as the largest, whatever the order.
+        assets.swap(0, 2);
+        let best = rules.select_best_asset(&assets, "linux", "x86_64", None);
+        assert_eq!(best.unwrap().name, "b-linux-amd64.tar.gz");
+    }
+
+    #[test]
+    fn test_variant_preference() {
+        let mut assets = make_assets(&[
+            "tool-minimal-linux-amd64.tar.gz",
+            "tool-full-linux-amd64.tar.gz",
+            "tool-linux-amd64.tar.gz",
+        ]);
+        assets[0].size = Some(2_000_000);
+        assets[1].size = Some(30_000_000);
+        let mut rules = Rules::default();
+        let best = |rules: &Rules| {
+            rules
+                .select_best_asset(&assets, "linux", "x86_64", None)
+                .unwrap()
+                .name
+                .clone()
+        };
+        assert_eq!(best(&rules), "tool-minimal-linux-amd64.tar.gz");
+        rules.set_variant_preference("largest".parse().unwrap());
+        assert_eq!(best(&rules), "tool-full-linux-amd64.tar.gz");
+        rules.set_variant_preference("keyword=FULL".parse().unwrap());
+        assert_eq!(best(&rules), "tool-full-linux-amd64.tar.gz");
+        rules.set_variant_preference("keyword=static".parse().unwrap());
+        assert_eq!(best(&rules), "tool-minimal-linux-amd64.tar.gz");
+
+        assert!("keyword=".parse::<VariantPreference>().is_err());
+        assert!("biggest".parse::<VariantPreference>().is_err());
+    }
+
+    #[test]
+    fn test_appimage_selected_without_archive() {
+        let assets = make_assets(&[
+            "Tool-1.0-x86_64.AppImage",
+            "Tool-1.0-arm64.AppImage",
+            "Tool-1.0-x86_64.dmg",
+        ]);
+
+        let result = Rules::default().select_best_asset(&assets, "linux", "x86_64", None);
+        assert_eq!(result.unwrap().name, "Tool-1.0-x86_64.AppImage");
+
+        let result = Rules::default().select_best_asset(&assets, "macos", "x86_64", None);
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_archive_preferred_over_bare_binary() {
+        let assets = make_assets(&["tool-v1.2.3-linux-amd64", "tool-v1.2.3-linux-amd64.tar.gz"]);
+
+        let result = Rules::default().select_best_asset(&assets, "linux", "x86_64", None);
+        assert_eq!(result.unwrap().name, "tool-v1.2.3-linux-amd64.tar.gz");
+    }
+
+    #[test]
+    fn test_asset_kind() {
+        assert_eq!(asset_kind("tool-linux-amd64.tar.gz"), AssetKind::Archive);
+        assert_eq!(asset_kind("tool-linux-amd64.tar"), AssetKind::Archive);
+        assert_eq!(asset_kind("tool-linux-amd64.tar.br"), AssetKind::Archive);
+        assert_eq!(asset_kind("Tool-1.0-x86_64.AppImage"), AssetKind::Binary);
+        assert_eq!(asset_kind("tool-windows-amd64.exe"), AssetKind::Binary);
+        assert_eq!(asset_kind("tool-v1.2.3-linux-amd64"), AssetKind::Binary);
+        assert_eq!(asset_kind("tool_linux_arm64"), AssetKind::Binary);
+        assert_eq!(asset_kind("tool_1.0_amd64.deb"), AssetKind::Other);
+        assert_eq!(asset_kind("README.pdf"), AssetKind::Other);
+        assert_eq!(asset_kind("tool.tar.gz.sha256"), AssetKind::Other);
+    }
+
+    #[test]
+    fn test_windows_formats_only_for_windows() {
+        let assets = make_assets(&["tool-windows-x86_64.cab", "tool-windows-x86_64-setup.exe"]);
+
+        let result = Rules::default().select_best_asset(&assets, "linux", "x86_64", None);
+        assert!(result.is_none());
+
+        let result = Rules::default().select_best_asset(&assets, "windows", "x86_64", None);
+        assert_eq!(result.unwrap().name, "tool-windows-x86_64.cab");
+    }
+
+    // ── Config-extended rules ───────────────────────────────────────
+
+    fn rules_with(toml: &str) -> Rules {
+        let selection: SelectionConfig = toml::from_str(toml).unwrap();
+        let mut rules = Rules::default();
+        rules.extend(&selection);
+        rules
+    }
+
+    #[test]
+    fn test_musl_hints() {
+        let assets = make_assets(&[
+            "tool-1.0-linux-x86_64.tar.gz",
+            "tool-1.0-linux-x86_64-static.tar.gz",
+        ]);
+        let rules = Rules::default();
+        let result = rules.select_best_asset(&assets, "linux", "x86_64", Some("musl"));
+        assert_eq!(result.unwrap().name, "tool-1.0-linux-x86_64-static.tar.gz");
+        let result = rules.select_best_asset(&assets, "linux", "x86_64", None);
+        assert_eq!(result.unwrap().name, "tool-1.0-linux-x86_64.tar.gz");
+
+        // Like "musl" itself, the hint outweighs the archive format.
+        let assets = make_assets(&[
+            "tool-1.0-linux-x86_64.tar.gz",
+            "tool-1.0-alpine-x86_64-linux.zip",
+        ]);
+        let result = rules.select_best_asset(&assets, "linux", "x86_64", Some("musl"));
+        assert_eq!(result.unwrap().name, "tool-1.0-alpine-x86_64-linux.zip");
+    }
+
+    #[test]
+    fn test_config_libc_alias() {
+        let rules = rules_with("[libc_aliases]\nmusl = [\"alpine\"]");
+        let assets = make_assets(&[
+            "tool-1.0-linux-x86_64.tar.gz",
+            "tool-1.0-linux-x86_64-alpine.tar.gz",
+        ]);
+
+        let result = rules.select_best_asset(&assets, "linux", "x86_64", Some("musl"));
+        assert_eq!(result.unwrap().name, "tool-1.0-linux-x86_64-alpine.tar.gz");
+    }
+
+    #[test]
+    fn test_config_new_platform_group() {
+        let rules = rules_with("[platform_aliases]\nillumos = [\"solaris\"]");
+        let assets = make_assets(&["tool-solaris-amd64.tar.gz", "tool-linux-amd64.tar.gz"]);
+
+        let result = rules.select_best_asset(&assets, "illumos", "x86_64", None);
+        assert_eq!(result.unwrap().name, "tool-solaris-amd64.tar.gz");
+    }
+
+    #[test]
+    fn test_config_skip_keywords() {
+        let rules = rules_with("skip_keywords = [\"dbgsym\"]");
+        let assets = make_assets(&["tool-dbgsym-linux-amd64.tar.gz", "tool-linux-amd64.zip"]);
+
+        let result = rules.select_best_asset(&assets, "linux", "x86_64", None);
+        assert_eq!(result.unwrap().name, "tool-linux-amd64.zip");
+    }
+
+    #[test]
+    fn test_filter_names() {
+        let assets = make_assets(&[
+            "tool-full-linux-amd64.tar.gz",
+            "tool-linux-amd64.tar.gz",
+            "tool-linux-amd64-dbgsym.tar.gz",
+            "tool-linux-amd64.zip",
+        ]);
+        let mut rules = Rules::default();
+        rules.filter_names(&[], &["*-FULL-*".into(), "*dbgsym*".into()]);
+        let matching = rules.select_matching_assets(&assets, Some("linux"), None);
+        assert_eq!(matching.len(), 2);
+        assert!(matching.iter().all(|a| !a.name.contains("full")));
+
+        rules.filter_names(&["*.zip".into()], &[]);
+        let result = rules.select_best_asset(&assets, "linux", "x86_64", None);
+        assert_eq!(result.unwrap().name, "tool-linux-amd64.zip");
+        rules.filter_names(&["*.zip".into()], &["*.zip".into()]);
+        assert!(
+            rules
+                .select_best_asset(&assets, "linux", "x86_64", None)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_config_preferred_formats() {
+        let rules = rules_with("preferred_formats = [\".tar.zst\"]");
+        let assets = make_assets(&["tool-linux-amd64.tar.gz", "tool-linux-amd64.tar.zst"]);
+
+        let result = rules.select_best_asset(&assets, "linux", "x86_64", None);
+        assert_eq!(result.unwrap().name, "tool-linux-amd64.tar.zst");
+    }
+
+    // ── Target triples ──────────────────────────────────────────────
+
+    #[test]
+    fn test_parse_target_linux_musl() {
+        assert_eq!(
+            parse_target("x86_64-unknown-linux-musl"),
+            Some(Target {
+                platform: "linux".into(),
+                arch: "x86_64".into(),
+                libc: Some("musl".into()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_target_apple() {
+        assert_eq!(
+            parse_target("aarch64-apple-darwin"),
+            Some(Target {
+                platform: "macos".into(),
+                arch: "arm64".into(),
+                libc: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_target_abi_suffix() {
+        let target = parse_target("armv7-unknown-linux-gnueabihf").unwrap();
+        assert_eq!(target.arch, "armv7");
+        assert_eq!(target.libc.as_deref(), Some("gnu"));
+
+        let target = parse_target("x86_64-pc-windows-msvc").unwrap();
+        assert_eq!(target.platform, "windows");
+        assert_eq!(target.libc.as_deref(), Some("msvc"));
+    }
+
+    #[test]
+    fn test_parse_target_invalid() {
+        assert!(parse_target("x86_64").is_none());
+        assert!(parse_target("x86_64-unknown-plan9").is_none());
+    }
+
     #[test]
     fn test_win64_alias() {
         let assets = make_assets(&["tool-1.0-win64-x64.zip", "tool-1.0-linux-amd64.tar.gz"]);
 
-        let result = select_best_asset(&assets, "windows", "x86_64");
+        let result = Rules::default().select_best_asset(&assets, "windows", "x86_64", None);
         assert_eq!(result.unwrap().name, "tool-1.0-win64-x64.zip");
     }
 }
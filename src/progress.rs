@@ -0,0 +1,157 @@
+use indicatif::HumanBytes;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How far back the moving average looks. Long enough to ride out bursty
+/// arrivals, short enough to follow a real change in speed.
+const WINDOW: Duration = Duration::from_secs(5);
+
+/// How often the progress bar's speed and ETA are redrawn.
+const REFRESH: Duration = Duration::from_millis(250);
+
+/// Watches a transfer's progress: a moving-average speed for the progress
+/// bar, and how long it's been since any bytes arrived.
+pub struct TransferMonitor {
+    /// (when, total bytes so far), oldest first, covering about `WINDOW`.
+    samples: VecDeque<(Instant, u64)>,
+    last_progress: Instant,
+    last_refresh: Option<Instant>,
+}
+
+impl TransferMonitor {
+    pub fn new(now: Instant) -> Self {
+        TransferMonitor {
+            samples: VecDeque::from([(now, 0)]),
+            last_progress: now,
+            last_refresh: None,
+        }
+    }
+
+    /// Notes that `total` bytes have arrived so far, as of `now`.
+    pub fn record(&mut self, now: Instant, total: u64) {
+        if self.samples.back().is_some_and(|&(_, last)| total > last) {
+            self.last_progress = now;
+        }
+        self.samples.push_back((now, total));
+        // Keep one sample from before the window, so the average always
+        // spans the whole of it.
+        while self
+            .samples
+            .get(1)
+            .is_some_and(|&(when, _)| now.duration_since(when) >= WINDOW)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Average speed over the last few seconds, in bytes per second. None
+    /// until there's enough of a span to average over.
+    pub fn speed(&self) -> Option<f64> {
+        let (&(start, start_bytes), &(end, end_bytes)) =
+            (self.samples.front()?, self.samples.back()?);
+        let secs = end.duration_since(start).as_secs_f64();
+        (secs >= 0.5).then(|| (end_bytes - start_bytes) as f64 / secs)
+    }
+
+    /// Time left to receive `remaining` bytes at the current speed.
+    pub fn eta(&self, remaining: u64) -> Option<Duration> {
+        let speed = self.speed().filter(|&s| s > 0.0)?;
+        Some(Duration::from_secs_f64(remaining as f64 / speed))
+    }
+
+    /// How long it's been since any bytes arrived.
+    pub fn idle(&self, now: Instant) -> Duration {
+        now.duration_since(self.last_progress)
+    }
+
+    /// The progress bar message, e.g. "1.20 MiB/s, 12s left", if it's time
+    /// to redraw it. `remaining` is None when the size isn't known.
+    pub fn status(&mut self, now: Instant, remaining: Option<u64>) -> Option<String> {
+        if self
+            .last_refresh
+            .is_some_and(|last| now.duration_since(last) < REFRESH)
+        {
+            return None;
+        }
+        self.last_refresh = Some(now);
+
+        let Some(speed) = self.speed() else {
+            return Some(String::new());
+        };
+        let speed = format!("{}/s", HumanBytes(speed as u64));
+        Some(match remaining.and_then(|r| self.eta(r)) {
+            Some(eta) => format!("{speed}, {} left", format_eta(eta)),
+            None => speed,
+        })
+    }
+}
+
+/// Formats a time left to the second: "45s", "3m 07s", "1h 02m".
+fn format_eta(eta: Duration) -> String {
+    let secs = eta.as_secs();
+    if secs >= 3600 {
+        format!("{}h {:02}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{secs}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_moving_average_speed() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut monitor = TransferMonitor::new(start);
+        assert_eq!(monitor.speed(), None);
+
+        // 1 MB/s for ten seconds, then 4 MB/s for five.
+        for s in 1..=10 {
+            monitor.record(at(s * 1000), s * 1_000_000);
+        }
+        assert_eq!(monitor.speed(), Some(1_000_000.0));
+        for s in 1..=5 {
+            monitor.record(at(10_000 + s * 1000), 10_000_000 + s * 4_000_000);
+        }
+        // The window has moved past the slow part entirely.
+        assert_eq!(monitor.speed(), Some(4_000_000.0));
+        assert_eq!(monitor.eta(8_000_000), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn test_idle_time() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut monitor = TransferMonitor::new(start);
+        monitor.record(at(1), 100);
+        monitor.record(at(5), 100);
+        monitor.record(at(7), 100);
+        assert_eq!(monitor.idle(at(8)), Duration::from_secs(7));
+        // Nothing arrived within the window, so there's no ETA.
+        assert_eq!(monitor.speed(), Some(0.0));
+        assert_eq!(monitor.eta(100), None);
+    }
+
+    #[test]
+    fn test_status() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut monitor = TransferMonitor::new(start);
+        monitor.record(at(2000), 2 * 1024 * 1024);
+        assert_eq!(
+            monitor.status(at(2000), Some(3 * 1024 * 1024)).as_deref(),
+            Some("1.00 MiB/s, 3s left")
+        );
+        // Too soon to redraw.
+        assert_eq!(monitor.status(at(2100), None), None);
+        assert_eq!(
+            monitor.status(at(2300), None).as_deref(),
+            Some("1.00 MiB/s")
+        );
+        assert_eq!(format_eta(Duration::from_secs(3720)), "1h 02m");
+    }
+}
@@ -0,0 +1,316 @@
+use clap_complete::Shell;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// File name of a project's tool list, looked for in the current directory
+/// and each of its parents.
+pub const MANIFEST: &str = "dex.toml";
+
+/// File name of the lockfile `dex sync` writes next to the manifest.
+pub const LOCKFILE: &str = "dex.lock";
+
+/// The tools a project uses, read from its `dex.toml`.
+///
+/// ```toml
+/// [tools]
+/// rg = "BurntSushi/ripgrep@14.1.1"
+/// jq = { url = "https://github.com/jqlang/jq/releases/tag/jq-1.7.1", asset = "jq-linux-amd64" }
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Manifest {
+    /// Tool name → where to get it.
+    pub tools: BTreeMap<String, Tool>,
+}
+
+/// Where one tool comes from: "owner/repo[@tag]" or any URL dex can download,
+/// optionally with the exact asset to pick from the release.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Tool {
+    Url(String),
+    Detailed { url: String, asset: Option<String> },
+}
+
+impl Tool {
+    pub fn url(&self) -> &str {
+        match self {
+            Tool::Url(url) | Tool::Detailed { url, .. } => url,
+        }
+    }
+
+    pub fn asset(&self) -> Option<&str> {
+        match self {
+            Tool::Url(_) => None,
+            Tool::Detailed { asset, .. } => asset.as_deref(),
+        }
+    }
+}
+
+/// What `dex sync` installed for each tool, so every checkout gets the same
+/// bytes until the manifest changes.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Lockfile {
+    pub tools: BTreeMap<String, LockedTool>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct LockedTool {
+    /// The manifest's URL when this was locked; editing it re-resolves the tool.
+    pub source: String,
+    /// "platform-arch" → the file installed there, since each machine of a
+    /// team may need a different build.
+    #[serde(default)]
+    pub builds: BTreeMap<String, Locked>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Locked {
+    /// Release tag the asset came from, if it came from a release.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// File name of the downloaded asset.
+    pub asset: String,
+    /// Direct download URL of the asset.
+    pub url: String,
+    pub sha256: String,
+}
+
+/// What `dex sync` last installed in this checkout, so the next sync can
+/// skip what's still current. Kept in `.dex/state.toml`, out of version
+/// control.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct SyncState {
+    pub tools: BTreeMap<String, Synced>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Synced {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    pub sha256: String,
+    /// What was put in `.dex/bin`: the binary, or its shim.
+    pub command: PathBuf,
+}
+
+impl Synced {
+    /// Whether this install is the locked file and still in place.
+    pub fn is_current(&self, locked: &Locked) -> bool {
+        self.sha256 == locked.sha256 && self.tag == locked.tag && self.command.exists()
+    }
+}
+
+/// Where a project's sync state is kept: `.dex/state.toml` under its root.
+pub fn state_path(root: &Path) -> PathBuf {
+    root.join(".dex").join("state.toml")
+}
+
+/// Finds the manifest governing `start`: `dex.toml` in it or the nearest
+/// parent directory that has one.
+pub fn find(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(MANIFEST))
+        .find(|path| path.is_file())
+}
+
+/// The lockfile's name for this machine's build: "platform-arch", e.g.
+/// "linux-x86_64".
+pub fn host_build() -> String {
+    format!(
+        "{}-{}",
+        crate::platform::normalize_platform(std::env::consts::OS),
+        crate::platform::normalize_arch(std::env::consts::ARCH)
+    )
+}
+
+/// Where a project's tools are installed: `.dex/bin` under its root.
+pub fn bin_dir(root: &Path) -> PathBuf {
+    root.join(".dex").join("bin")
+}
+
+/// Shell code that puts `bin_dir` at the front of `PATH`, for
+/// `eval "$(dex env)"`. Evaluating it again doesn't add a second copy.
+pub fn activation_script(shell: Shell, bin_dir: &Path) -> String {
+    let dir = bin_dir.display().to_string();
+    match shell {
+        Shell::Fish => format!(
+            "fish_add_path --global --path --move {}\n",
+            quote(&dir, "\\'")
+        ),
+        Shell::PowerShell => {
+            let dir = quote(&dir, "''");
+            format!(
+                "if (-not ($env:PATH -split [IO.Path]::PathSeparator -contains {dir})) {{ \
+                 $env:PATH = {dir} + [IO.Path]::PathSeparator + $env:PATH }}\n"
+            )
+        }
+        Shell::Elvish => {
+            let dir = quote(&dir, "''");
+            format!("if (not (has-value $paths {dir})) {{ set paths = [{dir} $@paths] }}\n")
+        }
+        // bash, zsh, and anything else POSIX-like.
+        _ => {
+            let dir = quote(&dir, "'\\''");
+            format!(
+                "case \":$PATH:\" in *:{dir}:*) ;; *) export PATH={dir}\"${{PATH:+:$PATH}}\" ;; esac\n"
+            )
+        }
+    }
+}
+
+/// Single-quotes `s`, writing each `'` inside it as `escaped_quote`.
+fn quote(s: &str, escaped_quote: &str) -> String {
+    format!("'{}'", s.replace('\'', escaped_quote))
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Manifest, String> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        toml::from_str(&contents).map_err(|e| format!("{}: {e}", path.display()))
+    }
+}
+
+impl SyncState {
+    /// Reads the state; a missing or unreadable file means nothing is known
+    /// to be installed, so everything is installed again.
+    pub fn load(path: &Path) -> SyncState {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| toml::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        std::fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+impl Lockfile {
+    /// Reads a lockfile; a missing one is empty.
+    pub fn load(path: &Path) -> Result<Lockfile, String> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => {
+                toml::from_str(&contents).map_err(|e| format!("{}: {e}", path.display()))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Lockfile::default()),
+            Err(e) => Err(format!("{}: {e}", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let contents = toml::to_string_pretty(self)?;
+        std::fs::write(
+            path,
+            format!("# Written by `dex sync`; commit it alongside {MANIFEST}.\n\n{contents}"),
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_walks_up() {
+        let root = std::env::temp_dir().join(format!("dex-test-project-{}", std::process::id()));
+        let nested = root.join("src").join("deep");
+        std::fs::create_dir_all(&nested).unwrap();
+        assert_ne!(find(&nested), Some(root.join(MANIFEST)));
+
+        std::fs::write(root.join(MANIFEST), "").unwrap();
+        assert_eq!(find(&nested), Some(root.join(MANIFEST)));
+        assert_eq!(find(&root), Some(root.join(MANIFEST)));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest: Manifest = toml::from_str(
+            r#"
+            [tools]
+            rg = "BurntSushi/ripgrep@14.1.1"
+            jq = { url = "https://github.com/jqlang/jq/releases/tag/jq-1.7.1", asset = "jq-linux-amd64" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(manifest.tools["rg"].url(), "BurntSushi/ripgrep@14.1.1");
+        assert_eq!(manifest.tools["rg"].asset(), None);
+        assert_eq!(manifest.tools["jq"].asset(), Some("jq-linux-amd64"));
+    }
+
+    #[test]
+    fn test_activation_script() {
+        let dir = Path::new("/home/me/it's/.dex/bin");
+        assert_eq!(
+            activation_script(Shell::Bash, dir),
+            "case \":$PATH:\" in *:'/home/me/it'\\''s/.dex/bin':*) ;; \
+             *) export PATH='/home/me/it'\\''s/.dex/bin'\"${PATH:+:$PATH}\" ;; esac\n"
+        );
+        assert_eq!(
+            activation_script(Shell::Fish, dir),
+            "fish_add_path --global --path --move '/home/me/it\\'s/.dex/bin'\n"
+        );
+        assert!(activation_script(Shell::PowerShell, dir).contains("'/home/me/it''s/.dex/bin'"));
+    }
+
+    #[test]
+    fn test_synced_is_current() {
+        let root = std::env::temp_dir().join(format!("dex-test-synced-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let locked = Locked {
+            tag: Some("v1.0".into()),
+            asset: "tool.tar.gz".into(),
+            url: "https://example.com/tool.tar.gz".into(),
+            sha256: "ab".repeat(32),
+        };
+        let synced = Synced {
+            tag: locked.tag.clone(),
+            sha256: locked.sha256.clone(),
+            command: root.join("tool"),
+        };
+        // Deleted from .dex/bin: installed again.
+        assert!(!synced.is_current(&locked));
+
+        std::fs::write(root.join("tool"), "").unwrap();
+        assert!(synced.is_current(&locked));
+        let relocked = Locked {
+            sha256: "cd".repeat(32),
+            ..locked
+        };
+        assert!(!synced.is_current(&relocked));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_lockfile_round_trip() {
+        let path = std::env::temp_dir().join(format!("dex-test-lock-{}", std::process::id()));
+        assert!(Lockfile::load(&path).unwrap().tools.is_empty());
+
+        let mut lock = Lockfile::default();
+        lock.tools.insert(
+            "rg".into(),
+            LockedTool {
+                source: "BurntSushi/ripgrep".into(),
+                builds: BTreeMap::from([(
+                    "linux-x86_64".into(),
+                    Locked {
+                        tag: Some("14.1.1".into()),
+                        asset: "ripgrep.tar.gz".into(),
+                        url: "https://example.com/ripgrep.tar.gz".into(),
+                        sha256: "ab".repeat(32),
+                    },
+                )]),
+            },
+        );
+        lock.save(&path).unwrap();
+        assert_eq!(Lockfile::load(&path).unwrap().tools, lock.tools);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,311 @@
+use crate::http::HttpContext;
+use crate::platform::{self, Asset};
+use crate::verify;
+use crate::{artifactory, azure, bitbucket, github, maven, nexus};
+use serde::{Deserialize, Serialize};
+
+/// A release's identity and its assets, converted to provider-agnostic form.
+#[derive(Serialize, Deserialize)]
+pub struct Release {
+    pub owner: String,
+    pub repo: String,
+    pub tag: String,
+    pub assets: Vec<Asset>,
+    /// The release notes (markdown), if the provider publishes any.
+    #[serde(default)]
+    pub notes: Option<String>,
+    /// Where the provider serves the tag's source code as a tarball, if it does.
+    #[serde(default)]
+    pub source_tarball: Option<String>,
+}
+
+/// A release asset chosen for download, plus its published checksum file (if any).
+pub struct ResolvedAsset {
+    /// The release the asset belongs to, for locating companion files (signatures, bundles).
+    pub release: Release,
+    pub asset: Asset,
+    pub checksum: Option<Asset>,
+}
+
+impl ResolvedAsset {
+    /// Whether the asset is the release's source tarball rather than a build.
+    pub fn is_source(&self) -> bool {
+        self.release.source_tarball.as_ref() == Some(&self.asset.url)
+    }
+}
+
+/// Which release a URL that names none means.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Latest {
+    /// The most recently published one — what the site itself calls latest.
+    #[default]
+    Date,
+    /// The one with the highest version, for projects that publish patches
+    /// to older lines after newer releases.
+    Semver,
+}
+
+impl std::str::FromStr for Latest {
+    type Err = String;
+
+    /// Parses "date" or "semver".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "date" => Ok(Latest::Date),
+            "semver" => Ok(Latest::Semver),
+            _ => Err(format!("unknown strategy {s} (expected date or semver)")),
+        }
+    }
+}
+
+/// Which asset of a release to download.
+pub struct Selector<'a> {
+    pub rules: &'a platform::Rules,
+    pub platform: &'a str,
+    pub arch: &'a str,
+    /// Arches to settle for, in order, when the release has no `arch` build.
+    pub fallback_arches: &'a [&'a str],
+    pub libc: Option<&'a str>,
+    /// An exact asset name, bypassing platform matching.
+    pub asset_name: Option<&'a str>,
+    /// Which release is the latest, for URLs that name none.
+    pub latest: Latest,
+    /// Download the source tarball when no asset matches the platform.
+    pub source_fallback: bool,
+}
+
+/// A site that publishes releases: it recognizes its URLs and lists the
+/// assets of the release one points at.
+///
+/// To support a new site, implement this in its own module and add it to
+/// `PROVIDERS`.
+pub trait Provider: Sync {
+    /// What the provider's URLs point at, for messages ("GitHub release").
+    fn name(&self) -> &'static str;
+
+    /// Whether `url` belongs to this provider.
+    fn matches(&self, url: &str) -> bool;
+
+    /// Whether the provider fetches assets itself and lists them as file://
+    /// URLs, the only ones `download::download_file` will copy.
+    fn local_assets(&self) -> bool {
+        false
+    }
+
+    /// The repository a URL names, for labels.
+    fn repo_name<'u>(&self, url: &'u str) -> Option<&'u str>;
+
+    /// Fetches the release `url` points at (the latest by `latest`, if it
+    /// names none).
+    fn fetch_release(
+        &self,
+        http: &HttpContext,
+        url: &str,
+        latest: Latest,
+    ) -> Result<Release, Box<dyn std::error::Error>>;
+
+    /// Every release of the repository `url` belongs to, newest first, for
+    /// `dex update --preview`.
+    fn releases(
+        &self,
+        _http: &HttpContext,
+        _url: &str,
+    ) -> Result<Vec<Release>, Box<dyn std::error::Error>> {
+        Err(format!("listing past releases isn't supported for {}", self.name()).into())
+    }
+
+    /// Picks the asset `selector` asks for from the release `url` points at.
+    /// Providers override this to recover when the release can't be listed.
+    fn resolve(
+        &self,
+        http: &HttpContext,
+        url: &str,
+        selector: &Selector,
+    ) -> Result<ResolvedAsset, Box<dyn std::error::Error>> {
+        selector.select(self.fetch_release(http, url, selector.latest)?)
+    }
+}
+
+/// Every provider, in the order URLs are checked against them.
+const PROVIDERS: &[&dyn Provider] = &[
+    &github::GitHub,
+    &bitbucket::Bitbucket,
+    &maven::Maven,
+    &azure::AzureArtifacts,
+    &artifactory::Artifactory,
+    &nexus::Nexus,
+];
+
+/// The provider that handles `url`, if it's a release URL.
+pub fn find(url: &str) -> Option<&'static dyn Provider> {
+    PROVIDERS.iter().copied().find(|p| p.matches(url))
+}
+
+impl Selector<'_> {
+    /// Picks the asset to download from a release — the one named `asset_name`,
+    /// or the best match for the platform — along with its checksum file.
+    /// With `source_fallback`, a release with no match gives its source
+    /// tarball instead.
+    pub fn select(&self, release: Release) -> Result<ResolvedAsset, Box<dyn std::error::Error>> {
+        if self.source_fallback
+            && self.asset_name.is_none()
+            && let Some(url) = &release.source_tarball
+            && self
+                .rules
+                .select_best_asset_or_fallback(
+                    &release.assets,
+                    self.platform,
+                    self.arch,
+                    self.fallback_arches,
+                    self.libc,
+                )
+                .is_none()
+        {
+            let name = format!("{}-{}-source.tar.gz", release.repo, release.tag);
+            eprintln!(
+                "Found: {} {} → {name} (SOURCE CODE: the release has no build for \
+                 platform={}, arch={})",
+                release.repo, release.tag, self.platform, self.arch
+            );
+            let asset = Asset {
+                name,
+                url: url.clone(),
+                digest: None,
+                size: None,
+                content_type: None,
+                updated_at: None,
+            };
+            return Ok(ResolvedAsset {
+                release,
+                asset,
+                checksum: None,
+            });
+        }
+
+        let (platform, arch) = (self.platform, self.arch);
+        let asset = match self.asset_name {
+            Some(name) => release
+                .assets
+                .iter()
+                .find(|a| a.name == name)
+                .ok_or_else(|| format!("Release {} has no asset named {name}", release.tag))?,
+            None => {
+                let (asset, picked) = self
+                    .rules
+                    .select_best_asset_or_fallback(
+                        &release.assets,
+                        platform,
+                        arch,
+                        self.fallback_arches,
+                        self.libc,
+                    )
+                    .ok_or_else(|| {
+                        format!(
+                            "No matching asset for platform={platform}, arch={arch} in release {}",
+                            release.tag
+                        )
+                    })?;
+                if picked != arch {
+                    eprintln!(
+                        "No {arch} build in {}; using {picked} under emulation",
+                        release.tag
+                    );
+                }
+                asset
+            }
+        };
+
+        eprintln!("Found: {} {} → {}", release.repo, release.tag, asset.name);
+
+        let asset = asset.clone();
+        let checksum = verify::find_checksum_asset(&release.assets, &asset.name).cloned();
+        Ok(ResolvedAsset {
+            release,
+            asset,
+            checksum,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_provider() {
+        let name = |url| find(url).map(|p| p.name());
+        assert_eq!(
+            name("https://github.com/BurntSushi/ripgrep/releases"),
+            Some("GitHub release")
+        );
+        assert_eq!(
+            name("https://bitbucket.org/atlassian/tool/downloads"),
+            Some("Bitbucket downloads")
+        );
+        assert_eq!(
+            name("maven://repo1.maven.org/maven2/com.acme:tool"),
+            Some("Maven artifact")
+        );
+        assert_eq!(
+            name("azdo://contoso/tools/internal/cli"),
+            Some("Azure Artifacts package")
+        );
+        assert_eq!(
+            name("artifactory://acme.jfrog.io/artifactory/tools-local/mytool"),
+            Some("Artifactory repository")
+        );
+        assert_eq!(
+            name("nexus://nexus.example.com/repository/tools/mytool"),
+            Some("Nexus repository")
+        );
+        assert_eq!(name("https://example.com/tool.tar.gz"), None);
+    }
+
+    #[test]
+    fn test_select_by_platform_and_name() {
+        let release = || Release {
+            owner: "owner".into(),
+            repo: "tool".into(),
+            tag: "v1.0".into(),
+            assets: platform::make_assets(&[
+                "tool-linux-amd64.tar.gz",
+                "tool-darwin-arm64.tar.gz",
+                "checksums.txt",
+            ]),
+            notes: None,
+            source_tarball: Some("https://example.com/v1.0.tar.gz".into()),
+        };
+        let rules = platform::Rules::default();
+        let mut selector = Selector {
+            rules: &rules,
+            platform: "linux",
+            arch: "x86_64",
+            fallback_arches: &[],
+            libc: None,
+            asset_name: None,
+            latest: Latest::Date,
+            source_fallback: false,
+        };
+
+        let resolved = selector.select(release()).unwrap();
+        assert_eq!(resolved.asset.name, "tool-linux-amd64.tar.gz");
+        assert_eq!(resolved.checksum.unwrap().name, "checksums.txt");
+
+        selector.asset_name = Some("tool-darwin-arm64.tar.gz");
+        assert_eq!(
+            selector.select(release()).unwrap().asset.name,
+            "tool-darwin-arm64.tar.gz"
+        );
+        selector.asset_name = Some("missing.zip");
+        assert!(selector.select(release()).is_err());
+
+        selector.asset_name = None;
+        selector.platform = "freebsd";
+        assert!(selector.select(release()).is_err());
+        selector.source_fallback = true;
+        let resolved = selector.select(release()).unwrap();
+        assert_eq!(resolved.asset.name, "tool-v1.0-source.tar.gz");
+        assert_eq!(resolved.asset.url, "https://example.com/v1.0.tar.gz");
+        assert!(resolved.checksum.is_none() && resolved.is_source());
+    }
+}
@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// A tool installed into one bin directory, as `dex which` and `dex info`
+/// report it. Every receipt for a tool is kept in dex's state directory as
+/// `installs/<tool>.json`.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Receipt {
+    /// What users run: the binary itself, or its --shim.
+    pub command: PathBuf,
+    pub shim: bool,
+    /// Every version installed there; only --shim installs keep more than one.
+    pub installs: Vec<Install>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Install {
+    /// The release tag, or a version read from the file name.
+    pub version: Option<String>,
+    /// URL the download came from.
+    pub url: String,
+    /// "owner/repo" of the release, if it came from one.
+    pub repo: Option<String>,
+    pub tag: Option<String>,
+    /// SHA-256 of the download.
+    pub sha256: String,
+    /// Seconds since the Unix epoch.
+    pub installed_at: u64,
+    /// The installed binary (inside the store for a --shim install).
+    pub binary: PathBuf,
+    /// Man pages and completion scripts installed with it.
+    #[serde(default)]
+    pub files: Vec<PathBuf>,
+}
+
+impl Receipt {
+    /// The version the command runs: for a --shim install, the one the
+    /// store's `current` link points at.
+    pub fn active(&self) -> Option<&Install> {
+        if !self.shim {
+            return self.installs.last();
+        }
+        // <store>/<tool>/<version>/<binary>
+        let tool_dir = self.installs.first()?.binary.parent()?.parent()?;
+        let store = tool_dir.parent()?;
+        let tool = tool_dir.file_name()?.to_str()?;
+        let current = crate::layout::current_version(store, tool)?;
+        self.installs
+            .iter()
+            .find(|install| install.version.as_ref() == Some(&current))
+    }
+}
+
+/// Where a tool's receipts are kept.
+fn path(tool: &str) -> Option<PathBuf> {
+    Some(
+        crate::dirs::state_dir()?
+            .join("installs")
+            .join(format!("{tool}.json")),
+    )
+}
+
+/// Every receipt for `tool`, one per bin directory it was installed into.
+pub fn load(tool: &str) -> Result<Vec<Receipt>, Box<dyn std::error::Error>> {
+    let Some(path) = path(tool) else {
+        return Ok(Vec::new());
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => {
+            serde_json::from_str(&contents).map_err(|e| format!("{}: {e}", path.display()).into())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("{}: {e}", path.display()).into()),
+    }
+}
+
+/// Records that `install` was put in place behind `command`. A plain
+/// install replaces whatever was recorded for that command; a --shim install
+/// replaces only the same version.
+pub fn record(
+    tool: &str,
+    command: &Path,
+    shim: bool,
+    install: Install,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = path(tool).ok_or("could not determine dex's state directory")?;
+    let _guard = crate::filelock::lock(&path)?;
+    let mut receipts = load(tool)?;
+    match receipts.iter_mut().find(|r| r.command == command) {
+        Some(receipt) => add(receipt, shim, install),
+        None => receipts.push(Receipt {
+            command: command.to_path_buf(),
+            shim,
+            installs: vec![install],
+        }),
+    }
+
+    std::fs::write(&path, serde_json::to_string_pretty(&receipts)?)?;
+    Ok(())
+}
+
+fn add(receipt: &mut Receipt, shim: bool, install: Install) {
+    if shim && receipt.shim {
+        receipt.installs.retain(|i| i.version != install.version);
+    } else {
+        receipt.installs.clear();
+    }
+    receipt.shim = shim;
+    receipt.installs.push(install);
+}
+
+/// The receipt for the command a shell would run: the first directory on
+/// `PATH` holding one of `receipts`' commands, else the latest install.
+pub fn on_path(mut receipts: Vec<Receipt>) -> Option<Receipt> {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let index = std::env::split_paths(&path)
+        .find_map(|dir| {
+            receipts
+                .iter()
+                .position(|r| r.command.parent() == Some(&dir))
+        })
+        .or_else(|| {
+            (0..receipts.len())
+                .max_by_key(|&i| receipts[i].installs.iter().map(|i| i.installed_at).max())
+        })?;
+    Some(receipts.swap_remove(index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn install(version: &str) -> Install {
+        Install {
+            version: Some(version.into()),
+            url: format!("https://example.com/tool-{version}.tar.gz"),
+            repo: None,
+            tag: None,
+            sha256: "ab".repeat(32),
+            installed_at: 0,
+            binary: PathBuf::from(format!("/store/tool/{version}/tool")),
+            files: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_add_keeps_shim_versions() {
+        let mut receipt = Receipt {
+            command: "/bin/tool".into(),
+            shim: true,
+            installs: vec![install("1.0"), install("1.1")],
+        };
+        add(&mut receipt, true, install("1.0"));
+        let versions: Vec<_> = receipt.installs.iter().map(|i| i.version.clone()).collect();
+        assert_eq!(versions, [Some("1.1".into()), Some("1.0".into())]);
+
+        // A plain install over it leaves just the one binary.
+        add(&mut receipt, false, install("2.0"));
+        assert_eq!(receipt.installs, [install("2.0")]);
+        assert!(!receipt.shim);
+    }
+}
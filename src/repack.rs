@@ -0,0 +1,275 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The modification time every repacked entry gets, the earliest a zip
+/// archive can record, so all three formats agree.
+const MTIME: u64 = crate::extract::FIXED_MTIME;
+
+/// An archive format --repack writes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    TarGz,
+    TarZst,
+    Zip,
+}
+
+impl std::str::FromStr for Format {
+    type Err = String;
+
+    /// Parses "tar.gz" (or "tgz"), "tar.zst" (or "tzst"), or "zip".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tar.gz" | "tgz" => Ok(Format::TarGz),
+            "tar.zst" | "tzst" => Ok(Format::TarZst),
+            "zip" => Ok(Format::Zip),
+            other => Err(format!(
+                "unknown repack format '{other}' (expected tar.gz, tar.zst, or zip)"
+            )),
+        }
+    }
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::TarGz => "tar.gz",
+            Format::TarZst => "tar.zst",
+            Format::Zip => "zip",
+        }
+    }
+}
+
+/// One thing under the tree being repacked.
+enum Entry {
+    Dir,
+    File { executable: bool },
+    Symlink(PathBuf),
+}
+
+/// Everything under `dir`, as paths relative to `root` with `/` separators.
+fn collect(
+    root: &Path,
+    dir: &Path,
+    entries: &mut Vec<(String, PathBuf, Entry)>,
+) -> std::io::Result<()> {
+    for item in std::fs::read_dir(dir)? {
+        let path = item?.path();
+        let relative = path
+            .strip_prefix(root)
+            .unwrap_or(&path)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let file_type = std::fs::symlink_metadata(&path)?.file_type();
+        if file_type.is_symlink() {
+            let target = std::fs::read_link(&path)?;
+            entries.push((relative, path, Entry::Symlink(target)));
+        } else if file_type.is_dir() {
+            entries.push((relative, path.clone(), Entry::Dir));
+            collect(root, &path, entries)?;
+        } else {
+            let executable = crate::binaries::is_executable(&path);
+            entries.push((relative, path, Entry::File { executable }));
+        }
+    }
+    Ok(())
+}
+
+/// The mode an entry is archived with. Only whether a file is executable
+/// survives, so the archive doesn't depend on the umask it was extracted under.
+fn mode(entry: &Entry) -> u32 {
+    match entry {
+        Entry::Dir | Entry::File { executable: true } => 0o755,
+        Entry::File { executable: false } => 0o644,
+        Entry::Symlink(_) => 0o777,
+    }
+}
+
+fn write_tar<W: Write>(entries: &[(String, PathBuf, Entry)], out: W) -> std::io::Result<W> {
+    let mut builder = tar::Builder::new(out);
+    for (name, path, entry) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(mode(entry));
+        header.set_mtime(MTIME);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_size(0);
+        match entry {
+            Entry::Dir => {
+                header.set_entry_type(tar::EntryType::Directory);
+                builder.append_data(&mut header, format!("{name}/"), std::io::empty())?;
+            }
+            Entry::Symlink(target) => {
+                header.set_entry_type(tar::EntryType::Symlink);
+                builder.append_link(&mut header, name, target)?;
+            }
+            Entry::File { .. } => {
+                let file = std::fs::File::open(path)?;
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_size(file.metadata()?.len());
+                builder.append_data(&mut header, name, file)?;
+            }
+        }
+    }
+    builder.into_inner()
+}
+
+fn write_zip(
+    entries: &[(String, PathBuf, Entry)],
+    out: std::fs::File,
+) -> Result<std::fs::File, Box<dyn std::error::Error>> {
+    let mut writer = zip::ZipWriter::new(out);
+    let options = |entry: &Entry| {
+        zip::write::SimpleFileOptions::default()
+            .last_modified_time(zip::DateTime::default())
+            .unix_permissions(mode(entry))
+    };
+    for (name, path, entry) in entries {
+        match entry {
+            Entry::Dir => writer.add_directory(name.as_str(), options(entry))?,
+            Entry::Symlink(target) => {
+                writer.add_symlink(name.as_str(), target.to_string_lossy(), options(entry))?
+            }
+            Entry::File { .. } => {
+                writer.start_file(name.as_str(), options(entry))?;
+                std::io::copy(&mut std::fs::File::open(path)?, &mut writer)?;
+            }
+        }
+    }
+    Ok(writer.finish()?)
+}
+
+/// Archives everything under `root` into `out` as `format`, the same way
+/// every time for the same files: entries are sorted, every timestamp is
+/// 1980-01-01, owners are root, and modes are 0755 or 0644. The archive is
+/// written under a temporary name and renamed into place. Returns how many
+/// entries it holds.
+pub fn repack(
+    root: &Path,
+    format: Format,
+    out: &Path,
+) -> Result<usize, Box<dyn std::error::Error>> {
+    let mut entries = Vec::new();
+    collect(root, root, &mut entries)?;
+    // Sorted, so the order files came out of the archive doesn't matter.
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut partial = out.as_os_str().to_owned();
+    partial.push(format!(".{}", std::process::id()));
+    let partial = PathBuf::from(partial);
+    let written = (|| -> Result<(), Box<dyn std::error::Error>> {
+        let file = std::fs::File::create(&partial)?;
+        let file = match format {
+            Format::TarGz => {
+                // No name and a zero timestamp in the gzip header either.
+                let encoder = flate2::GzBuilder::new().write(file, flate2::Compression::default());
+                write_tar(&entries, encoder)?.finish()?
+            }
+            Format::TarZst => write_tar(&entries, zstd::Encoder::new(file, 0)?)?.finish()?,
+            Format::Zip => write_zip(&entries, file)?,
+        };
+        file.sync_all()?;
+        std::fs::rename(&partial, out)?;
+        Ok(())
+    })();
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&partial);
+        return Err(e);
+    }
+    Ok(entries.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verify::{Algorithm, hash_file};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("dex-test-repack-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_parse_format() {
+        assert_eq!("tar.gz".parse(), Ok(Format::TarGz));
+        assert_eq!("tzst".parse(), Ok(Format::TarZst));
+        assert_eq!("zip".parse(), Ok(Format::Zip));
+        assert!("rar".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn test_repack_is_reproducible() {
+        let dir = temp_dir("reproducible");
+        for (tree, stamp) in [("a", 1_600_000_000), ("b", 1_700_000_000)] {
+            let root = dir.join(tree);
+            std::fs::create_dir_all(root.join("tool-1.0/bin")).unwrap();
+            std::fs::create_dir_all(root.join("tool-1.0/empty")).unwrap();
+            // Written in a different order each time.
+            let files = [
+                ("tool-1.0/README", &b"docs"[..]),
+                ("tool-1.0/bin/tool", b"\x7fELF"),
+            ];
+            let order: Vec<_> = match tree {
+                "a" => files.iter().collect(),
+                _ => files.iter().rev().collect(),
+            };
+            for (name, contents) in order {
+                let path = root.join(name);
+                std::fs::write(&path, contents).unwrap();
+                let mtime = std::time::UNIX_EPOCH + std::time::Duration::from_secs(stamp);
+                std::fs::File::options()
+                    .write(true)
+                    .open(&path)
+                    .unwrap()
+                    .set_modified(mtime)
+                    .unwrap();
+            }
+        }
+
+        for format in [Format::TarGz, Format::TarZst, Format::Zip] {
+            let out = |tree: &str| dir.join(format!("{tree}.{}", format.extension()));
+            assert_eq!(repack(&dir.join("a"), format, &out("a")).unwrap(), 5);
+            repack(&dir.join("b"), format, &out("b")).unwrap();
+            assert_eq!(
+                hash_file(&out("a"), Algorithm::Sha256).unwrap().hex,
+                hash_file(&out("b"), Algorithm::Sha256).unwrap().hex,
+                "{format:?}"
+            );
+
+            let unpacked = dir.join(format!("unpacked-{}", format.extension()));
+            crate::extract::extract_file(&out("a"), &unpacked, &Default::default()).unwrap();
+            assert_eq!(
+                std::fs::read(unpacked.join("tool-1.0/bin/tool")).unwrap(),
+                b"\x7fELF"
+            );
+            assert!(unpacked.join("tool-1.0/empty").is_dir());
+            // dex's zip extraction doesn't apply modes, so read the archive's.
+            if format == Format::Zip {
+                let mut archive =
+                    zip::ZipArchive::new(std::fs::File::open(out("a")).unwrap()).unwrap();
+                let mut mode = |p: &str| archive.by_name(p).unwrap().unix_mode().unwrap() & 0o777;
+                assert_eq!(mode("tool-1.0/bin/tool"), 0o755);
+                assert_eq!(mode("tool-1.0/README"), 0o644);
+                continue;
+            }
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = |p: &str| {
+                    std::fs::metadata(unpacked.join(p))
+                        .unwrap()
+                        .permissions()
+                        .mode()
+                        & 0o777
+                };
+                assert_eq!(mode("tool-1.0/bin/tool"), 0o755);
+                assert_eq!(mode("tool-1.0/README"), 0o644);
+            }
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,165 @@
+use crate::stats::Stats;
+use serde::Serialize;
+use std::fmt::Write;
+use std::path::Path;
+use std::time::Duration;
+
+/// Everything --report records about one URL.
+#[derive(Serialize, Default, Debug, Clone)]
+pub struct Item {
+    pub url: String,
+    /// "ok", "skipped", or what went wrong, as in the batch summary.
+    pub status: String,
+    pub duration_secs: f64,
+    /// What the asset was picked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub libc: Option<String>,
+    /// "owner/repo" of the release the URL resolved to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repo: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// The asset picked from the release.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset: Option<String>,
+    /// What was actually downloaded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+    /// The server's Content-Encoding, if it sent one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_encoding: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Algorithm of the published or pinned checksum the download matched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verified: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub from_cache: bool,
+}
+
+/// Writes the --report file: JUnit XML if its name ends in `.xml`, else JSON.
+pub fn write(
+    path: &Path,
+    items: &[Item],
+    exit_code: i32,
+    elapsed: Duration,
+    stats: &Stats,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let is_xml = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("xml"));
+    let contents = if is_xml {
+        junit(items, elapsed)
+    } else {
+        let report = serde_json::json!({
+            "exit_code": exit_code,
+            "duration_secs": elapsed.as_secs_f64(),
+            "results": items,
+            "stats": stats,
+        });
+        format!("{report:#}\n")
+    };
+    std::fs::write(path, contents)?;
+    Ok(())
+}
+
+/// The report as a JUnit test suite, one test case per URL, for CI systems
+/// that show those natively.
+fn junit(items: &[Item], elapsed: Duration) -> String {
+    let failures = items
+        .iter()
+        .filter(|i| i.status != "ok" && i.status != "skipped")
+        .count();
+    let skipped = items.iter().filter(|i| i.status == "skipped").count();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuite name=\"dex\" tests=\"{}\" failures=\"{failures}\" skipped=\"{skipped}\" time=\"{:.3}\">",
+        items.len(),
+        elapsed.as_secs_f64(),
+    );
+    for item in items {
+        let _ = write!(
+            xml,
+            "  <testcase classname=\"dex\" name=\"{}\" time=\"{:.3}\">",
+            escape(&item.url),
+            item.duration_secs
+        );
+        match item.status.as_str() {
+            "ok" => {}
+            "skipped" => xml.push_str("\n    <skipped/>"),
+            status => {
+                let _ = write!(xml, "\n    <failure message=\"{}\"/>", escape(status));
+            }
+        }
+        let details = details(item);
+        if !details.is_empty() {
+            let _ = write!(xml, "\n    <system-out>{}</system-out>", escape(&details));
+        }
+        xml.push_str("\n  </testcase>\n");
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+/// An item's resolution and digest, one "key: value" per line.
+fn details(item: &Item) -> String {
+    let fields = [
+        ("repo", item.repo.as_deref()),
+        ("tag", item.tag.as_deref()),
+        ("asset", item.asset.as_deref()),
+        ("download_url", item.download_url.as_deref()),
+        ("sha256", item.sha256.as_deref()),
+        ("verified", item.verified.as_deref()),
+    ];
+    fields
+        .iter()
+        .filter_map(|(key, value)| Some(format!("{key}: {}\n", (*value)?)))
+        .collect()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_junit() {
+        let items = [
+            Item {
+                url: "https://example.com/a.zip?x=1&y=2".into(),
+                status: "ok".into(),
+                tag: Some("v1.0".into()),
+                ..Default::default()
+            },
+            Item {
+                url: "https://example.com/b.zip".into(),
+                status: "verification failed".into(),
+                ..Default::default()
+            },
+            Item {
+                url: "https://example.com/c.zip".into(),
+                status: "skipped".into(),
+                ..Default::default()
+            },
+        ];
+        let xml = junit(&items, Duration::from_secs(2));
+        assert!(xml.contains("tests=\"3\" failures=\"1\" skipped=\"1\""));
+        assert!(xml.contains("name=\"https://example.com/a.zip?x=1&amp;y=2\""));
+        assert!(xml.contains("<system-out>tag: v1.0\n</system-out>"));
+        assert!(xml.contains("<failure message=\"verification failed\"/>"));
+        assert!(xml.contains("<skipped/>"));
+    }
+}
@@ -0,0 +1,253 @@
+use crate::platform::{self, Asset};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+// Suffixes of SBOM files, most specific first.
+const SBOM_SUFFIXES: &[&str] = &[
+    ".spdx.json",
+    ".cdx.json",
+    ".sbom.json",
+    ".bom.json",
+    ".sbom",
+    ".spdx",
+];
+
+/// Finds the SBOM describing `asset_name` among a release's assets.
+///
+/// Looks for, in order:
+///   tool-linux.tar.gz.sbom.json   (named after the asset)
+///   tool-linux.sbom.json          (named after the asset without its archive extension)
+///   sbom.spdx.json                (a single SBOM for the whole release)
+pub fn find_sbom_asset<'a>(assets: &'a [Asset], asset_name: &str) -> Option<&'a Asset> {
+    let is_sbom = |name: &str| {
+        let lower = name.to_lowercase();
+        SBOM_SUFFIXES.iter().any(|s| lower.ends_with(s))
+    };
+
+    let sboms: Vec<&Asset> = assets.iter().filter(|a| is_sbom(&a.name)).collect();
+
+    if let Some(asset) = sboms
+        .iter()
+        .find(|a| a.name.starts_with(&format!("{asset_name}.")))
+    {
+        return Some(asset);
+    }
+
+    let stem = platform::strip_archive_ext(asset_name);
+    if let Some(asset) = sboms
+        .iter()
+        .find(|a| a.name.starts_with(&format!("{stem}.")))
+    {
+        return Some(asset);
+    }
+
+    // Only fall back to a release-wide SBOM when it's unambiguous.
+    match sboms.as_slice() {
+        [only] => Some(only),
+        _ => None,
+    }
+}
+
+// Only the fields we summarize from SPDX 2.x JSON documents.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxDocument {
+    #[serde(default)]
+    packages: Vec<SpdxPackage>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxPackage {
+    license_concluded: Option<String>,
+    license_declared: Option<String>,
+}
+
+// Only the fields we summarize from CycloneDX JSON documents.
+#[derive(Deserialize)]
+struct CycloneDxDocument {
+    #[serde(default)]
+    components: Vec<CycloneDxComponent>,
+}
+
+#[derive(Deserialize)]
+struct CycloneDxComponent {
+    #[serde(default)]
+    licenses: Vec<CycloneDxLicenseChoice>,
+}
+
+#[derive(Deserialize)]
+struct CycloneDxLicenseChoice {
+    license: Option<CycloneDxLicense>,
+    expression: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CycloneDxLicense {
+    id: Option<String>,
+    name: Option<String>,
+}
+
+/// Component and license counts from an SBOM.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Summary {
+    pub format: &'static str,
+    pub components: usize,
+    /// License expression → number of components under it.
+    pub licenses: BTreeMap<String, usize>,
+}
+
+/// Summarizes an SPDX or CycloneDX JSON document.
+pub fn summarize(json: &str) -> Result<Summary, String> {
+    let value: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("invalid SBOM JSON: {e}"))?;
+
+    if value.get("spdxVersion").is_some() {
+        let doc: SpdxDocument = serde_json::from_value(value).map_err(|e| e.to_string())?;
+        let licenses = count(doc.packages.iter().map(|p| {
+            // NOASSERTION means "the tool didn't determine it" — fall back to declared.
+            p.license_concluded
+                .as_deref()
+                .filter(|l| *l != "NOASSERTION")
+                .or(p.license_declared.as_deref())
+        }));
+        return Ok(Summary {
+            format: "SPDX",
+            components: doc.packages.len(),
+            licenses,
+        });
+    }
+
+    if value.get("bomFormat").and_then(|f| f.as_str()) == Some("CycloneDX") {
+        let doc: CycloneDxDocument = serde_json::from_value(value).map_err(|e| e.to_string())?;
+        let licenses = count(doc.components.iter().map(|c| {
+            c.licenses.first().and_then(|choice| {
+                choice.expression.as_deref().or_else(|| {
+                    let license = choice.license.as_ref()?;
+                    license.id.as_deref().or(license.name.as_deref())
+                })
+            })
+        }));
+        return Ok(Summary {
+            format: "CycloneDX",
+            components: doc.components.len(),
+            licenses,
+        });
+    }
+
+    Err("unrecognized SBOM format (expected SPDX or CycloneDX JSON)".into())
+}
+
+fn count<'a>(licenses: impl Iterator<Item = Option<&'a str>>) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for license in licenses {
+        let key = match license {
+            Some(l) if l != "NOASSERTION" && !l.is_empty() => l.to_string(),
+            _ => "unknown".to_string(),
+        };
+        *counts.entry(key).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Prints a summary to stdout, most common licenses first.
+pub fn print_summary(name: &str, summary: &Summary) {
+    println!(
+        "SBOM {name} ({}): {} components",
+        summary.format, summary.components
+    );
+
+    let mut licenses: Vec<(&String, &usize)> = summary.licenses.iter().collect();
+    licenses.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    for (license, n) in licenses {
+        println!("  {n:>5}  {license}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::make_assets;
+
+    // ── SBOM discovery ──────────────────────────────────────────────
+
+    #[test]
+    fn test_find_sbom_named_after_asset() {
+        let assets = make_assets(&[
+            "tool_linux_amd64.tar.gz",
+            "tool_linux_amd64.tar.gz.sbom.json",
+            "tool_darwin_arm64.tar.gz.sbom.json",
+        ]);
+        let found = find_sbom_asset(&assets, "tool_linux_amd64.tar.gz").unwrap();
+        assert_eq!(found.name, "tool_linux_amd64.tar.gz.sbom.json");
+    }
+
+    #[test]
+    fn test_find_sbom_named_after_stem() {
+        let assets = make_assets(&[
+            "tool-linux.tar.gz",
+            "tool-linux.spdx.json",
+            "tool-macos.spdx.json",
+        ]);
+        let found = find_sbom_asset(&assets, "tool-linux.tar.gz").unwrap();
+        assert_eq!(found.name, "tool-linux.spdx.json");
+    }
+
+    #[test]
+    fn test_find_release_wide_sbom() {
+        let assets = make_assets(&["tool-linux.tar.gz", "sbom.cdx.json"]);
+        let found = find_sbom_asset(&assets, "tool-linux.tar.gz").unwrap();
+        assert_eq!(found.name, "sbom.cdx.json");
+    }
+
+    #[test]
+    fn test_ambiguous_sboms_not_selected() {
+        let assets = make_assets(&["tool-linux.tar.gz", "a.spdx.json", "b.spdx.json"]);
+        assert!(find_sbom_asset(&assets, "tool-linux.tar.gz").is_none());
+    }
+
+    // ── Summaries ───────────────────────────────────────────────────
+
+    #[test]
+    fn test_summarize_spdx() {
+        let json = r#"{
+            "spdxVersion": "SPDX-2.3",
+            "packages": [
+                {"name": "a", "licenseConcluded": "MIT"},
+                {"name": "b", "licenseConcluded": "NOASSERTION", "licenseDeclared": "Apache-2.0"},
+                {"name": "c", "licenseConcluded": "MIT"},
+                {"name": "d"}
+            ]
+        }"#;
+        let summary = summarize(json).unwrap();
+        assert_eq!(summary.format, "SPDX");
+        assert_eq!(summary.components, 4);
+        assert_eq!(summary.licenses["MIT"], 2);
+        assert_eq!(summary.licenses["Apache-2.0"], 1);
+        assert_eq!(summary.licenses["unknown"], 1);
+    }
+
+    #[test]
+    fn test_summarize_cyclonedx() {
+        let json = r#"{
+            "bomFormat": "CycloneDX",
+            "specVersion": "1.5",
+            "components": [
+                {"name": "a", "licenses": [{"license": {"id": "MIT"}}]},
+                {"name": "b", "licenses": [{"expression": "MIT OR Apache-2.0"}]},
+                {"name": "c", "licenses": [{"license": {"name": "Custom"}}]}
+            ]
+        }"#;
+        let summary = summarize(json).unwrap();
+        assert_eq!(summary.format, "CycloneDX");
+        assert_eq!(summary.components, 3);
+        assert_eq!(summary.licenses["MIT OR Apache-2.0"], 1);
+        assert_eq!(summary.licenses["Custom"], 1);
+    }
+
+    #[test]
+    fn test_summarize_unknown_format() {
+        assert!(summarize(r#"{"hello": "world"}"#).is_err());
+        assert!(summarize("not json").is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use crate::download;
+use crate::http::HttpContext;
+
+/// Returns true if the URL points at a SourceForge project or file.
+pub fn is_sourceforge_url(url: &str) -> bool {
+    parse_sourceforge_url(url).is_some()
+}
+
+/// Parses a SourceForge URL into (project, optional file path).
+///
+/// Supported patterns:
+///   https://sourceforge.net/projects/name
+///   https://sourceforge.net/projects/name/files
+///   https://sourceforge.net/projects/name/files/latest/download
+///   https://sourceforge.net/projects/name/files/1.0/name-1.0.tar.gz
+///   https://sourceforge.net/projects/name/files/1.0/name-1.0.tar.gz/download
+fn parse_sourceforge_url(url: &str) -> Option<(&str, Option<&str>)> {
+    let path = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))?;
+    let path = path
+        .strip_prefix("www.")
+        .unwrap_or(path)
+        .strip_prefix("sourceforge.net/projects/")?;
+    let path = path.split(['?', '#']).next()?.trim_end_matches('/');
+
+    let (project, rest) = path.split_once('/').unwrap_or((path, ""));
+    if project.is_empty() {
+        return None;
+    }
+
+    let file = rest
+        .strip_prefix("files")
+        .map(|f| f.trim_start_matches('/'))
+        .map(|f| f.strip_suffix("/download").unwrap_or(f))
+        .map(|f| f.strip_suffix("download").unwrap_or(f))
+        .filter(|f| !f.is_empty() && *f != "latest");
+
+    match (rest, file) {
+        // The project page, its file browser, or the "latest" link.
+        ("" | "files" | "files/latest/download" | "files/latest", None) => Some((project, None)),
+        (_, Some(file)) => Some((project, Some(file))),
+        _ => None,
+    }
+}
+
+/// Builds the URL SourceForge serves a download from: the project's current
+/// default download when no file is given.
+fn download_page_url(project: &str, file: Option<&str>) -> String {
+    match file {
+        Some(file) => format!("https://sourceforge.net/projects/{project}/files/{file}/download"),
+        None => format!("https://sourceforge.net/projects/{project}/files/latest/download"),
+    }
+}
+
+/// Resolves a SourceForge URL to a direct mirror URL.
+///
+/// SourceForge download links redirect through downloads.sourceforge.net to
+/// whichever mirror it picks; the file's real name only appears in the final
+/// URL. Following the chain up front gives the downloader a URL whose last
+/// segment is that name, so archive detection works.
+pub fn resolve(http: &HttpContext, url: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let (project, file) = parse_sourceforge_url(url).ok_or("Not a SourceForge project URL")?;
+
+    // Only the final URL matters; the body is dropped without being read.
+    let response = http
+        .send(http.get(&download_page_url(project, file)))?
+        .error_for_status()?;
+    let mirror_url = response.url().to_string();
+
+    // Without a redirect SourceForge served an HTML page instead of a file
+    // (e.g. a project with no files released).
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/html"));
+    if is_html {
+        return Err(format!("SourceForge has no downloadable file for {project}").into());
+    }
+
+    eprintln!(
+        "Found: {project} → {}",
+        download::filename_from_url(&mirror_url)
+    );
+    Ok(mirror_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ── URL parsing ─────────────────────────────────────────────────
+
+    #[test]
+    fn test_parse_project() {
+        let expected = Some(("sevenzip", None));
+        assert_eq!(
+            parse_sourceforge_url("https://sourceforge.net/projects/sevenzip"),
+            expected
+        );
+        assert_eq!(
+            parse_sourceforge_url("https://sourceforge.net/projects/sevenzip/files/"),
+            expected
+        );
+        assert_eq!(
+            parse_sourceforge_url(
+                "https://sourceforge.net/projects/sevenzip/files/latest/download"
+            ),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_parse_file() {
+        let expected = Some(("tool", Some("1.0/tool-1.0.tar.gz")));
+        assert_eq!(
+            parse_sourceforge_url(
+                "https://sourceforge.net/projects/tool/files/1.0/tool-1.0.tar.gz"
+            ),
+            expected
+        );
+        assert_eq!(
+            parse_sourceforge_url(
+                "https://sourceforge.net/projects/tool/files/1.0/tool-1.0.tar.gz/download"
+            ),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_parse_non_sourceforge_url() {
+        assert!(parse_sourceforge_url("https://example.com/projects/tool").is_none());
+        assert!(parse_sourceforge_url("https://sourceforge.net/projects/").is_none());
+        assert!(parse_sourceforge_url("https://sourceforge.net/projects/tool/reviews").is_none());
+    }
+
+    #[test]
+    fn test_download_page_url() {
+        assert_eq!(
+            download_page_url("tool", None),
+            "https://sourceforge.net/projects/tool/files/latest/download"
+        );
+        assert_eq!(
+            download_page_url("tool", Some("1.0/tool-1.0.tar.gz")),
+            "https://sourceforge.net/projects/tool/files/1.0/tool-1.0.tar.gz/download"
+        );
+    }
+}
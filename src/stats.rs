@@ -0,0 +1,118 @@
+use indicatif::HumanBytes;
+use serde::Serialize;
+use std::time::Duration;
+
+/// Totals for a run, reported by --stats and --json.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct Stats {
+    /// Files fetched over the network.
+    pub downloads: usize,
+    /// Bytes fetched over the network.
+    pub bytes: u64,
+    /// Time spent receiving downloads.
+    #[serde(rename = "download_seconds", serialize_with = "seconds")]
+    pub download_time: Duration,
+    /// Time spent unpacking archives.
+    #[serde(rename = "extract_seconds", serialize_with = "seconds")]
+    pub extract_time: Duration,
+    /// Downloads served from the local cache instead of the network.
+    pub cache_hits: usize,
+}
+
+// serde has no standard form for a Duration; fractional seconds are the
+// easiest for scripts to use.
+fn seconds<S: serde::Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+impl Stats {
+    /// Average download speed in bytes per second.
+    pub fn speed(&self) -> f64 {
+        let secs = self.download_time.as_secs_f64();
+        if secs > 0.0 {
+            self.bytes as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    /// One line for --stats, e.g.
+    /// "Downloaded 12.4 MiB in 2.1s (5.9 MiB/s), extracted in 350ms, 0 cache hits".
+    pub fn summary(&self) -> String {
+        format!(
+            "Downloaded {} in {} ({}/s), extracted in {}, {} cache hit{}",
+            HumanBytes(self.bytes),
+            format_elapsed(self.download_time),
+            HumanBytes(self.speed() as u64),
+            format_elapsed(self.extract_time),
+            self.cache_hits,
+            if self.cache_hits == 1 { "" } else { "s" }
+        )
+    }
+}
+
+// Adding stats together lets each download of a batch keep its own tally.
+impl std::ops::AddAssign for Stats {
+    fn add_assign(&mut self, other: Stats) {
+        self.downloads += other.downloads;
+        self.bytes += other.bytes;
+        self.download_time += other.download_time;
+        self.extract_time += other.extract_time;
+        self.cache_hits += other.cache_hits;
+    }
+}
+
+/// Formats a duration for people: "850ms", "12.3s", "4m 05s".
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    if secs >= 60 {
+        format!("{}m {:02}s", secs / 60, secs % 60)
+    } else if secs >= 1 {
+        format!("{:.1}s", elapsed.as_secs_f64())
+    } else {
+        format!("{}ms", elapsed.as_millis())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_elapsed() {
+        assert_eq!(format_elapsed(Duration::from_millis(850)), "850ms");
+        assert_eq!(format_elapsed(Duration::from_millis(12_340)), "12.3s");
+        assert_eq!(format_elapsed(Duration::from_secs(245)), "4m 05s");
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut stats = Stats::default();
+        stats += Stats {
+            downloads: 1,
+            bytes: 4 * 1024 * 1024,
+            download_time: Duration::from_secs(2),
+            extract_time: Duration::from_millis(350),
+            cache_hits: 1,
+        };
+        assert_eq!(stats.speed(), 2.0 * 1024.0 * 1024.0);
+        assert_eq!(
+            stats.summary(),
+            "Downloaded 4.00 MiB in 2.0s (2.00 MiB/s), extracted in 350ms, 1 cache hit"
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let stats = Stats {
+            downloads: 2,
+            bytes: 10,
+            download_time: Duration::from_millis(1500),
+            ..Default::default()
+        };
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["download_seconds"], 1.5);
+        assert_eq!(json["extract_seconds"], 0.0);
+        assert_eq!(json["bytes"], 10);
+    }
+}